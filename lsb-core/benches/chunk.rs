@@ -0,0 +1,42 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use lsb_core::{hash::Hash, metrics::quality_metrics, *};
+
+/// Embeds and extracts a 16 KiB payload, and compares whole images, in containers of
+/// 0.1, 10 and 100 megapixels.
+///
+/// The payload is small so that the cost of splitting the container into chunks, and
+/// of finding the payload bits of each chunk, is a large part of the work.
+fn bench_chunks(c: &mut Criterion) {
+    let input = (0..16 * 1024)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("chunks");
+    group.sample_size(10);
+
+    for (megapixels, (width, height)) in [
+        ("0.1", (400, 250)),
+        ("10", (4000, 2500)),
+        ("100", (12500, 8000)),
+    ] {
+        let original = ::image::RgbImage::from_fn(width, height, |x, y| {
+            ::image::Rgb([(x * 7) as u8, (y * 3) as u8, (x ^ y) as u8])
+        });
+        let mut image = original.clone();
+
+        group.bench_function(BenchmarkId::new("embed", megapixels), |b| {
+            b.iter(|| embed_into_image(&mut image, &input, "bin", 1, Hash::Blake3, 42).unwrap());
+        });
+        group.bench_function(BenchmarkId::new("extract", megapixels), |b| {
+            b.iter(|| extract_from_image(&image, 1, 42).unwrap());
+        });
+        group.bench_function(BenchmarkId::new("metrics", megapixels), |b| {
+            b.iter(|| quality_metrics(&original, &image).unwrap());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_chunks);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use lsb_core::{Format, StegoContainer};
+
+#[path = "../tests/test_util/mod.rs"]
+mod test_util;
+
+use test_util::*;
+
+/// Decodes JPEG containers of the suite through `image` and through the direct
+/// zune-jpeg path of the `zune` feature.
+fn bench_decode_jpeg(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_jpeg");
+    group.sample_size(10);
+
+    for (name, width, height) in CONTAINER_SIZES {
+        let container = encoded_container(width, height, Format::Jpeg);
+
+        group.bench_function(BenchmarkId::new("image", name), |b| {
+            b.iter(|| ::image::load_from_memory(&container).unwrap());
+        });
+        group.bench_function(BenchmarkId::new("zune", name), |b| {
+            b.iter(|| StegoContainer::new(&container).unwrap());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode_jpeg);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use lsb_core::{hash::Hash, image::*, *};
+
+/// Extracts a 4 MiB payload from a 24 megapixel container with 1 and 3 lsbs.
+///
+/// The payload bits are spread over the whole container, so reading them in payload
+/// order touches the image almost at random.
+fn bench_extract(c: &mut Criterion) {
+    let input = (0..4 * 1024 * 1024)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("extract");
+    group.sample_size(10);
+
+    for lsbs in [1, 3] {
+        let mut image = generate_container(6000, 4000, NoiseStyle::Uniform, 42);
+        embed_into_image(&mut image, &input, "bin", lsbs, Hash::Blake3, 42).unwrap();
+
+        group.bench_function(BenchmarkId::new("24mp_4mib", lsbs), |b| {
+            b.iter(|| extract_from_image(&image, lsbs, 42).unwrap());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_extract);
+criterion_main!(benches);
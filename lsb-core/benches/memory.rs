@@ -0,0 +1,186 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{
+    BenchmarkId, Criterion, Throughput, criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter},
+};
+use lsb_core::{capacity_for_dimensions, embed_into_image, hash::Hash, order::*};
+
+#[path = "../tests/test_util/mod.rs"]
+mod test_util;
+
+use test_util::*;
+
+/// Tracks the peak of the heap memory in use, so that `PeakMemory` can measure it.
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+/// Measures the most heap memory in use at once above what was in use at the start, in
+/// bytes, instead of the time taken.
+///
+/// Every benchmark runs its iterations with `BatchSize::PerIteration`, as the peaks of
+/// the iterations of a batch are not added up.
+struct PeakMemory;
+
+impl Measurement for PeakMemory {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> usize {
+        let current = CURRENT.load(Ordering::Relaxed);
+        PEAK.store(current, Ordering::Relaxed);
+        current
+    }
+
+    fn end(&self, before: usize) -> usize {
+        PEAK.load(Ordering::Relaxed) - before
+    }
+
+    fn add(&self, v1: &usize, v2: &usize) -> usize {
+        v1 + v2
+    }
+
+    fn zero(&self) -> usize {
+        0
+    }
+
+    fn to_f64(&self, value: &usize) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (denominator, unit) = if typical_value < 1024.0 {
+            (1.0, "B")
+        } else if typical_value < 1024.0 * 1024.0 {
+            (1024.0, "KiB")
+        } else if typical_value < 1024.0 * 1024.0 * 1024.0 {
+            (1024.0 * 1024.0, "MiB")
+        } else {
+            (1024.0 * 1024.0 * 1024.0, "GiB")
+        };
+
+        for value in values {
+            *value /= denominator;
+        }
+        unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let elements = match *throughput {
+            Throughput::Bytes(n) | Throughput::BytesDecimal(n) | Throughput::Elements(n) => n,
+        };
+        for value in values {
+            *value /= elements as f64;
+        }
+        "B/elem"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+/// The payload bits paired with their positions, sorted by position, in indices of
+/// type `I`, the way embedding pairs them.
+fn pairs<I: Ord + TryFrom<u64, Error: std::fmt::Debug>>(
+    capacity_bits: u64,
+    needed: u64,
+) -> Vec<(I, I)> {
+    let mut pairs = KeyedPermutation::new(42)
+        .positions(capacity_bits, needed)
+        .enumerate()
+        .map(|(i, x)| (I::try_from(x).unwrap(), I::try_from(i as u64).unwrap()))
+        .collect::<Vec<_>>();
+    pairs.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    pairs
+}
+
+/// Measures the peak heap memory of embedding at 1 lsb into decoded containers of the
+/// suite, and of the pairs of payload bits and positions embedding sorts: with `u32`
+/// indices, as used for every container below 4 Gi embeddable bits, and with `u64`
+/// indices, as used above.
+///
+/// The 32 MiB payloads are left out, as their `u64` pairs alone take 4 GiB.
+fn bench_embed_memory(c: &mut Criterion<PeakMemory>) {
+    let mut group = c.benchmark_group("memory_embed");
+    group.sample_size(10);
+
+    for (size_name, width, height) in CONTAINER_SIZES {
+        for (payload_name, payload_len) in &PAYLOAD_SIZES[..2] {
+            if capacity_for_dimensions(width, height, 1).unwrap() < *payload_len {
+                continue;
+            }
+
+            let name = format!("{}_{}", size_name, payload_name);
+            let capacity_bits = width as u64 * height as u64 * 3;
+            let needed = *payload_len as u64 * 8;
+            let (original, input) = (container(width, height), payload(*payload_len));
+
+            group.throughput(Throughput::Elements(needed));
+            group.bench_function(BenchmarkId::new("embed", &name), |b| {
+                b.iter_batched_ref(
+                    || original.clone(),
+                    |image| embed_into_image(image, &input, "bin", 1, Hash::Blake3, 42).unwrap(),
+                    criterion::BatchSize::PerIteration,
+                );
+            });
+            group.bench_function(BenchmarkId::new("pairs_u32", &name), |b| {
+                b.iter_batched(
+                    || (),
+                    |()| pairs::<u32>(capacity_bits, needed),
+                    criterion::BatchSize::PerIteration,
+                );
+            });
+            group.bench_function(BenchmarkId::new("pairs_u64", &name), |b| {
+                b.iter_batched(
+                    || (),
+                    |()| pairs::<u64>(capacity_bits, needed),
+                    criterion::BatchSize::PerIteration,
+                );
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_measurement(PeakMemory).without_plots();
+    targets = bench_embed_memory
+}
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+use std::io::Cursor;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use lsb_core::{hash::Hash, image::*, *};
+
+/// Embeds a 16 KiB payload into a 24 megapixel PNG container.
+///
+/// The payload is small so that decoding and encoding the container are most of the
+/// work. Run it with and without `--features fast-png` to compare the PNG encoders.
+fn bench_png(c: &mut Criterion) {
+    let input = (0..16 * 1024)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    let mut container = Vec::new();
+    generate_container(6000, 4000, NoiseStyle::GradientNoise, 42)
+        .write_to(&mut Cursor::new(&mut container), ::image::ImageFormat::Png)
+        .unwrap();
+
+    let mut group = c.benchmark_group("png");
+    group.sample_size(10);
+    group.bench_function("embed_24mp", |b| {
+        b.iter(|| embed(&input, "bin", &container, 1, Hash::Blake3, 42, Format::Png).unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_png);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+use std::io::Cursor;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use lsb_core::{
+    hash::Hash,
+    order::{KeyedPermutation, PixelOrder},
+    *,
+};
+
+/// The positions of a `KeyedPermutation`, computed once for a single capacity.
+struct Precomputed(Vec<u64>);
+
+impl Precomputed {
+    fn new(seed: u64, capacity_bits: u64) -> Self {
+        Precomputed(
+            KeyedPermutation::new(seed)
+                .positions(capacity_bits, capacity_bits)
+                .collect(),
+        )
+    }
+}
+
+impl PixelOrder for Precomputed {
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        assert_eq!(capacity_bits, self.0.len() as u64);
+        self.0[..needed.min(capacity_bits) as usize].iter().copied()
+    }
+}
+
+/// Extracts a payload filling 90% of a 2.5 megapixel container at 1 lsb, with the SIMD
+/// path disabled and enabled.
+///
+/// The positions are computed in advance by a `Precomputed` order, and the container is
+/// a PNM, so that reading the bits is most of the work instead of computing positions or
+/// decoding. The payload
+/// stays below 1 MiB, as larger reads sort their positions instead.
+fn bench_extract(c: &mut Criterion) {
+    let (width, height) = (1600, 1600);
+    let image = ::image::RgbImage::from_fn(width, height, |x, y| {
+        ::image::Rgb([(x * 7) as u8, (y * 3) as u8, (x ^ y) as u8])
+    });
+    let input = (0..width as usize * height as usize * 3 / 8 * 9 / 10)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    let order = Precomputed::new(42, width as u64 * height as u64 * 3);
+    let mut container = Vec::new();
+    ::image::DynamicImage::from(image)
+        .write_to(&mut Cursor::new(&mut container), ::image::ImageFormat::Pnm)
+        .unwrap();
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(1),
+        format: Some(Format::Pnm),
+        hash: Hash::Blake3,
+        ..EmbedOptions::default()
+    };
+    let embedded = embed_with_order(&input, "bin", &container, &options, &order).unwrap();
+
+    let mut group = c.benchmark_group("extract_lsb1");
+    group.sample_size(10);
+    for (name, enabled) in [("scalar", false), ("simd", true)] {
+        group.bench_function(name, |b| {
+            set_simd_enabled(enabled);
+            b.iter(|| extract_with_order(&embedded, 1, &order).unwrap());
+        });
+    }
+    group.finish();
+
+    set_simd_enabled(true);
+}
+
+criterion_group!(benches, bench_extract);
+criterion_main!(benches);
@@ -0,0 +1,192 @@
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use lsb_core::{
+    Plane, capacity_for_dimensions, embed_into_image, extract_from_image, hash::Hash, order::*,
+};
+
+#[path = "../tests/test_util/mod.rs"]
+mod test_util;
+
+use test_util::*;
+
+const LSBS: [usize; 3] = [1, 4, 8];
+
+/// Runs `bench` for every container size, payload size and lsbs value of the suite
+/// whose payload fits into the container.
+fn for_each_case(mut bench: impl FnMut(&str, (u32, u32), usize, usize)) {
+    for (size_name, width, height) in CONTAINER_SIZES {
+        for (payload_name, payload_len) in PAYLOAD_SIZES {
+            for lsbs in LSBS {
+                if capacity_for_dimensions(width, height, lsbs).unwrap() < payload_len {
+                    continue;
+                }
+
+                let name = format!("{}_{}", size_name, payload_name);
+                bench(&name, (width, height), payload_len, lsbs);
+            }
+        }
+    }
+}
+
+/// Embeds into decoded containers, so that codecs do not take part.
+fn bench_embed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suite_embed");
+    group.sample_size(10);
+
+    for_each_case(|name, (width, height), payload_len, lsbs| {
+        let (original, input) = (container(width, height), payload(payload_len));
+        group.throughput(Throughput::Bytes(payload_len as u64));
+        group.bench_function(BenchmarkId::new(name, lsbs), |b| {
+            b.iter_batched_ref(
+                || original.clone(),
+                |image| embed_into_image(image, &input, "bin", lsbs, Hash::Blake3, 42).unwrap(),
+                criterion::BatchSize::LargeInput,
+            );
+        });
+    });
+
+    group.finish();
+}
+
+/// Extracts from decoded containers, so that codecs do not take part.
+fn bench_extract(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suite_extract");
+    group.sample_size(10);
+
+    for_each_case(|name, (width, height), payload_len, lsbs| {
+        let mut image = container(width, height);
+        embed_into_image(
+            &mut image,
+            &payload(payload_len),
+            "bin",
+            lsbs,
+            Hash::Blake3,
+            42,
+        )
+        .unwrap();
+        group.throughput(Throughput::Bytes(payload_len as u64));
+        group.bench_function(BenchmarkId::new(name, lsbs), |b| {
+            b.iter(|| extract_from_image(&image, lsbs, 42).unwrap());
+        });
+    });
+
+    group.finish();
+}
+
+/// Generates the positions of the payload bits on their own, the part of embedding and
+/// extraction that does not touch the container.
+fn bench_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suite_order");
+    group.sample_size(10);
+
+    let order = KeyedPermutation::new(42);
+    for_each_case(|name, (width, height), payload_len, lsbs| {
+        let capacity_bits = width as u64 * height as u64 * 3 * lsbs as u64;
+        let needed = payload_len as u64 * 8;
+        group.throughput(Throughput::Elements(needed));
+        group.bench_function(BenchmarkId::new(name, lsbs), |b| {
+            b.iter(|| {
+                order
+                    .positions(black_box(capacity_bits), needed)
+                    .fold(0, |acc, position| acc ^ position)
+            });
+        });
+    });
+
+    group.finish();
+}
+
+/// Writes and reads a 4 KiB payload in a 50 megapixel plane at 1 lsb, with the keyed
+/// order of current images and the seeded shuffle of older ones.
+///
+/// The plane is in memory, so that no codec takes part: the keyed order only computes
+/// the positions of the payload, while the shuffle generates a permutation of the whole
+/// container for every operation.
+fn bench_legacy_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suite_legacy_order");
+    group.sample_size(10);
+
+    let (width, height) = (8200, 6100);
+    let input = payload(4 * 1024);
+    let mut plane = Plane::new(container(width, height).into_raw(), width, height).unwrap();
+
+    fn bench_one(
+        group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+        name: &str,
+        plane: &mut Plane<Vec<u8>>,
+        input: &[u8],
+        order: &impl PixelOrder,
+    ) {
+        group.bench_function(BenchmarkId::new("embed", name), |b| {
+            b.iter(|| plane.write_bytes(input, 1, order, 0).unwrap());
+        });
+        group.bench_function(BenchmarkId::new("extract", name), |b| {
+            b.iter(|| plane.read_bytes(1, order, 0, input.len()).unwrap());
+        });
+    }
+
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    bench_one(
+        &mut group,
+        "keyed",
+        &mut plane,
+        &input,
+        &KeyedPermutation::new(42),
+    );
+    bench_one(
+        &mut group,
+        "shuffle",
+        &mut plane,
+        &input,
+        &SeededShuffle::new(42),
+    );
+
+    group.finish();
+}
+
+/// Embeds 100 small payloads into containers of the same size, with a
+/// `KeyedPermutation` created for every embedding against one `Order` shared by all of
+/// them.
+///
+/// Both take about the same time, as a `KeyedPermutation` only derives 4 round keys
+/// and the bounds of its network before computing the positions themselves.
+fn bench_shared_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suite_shared_order");
+    group.sample_size(10);
+
+    let (_, width, height) = CONTAINER_SIZES[0];
+    let input = payload(PAYLOAD_SIZES[0].1);
+    let mut plane = Plane::new(container(width, height).into_raw(), width, height).unwrap();
+    let capacity_bits = width as u64 * height as u64 * 3;
+
+    group.throughput(Throughput::Elements(100));
+    group.bench_function("per_embedding", |b| {
+        b.iter(|| {
+            for _ in 0..100 {
+                let order = KeyedPermutation::new(black_box(42));
+                plane.write_bytes(&input, 1, &order, 0).unwrap();
+            }
+        });
+    });
+    let order = Order::new(42, capacity_bits);
+    group.bench_function("shared", |b| {
+        b.iter(|| {
+            for _ in 0..100 {
+                plane.write_bytes(&input, 1, &order, 0).unwrap();
+            }
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_embed,
+    bench_extract,
+    bench_order,
+    bench_legacy_order,
+    bench_shared_order
+);
+criterion_main!(benches);
@@ -10,6 +10,7 @@ rustPlatform.buildRustPackage (_: {
   };
 
   buildAndTestSubdir = "lsb-core";
+  buildFeatures = [ "cli" ];
 
   nativeBuildInputs = [ installShellFiles ];
 
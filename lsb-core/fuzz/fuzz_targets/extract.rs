@@ -0,0 +1,15 @@
+//! Extracts from arbitrary bytes as if they were an uploaded image.
+//!
+//! Besides random byte blobs, mutating valid stego PNGs reaches past the decoder, e.g.
+//! `cargo fuzz run extract corpus/extract ../../data/layout-v1`, of which
+//! `sha256-l2-33x7.png` was embedded with seed 42 and 2 LSBs like this target extracts.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lsb_core::*;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = extract_auto(data, 42);
+    let _ = extract_range(data, 0, 16, 2, 42);
+    let _ = extract_to_writer(data, std::io::sink(), 2, 42);
+});
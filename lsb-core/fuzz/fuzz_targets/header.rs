@@ -0,0 +1,11 @@
+//! Parses arbitrary bytes as the metadata of a payload, without an image around it.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, hash, _, rest)) = lsb_core::fuzzing::parse_payload_header(data) {
+        assert!(rest.len() <= data.len());
+        let _ = hash.digest_size();
+    }
+});
@@ -0,0 +1,70 @@
+//! Extracts payloads whose header fields are arbitrary, from a container holding them
+//! in exactly the bits extraction reads.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use lsb_core::{layout::*, order::KeyedPermutation, *};
+
+/// A payload laid out like `HEADER_FIELDS`, with fields that need not agree.
+#[derive(Debug, Arbitrary)]
+struct CorruptPayload {
+    width: u8,
+    height: u8,
+    lsbs: u8,
+    seed: u64,
+    length: u32,
+    ext_len: u8,
+    extension: Vec<u8>,
+    hash_flag: u8,
+    fingerprint: [u8; FINGERPRINT_SIZE],
+    checksum: Vec<u8>,
+    data: Vec<u8>,
+    /// The value of the samples before the payload is written.
+    fill: u8,
+}
+
+impl CorruptPayload {
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = self.length.to_le_bytes().to_vec();
+        bytes.push(self.ext_len);
+        bytes.extend_from_slice(&self.extension);
+        bytes.push(self.hash_flag);
+        bytes.extend_from_slice(&self.fingerprint);
+        bytes.extend_from_slice(&self.checksum);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Writes as many bits of the payload as the container holds, most significant first.
+    fn pixels(&self) -> Vec<u8> {
+        let lsbs = self.lsbs();
+        let mut samples = vec![self.fill; self.width as usize * self.height as usize * 3];
+        let capacity_bits = (samples.len() * lsbs) as u64;
+        let permutation = KeyedPermutation::new(self.seed);
+
+        let bits = self
+            .bytes()
+            .into_iter()
+            .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+        for (index, bit) in (0..capacity_bits).zip(bits) {
+            let position = permutation.position(capacity_bits, index);
+            let sample = &mut samples[(position / lsbs as u64) as usize];
+            let bit_in_sample = position % lsbs as u64;
+            *sample = (*sample & !(1 << bit_in_sample)) | (bit << bit_in_sample);
+        }
+        samples
+    }
+
+    fn lsbs(&self) -> usize {
+        (self.lsbs % 8 + 1) as usize
+    }
+}
+
+fuzz_target!(|payload: CorruptPayload| {
+    let pixels = payload.pixels();
+    let (width, height) = (payload.width as u32, payload.height as u32);
+    let _ = extract_raw_rgb(&pixels, width, height, payload.lsbs(), payload.seed);
+    // Invalid numbers of bits are rejected rather than read
+    let _ = extract_raw_rgb(&pixels, width, height, payload.lsbs as usize, payload.seed);
+});
@@ -0,0 +1,117 @@
+use std::io;
+
+use tokio::task::{JoinError, spawn_blocking};
+
+use super::{
+    cancel::CancelToken,
+    embed::{EmbedOptions, EmbedOutcome, embed_with},
+    error::*,
+    extract::{ExtractOptions, Extracted, extract_with},
+};
+
+/// Embeds data into a container image on tokio's blocking thread pool, like
+/// `embed_with`.
+///
+/// Embedding is CPU-bound, so running it directly in an async task would stall the
+/// other tasks of its worker thread. The inputs are taken by value, so nothing borrowed
+/// has to outlive an `.await`. This must be called from within a tokio runtime.
+///
+/// Dropping the returned future cancels the embedding through `options.cancel`, which
+/// is created if there is none, so an aborted request stops using the CPU soon after.
+///
+/// # Arguments
+///
+/// * `input`: The data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: The container image data.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `EmbedOutcome`, with the new image data and how it was
+/// embedded, if successful.
+///
+/// # Errors
+///
+/// See `embed_with`. Additionally:
+/// * `StegError::Io`: If the runtime shut down before the embedding finished.
+///
+/// # Panics
+///
+/// Panics raised by the embedding are resumed in the calling task.
+pub async fn embed(
+    input: Vec<u8>,
+    extension: String,
+    container: Vec<u8>,
+    mut options: EmbedOptions,
+) -> StegResult<EmbedOutcome> {
+    let guard = CancelOnDrop::new(options.cancel.get_or_insert_default());
+
+    let result = spawn_blocking(move || embed_with(&input, &extension, &container, &options)).await;
+    guard.disarm();
+    join(result)
+}
+
+/// Extracts data embedded in an image on tokio's blocking thread pool, like
+/// `extract_with`.
+///
+/// See `embed` for how the extraction is run and cancelled.
+///
+/// # Arguments
+///
+/// * `container`: The image data from which to extract content.
+/// * `options`: The parameters of the extraction.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `Extracted` payload, if successful.
+///
+/// # Errors
+///
+/// See `extract_with`. Additionally:
+/// * `StegError::Io`: If the runtime shut down before the extraction finished.
+///
+/// # Panics
+///
+/// Panics raised by the extraction are resumed in the calling task.
+pub async fn extract(container: Vec<u8>, mut options: ExtractOptions) -> StegResult<Extracted> {
+    let guard = CancelOnDrop::new(options.cancel.get_or_insert_default());
+
+    let result = spawn_blocking(move || extract_with(&container, &options)).await;
+    guard.disarm();
+    join(result)
+}
+
+/// Cancels an operation when the future awaiting it is dropped before it finishes.
+struct CancelOnDrop(Option<CancelToken>);
+
+impl CancelOnDrop {
+    fn new(cancel: &CancelToken) -> Self {
+        CancelOnDrop(Some(cancel.clone()))
+    }
+
+    /// Lets the operation go, as it has finished.
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(cancel) = &self.0 {
+            cancel.cancel();
+        }
+    }
+}
+
+/// Unwraps the result of a blocking task, resuming its panic if it panicked.
+fn join<T>(result: Result<StegResult<T>, JoinError>) -> StegResult<T> {
+    match result {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => Err(StegError::Io(io::Error::new(
+            io::ErrorKind::Interrupted,
+            err,
+        ))),
+    }
+}
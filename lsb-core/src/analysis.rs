@@ -0,0 +1,476 @@
+use std::cmp::Ordering;
+
+use super::{
+    capacity::{capacity_bits, check_lsbs},
+    consts::*,
+    error::*,
+    hash::Hash,
+    image::*,
+    metrics::luma,
+    par::*,
+    payload::header_size,
+};
+
+/// The smallest expected frequency of a pair of values for it to count in the chi-square test.
+const MIN_EXPECTED_FREQUENCY: f64 = 5.0;
+/// The maximum number of iterations when evaluating the incomplete gamma function.
+const GAMMA_ITERATIONS: usize = 1000;
+/// The relative precision at which the incomplete gamma function is evaluated.
+const GAMMA_EPSILON: f64 = 1e-12;
+/// The side length of the square blocks whose flatness `risk_report` measures.
+const FLATNESS_BLOCK: usize = 8;
+/// The luma variance below which a block counts as flat.
+const FLAT_VARIANCE: f64 = 1.0;
+/// The number of rows per block of the chi-square baseline in `risk_report`.
+const BASELINE_BLOCK_ROWS: usize = 64;
+
+/// A coarse rating of how likely an embedding is to be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskRating {
+    Low,
+    Medium,
+    High,
+}
+
+/// An assessment of how detectable embedding a payload into a container would be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskReport {
+    /// The overall rating.
+    pub rating: RiskRating,
+    /// The share of the capacity the payload and its metadata would use, above 1 if
+    /// it does not fit.
+    pub utilization: f64,
+    /// The share of 8x8 blocks whose luma is nearly constant.
+    pub flatness: f64,
+    /// The highest chi-square embedding probability of the clean container's blocks.
+    pub chi_square_baseline: f64,
+    /// The embedding rate estimated for the clean container by sample pair analysis.
+    pub spa_baseline: f64,
+    /// Human-readable reasons for the rating, empty for a low risk.
+    pub reasons: Vec<String>,
+}
+
+/// Extracts a single bit plane of a container as a black and white image.
+///
+/// Looking at the least significant planes directly is a simple way to judge how
+/// noisy a container is, and to spot the structure that embedding destroys.
+/// Floating-point formats are analyzed on the samples that carry embedded data.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the image data.
+/// * `channel`: The color channel to read, 0 for red, 1 for green and 2 for blue.
+/// * `bit`: The bit to read from each sample, 0 being the least significant.
+///
+/// # Returns
+///
+/// A `StegResult` containing a `GrayImage` that is white where the bit is set and
+/// black elsewhere, if successful.
+///
+/// # Errors
+///
+/// * `StegError::InvalidBitPlane`: If `channel` or `bit` is out of range.
+/// * Errors from the `image` crate during image decoding.
+pub fn bit_plane(container: &[u8], channel: usize, bit: u8) -> StegResult<GrayImage> {
+    if channel >= EMBEDDABLE_CHANNELS || bit as usize >= BITS_PER_BYTE {
+        return Err(StegError::InvalidBitPlane { channel, bit });
+    }
+
+    let image = decode(container, None)?.plane;
+
+    let mut plane = GrayImage::new(image.width(), image.height());
+    plane
+        .par_iter_mut()
+        .zip(image.par_pixels())
+        .for_each(|(out, pixel)| *out = ((pixel[channel] >> bit) & 1) * u8::MAX);
+
+    Ok(plane)
+}
+
+/// Runs the chi-square attack of Westfeld and Pfitzmann on the LSB plane of a container.
+///
+/// Replacing least significant bits with random data evens out the frequencies of
+/// each pair of values `2k` and `2k + 1`. For every block of `block_rows` rows, the
+/// histogram of all color samples is tested for this, giving the probability that
+/// the block holds embedded data. Clean images usually score near 0, and blocks
+/// whose LSBs are fully replaced near 1.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the image data.
+/// * `block_rows`: The number of rows per block. The last block may be shorter.
+///
+/// # Returns
+///
+/// A `StegResult` containing the embedding probability of every block, from the top
+/// of the image, if successful.
+///
+/// # Errors
+///
+/// * `StegError::InvalidBlockSize`: If `block_rows` is 0.
+/// * Errors from the `image` crate during image decoding.
+pub fn chi_square_analysis(container: &[u8], block_rows: usize) -> StegResult<Vec<f64>> {
+    if block_rows == 0 {
+        return Err(StegError::InvalidBlockSize { block_rows });
+    }
+
+    let image = decode(container, None)?.plane;
+    let row_len = image.width() as usize * EMBEDDABLE_CHANNELS;
+    if row_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    Ok(image
+        .par_chunks(row_len * block_rows)
+        .map(|block| {
+            let mut histogram = [0usize; 1 << BITS_PER_BYTE];
+            block
+                .iter()
+                .for_each(|&sample| histogram[sample as usize] += 1);
+
+            chi_square_probability(&histogram)
+        })
+        .collect())
+}
+
+/// Estimates the share of a container's samples that carry embedded data, using the
+/// sample pair analysis (SPA) of Dumitrescu, Wu and Wang.
+///
+/// Unlike the chi-square attack, SPA looks at the relation between horizontally
+/// adjacent samples of each channel, which also exposes randomly scattered payloads
+/// that do not fill the image. Pairs touching a saturated sample are skipped, as
+/// embedding cannot move them in both directions.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the image data.
+///
+/// # Returns
+///
+/// A `StegResult` containing the estimated embedding rate, between 0 for a clean image
+/// and 1 for one whose every LSB was replaced, if successful. Images too small or too
+/// flat to hold any usable pair are reported as clean.
+///
+/// # Errors
+///
+/// * Errors from the `image` crate during image decoding.
+pub fn estimate_embedding_rate(container: &[u8]) -> StegResult<f64> {
+    Ok(plane_embedding_rate(&decode(container, None)?.plane))
+}
+
+/// Estimates the embedding rate of an already decoded plane, like
+/// `estimate_embedding_rate`.
+pub(crate) fn plane_embedding_rate(image: &RgbImage) -> f64 {
+    let row_len = image.width() as usize * EMBEDDABLE_CHANNELS;
+    if row_len == 0 {
+        return 0.0;
+    }
+
+    let counts = image
+        .par_chunks(row_len)
+        .map(|row| {
+            let pixels = row.chunks_exact(EMBEDDABLE_CHANNELS);
+            let mut counts = PairCounts::default();
+            for (left, right) in pixels.clone().zip(pixels.skip(1)) {
+                for channel in 0..EMBEDDABLE_CHANNELS {
+                    counts.add(left[channel], right[channel]);
+                }
+            }
+            counts
+        })
+        .reduce(PairCounts::default, PairCounts::merge);
+
+    counts.embedding_rate()
+}
+
+/// Assesses how detectable embedding a payload into a container would be.
+///
+/// The rating is a deterministic heuristic. It grows with the share of the capacity
+/// the payload uses, the number of LSBs, and the share of flat areas, whose LSB noise
+/// stands out. A large payload is riskier still when the chi-square and sample pair
+/// analyses find the container clean, as there is no natural noise to hide it in.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the container image data.
+/// * `payload_len`: The length, in bytes, of the data to embed.
+/// * `lsbs`: The number of least significant bits per channel to embed with (1-8).
+///
+/// # Returns
+///
+/// A `StegResult` containing the `RiskReport` if successful.
+///
+/// # Errors
+///
+/// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+/// * Errors from the `image` crate during image decoding.
+pub fn risk_report(container: &[u8], payload_len: usize, lsbs: usize) -> StegResult<RiskReport> {
+    check_lsbs(lsbs, BITS_PER_BYTE)?;
+
+    let image = decode(container, None)?.plane;
+    let (width, height) = image.dimensions();
+
+    let capacity_bits = capacity_bits(width, height, lsbs)?;
+    let required_bits = (payload_len + header_size(Hash::Blake3, 0)) as f64 * BITS_PER_BYTE as f64;
+    let utilization = if capacity_bits == 0 {
+        f64::INFINITY
+    } else {
+        required_bits / capacity_bits as f64
+    };
+
+    let flatness = flatness(&image);
+    let chi_square_baseline = chi_square_analysis(container, BASELINE_BLOCK_ROWS)?
+        .into_iter()
+        .fold(0.0, f64::max);
+    let spa_baseline = estimate_embedding_rate(container)?;
+
+    let mut score = 0;
+    let mut reasons = Vec::new();
+
+    if utilization > 1.0 {
+        score += 3;
+        reasons.push("The payload does not fit into the container".to_string());
+    } else if utilization > 0.5 {
+        score += 2;
+        reasons.push(format!(
+            "The payload uses {:.0}% of the capacity, which SPA readily detects",
+            utilization * 100.0
+        ));
+    } else if utilization > 0.1 {
+        score += 1;
+        reasons.push(format!(
+            "The payload uses {:.0}% of the capacity",
+            utilization * 100.0
+        ));
+    }
+
+    if lsbs > 2 {
+        score += 1;
+        reasons.push(format!("{} LSBs per channel cause visible noise", lsbs));
+    }
+
+    if flatness > 0.5 {
+        score += 2;
+        reasons.push(format!(
+            "{:.0}% of the container is flat, where LSB noise stands out",
+            flatness * 100.0
+        ));
+    } else if flatness > 0.2 {
+        score += 1;
+        reasons.push(format!("{:.0}% of the container is flat", flatness * 100.0));
+    }
+
+    if utilization > 0.1 && chi_square_baseline < 0.05 && spa_baseline < 0.05 {
+        score += 1;
+        reasons.push(
+            "The container has structured LSBs, so the chi-square and SPA analyses will notice the payload"
+                .to_string(),
+        );
+    }
+
+    let rating = match score {
+        0 => RiskRating::Low,
+        1..=2 => RiskRating::Medium,
+        _ => RiskRating::High,
+    };
+
+    Ok(RiskReport {
+        rating,
+        utilization,
+        flatness,
+        chi_square_baseline,
+        spa_baseline,
+        reasons,
+    })
+}
+
+/// Returns the share of complete 8x8 blocks whose luma variance is below `FLAT_VARIANCE`.
+fn flatness(image: &RgbImage) -> f64 {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let (columns, rows) = (width / FLATNESS_BLOCK, height / FLATNESS_BLOCK);
+    if columns == 0 || rows == 0 {
+        return 0.0;
+    }
+
+    let luma = luma(image);
+    let flat = (0..rows * columns)
+        .into_par_iter()
+        .filter(|&block| {
+            let (top, left) = (
+                block / columns * FLATNESS_BLOCK,
+                block % columns * FLATNESS_BLOCK,
+            );
+            let values = (top..top + FLATNESS_BLOCK)
+                .flat_map(|row| &luma[row * width + left..row * width + left + FLATNESS_BLOCK]);
+
+            let n = (FLATNESS_BLOCK * FLATNESS_BLOCK) as f64;
+            let mean = values.clone().sum::<f64>() / n;
+            let variance = values.map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+
+            variance < FLAT_VARIANCE
+        })
+        .count();
+
+    flat as f64 / (rows * columns) as f64
+}
+
+/// The pair statistics sample pair analysis is based on.
+#[derive(Debug, Default, Clone, Copy)]
+struct PairCounts {
+    /// The number of pairs considered.
+    pairs: usize,
+    /// Pairs whose LSB flips move the values apart, `X` in the literature.
+    x: usize,
+    /// Pairs whose LSB flips move the values together, `Y` in the literature.
+    y: usize,
+    /// Pairs whose values only differ in their LSBs, `C_0` in the literature.
+    close: usize,
+}
+
+impl PairCounts {
+    fn add(&mut self, u: u8, v: u8) {
+        if [u, v]
+            .iter()
+            .any(|&sample| sample == 0 || sample == u8::MAX)
+        {
+            return;
+        }
+
+        self.pairs += 1;
+        match (v & 1 == 0, u.cmp(&v)) {
+            (true, Ordering::Less) | (false, Ordering::Greater) => self.x += 1,
+            (true, Ordering::Greater) | (false, Ordering::Less) => self.y += 1,
+            _ => {}
+        }
+        if u >> 1 == v >> 1 {
+            self.close += 1;
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        PairCounts {
+            pairs: self.pairs + other.pairs,
+            x: self.x + other.x,
+            y: self.y + other.y,
+            close: self.close + other.close,
+        }
+    }
+
+    /// Solves the SPA quadratic for the share of flipped LSBs, and doubles it, as only
+    /// about half of the embedded bits differ from the bits they replace.
+    fn embedding_rate(self) -> f64 {
+        if self.close == 0 {
+            return 0.0;
+        }
+
+        let a = 2.0 * self.close as f64;
+        let b = 2.0 * (2.0 * self.x as f64 - self.pairs as f64);
+        let c = self.y as f64 - self.x as f64;
+
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+
+        let root = discriminant.sqrt();
+        let flipped = f64::min((-b + root) / (2.0 * a), (-b - root) / (2.0 * a));
+
+        (2.0 * flipped).clamp(0.0, 1.0)
+    }
+}
+
+/// Computes the probability that the pairs of values of a histogram were equalized.
+fn chi_square_probability(histogram: &[usize]) -> f64 {
+    let (chi_square, categories) = histogram
+        .chunks_exact(2)
+        .filter_map(|pair| {
+            let expected = (pair[0] + pair[1]) as f64 / 2.0;
+            (expected >= MIN_EXPECTED_FREQUENCY).then(|| {
+                let deviation = pair[0] as f64 - expected;
+                deviation * deviation / expected
+            })
+        })
+        .fold((0.0, 0usize), |(sum, count), term| (sum + term, count + 1));
+
+    if categories < 2 {
+        // Too few samples to tell anything
+        return 0.0;
+    }
+
+    let degrees_of_freedom = (categories - 1) as f64;
+    upper_regularized_gamma(degrees_of_freedom / 2.0, chi_square / 2.0)
+}
+
+/// Evaluates the upper regularized incomplete gamma function `Q(a, x)`, the complement
+/// of the chi-square distribution function with `2a` degrees of freedom at `2x`.
+fn upper_regularized_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let prefactor = (a * x.ln() - x - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        // Series expansion of the lower function P(a, x)
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..GAMMA_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * GAMMA_EPSILON {
+                break;
+            }
+        }
+        (1.0 - sum * prefactor).clamp(0.0, 1.0)
+    } else {
+        // Continued fraction for Q(a, x), evaluated with the modified Lentz method
+        let tiny = f64::MIN_POSITIVE / GAMMA_EPSILON;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for n in 1..GAMMA_ITERATIONS {
+            let an = -(n as f64) * (n as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < GAMMA_EPSILON {
+                break;
+            }
+        }
+        (prefactor * h).clamp(0.0, 1.0)
+    }
+}
+
+/// Evaluates the natural logarithm of the gamma function for positive arguments, using
+/// the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
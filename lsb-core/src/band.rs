@@ -0,0 +1,244 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use png::{BitDepth, ColorType, Decoder, Reader, Transformations};
+use tracing::debug;
+
+use super::{
+    cancel::check_cancelled,
+    capacity::capacity_bits,
+    consts::*,
+    embed::{EmbedOptions, InverseOrder, OverwritePolicy, ResizePolicy, plan},
+    error::*,
+    format::Format,
+    hash::Fingerprinter,
+    image::png_encoder,
+    order::KeyedPermutation,
+    payload::build_payload,
+    progress::*,
+    threads::with_threads,
+};
+
+/// Embeds data into a PNG container band by band, so that memory use does not grow
+/// with the size of the container.
+///
+/// The container is decoded twice, a few rows at a time: once for its fingerprint, and
+/// once to write the payload bits that fall into each band of rows and encode the band
+/// right away. Neither the decoded container nor the encoded output is ever held in
+/// memory as a whole, only a band of about 4 MiB and the positions of the payload bits.
+/// The output extracts exactly like the output of `embed_with` for the same options,
+/// and has the same pixels.
+///
+/// Only non-interlaced 8-bit PNG containers and PNG output are supported, and options
+/// that need the whole container at once, such as checking for an existing payload,
+/// are rejected.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: The source of the PNG container, such as a file. It is read twice.
+/// * `options`: The parameters of the embedding. `format` must be `Format::Png`.
+/// * `writer`: The sink the output image is written to, such as a file.
+///
+/// # Errors
+///
+/// * `StegError::UnsupportedFormat`: If the output format is not PNG, or the container
+///   is interlaced or has 16-bit samples.
+/// * `StegError::UnsupportedOption`: If `overwrite_policy` is not `OverwritePolicy::Allow`,
+///   `resize_policy` is not `ResizePolicy::Never`, or `verify_after_embed` or
+///   `output.png_interlaced` is set.
+/// * `StegError::Io`: If decoding the container or writing the output fails.
+/// * See `embed_with` for the errors of the embedding itself.
+pub fn embed_banded<R: Read + Seek, W: Write>(
+    input: &[u8],
+    extension: &str,
+    container: R,
+    options: &EmbedOptions,
+    writer: W,
+) -> StegResult<()> {
+    check_options(options)?;
+
+    with_threads(options.threads, || {
+        embed_bands(input, extension, container, options, writer)
+    })
+}
+
+/// Embeds data band by band like `embed_banded`, in the pool of the calling thread.
+fn embed_bands<R: Read + Seek, W: Write>(
+    input: &[u8],
+    extension: &str,
+    mut container: R,
+    options: &EmbedOptions,
+    writer: W,
+) -> StegResult<()> {
+    let start = container.stream_position()?;
+    let mut rows = Rows::new(&mut container)?;
+    let (width, height) = (rows.width, rows.height);
+    let plan = plan(input.len(), extension, (width, height), options)?;
+    let lsbs = plan.lsbs;
+
+    let mut fingerprinter = Fingerprinter::new(lsbs);
+    while let Some(row) = rows.next()? {
+        check_cancelled(options.cancel.as_ref())?;
+        fingerprinter.update(row);
+    }
+    drop(rows);
+
+    let total = build_payload(input, extension, options.hash, fingerprinter.finish())?;
+    let total_len_bits = (total.len() * BITS_PER_BYTE) as u64;
+    let inverse_ord = InverseOrder::new(
+        &KeyedPermutation::new(options.seed),
+        capacity_bits(width, height, lsbs)?,
+        total_len_bits,
+    );
+    milestone(options.progress.as_ref(), Phase::Order);
+    let progress = Tracker::new(
+        options.progress.as_ref(),
+        options.cancel.as_ref(),
+        Phase::Embed,
+        total_len_bits,
+    );
+
+    container.seek(SeekFrom::Start(start))?;
+    let mut rows = Rows::new(&mut container)?;
+
+    let mut output = png_encoder(writer, width, height, &options.output)
+        .write_header()
+        .map_err(std::io::Error::from)?;
+    let mut output = output.stream_writer().map_err(std::io::Error::from)?;
+
+    let row_len = width as usize * EMBEDDABLE_CHANNELS;
+    let band_rows = (BAND_SIZE / row_len).max(1);
+    let mut band = Vec::with_capacity(band_rows * row_len);
+    let (mut first_sample, mut bits_flipped) = (0, 0);
+
+    loop {
+        band.clear();
+        while band.len() < band_rows * row_len
+            && let Some(row) = rows.next()?
+        {
+            band.extend_from_slice(row);
+        }
+        if band.is_empty() {
+            break;
+        }
+
+        bits_flipped += inverse_ord.embed(&mut band, first_sample, &total, lsbs, &progress);
+        progress.check()?;
+        output.write_all(&band)?;
+        first_sample += band.len();
+    }
+
+    output.finish().map_err(std::io::Error::from)?;
+    milestone(options.progress.as_ref(), Phase::Encode);
+    debug!(
+        "Flipped {} bits in bands of {} rows",
+        bits_flipped, band_rows
+    );
+
+    Ok(())
+}
+
+/// Rejects the options that banded embedding cannot honor.
+fn check_options(options: &EmbedOptions) -> StegResult<()> {
+    if options.output_format() != Format::Png {
+        return Err(StegError::UnsupportedFormat(format!(
+            "Banded embedding only writes PNG, not {:?}",
+            options.output_format()
+        )));
+    }
+    if options.overwrite_policy != OverwritePolicy::Allow {
+        return Err(StegError::UnsupportedOption(
+            "Banded embedding cannot check for an existing payload".to_string(),
+        ));
+    }
+    if options.resize_policy != ResizePolicy::Never {
+        return Err(StegError::UnsupportedOption(
+            "Banded embedding cannot resize the container".to_string(),
+        ));
+    }
+    if options.verify_after_embed {
+        return Err(StegError::UnsupportedOption(
+            "Banded embedding cannot verify its output".to_string(),
+        ));
+    }
+    if options.output.png_interlaced {
+        return Err(StegError::UnsupportedOption(
+            "Banded embedding cannot interlace its output".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Decodes the rows of a PNG one at a time, converted to 8-bit RGB like `into_rgb8`.
+struct Rows<R: Read> {
+    reader: Reader<R>,
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    rgb: Vec<u8>,
+}
+
+impl<R: Read> Rows<R> {
+    fn new(container: R) -> StegResult<Self> {
+        let mut decoder = Decoder::new(container);
+        // Palettes and low bit depths are expanded to 8 bits, as the `image` crate does
+        decoder.set_transformations(Transformations::EXPAND);
+        let reader = decoder.read_info().map_err(std::io::Error::from)?;
+
+        let info = reader.info();
+        let (width, height) = (info.width, info.height);
+        if info.interlaced {
+            return Err(StegError::UnsupportedFormat(
+                "Banded embedding does not support interlaced PNG containers".to_string(),
+            ));
+        }
+
+        let (color_type, depth) = reader.output_color_type();
+        if depth != BitDepth::Eight {
+            return Err(StegError::UnsupportedFormat(format!(
+                "Banded embedding only supports 8-bit PNG containers, not {:?}",
+                depth
+            )));
+        }
+
+        Ok(Rows {
+            reader,
+            width,
+            height,
+            color_type,
+            rgb: vec![0; width as usize * EMBEDDABLE_CHANNELS],
+        })
+    }
+
+    /// Decodes the next row, returning `None` after the last one.
+    fn next(&mut self) -> StegResult<Option<&[u8]>> {
+        let Some(row) = self.reader.next_row().map_err(std::io::Error::from)? else {
+            return Ok(None);
+        };
+        let row = row.data();
+
+        match self.color_type {
+            ColorType::Rgb => self.rgb.copy_from_slice(row),
+            ColorType::Rgba => {
+                for (rgb, rgba) in self.rgb.chunks_exact_mut(3).zip(row.chunks_exact(4)) {
+                    rgb.copy_from_slice(&rgba[..3]);
+                }
+            }
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                let channels = self.color_type.samples();
+                for (rgb, gray) in self.rgb.chunks_exact_mut(3).zip(row.chunks_exact(channels)) {
+                    rgb.fill(gray[0]);
+                }
+            }
+            ColorType::Indexed => {
+                return Err(StegError::UnsupportedFormat(
+                    "Palette was not expanded".to_string(),
+                ));
+            }
+        }
+
+        Ok(Some(&self.rgb))
+    }
+}
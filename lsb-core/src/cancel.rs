@@ -0,0 +1,40 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use super::error::*;
+
+/// A flag that stops the operations it is passed to, shared by all of its clones.
+///
+/// Operations check the token between their phases and about once per chunk while
+/// payload bits are written or read, and return `StegError::Cancelled` soon after it
+/// is cancelled from another thread. A cancelled embedding returns no output, but
+/// streaming operations may already have written part of theirs.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Cancels the operations the token was passed to. This cannot be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Fails with `StegError::Cancelled` if `cancel` was cancelled.
+pub(crate) fn check_cancelled(cancel: Option<&CancelToken>) -> StegResult<()> {
+    match cancel {
+        Some(cancel) if cancel.is_cancelled() => Err(StegError::Cancelled),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,331 @@
+use alloc::format;
+
+use tracing::debug;
+
+#[cfg(feature = "std")]
+use super::image::*;
+use super::{consts::*, error::*, hash::*, payload::header_size};
+
+/// Calculates how many bytes of data fit into a container image.
+///
+/// Only the dimensions of the container are read, its pixels are not decoded.
+/// The result accounts for the metadata stored with the data, assuming the
+/// defaults of `EmbedOptions` (a `Hash::Blake3` checksum) and an empty extension.
+/// Use `capacity_with` for the exact capacity of another configuration.
+///
+/// Any data from 0 bytes up to the capacity can be embedded, so a capacity of 0 means
+/// that only an empty payload fits. A container too small for even that fails instead.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the container image data.
+/// * `lsbs`: The number of least significant bits to use per color channel (1-8).
+///
+/// # Returns
+///
+/// A `StegResult` containing the number of bytes that can be embedded, if successful.
+///
+/// # Errors
+///
+/// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+/// * `StegError::InsufficientCapacity`: If the container cannot hold even the metadata
+///   of an empty payload.
+/// * `StegError::CalculationOverflow`: If the number of embeddable bits does not fit into a `u64`.
+/// * `StegError::CapacityExceedsUsizeMax`: If the capacity does not fit into a `usize`.
+/// * Errors from the `image` crate while reading the image header.
+#[cfg(feature = "std")]
+pub fn capacity(container: &[u8], lsbs: usize) -> StegResult<usize> {
+    capacity_with(container, lsbs, Hash::Blake3, 0)
+}
+
+/// Calculates exactly how many bytes of data fit into a container image for a given
+/// checksum algorithm and extension length.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the container image data.
+/// * `lsbs`: The number of least significant bits to use per color channel (1-8).
+/// * `hash`: The hashing algorithm that will be used for the checksum.
+/// * `ext_len`: The length, in bytes, of the extension that will be embedded.
+///
+/// # Returns
+///
+/// A `StegResult` containing the number of bytes that can be embedded, if successful.
+///
+/// # Errors
+///
+/// See `capacity`.
+#[cfg(feature = "std")]
+pub fn capacity_with(
+    container: &[u8],
+    lsbs: usize,
+    hash: Hash,
+    ext_len: usize,
+) -> StegResult<usize> {
+    let (width, height) = dimensions(container)?;
+
+    usable_bytes(width, height, lsbs, hash, ext_len)
+}
+
+/// Calculates how many bytes of data fit into a container image for every number of
+/// least significant bits.
+///
+/// The image header is read only once, and the same assumptions as `capacity` apply.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the container image data.
+///
+/// # Returns
+///
+/// A `StegResult` containing the number of bytes that can be embedded with 1 to 8
+/// least significant bits, in that order, if successful. Unlike `capacity`, numbers of
+/// bits that cannot hold even an empty payload have a capacity of 0 rather than failing.
+///
+/// # Errors
+///
+/// * `StegError::CalculationOverflow`: If a capacity does not fit into a `usize`.
+/// * Errors from the `image` crate while reading the image header.
+#[cfg(feature = "std")]
+pub fn capacity_table(container: &[u8]) -> StegResult<[usize; BITS_PER_BYTE]> {
+    let (width, height) = dimensions(container)?;
+
+    let mut table = [0; BITS_PER_BYTE];
+    for (lsbs, capacity) in (1..).zip(table.iter_mut()) {
+        *capacity = match usable_bytes(width, height, lsbs, Hash::Blake3, 0) {
+            Err(StegError::InsufficientCapacity { .. }) => 0,
+            capacity => capacity?,
+        };
+    }
+
+    Ok(table)
+}
+
+/// Calculates how many bytes of data fit into a container image of the given dimensions.
+///
+/// This makes the same assumptions as `capacity`, without needing the image itself.
+///
+/// # Arguments
+///
+/// * `width`: The width of the container image, in pixels.
+/// * `height`: The height of the container image, in pixels.
+/// * `lsbs`: The number of least significant bits to use per color channel (1-8).
+///
+/// # Returns
+///
+/// A `StegResult` containing the number of bytes that can be embedded, if successful.
+///
+/// # Errors
+///
+/// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+/// * `StegError::InsufficientCapacity`: If the dimensions cannot hold even the metadata
+///   of an empty payload.
+/// * `StegError::CalculationOverflow`: If the number of embeddable bits does not fit into a `u64`.
+/// * `StegError::CapacityExceedsUsizeMax`: If the capacity does not fit into a `usize`.
+pub fn capacity_for_dimensions(width: u32, height: u32, lsbs: usize) -> StegResult<usize> {
+    usable_bytes(width, height, lsbs, Hash::Blake3, 0)
+}
+
+/// Calculates how many pixels a container needs to hold a payload.
+///
+/// This is the inverse of `capacity_with`: any container with at least this many
+/// pixels can hold `payload_len` bytes of data with the given settings, and any
+/// container with fewer pixels cannot.
+///
+/// # Arguments
+///
+/// * `payload_len`: The length, in bytes, of the data to embed.
+/// * `lsbs`: The number of least significant bits to use per color channel (1-8).
+/// * `hash`: The hashing algorithm that will be used for the checksum.
+/// * `ext`: The extension that will be embedded with the data.
+///
+/// # Returns
+///
+/// A `StegResult` containing the minimum number of pixels, if successful.
+///
+/// # Errors
+///
+/// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+/// * `StegError::ExtensionTooLong`: If `ext` is longer than 255 bytes.
+/// * `StegError::CalculationOverflow`: If the number of pixels does not fit into a `u64`.
+pub fn required_pixels(payload_len: usize, lsbs: usize, hash: Hash, ext: &str) -> StegResult<u64> {
+    check_lsbs(lsbs, BITS_PER_BYTE)?;
+
+    if ext.len() > u8::MAX as usize {
+        return Err(StegError::ExtensionTooLong {
+            len: ext.len(),
+            max: u8::MAX as usize,
+        });
+    }
+
+    let total_len_bits = (payload_len as u64)
+        .checked_add(header_size(hash, ext.len()) as u64)
+        .and_then(|res| res.checked_mul(BITS_PER_BYTE as u64))
+        .ok_or_else(|| {
+            StegError::CalculationOverflow(format!(
+                "Overflow calculating the payload size: payload_len ({}) + header",
+                payload_len
+            ))
+        })?;
+
+    Ok(pixels_for_bits(total_len_bits, lsbs))
+}
+
+/// Calculates near-square dimensions for a container that can hold a payload.
+///
+/// The returned image has at least `required_pixels` pixels, and is as close to
+/// a square as possible while removing a row would make it too small.
+///
+/// # Arguments
+///
+/// See `required_pixels`.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `(width, height)` of the container, if successful.
+///
+/// # Errors
+///
+/// See `required_pixels`. `StegError::CalculationOverflow` is also returned if a side
+/// does not fit into a `u32`.
+pub fn required_dimensions(
+    payload_len: usize,
+    lsbs: usize,
+    hash: Hash,
+    ext: &str,
+) -> StegResult<(u32, u32)> {
+    near_square(required_pixels(payload_len, lsbs, hash, ext)?)
+}
+
+/// Rejects containers too small to hold a header of `header_size` bytes with `lsbs`,
+/// telling the dimensions they would need.
+///
+/// This runs right after decoding, so that degenerate images, e.g. of 0 pixels, never
+/// reach the arithmetic of embedding and extraction.
+pub(crate) fn check_dimensions(
+    (width, height): (u32, u32),
+    lsbs: usize,
+    header_size: usize,
+) -> StegResult<()> {
+    check_lsbs(lsbs, BITS_PER_BYTE)?;
+
+    let header_bits = (header_size * BITS_PER_BYTE) as u64;
+    let available_bits = capacity_bits(width, height, lsbs)?;
+    if available_bits >= header_bits {
+        return Ok(());
+    }
+
+    let pixels = pixels_for_bits(header_bits, lsbs);
+    let (min_width, min_height) = near_square(pixels)?;
+    debug!(
+        "Container of {}x{} pixels is too small to hold even the {} byte header at {} lsbs, it needs at least {} pixels, e.g. {}x{}",
+        width, height, header_size, lsbs, pixels, min_width, min_height
+    );
+    Err(insufficient_capacity(header_bits, available_bits, lsbs))
+}
+
+/// Returns the dimensions of an image of at least `pixels` pixels, as close to a square
+/// as possible while removing a row would make it too small.
+fn near_square(pixels: u64) -> StegResult<(u32, u32)> {
+    let pixels = pixels.max(1);
+
+    let mut width = pixels.isqrt();
+    if width * width < pixels {
+        width += 1;
+    }
+    let height = pixels.div_ceil(width);
+
+    match (u32::try_from(width), u32::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(StegError::CalculationOverflow(format!(
+            "Container of {}x{} pixels exceeds the maximum image size",
+            width, height
+        ))),
+    }
+}
+
+/// Returns the number of pixels needed to store `bits` bits with `lsbs` bits per channel.
+pub(crate) fn pixels_for_bits(bits: u64, lsbs: usize) -> u64 {
+    bits.div_ceil((EMBEDDABLE_CHANNELS * lsbs) as u64)
+}
+
+/// Builds the `InsufficientCapacity` error of a container offering `available_bits` with
+/// `lsbs`, with the pixels it lacks to offer `required_bits`.
+pub(crate) fn insufficient_capacity(
+    required_bits: u64,
+    available_bits: u64,
+    lsbs: usize,
+) -> StegError {
+    StegError::InsufficientCapacity {
+        required_bits,
+        available_bits,
+        missing_pixels: pixels_for_bits(required_bits.saturating_sub(available_bits), lsbs),
+    }
+}
+
+/// Returns the number of embeddable bits of an image, checking for overflow.
+///
+/// The capacity is a `u64` on every platform, so that the order of the payload bits
+/// does not depend on the width of `usize`.
+pub(crate) fn capacity_bits(width: u32, height: u32, lsbs: usize) -> StegResult<u64> {
+    // Potential overflow when calculating width_bits
+    let width_bits = u64::from(width)
+        .checked_mul(EMBEDDABLE_CHANNELS as u64)
+        .and_then(|res| res.checked_mul(lsbs as u64))
+        .ok_or_else(|| {
+            StegError::CalculationOverflow(format!(
+                "Overflow calculating width_bits: width ({}) * EMBEDDABLE_CHANNELS ({}) * lsbs ({})",
+                width, EMBEDDABLE_CHANNELS, lsbs
+            ))
+        })?;
+
+    // Potential overflow when calculating capacity_bits
+    width_bits.checked_mul(u64::from(height)).ok_or_else(|| {
+        StegError::CalculationOverflow(format!(
+            "Overflow calculating capacity_bits: width_bits ({}) * height ({})",
+            width_bits, height
+        ))
+    })
+}
+
+/// Returns the number of data bytes that fit after the header, failing with
+/// `StegError::InsufficientCapacity` if not even the header fits.
+pub(crate) fn usable_bytes(
+    width: u32,
+    height: u32,
+    lsbs: usize,
+    hash: Hash,
+    ext_len: usize,
+) -> StegResult<usize> {
+    check_lsbs(lsbs, BITS_PER_BYTE)?;
+
+    let capacity_bits = capacity_bits(width, height, lsbs)?;
+    let header_size = header_size(hash, ext_len) as u64;
+    // Saturating would report the same 0 for a container that holds an empty payload
+    // and one that does not
+    let usable = (capacity_bits / BITS_PER_BYTE as u64)
+        .checked_sub(header_size)
+        .ok_or_else(|| {
+            insufficient_capacity(header_size * BITS_PER_BYTE as u64, capacity_bits, lsbs)
+        })?;
+
+    // The data has to be in memory to be embedded, so no more of it can fit than that
+    usize::try_from(usable).map_err(|_| {
+        StegError::CapacityExceedsUsizeMax(format!(
+            "Capacity of {} bytes exceeds the address space of this platform",
+            usable
+        ))
+    })
+}
+
+/// Returns `StegError::InvalidLsbValue` unless `lsbs` is between 1 and `max` inclusive.
+///
+/// Embedding, extraction and analysis all validate `lsbs` with this, so that they accept
+/// the same values.
+pub(crate) fn check_lsbs(lsbs: usize, max: usize) -> StegResult<()> {
+    if lsbs == 0 || lsbs > max {
+        return Err(StegError::InvalidLsbValue { given: lsbs, max });
+    }
+
+    Ok(())
+}
@@ -1,8 +1,8 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
 
-pub use clap::{Parser, Subcommand};
+pub use clap::{Args, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
-use lsb_core::hash::Hash;
+use lsb_core::{LsbSelection, error::StegError, hash::Hash};
 
 #[derive(Parser)]
 #[command(version, about, long_about)]
@@ -11,40 +11,191 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
 
-    /// The number of least significant bits to use for embedding.
-    #[arg(short, long, default_value = "1")]
-    pub lsbs: usize,
+    /// The number of least significant bits to use for embedding, or "auto" to use
+    /// the fewest that fit the payload (and to detect it when extracting).
+    #[arg(short, long, default_value = "1", global = true)]
+    pub lsbs: LsbSelection,
 
-    /// The seed for the random number generator.
-    #[arg(short, long, default_value = "42")]
-    pub seed: u64,
+    /// The seed for the random number generator, or "random" to embed with a fresh
+    /// one, which is printed to stderr.
+    ///
+    /// At most one of --seed, --seed-file and --seed-env can be given, and without any
+    /// of them the seed is 42. A passphrase replaces all three.
+    #[arg(short, long, default_value = "42", global = true)]
+    pub seed: SeedSelection,
+
+    /// Read the seed from a file, as a decimal or 0x-prefixed hexadecimal number, to
+    /// keep it out of command lines.
+    #[arg(long, value_name = "PATH", global = true, conflicts_with = "seed")]
+    pub seed_file: Option<PathBuf>,
+
+    /// Read the seed from an environment variable, like --seed-file.
+    #[arg(
+        long,
+        value_name = "VAR",
+        global = true,
+        conflicts_with_all = ["seed", "seed_file"]
+    )]
+    pub seed_env: Option<String>,
+
+    /// Write binary data to stdout even if it is a terminal.
+    #[arg(long, global = true)]
+    pub force_tty: bool,
+
+    /// The number of threads to use, instead of one per CPU.
+    #[cfg(feature = "parallel")]
+    #[arg(long)]
+    pub threads: Option<usize>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Embed a file into a container image.
+    /// Embed a file into a container image, or into many of them with --output-dir.
     Embed {
-        /// The input file to embed.
+        /// The input file to embed, or - for stdin. A directory is embedded as a tar
+        /// archive of its files, which `extract --unpack` expands again. With
+        /// --output-dir, it may hold the placeholders of --output-template, to embed a
+        /// file of its own into every container.
         input: PathBuf,
-        /// The container image file.
-        container: PathBuf,
+        /// The container image file, or - for stdin. With --output-dir, any number of
+        /// container image files or glob patterns matching them.
+        #[arg(required = true)]
+        containers: Vec<PathBuf>,
 
-        /// The hashing algorithm to use.
+        /// The hashing algorithm to use, such as blake3, sha-256, sha-512 or sha-1.
         #[arg(long, default_value = "blake3")]
         hash: Hash,
-        /// The output file for the embedded image.
-        #[arg(short, long, default_value = "embedded.png")]
-        output: String,
+        /// The output file for the embedded image, whose extension picks its format, or -
+        /// for stdout in the format of the container. Defaults to `embedded` with the
+        /// extension of the container, when that is a lossless format.
+        #[arg(short, long, conflicts_with = "output_dir")]
+        output: Option<PathBuf>,
+        /// The directory to write the embedded images into, embedding into every
+        /// container in parallel and carrying on past the ones that fail.
+        #[arg(long, conflicts_with = "dry_run")]
+        output_dir: Option<PathBuf>,
+        /// The name of the embedded image of each container in --output-dir, where
+        /// {stem}, {name}, {ext} and {index} stand for the file stem, file name and
+        /// extension of the container, and its position among the containers.
+        #[arg(long, default_value = "{name}", requires = "output_dir")]
+        output_template: String,
+        /// Write the seed made up by --seed random to a file only the owner can read, as
+        /// --seed-file reads it.
+        #[arg(long, value_name = "PATH")]
+        seed_out: Option<PathBuf>,
+        /// Derive the seed of each container in --output-dir from --seed.
+        #[arg(long, requires = "output_dir")]
+        seed_per_file: Option<SeedPerFile>,
+        /// Write the outcome and seed of each container in --output-dir to a JSON file.
+        #[arg(long, requires = "output_dir")]
+        manifest: Option<PathBuf>,
+        /// The number of containers in --output-dir to embed into at once, instead of one
+        /// per CPU.
+        #[cfg(feature = "parallel")]
+        #[arg(long, requires = "output_dir")]
+        jobs: Option<usize>,
+        /// Embed even if the container already holds a payload, without warning.
+        #[arg(long)]
+        force_overwrite: bool,
+        /// Do not warn when the embedding is likely to be detected.
+        #[arg(long)]
+        no_risk_check: bool,
+        /// Validate the embedding and print its parameters without writing an image.
+        #[arg(long)]
+        dry_run: bool,
+        /// Extract the output again before writing it, failing if it does not match the input.
+        #[arg(long)]
+        verify: bool,
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
     },
 
     /// Extract a file from a container image.
     Extract {
+        /// The container image file, or - for stdin.
+        container: PathBuf,
+
+        /// The directory to write the extracted file into, named after the container, or
+        /// - to write the data to stdout instead.
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+        /// Write the data to stdout, even if it is a terminal, and its extension and size
+        /// to stderr, instead of writing a file.
+        #[arg(long, conflicts_with = "output_dir")]
+        stdout: bool,
+        /// Unpack a payload embedded from a directory into a directory named after the
+        /// container, instead of writing it as a tar archive.
+        #[arg(long, conflicts_with = "stdout")]
+        unpack: bool,
+        /// Print the files of the payload, their sizes and the total, like `tar -t`,
+        /// instead of writing anything. The checksum is not verified.
+        #[arg(long, conflicts_with_all = ["stdout", "unpack"])]
+        list: bool,
+        /// Print the listing as JSON.
+        #[arg(long, requires = "list")]
+        json: bool,
+        /// Also read containers embedded with the pixel order of older versions with
+        /// --lsbs auto, which costs memory for every bit of the container for every
+        /// value tried. A fixed --lsbs always reads them.
+        #[arg(long)]
+        legacy_order: bool,
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
+    },
+
+    /// Print how many bytes of data a container image can hold.
+    Capacity {
+        /// The container image file.
+        image: PathBuf,
+
+        /// The hashing algorithm the data would be embedded with, whose checksum takes
+        /// up room.
+        #[arg(long, default_value = "blake3")]
+        hash: Hash,
+        /// Print the capacity for every number of least significant bits, which is also
+        /// what "auto" does.
+        #[arg(long)]
+        all: bool,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print the metadata of the payload of a container image without extracting it.
+    ///
+    /// Exits with 0 if the payload verifies, 5 if there is none and 6 if its checksum
+    /// does not match.
+    Inspect {
         /// The container image file.
         container: PathBuf,
 
-        /// The output file for the extracted data.
-        #[arg(short, long, default_value = "extracted")]
-        output: PathBuf,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+        /// Also read containers embedded with the pixel order of older versions with
+        /// --lsbs auto, which costs memory for every bit of the container for every
+        /// value tried. A fixed --lsbs always reads them.
+        #[arg(long)]
+        legacy_order: bool,
+    },
+
+    /// Check that the payloads of container images extract with a valid checksum,
+    /// without writing anything.
+    ///
+    /// Exits with 1 if any of them fails.
+    Verify {
+        /// The container image files, or glob patterns matching them.
+        #[arg(required = true)]
+        images: Vec<PathBuf>,
+
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+        /// Also read containers embedded with the pixel order of older versions with
+        /// --lsbs auto, which costs memory for every bit of the container for every
+        /// value tried. A fixed --lsbs always reads them.
+        #[arg(long)]
+        legacy_order: bool,
     },
 
     /// Generate shell completions for the CLI.
@@ -53,3 +204,56 @@ pub enum Commands {
         shell: Shell,
     },
 }
+
+/// The value of --seed.
+#[derive(Clone, Copy)]
+pub enum SeedSelection {
+    /// A given seed.
+    Fixed(u64),
+    /// A seed drawn from a cryptographically secure generator, only when embedding.
+    Random,
+}
+
+impl FromStr for SeedSelection {
+    type Err = StegError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("random") {
+            return Ok(SeedSelection::Random);
+        }
+
+        s.parse().map(SeedSelection::Fixed).map_err(|_| {
+            StegError::UnsupportedOption(format!(
+                "Expected a number or \"random\" for the seed, got {:?}",
+                s
+            ))
+        })
+    }
+}
+
+/// Where to read a passphrase from, which replaces --seed and encrypts the payload with
+/// ChaCha20-Poly1305, under a key derived with Argon2id.
+#[derive(Args)]
+#[group(multiple = false)]
+pub struct PassphraseArgs {
+    /// Prompt for the passphrase without echoing it, which keeps it out of the shell
+    /// history.
+    #[arg(long, conflicts_with_all = ["seed", "seed_file", "seed_env"])]
+    pub passphrase_prompt: bool,
+    /// Read the passphrase from an environment variable.
+    #[arg(long, value_name = "VAR", conflicts_with_all = ["seed", "seed_file", "seed_env"])]
+    pub passphrase_env: Option<String>,
+    /// Read the passphrase from a file, without its trailing newline.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["seed", "seed_file", "seed_env"])]
+    pub passphrase_file: Option<PathBuf>,
+}
+
+/// How the seed of every container of a batch is derived from `--seed`.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SeedPerFile {
+    /// The seed plus the position of the container among the containers, from 0.
+    Index,
+    /// The seed plus the first 8 bytes of the BLAKE3 hash of the file name of the
+    /// container, in little-endian order.
+    Name,
+}
@@ -1,6 +1,39 @@
+#[cfg(feature = "std")]
+use super::par::current_num_threads;
+
 /// The number of bits in a byte.
 pub const BITS_PER_BYTE: usize = 8;
 /// The number of color channels in an image that can be used for embedding (e.g., R, G, B).
 pub const EMBEDDABLE_CHANNELS: usize = 3;
-/// The size of chunks to process in parallel operations, in bytes.
+/// The number of bytes of the container fingerprint stored in the header.
+pub const FINGERPRINT_SIZE: usize = 8;
+/// The bit of the hash flag marking a header that holds a container fingerprint.
+pub const FINGERPRINT_FLAG: u8 = 0x80;
+/// The bit of the hash flag marking a payload whose seeded order is a `KeyedPermutation`.
+/// Payloads without it were embedded with a `SeededShuffle`.
+pub const KEYED_ORDER_FLAG: u8 = 0x40;
+/// The size of chunks in which streamed input data is read, in bytes.
+#[cfg(feature = "std")]
+pub const READ_CHUNK_SIZE: usize = 64 * 1024;
+/// The size of the bands a container is processed in by banded embedding, in bytes.
+#[cfg(feature = "std")]
+pub const BAND_SIZE: usize = 4 * 1024 * 1024;
+/// The size of chunks to process at a time, and the smallest chunk of a parallel operation.
 pub const CHUNK_SIZE: usize = 1024;
+/// The largest chunk of a parallel operation.
+#[cfg(feature = "std")]
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+/// The number of chunks a parallel operation aims to give each thread, so that threads
+/// finishing early can take over the chunks of the others.
+#[cfg(feature = "std")]
+pub const CHUNKS_PER_THREAD: usize = 4;
+
+/// Returns the size of the chunks a parallel operation over `len` items is split into.
+///
+/// The size aims for `CHUNKS_PER_THREAD` chunks per thread of the current rayon pool,
+/// within `CHUNK_SIZE` and `MAX_CHUNK_SIZE`. The results of every operation are the same
+/// whatever the chunk size.
+#[cfg(feature = "std")]
+pub fn chunk_size(len: usize) -> usize {
+    (len / (current_num_threads() * CHUNKS_PER_THREAD)).clamp(CHUNK_SIZE, MAX_CHUNK_SIZE)
+}
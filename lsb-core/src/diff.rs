@@ -0,0 +1,423 @@
+use image::{DynamicImage, ImageBuffer, Luma, Pixel, Rgb};
+
+use super::{
+    consts::*,
+    embed::{EmbedOptions, LsbSelection, ResizePolicy},
+    error::*,
+    image::*,
+    par::*,
+};
+
+/// A single sample that differs between two images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleChange {
+    /// The column of the pixel.
+    pub x: u32,
+    /// The row of the pixel.
+    pub y: u32,
+    /// The index of the channel within the pixel.
+    pub channel: usize,
+    /// The sample in the original image. Floating-point samples are given as their bits.
+    pub old: u32,
+    /// The sample in the modified image. Floating-point samples are given as their bits.
+    pub new: u32,
+}
+
+/// The changes an embedding made to the carrying plane of a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LsbDiffStats {
+    /// The number of samples compared.
+    pub samples: usize,
+    /// The number of samples that differ.
+    pub changed_samples: usize,
+    /// The number of bits that differ, all within the allowed least significant bits.
+    pub changed_bits: usize,
+}
+
+/// The differences between an image and a modified copy of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffReport {
+    /// The width of both images, in pixels.
+    pub width: u32,
+    /// The height of both images, in pixels.
+    pub height: u32,
+    /// The number of channels per pixel.
+    pub channels: usize,
+    /// The number of pixels with at least one differing sample.
+    pub changed_pixels: usize,
+    /// The number of differing samples.
+    pub changed_samples: usize,
+    /// The number of differing bits in each bit plane, from the least significant bit.
+    /// There is one entry per bit of a sample.
+    pub bit_planes: Vec<usize>,
+    /// The differing samples in raster order, up to the maximum requested.
+    pub changes: Vec<SampleChange>,
+    /// Whether `changes` was cut short by the maximum.
+    pub truncated: bool,
+}
+
+impl DiffReport {
+    /// Returns the number of differing bits across all bit planes.
+    pub fn changed_bits(&self) -> usize {
+        self.bit_planes.iter().sum()
+    }
+}
+
+/// Compares two encoded images sample by sample, without listing the differing samples.
+///
+/// # Arguments
+///
+/// * `original`: A slice of bytes representing the image before modification.
+/// * `modified`: A slice of bytes representing the image after modification.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `DiffReport` if successful.
+///
+/// # Errors
+///
+/// See `diff_with`.
+pub fn diff(original: &[u8], modified: &[u8]) -> StegResult<DiffReport> {
+    diff_with(original, modified, 0)
+}
+
+/// Compares two encoded images sample by sample, listing up to `max_changes` of the
+/// differing samples.
+///
+/// The images are compared in their own color type and sample depth, so this also
+/// reveals changes that an 8-bit RGB conversion would hide.
+///
+/// # Arguments
+///
+/// * `original`: A slice of bytes representing the image before modification.
+/// * `modified`: A slice of bytes representing the image after modification.
+/// * `max_changes`: The maximum number of differing samples to list in the report.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `DiffReport` if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+/// * `StegError::ColorTypeMismatch`: If the images do not have the same color type.
+/// * Errors from the `image` crate during image decoding.
+pub fn diff_with(original: &[u8], modified: &[u8], max_changes: usize) -> StegResult<DiffReport> {
+    let original = decode_image(original)?;
+    let modified = decode_image(modified)?;
+
+    check_dimensions(
+        (original.width(), original.height()),
+        (modified.width(), modified.height()),
+    )?;
+
+    if original.color() != modified.color() {
+        return Err(StegError::ColorTypeMismatch(format!(
+            "Original is {:?}, modified is {:?}",
+            original.color(),
+            modified.color()
+        )));
+    }
+
+    let channels = original.color().channel_count() as usize;
+    let sample_size = original.color().bytes_per_pixel() as usize / channels;
+
+    Ok(diff_samples(
+        &samples(&original, sample_size),
+        &samples(&modified, sample_size),
+        (original.width(), original.height()),
+        channels,
+        sample_size * BITS_PER_BYTE,
+        max_changes,
+    ))
+}
+
+/// Builds a mask of the pixels that differ between an image and a modified copy of it.
+///
+/// # Arguments
+///
+/// * `original`: The image before modification, e.g. the container.
+/// * `modified`: The image after modification, e.g. the output of `embed`.
+///
+/// # Returns
+///
+/// A `StegResult` containing a `GrayImage` that is white where any bit of a pixel
+/// differs and black elsewhere, if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+pub fn change_map(original: &RgbImage, modified: &RgbImage) -> StegResult<GrayImage> {
+    check_dimensions(original.dimensions(), modified.dimensions())?;
+
+    Ok(map_pixels(original, modified, |a, b| {
+        Luma([if a == b { 0 } else { u8::MAX }])
+    }))
+}
+
+/// Visualizes the per-channel differences between an image and a modified copy of it.
+///
+/// The absolute difference of every sample is scaled so that the largest one becomes
+/// fully saturated, which makes changes to the lowest bits visible.
+///
+/// # Arguments
+///
+/// * `original`: The image before modification, e.g. the container.
+/// * `modified`: The image after modification, e.g. the output of `embed`.
+///
+/// # Returns
+///
+/// A `StegResult` containing an `RgbImage` of the scaled differences, black for
+/// identical images, if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+pub fn amplified_change_map(original: &RgbImage, modified: &RgbImage) -> StegResult<RgbImage> {
+    check_dimensions(original.dimensions(), modified.dimensions())?;
+
+    let max_diff = original
+        .par_iter()
+        .zip(modified.par_iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let scale =
+        |a: u8, b: u8| (u16::from(a.abs_diff(b)) * u16::from(u8::MAX) / u16::from(max_diff)) as u8;
+
+    Ok(map_pixels(original, modified, |a, b| {
+        Rgb([scale(a[0], b[0]), scale(a[1], b[1]), scale(a[2], b[2])])
+    }))
+}
+
+/// Checks that an embedding only changed the lowest `lsbs` bits of every sample.
+///
+/// The output format is detected from `embedded`, and both images are compared in
+/// the plane that format carries the data in, such as the mantissas for HDR.
+///
+/// # Arguments
+///
+/// * `original`: A slice of bytes representing the container.
+/// * `embedded`: A slice of bytes representing the output of `embed`.
+/// * `lsbs`: The number of least significant bits the embedding was allowed to change.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `LsbDiffStats` if only the allowed bits changed.
+///
+/// # Errors
+///
+/// * `StegError::UnexpectedChange`: Naming the first sample with a change above the
+///   lowest `lsbs` bits.
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+/// * Errors from the `image` crate during image decoding.
+pub fn assert_only_lsbs_changed(
+    original: &[u8],
+    embedded: &[u8],
+    lsbs: usize,
+) -> StegResult<LsbDiffStats> {
+    let embedded = decode(embedded, None)?;
+    let original = Carrier::new(decode_image(original)?, embedded.kind());
+
+    compare_lsbs(&original.plane, &embedded.plane, lsbs)
+}
+
+/// Checks that an embedding with the given options only changed the bits it was
+/// allowed to.
+///
+/// This follows `options` the way `embed_with` does: `embedded` is decoded as
+/// `options.format`, and the container is resized first if `options.resize_policy`
+/// allows upscaling. With `LsbSelection::Auto`, every bit the format can carry data
+/// in is allowed to change.
+///
+/// # Arguments
+///
+/// * `original`: A slice of bytes representing the container.
+/// * `embedded`: A slice of bytes representing the output of `embed_with`.
+/// * `options`: The options the embedding was made with.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `LsbDiffStats` if only the allowed bits changed.
+///
+/// # Errors
+///
+/// See `assert_only_lsbs_changed`.
+pub fn assert_only_lsbs_changed_with(
+    original: &[u8],
+    embedded: &[u8],
+    options: &EmbedOptions,
+) -> StegResult<LsbDiffStats> {
+    let format = options
+        .for_container(guess_format(original))?
+        .output_format();
+    let embedded = decode_as(embedded, format)?;
+    let (width, height) = embedded.plane.dimensions();
+
+    let original = match options.resize_policy {
+        ResizePolicy::UpscaleToFit { filter, .. } if dimensions(original)? != (width, height) => {
+            decode_resized(original, format, (width, height), filter)?
+        }
+        _ => decode(original, Some(format))?,
+    };
+
+    let lsbs = match options.lsbs {
+        LsbSelection::Fixed(lsbs) => lsbs,
+        LsbSelection::Auto => embedded.kind().max_lsbs(),
+    };
+
+    compare_lsbs(&original.plane, &embedded.plane, lsbs)
+}
+
+/// Compares two carrying planes, failing on the first change above the lowest `lsbs` bits.
+fn compare_lsbs(original: &RgbImage, embedded: &RgbImage, lsbs: usize) -> StegResult<LsbDiffStats> {
+    check_dimensions(original.dimensions(), embedded.dimensions())?;
+
+    let allowed = u8::MAX
+        .checked_shl(lsbs as u32)
+        .map_or(u8::MAX, |high| !high);
+    let channels = Rgb::<u8>::CHANNEL_COUNT as usize;
+
+    let mut stats = LsbDiffStats {
+        samples: original.len(),
+        changed_samples: 0,
+        changed_bits: 0,
+    };
+
+    for (index, (&old, &new)) in original.iter().zip(embedded.iter()).enumerate() {
+        let changed = old ^ new;
+        if changed & !allowed != 0 {
+            let pixel = index / channels;
+            return Err(StegError::UnexpectedChange {
+                x: pixel as u32 % original.width(),
+                y: pixel as u32 / original.width(),
+                channel: index % channels,
+                original: old,
+                modified: new,
+                lsbs,
+            });
+        }
+
+        stats.changed_samples += (changed != 0) as usize;
+        stats.changed_bits += changed.count_ones() as usize;
+    }
+
+    Ok(stats)
+}
+
+/// Returns an error unless both images have the same dimensions.
+pub(crate) fn check_dimensions(original: (u32, u32), modified: (u32, u32)) -> StegResult<()> {
+    if original != modified {
+        return Err(StegError::DimensionMismatch {
+            expected: original,
+            actual: modified,
+        });
+    }
+
+    Ok(())
+}
+
+/// Builds an image from the corresponding pixels of two images of the same dimensions,
+/// in parallel.
+fn map_pixels<P: Pixel<Subpixel = u8> + Send>(
+    original: &RgbImage,
+    modified: &RgbImage,
+    f: impl Fn(&Rgb<u8>, &Rgb<u8>) -> P + Sync,
+) -> ImageBuffer<P, Vec<u8>> {
+    let (width, height) = original.dimensions();
+    let channels = P::CHANNEL_COUNT as usize;
+
+    let mut output = ImageBuffer::new(width, height);
+    output
+        .par_chunks_mut(channels)
+        .zip(original.par_pixels().zip(modified.par_pixels()))
+        .for_each(|(out, (a, b))| out.copy_from_slice(f(a, b).channels()));
+
+    output
+}
+
+/// Reads the samples of an image in its native depth.
+fn samples(image: &DynamicImage, sample_size: usize) -> Vec<u32> {
+    image
+        .as_bytes()
+        .chunks_exact(sample_size)
+        .map(|bytes| match *bytes {
+            [a] => u32::from(a),
+            [a, b] => u32::from(u16::from_ne_bytes([a, b])),
+            [a, b, c, d] => u32::from_ne_bytes([a, b, c, d]),
+            _ => unreachable!("samples are 1, 2 or 4 bytes"),
+        })
+        .collect()
+}
+
+fn diff_samples(
+    original: &[u32],
+    modified: &[u32],
+    (width, height): (u32, u32),
+    channels: usize,
+    sample_bits: usize,
+    max_changes: usize,
+) -> DiffReport {
+    let chunk_size = chunk_size(original.len());
+    let (changed_samples, bit_planes) = original
+        .par_chunks(chunk_size)
+        .zip(modified.par_chunks(chunk_size))
+        .map(|(a, b)| {
+            let mut planes = vec![0; sample_bits];
+            let mut changed = 0;
+            for (a, b) in a.iter().zip(b) {
+                let flipped = a ^ b;
+                if flipped != 0 {
+                    changed += 1;
+                    for (plane, count) in planes.iter_mut().enumerate() {
+                        *count += (flipped >> plane & 1) as usize;
+                    }
+                }
+            }
+            (changed, planes)
+        })
+        .reduce(
+            || (0, vec![0; sample_bits]),
+            |(c1, mut p1), (c2, p2)| {
+                p1.iter_mut().zip(p2).for_each(|(a, b)| *a += b);
+                (c1 + c2, p1)
+            },
+        );
+
+    let changed_pixels = original
+        .par_chunks(channels)
+        .zip(modified.par_chunks(channels))
+        .filter(|(a, b)| a != b)
+        .count();
+
+    let changes = original
+        .iter()
+        .zip(modified)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .take(max_changes)
+        .map(|(index, (&old, &new))| {
+            let pixel = (index / channels) as u32;
+            SampleChange {
+                x: pixel % width,
+                y: pixel / width,
+                channel: index % channels,
+                old,
+                new,
+            }
+        })
+        .collect::<Vec<_>>();
+    let truncated = changes.len() < changed_samples;
+
+    DiffReport {
+        width,
+        height,
+        channels,
+        changed_pixels,
+        changed_samples,
+        bit_planes,
+        changes,
+        truncated,
+    }
+}
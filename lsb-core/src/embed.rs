@@ -1,29 +1,414 @@
-use std::cmp::Ordering;
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    fmt,
+    io::{self, Cursor, Read, Seek, Write},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tracing::{Span, debug, field, instrument};
 
-use log::debug;
-use rand::{
-    prelude::*,
-    seq::index::{IndexVec, sample},
+use super::{
+    cancel::*,
+    capacity::{
+        capacity_bits, check_dimensions, check_lsbs, insufficient_capacity, pixels_for_bits,
+    },
+    consts::*,
+    error::*,
+    extract::{PayloadReader, extract_plane, read_header},
+    format::Format,
+    hash::*,
+    image::*,
+    order::*,
+    par::*,
+    payload::{build_payload, header_size, write_header},
+    plane::write_bits,
+    progress::*,
+    stego::{Stego, StegoContainer},
+    threads::{parallel, with_threads},
+    warning::Warning,
 };
-use rand_pcg::Pcg64Mcg;
-use rayon::prelude::*;
 
-use super::{consts::*, error::*, hash::*, image::*};
+/// What `embed_with` does when the container already seems to hold a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Refuse to embed, returning `StegError::ExistingPayload`.
+    Error,
+    /// Log a warning and embed anyway.
+    Warn,
+    /// Embed without checking for an existing payload.
+    #[default]
+    Allow,
+}
+
+/// What `embed_with` does when the payload does not fit into the container.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ResizePolicy {
+    /// Fail with `StegError::InsufficientCapacity`.
+    #[default]
+    Never,
+    /// Upscale the container just enough for the payload to fit, keeping its aspect ratio.
+    UpscaleToFit {
+        /// The largest factor either side may be scaled by. Payloads that need more
+        /// still fail with `StegError::InsufficientCapacity`.
+        max_scale: f32,
+        /// The filter used for resampling the container.
+        filter: FilterType,
+    },
+}
+
+/// How many least significant bits per color channel an embedding uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LsbSelection {
+    /// Use exactly this many bits.
+    Fixed(usize),
+    /// Use the fewest bits whose capacity fits the payload, minimizing the visual impact.
+    /// `extract_auto` finds the chosen value again when extracting.
+    Auto,
+}
+
+impl Default for LsbSelection {
+    fn default() -> Self {
+        LsbSelection::Fixed(1)
+    }
+}
+
+impl From<usize> for LsbSelection {
+    fn from(lsbs: usize) -> Self {
+        LsbSelection::Fixed(lsbs)
+    }
+}
+
+/// Parses either `"auto"` (case-insensitive) or a number of bits.
+impl FromStr for LsbSelection {
+    type Err = StegError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(LsbSelection::Auto);
+        }
+
+        s.parse().map(LsbSelection::Fixed).map_err(|_| {
+            StegError::UnsupportedOption(format!(
+                "Expected a number of bits or \"auto\" for lsbs, got {:?}",
+                s
+            ))
+        })
+    }
+}
+
+/// The parameters of an embedding operation.
+#[derive(Clone)]
+pub struct EmbedOptions {
+    /// The number of least significant bits to use per color channel for embedding (1-8,
+    /// or 1-7 for HDR output, whose top mantissa bit must stay set), or `LsbSelection::Auto`.
+    pub lsbs: LsbSelection,
+    /// The hashing algorithm to use for checksumming the input data.
+    pub hash: Hash,
+    /// A 64-bit seed for the pseudo-random number generator that determines pixel order.
+    pub seed: u64,
+    /// The `Format` of the output image, which must be a lossless format, or `None` for
+    /// the format of the container. Embedding into a container in a lossy format, such
+    /// as JPEG, then fails with `StegError::UnsupportedFormat`, and one of unknown format,
+    /// such as an already decoded image, is written as PNG.
+    /// HDR and OpenEXR outputs keep their floating-point samples and embed into the
+    /// mantissa LSBs instead of converting the container to 8 bits.
+    pub format: Option<Format>,
+    /// How the output image is encoded, such as how hard PNG outputs are compressed.
+    pub output: OutputOptions,
+    /// What to do when the container already holds a payload under the same `lsbs` and `seed`.
+    pub overwrite_policy: OverwritePolicy,
+    /// What to do when the payload does not fit into the container.
+    pub resize_policy: ResizePolicy,
+    /// Whether to extract the output again before returning it, failing with
+    /// `StegError::SelfVerificationFailed` if it does not match the input.
+    ///
+    /// This roughly doubles the cost of an embedding.
+    pub verify_after_embed: bool,
+    /// The number of threads to run the parallel parts of the operation on, in a pool
+    /// created for it, or `None` to use the pool of the calling thread.
+    ///
+    /// That is the global rayon pool, shared with everything else in the process that
+    /// uses rayon, unless the operation is called from within `ThreadPool::install`,
+    /// which runs it in that pool instead. A pool is created for every operation when this
+    /// is set, so callers running many small operations should rather install a pool of
+    /// their own once. The output does not depend on the number of threads.
+    ///
+    /// Without the `parallel` feature, everything runs on the calling thread and this is
+    /// ignored.
+    pub threads: Option<usize>,
+    /// A callback invoked as the embedding progresses, or `None` to report nothing.
+    ///
+    /// Embeddings report `Phase::Decode` if they decode the whole container, `Phase::Order`,
+    /// `Phase::Embed` about once per chunk, then `Phase::Encode` if they encode the
+    /// output, and `Phase::Verify` with `verify_after_embed`.
+    pub progress: Option<ProgressCallback>,
+    /// A token that stops the embedding with `StegError::Cancelled` once cancelled, or
+    /// `None` to always run it to completion.
+    pub cancel: Option<CancelToken>,
+}
+
+impl fmt::Debug for EmbedOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbedOptions")
+            .field("lsbs", &self.lsbs)
+            .field("hash", &self.hash)
+            .field("seed", &self.seed)
+            .field("format", &self.format)
+            .field("output", &self.output)
+            .field("overwrite_policy", &self.overwrite_policy)
+            .field("resize_policy", &self.resize_policy)
+            .field("verify_after_embed", &self.verify_after_embed)
+            .field("threads", &self.threads)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl Default for EmbedOptions {
+    fn default() -> Self {
+        EmbedOptions {
+            lsbs: LsbSelection::default(),
+            hash: Hash::Blake3,
+            seed: 42,
+            format: None,
+            output: OutputOptions::default(),
+            overwrite_policy: OverwritePolicy::default(),
+            resize_policy: ResizePolicy::default(),
+            verify_after_embed: false,
+            threads: None,
+            progress: None,
+            cancel: None,
+        }
+    }
+}
+
+impl EmbedOptions {
+    /// Starts from the default options, which match the defaults of the bindings.
+    ///
+    /// Every option has a method of the same name that sets it, so that options can be
+    /// chained without naming the others, e.g.
+    /// `EmbedOptions::new().lsbs(2).hash(Hash::Sha256).seed(7)`. The values are checked
+    /// by the embedding itself.
+    pub fn new() -> Self {
+        EmbedOptions::default()
+    }
+
+    /// Sets how many least significant bits per channel are used.
+    pub fn lsbs(mut self, lsbs: impl Into<LsbSelection>) -> Self {
+        self.lsbs = lsbs.into();
+        self
+    }
+
+    /// Sets the hashing algorithm of the checksum.
+    pub fn hash(mut self, hash: Hash) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// Sets the seed of the pixel order.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the format of the output image, or `None` for that of the container.
+    pub fn format(mut self, format: impl Into<Option<Format>>) -> Self {
+        self.format = format.into();
+        self
+    }
+
+    /// Sets how the output image is encoded.
+    pub fn output(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets what to do when the container already holds a payload.
+    pub fn overwrite_policy(mut self, overwrite_policy: OverwritePolicy) -> Self {
+        self.overwrite_policy = overwrite_policy;
+        self
+    }
+
+    /// Sets what to do when the payload does not fit into the container.
+    pub fn resize_policy(mut self, resize_policy: ResizePolicy) -> Self {
+        self.resize_policy = resize_policy;
+        self
+    }
+
+    /// Sets whether the output is extracted again before it is returned.
+    pub fn verify_after_embed(mut self, verify_after_embed: bool) -> Self {
+        self.verify_after_embed = verify_after_embed;
+        self
+    }
+
+    /// Sets the number of threads of the pool created for every operation, or `None` to
+    /// use the pool of the calling thread.
+    pub fn threads(mut self, threads: impl Into<Option<usize>>) -> Self {
+        self.threads = threads.into();
+        self
+    }
+
+    /// Sets the callback invoked as the embedding progresses.
+    pub fn progress(mut self, progress: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Sets the token that stops the embedding once cancelled.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// The format of the output: `format`, or PNG for a container of unknown format.
+    pub(crate) fn output_format(&self) -> Format {
+        self.format.unwrap_or(Format::Png)
+    }
+
+    /// Fills in an unset `format` with the format of the container, if it is known.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::UnsupportedFormat`: If `format` is unset and the container is in a
+    ///   lossy format.
+    pub(crate) fn for_container(
+        &self,
+        container: Option<Format>,
+    ) -> StegResult<Cow<'_, EmbedOptions>> {
+        match (self.format, container) {
+            (Some(_), _) | (None, None) => Ok(Cow::Borrowed(self)),
+            (None, Some(format)) if is_lossless(format) => {
+                Ok(Cow::Owned(self.clone().format(format)))
+            }
+            (None, Some(format)) => Err(StegError::UnsupportedFormat(format!(
+                "The container is {:?}, a lossy format that would destroy the payload, so \
+                 an output format such as Png must be given",
+                format
+            ))),
+        }
+    }
+}
+
+/// The share of the capacity above which an embedding warns with
+/// `Warning::HighUtilization`.
+const HIGH_UTILIZATION: f32 = 0.5;
+
+/// The output of `embed_with`, along with how the embedding went.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct EmbedOutcome {
+    /// The encoded output image.
+    pub image: Vec<u8>,
+    /// The number of least significant bits per channel that were used, as selected
+    /// for `LsbSelection::Auto`.
+    pub lsbs_used: usize,
+    /// The share of the capacity that was used, from 0 to 1.
+    pub capacity_used: f32,
+    /// The width of the output image, which differs from the container if it was upscaled.
+    pub width: u32,
+    /// The height of the output image, which differs from the container if it was upscaled.
+    pub height: u32,
+    /// The length of the data plus its metadata, in bytes.
+    pub payload_bytes: usize,
+    /// The number of written bits that differ from the bit they replaced.
+    pub bits_flipped: usize,
+    /// What the caller may want to know about the embedding, in the order it was found.
+    pub warnings: Vec<Warning>,
+}
+
+impl EmbedOutcome {
+    fn new(image: Vec<u8>, report: EmbedReport) -> Self {
+        EmbedOutcome {
+            image,
+            lsbs_used: report.lsbs,
+            capacity_used: (report.utilization / 100.0) as f32,
+            width: report.width,
+            height: report.height,
+            payload_bytes: report.payload_bytes,
+            bits_flipped: report.bits_flipped,
+            warnings: report.warnings,
+        }
+    }
+}
+
+/// Statistics about an embedding, returned by `embed_with_report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedReport {
+    /// The length of the embedded data, in bytes.
+    pub data_bytes: usize,
+    /// The length of the data plus its metadata, in bytes.
+    pub payload_bytes: usize,
+    /// The number of least significant bits per channel that were used.
+    pub lsbs: usize,
+    /// The width of the output image, which differs from the container if it was upscaled.
+    pub width: u32,
+    /// The height of the output image, which differs from the container if it was upscaled.
+    pub height: u32,
+    /// The number of bits the container can hold with `lsbs`.
+    pub capacity_bits: u64,
+    /// The number of bits written into the container.
+    pub bits_written: usize,
+    /// The number of written bits that differ from the bit they replaced.
+    pub bits_flipped: usize,
+    /// The share of the capacity that was used, as a percentage.
+    pub utilization: f64,
+    /// The time the embedding took, including decoding and encoding the image.
+    pub elapsed: Duration,
+    /// What the caller may want to know about the embedding, in the order it was found.
+    pub warnings: Vec<Warning>,
+}
+
+/// The parameters an embedding would use, returned by `plan_embed`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedPlan {
+    /// The length of the data to embed, in bytes.
+    pub data_bytes: usize,
+    /// The length of the data plus its metadata, in bytes.
+    pub payload_bytes: usize,
+    /// The number of least significant bits per channel that would be used.
+    pub lsbs: usize,
+    /// The width of the output image, which differs from the container if it would be upscaled.
+    pub width: u32,
+    /// The height of the output image, which differs from the container if it would be upscaled.
+    pub height: u32,
+    /// The number of bits the container can hold with `lsbs`.
+    pub capacity_bits: u64,
+    /// The share of the capacity that would be used, as a percentage.
+    pub utilization: f64,
+    /// The hashing algorithm that would be used for the checksum.
+    pub hash: Hash,
+    /// The seed that would determine the pixel order.
+    pub seed: u64,
+    /// The format the output would be written in.
+    pub format: Format,
+    /// A description of the payload the embedding would overwrite, if one was found
+    /// under `OverwritePolicy::Warn`.
+    pub existing_payload: Option<String>,
+}
 
 /// Embeds data into a container image using LSB steganography.
 ///
 /// The data is embedded along with metadata: the original file extension,
 /// a hash of the data for integrity checking, and the total length of the payload.
+/// This is a shorthand for `embed_with` that never checks for an existing payload.
 ///
 /// # Arguments
 ///
 /// * `input`: A slice of bytes representing the data to be embedded.
 /// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
 /// * `container`: A slice of bytes representing the container image data.
-/// * `lsbs`: The number of least significant bits to use per color channel for embedding (1-8).
+/// * `lsbs`: The number of least significant bits to use per color channel for embedding (1-8,
+///   or 1-7 for HDR output, whose top mantissa bit must stay set).
 /// * `hash`: The hashing algorithm to use for checksumming the input data.
 /// * `seed`: A 64-bit seed for the pseudo-random number generator that determines pixel order.
-/// * `format`: The `ImageFormat` of the output image. Must be a lossless format.
+/// * `format`: The `Format` of the output image, or `None` for the format of the container.
+///   Must be a lossless format.
+///   HDR and OpenEXR outputs keep their floating-point samples and embed into the
+///   mantissa LSBs instead of converting the container to 8 bits.
 ///
 /// # Returns
 ///
@@ -32,38 +417,1143 @@ use super::{consts::*, error::*, hash::*, image::*};
 ///
 /// # Errors
 ///
+/// See `embed_with`.
+pub fn embed(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    lsbs: usize,
+    hash: Hash,
+    seed: u64,
+    format: impl Into<Option<Format>>,
+) -> StegResult<Vec<u8>> {
+    let options = EmbedOptions::new()
+        .lsbs(lsbs)
+        .hash(hash)
+        .seed(seed)
+        .format(format)
+        .overwrite_policy(OverwritePolicy::Allow)
+        .resize_policy(ResizePolicy::Never);
+
+    embed_with(input, extension, container, &options).map(|outcome| outcome.image)
+}
+
+/// Embeds data into a container image using LSB steganography, as configured by `options`.
+///
+/// Unless the overwrite policy is `OverwritePolicy::Allow`, the container is first
+/// checked for a plausible payload header under the same `lsbs` and `seed`, before
+/// any of its bits are modified.
+///
+/// The data can be anywhere from empty, e.g. to embed a marker, up to the `capacity_with`
+/// of the container, and at most `u32::MAX` bytes along with its metadata. An empty
+/// extension is embedded as it is.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `EmbedOutcome`, with the new image data and how it was
+/// embedded, if successful.
+///
+/// # Errors
+///
 /// This function can return errors for various reasons, including:
-/// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and `BITS_PER_BYTE`.
-/// * `StegError::UnsupportedFormat`: If the specified `format` is not lossless.
+/// * `StegError::InvalidLsbValue`: If `lsbs` is outside the range supported by `format`.
+/// * `StegError::UnsupportedFormat`: If the specified `format` is not lossless, if it is
+///   unset and the container is in a lossy format, or if it cannot store an image as large
+///   as the container, such as ICO beyond 256x256 pixels.
 /// * `StegError::ExtensionTooLong`: If the `extension` string is too long.
 /// * `StegError::InsufficientCapacity`: If the container image is too small to hold the data.
+/// * `StegError::ExistingPayload`: If the container already holds a payload and the
+///   overwrite policy is `OverwritePolicy::Error`.
 /// * Errors from the `image` crate during image decoding or encoding.
-pub fn embed(
+pub fn embed_with(
     input: &[u8],
     extension: &str,
     container: &[u8],
+    options: &EmbedOptions,
+) -> StegResult<EmbedOutcome> {
+    let (output, report) = embed_inner(
+        input,
+        extension,
+        &decode_container(container, options)?,
+        options,
+        &KeyedPermutation::new(options.seed),
+    )?;
+
+    Ok(EmbedOutcome::new(output, report))
+}
+
+/// Embeds data into a container image like `embed_with`, writing the output image into
+/// `writer` instead of returning it.
+///
+/// The output is encoded straight into the writer, so it is never held in memory as a
+/// whole. With `verify_after_embed`, the output is still buffered to be verified before
+/// anything is written.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+/// * `writer`: The sink the output image is written to, such as a file.
+///
+/// # Errors
+///
+/// See `embed_with`. Failures of the writer are returned as `StegError::Io` or
+/// `StegError::ImageProcessing`, and may leave a partial image in the writer.
+pub fn embed_to_writer<W: Write + Seek>(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    options: &EmbedOptions,
+    writer: W,
+) -> StegResult<()> {
+    Stego::from(options.clone()).embed_to_writer(
+        input,
+        extension,
+        &decode_container(container, options)?,
+        writer,
+    )
+}
+
+/// Embeds data read from `input` into a container image like `embed_to_writer`, without
+/// holding the data in memory as a whole.
+///
+/// The data is read, hashed and embedded in chunks, and the header is written last,
+/// once the checksum is known. With `verify_after_embed`, the output and the data
+/// extracted from it are still held in memory to be verified.
+///
+/// # Arguments
+///
+/// * `input`: The source of the data to be embedded. Exactly `input_len` bytes are read.
+/// * `input_len`: The length of the data, in bytes.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+/// * `writer`: The sink the output image is written to, such as a file.
+///
+/// # Errors
+///
+/// See `embed_to_writer`. Failures of `input`, including ending before `input_len`
+/// bytes, are returned as `StegError::Io`.
+pub fn embed_from_reader<R: Read, W: Write + Seek>(
+    input: R,
+    input_len: u64,
+    extension: &str,
+    container: &[u8],
+    options: &EmbedOptions,
+    writer: W,
+) -> StegResult<()> {
+    Stego::from(options.clone()).embed_from_reader(
+        input,
+        input_len,
+        extension,
+        &decode_container(container, options)?,
+        writer,
+    )
+}
+
+/// Embeds data produced in chunks into a container image like `embed_with`, without
+/// collecting it first.
+///
+/// The chunks are hashed and embedded as they come, like the data read by
+/// `embed_from_reader`, and the header is written last, once the checksum is known.
+/// The output is the same as embedding the concatenated chunks with `embed_with`.
+///
+/// # Arguments
+///
+/// * `chunks`: The chunks of the data to be embedded, in order. They must add up to
+///   exactly `total_len` bytes.
+/// * `total_len`: The length of the data, in bytes, needed up front for the header and
+///   the capacity check.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing a `Vec<u8>` of the new image data with the embedded content
+/// if successful, or a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// See `embed_with`. Additionally:
+/// * `StegError::Io`: If the chunks add up to fewer or more than `total_len` bytes.
+pub fn embed_chunks<I>(
+    chunks: I,
+    total_len: u64,
+    extension: &str,
+    container: &[u8],
+    options: &EmbedOptions,
+) -> StegResult<Vec<u8>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut input = ChunkReader::new(chunks.into_iter());
+    let mut output = Cursor::new(Vec::new());
+
+    embed_reader_inner(
+        &mut input,
+        total_len,
+        extension,
+        &decode_container(container, options)?,
+        options,
+        &KeyedPermutation::new(options.seed),
+        &mut output,
+    )?;
+
+    if input.has_more() {
+        return Err(StegError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The chunks hold more than {} bytes", total_len),
+        )));
+    }
+
+    Ok(output.into_inner())
+}
+
+/// Reads the chunks of an iterator one after the other.
+struct ChunkReader<I: Iterator> {
+    chunks: I,
+    current: Option<I::Item>,
+    /// The number of bytes of `current` already read.
+    position: usize,
+}
+
+impl<I: Iterator<Item: AsRef<[u8]>>> ChunkReader<I> {
+    fn new(chunks: I) -> Self {
+        ChunkReader {
+            chunks,
+            current: None,
+            position: 0,
+        }
+    }
+
+    /// Moves on to the next chunk that is not read yet, returning its unread bytes, or
+    /// `None` after the last chunk.
+    fn unread(&mut self) -> Option<&[u8]> {
+        while self
+            .current
+            .as_ref()
+            .is_none_or(|chunk| self.position == chunk.as_ref().len())
+        {
+            self.current = Some(self.chunks.next()?);
+            self.position = 0;
+        }
+
+        self.current
+            .as_ref()
+            .map(|chunk| &chunk.as_ref()[self.position..])
+    }
+
+    /// Returns whether any bytes are left to read.
+    fn has_more(&mut self) -> bool {
+        self.unread().is_some()
+    }
+}
+
+impl<I: Iterator<Item: AsRef<[u8]>>> Read for ChunkReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(unread) = self.unread() else {
+            return Ok(0);
+        };
+
+        let len = unread.len().min(buf.len());
+        buf[..len].copy_from_slice(&unread[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Embeds data into a container image like `embed_with`, and reports statistics about it.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing a tuple `(Vec<u8>, EmbedReport)` with the new image data
+/// and the statistics of the embedding, if successful.
+///
+/// # Errors
+///
+/// See `embed_with`.
+pub fn embed_with_report(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    options: &EmbedOptions,
+) -> StegResult<(Vec<u8>, EmbedReport)> {
+    let start = Instant::now();
+
+    let (output, mut report) = embed_inner(
+        input,
+        extension,
+        &decode_container(container, options)?,
+        options,
+        &KeyedPermutation::new(options.seed),
+    )?;
+    report.elapsed = start.elapsed();
+
+    Ok((output, report))
+}
+
+/// Checks whether an embedding would succeed, and computes its parameters, without
+/// producing an image.
+///
+/// Everything `embed_with` validates is validated, in the same order and with the same
+/// errors. Only the image header is read, unless the overwrite policy requires looking
+/// for an existing payload, so this is much cheaper than embedding into a large image.
+///
+/// # Arguments
+///
+/// * `input_len`: The length, in bytes, of the data to embed.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `EmbedPlan` if the embedding would succeed.
+///
+/// # Errors
+///
+/// See `embed_with`.
+pub fn plan_embed(
+    input_len: usize,
+    extension: &str,
+    container: &[u8],
+    options: &EmbedOptions,
+) -> StegResult<EmbedPlan> {
+    let options = &*options.for_container(guess_format(container))?;
+    let plan = plan_resized(input_len, extension, dimensions(container)?, options)?;
+
+    if options.overwrite_policy == OverwritePolicy::Allow {
+        return Ok(plan);
+    }
+
+    plan_for(
+        input_len,
+        extension,
+        &StegoContainer::new(container)?,
+        options,
+    )
+}
+
+/// Plans an embedding into a decoded container, looking for an existing payload
+/// unless the overwrite policy is `OverwritePolicy::Allow`.
+pub(crate) fn plan_for(
+    input_len: usize,
+    extension: &str,
+    container: &StegoContainer,
+    options: &EmbedOptions,
+) -> StegResult<EmbedPlan> {
+    let options = &*options.for_container(container.format())?;
+    let mut plan = plan_resized(input_len, extension, container.dimensions(), options)?;
+
+    if options.overwrite_policy != OverwritePolicy::Allow {
+        let carrier = planned_carrier(container.image(), &plan, options);
+        plan.existing_payload = check_existing(
+            &carrier.plane,
+            plan.lsbs,
+            &KeyedPermutation::new(options.seed),
+            options.overwrite_policy,
+        )?;
+    }
+
+    Ok(plan)
+}
+
+/// Embeds data into a container image, placing the payload bits with a custom `PixelOrder`.
+///
+/// This behaves like `embed_with`, except that the positions of the payload bits come
+/// from `order` instead of a `KeyedPermutation` of `options.seed`, which is ignored.
+/// The image must be extracted with the same order.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: A slice of bytes representing the container image data.
+/// * `options`: The parameters of the embedding.
+/// * `order`: The order in which the payload bits are placed into the container.
+///
+/// # Returns
+///
+/// A `StegResult` containing a `Vec<u8>` of the new image data with the embedded content
+/// if successful, or a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// See `embed_with`.
+pub fn embed_with_order(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+) -> StegResult<Vec<u8>> {
+    embed_inner(
+        input,
+        extension,
+        &decode_container(container, options)?,
+        options,
+        order,
+    )
+    .map(|(output, _)| output)
+}
+
+/// Decodes a container, reporting `Phase::Decode` to the callback of `options`, and
+/// checking whether the embedding was cancelled meanwhile.
+pub(crate) fn decode_container(
+    container: &[u8],
+    options: &EmbedOptions,
+) -> StegResult<StegoContainer> {
+    let container = StegoContainer::new(container)?;
+    milestone(options.progress.as_ref(), Phase::Decode);
+    check_cancelled(options.cancel.as_ref())?;
+
+    Ok(container)
+}
+
+/// Embeds the data, reporting everything but the elapsed time, which is not
+/// available on every platform.
+#[instrument(
+    name = "embed",
+    skip_all,
+    fields(
+        data_bytes = input.len(),
+        extension = extension,
+        width = container.dimensions().0,
+        height = container.dimensions().1,
+        lsbs = field::Empty
+    )
+)]
+pub(crate) fn embed_inner(
+    input: &[u8],
+    extension: &str,
+    container: &StegoContainer,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+) -> StegResult<(Vec<u8>, EmbedReport)> {
+    let options = &*options.for_container(container.format())?;
+
+    with_threads(options.threads, || {
+        embed_encoded(input, extension, container, options, order)
+    })
+}
+
+/// Embeds the data like `embed_inner`, in the pool of the calling thread.
+fn embed_encoded(
+    input: &[u8],
+    extension: &str,
+    container: &StegoContainer,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+) -> StegResult<(Vec<u8>, EmbedReport)> {
+    let (carrier, report) = embed_carrier(
+        input,
+        extension,
+        container.image(),
+        container.format(),
+        options,
+        order,
+    )?;
+    let output = encode(
+        carrier,
+        options.output_format(),
+        &options.output,
+        container.metadata(),
+    )?;
+    milestone(options.progress.as_ref(), Phase::Encode);
+
+    if options.verify_after_embed {
+        check_cancelled(options.cancel.as_ref())?;
+        verify(
+            &output,
+            input,
+            extension,
+            report.lsbs,
+            options.output_format(),
+            order,
+        )?;
+        debug!("Verified the output");
+        milestone(options.progress.as_ref(), Phase::Verify);
+    }
+
+    Ok((output, report))
+}
+
+/// Embeds the data like `embed_inner`, writing the output into `writer`.
+#[instrument(
+    name = "embed",
+    skip_all,
+    fields(
+        data_bytes = input.len(),
+        extension = extension,
+        width = container.dimensions().0,
+        height = container.dimensions().1,
+        lsbs = field::Empty
+    )
+)]
+pub(crate) fn embed_inner_to<W: Write + Seek>(
+    input: &[u8],
+    extension: &str,
+    container: &StegoContainer,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+    mut writer: W,
+) -> StegResult<()> {
+    let options = &*options.for_container(container.format())?;
+
+    with_threads(options.threads, || {
+        // Verification needs the encoded output, so it cannot be streamed
+        if options.verify_after_embed {
+            let (output, _) = embed_encoded(input, extension, container, options, order)?;
+            writer.write_all(&output)?;
+            return Ok(());
+        }
+
+        let (carrier, _) = embed_carrier(
+            input,
+            extension,
+            container.image(),
+            container.format(),
+            options,
+            order,
+        )?;
+        encode_to(
+            carrier,
+            options.output_format(),
+            &options.output,
+            container.metadata(),
+            &mut writer,
+        )?;
+        milestone(options.progress.as_ref(), Phase::Encode);
+
+        Ok(())
+    })
+}
+
+/// Embeds data streamed from `input` like `embed_inner_to`, reporting everything but the
+/// elapsed time.
+#[instrument(
+    name = "embed",
+    skip_all,
+    fields(
+        data_bytes = input_len,
+        extension = extension,
+        width = container.dimensions().0,
+        height = container.dimensions().1,
+        lsbs = field::Empty
+    )
+)]
+pub(crate) fn embed_reader_inner<R: Read, W: Write + Seek>(
+    mut input: R,
+    input_len: u64,
+    extension: &str,
+    container: &StegoContainer,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+    mut writer: W,
+) -> StegResult<EmbedReport> {
+    let options = &*options.for_container(container.format())?;
+
+    with_threads(options.threads, || {
+        let input_len = usize::try_from(input_len).map_err(|_| {
+            StegError::CalculationOverflow(format!(
+                "Payload length exceeds maximum size: {} bytes of data",
+                input_len
+            ))
+        })?;
+        let plan = plan_resized(input_len, extension, container.dimensions(), options)?;
+        Span::current().record("lsbs", plan.lsbs);
+
+        debug!(
+            "Preparing to stream: {} bytes ({} bits)",
+            plan.payload_bytes,
+            plan.payload_bytes * BITS_PER_BYTE
+        );
+
+        let mut warnings = planned_warnings(&plan, container.image(), container.format(), options);
+        let mut carrier = planned_carrier(container.image(), &plan, options);
+        let bits_flipped = embed_stream(
+            &mut carrier.plane,
+            &mut input,
+            extension,
+            &plan,
+            options,
+            order,
+            &mut warnings,
+        )?;
+        debug!("Flipped {} bits", bits_flipped);
+        let report = planned_report(&plan, bits_flipped, warnings);
+
+        // Verification needs the encoded output, so it cannot be streamed
+        if options.verify_after_embed {
+            let output = encode(
+                carrier,
+                options.output_format(),
+                &options.output,
+                container.metadata(),
+            )?;
+            milestone(options.progress.as_ref(), Phase::Encode);
+            check_cancelled(options.cancel.as_ref())?;
+            let data = verify_extraction(
+                &output,
+                extension,
+                plan.lsbs,
+                options.output_format(),
+                order,
+            )?;
+            // The extraction checked the data against the checksum of the input
+            if data.len() != input_len {
+                return Err(StegError::SelfVerificationFailed(format!(
+                    "{} bytes were extracted instead of {}",
+                    data.len(),
+                    input_len
+                )));
+            }
+            debug!("Verified the output");
+            milestone(options.progress.as_ref(), Phase::Verify);
+
+            writer.write_all(&output)?;
+            return Ok(report);
+        }
+
+        encode_to(
+            carrier,
+            options.output_format(),
+            &options.output,
+            container.metadata(),
+            &mut writer,
+        )?;
+        milestone(options.progress.as_ref(), Phase::Encode);
+
+        Ok(report)
+    })
+}
+
+/// Embeds the data into a carrier of a decoded container, before it is encoded.
+///
+/// Every embedding goes through here, so this is where the color type of the container
+/// is converted to the carrier of the output format.
+///
+/// `container_format` is the format the container was decoded from, if known.
+fn embed_carrier(
+    input: &[u8],
+    extension: &str,
+    container: &DynamicImage,
+    container_format: Option<Format>,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+) -> StegResult<(Carrier, EmbedReport)> {
+    let plan = plan_resized(
+        input.len(),
+        extension,
+        (container.width(), container.height()),
+        options,
+    )?;
+    let total_len_bits = plan.payload_bytes * BITS_PER_BYTE;
+    Span::current().record("lsbs", plan.lsbs);
+
+    debug!(
+        "Preparing to embed: {} bytes ({} bits)",
+        plan.payload_bytes, total_len_bits
+    );
+    debug!("Data: {} bytes", input.len());
+
+    let mut warnings = planned_warnings(&plan, container, container_format, options);
+    let mut carrier = planned_carrier(container, &plan, options);
+    let bits_flipped = embed_plane(
+        &mut carrier.plane,
+        input,
+        extension,
+        &plan,
+        options,
+        order,
+        &mut warnings,
+    )?;
+    debug!("Flipped {} of {} bits", bits_flipped, total_len_bits);
+
+    Ok((carrier, planned_report(&plan, bits_flipped, warnings)))
+}
+
+/// The warnings a planned embedding into a container decoded from `container_format`
+/// raises before any bit is written.
+fn planned_warnings(
+    plan: &EmbedPlan,
+    container: &DynamicImage,
+    container_format: Option<Format>,
+    options: &EmbedOptions,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    if container.color().has_alpha() {
+        warnings.push(Warning::AlphaDropped);
+    }
+    if let Some(from) = container_format
+        && from != options.output_format()
+    {
+        warnings.push(Warning::FormatChanged {
+            from,
+            to: options.output_format(),
+        });
+    }
+    let dimensions = (container.width(), container.height());
+    if (plan.width, plan.height) != dimensions {
+        warnings.push(Warning::Upscaled {
+            from: dimensions,
+            to: (plan.width, plan.height),
+        });
+    }
+    let capacity_used = (plan.utilization / 100.0) as f32;
+    if capacity_used > HIGH_UTILIZATION {
+        warnings.push(Warning::HighUtilization { capacity_used });
+    }
+
+    for warning in &warnings {
+        debug!("{}", warning);
+    }
+    warnings
+}
+
+/// Reports a planned embedding that flipped `bits_flipped` bits, without its elapsed time.
+fn planned_report(plan: &EmbedPlan, bits_flipped: usize, warnings: Vec<Warning>) -> EmbedReport {
+    let total_len_bits = plan.payload_bytes * BITS_PER_BYTE;
+
+    EmbedReport {
+        data_bytes: plan.data_bytes,
+        payload_bytes: plan.payload_bytes,
+        lsbs: plan.lsbs,
+        width: plan.width,
+        height: plan.height,
+        capacity_bits: plan.capacity_bits,
+        bits_written: total_len_bits,
+        bits_flipped,
+        utilization: total_len_bits as f64 / plan.capacity_bits as f64 * 100.0,
+        elapsed: Duration::ZERO,
+        warnings,
+    }
+}
+
+/// Embeds data into an already decoded image in place, using LSB steganography.
+///
+/// This is what `embed` does between decoding the container and encoding the output,
+/// for callers that already hold a decoded image. The result is the same as decoding
+/// the output of `embed` into an `RgbImage` with PNG output.
+/// `embed_image` does the same with `EmbedOptions`, leaving the original image as is.
+///
+/// # Arguments
+///
+/// * `image`: The image to embed the data into.
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `lsbs`: The number of least significant bits to use per color channel for embedding (1-8).
+/// * `hash`: The hashing algorithm to use for checksumming the input data.
+/// * `seed`: A 64-bit seed for the pseudo-random number generator that determines pixel order.
+///
+/// # Errors
+///
+/// See `embed_with`. The image is left untouched when an error is returned.
+pub fn embed_into_image(
+    image: &mut RgbImage,
+    input: &[u8],
+    extension: &str,
     lsbs: usize,
     hash: Hash,
     seed: u64,
-    format: ImageFormat,
-) -> StegResult<Vec<u8>> {
-    if lsbs == 0 || lsbs > BITS_PER_BYTE {
-        return Err(StegError::InvalidLsbValue(format!(
-            "lsbs must be between 1 and {} inclusive",
-            BITS_PER_BYTE
+) -> StegResult<()> {
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(lsbs),
+        hash,
+        seed,
+        ..EmbedOptions::default()
+    };
+    let plan = plan(input.len(), extension, image.dimensions(), &options)?;
+
+    let bits_flipped = embed_plane(
+        image,
+        input,
+        extension,
+        &plan,
+        &options,
+        &KeyedPermutation::new(seed),
+        &mut Vec::new(),
+    )?;
+    debug!("Flipped {} bits in place", bits_flipped);
+
+    Ok(())
+}
+
+/// Embeds data into a buffer of raw interleaved RGB8 pixels in place, like
+/// `embed_into_image`.
+///
+/// This is for pipelines that hold plain pixel buffers, such as video frames or buffers
+/// passed over FFI, and do not use `image` types. The buffer holds `width` x `height`
+/// pixels row by row, each as its red, green and blue samples.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `pixels`: The RGB8 samples to embed the data into.
+/// * `width`: The width of the image, in pixels.
+/// * `height`: The height of the image, in pixels.
+/// * `lsbs`: The number of least significant bits to use per color channel for embedding (1-8).
+/// * `hash`: The hashing algorithm to use for checksumming the input data.
+/// * `seed`: A 64-bit seed for the pseudo-random number generator that determines pixel order.
+///
+/// # Errors
+///
+/// See `embed_with`. Additionally:
+/// * `StegError::BufferSizeMismatch`: If `pixels` does not hold exactly `width` x `height`
+///   pixels.
+///
+/// The buffer is left untouched when an error is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn embed_raw_rgb(
+    input: &[u8],
+    extension: &str,
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    lsbs: usize,
+    hash: Hash,
+    seed: u64,
+) -> StegResult<()> {
+    let mut image = raw_rgb_image(pixels, width, height)?;
+    embed_into_image(&mut image, input, extension, lsbs, hash, seed)?;
+
+    pixels.copy_from_slice(&image);
+    Ok(())
+}
+
+/// Embeds data into an already decoded image, returning a copy with the embedded content.
+///
+/// This is `embed_with` without the decoding and encoding steps, for callers that hold
+/// decoded images and encode them on their own. The options are validated just like by
+/// `embed_with`, including the overwrite and resize policies, `LsbSelection::Auto` and
+/// `verify_after_embed`, which extracts the data from the returned image. The result
+/// is the same as decoding the output of `embed_with` into an `RgbImage`.
+///
+/// `options.output_format()` is the format the returned image will be encoded to. It is checked
+/// like by `embed_with`, and must keep 8-bit samples, which rules out HDR and OpenEXR.
+///
+/// # Arguments
+///
+/// * `image`: The image to embed the data into.
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `RgbImage` with the embedded content if successful, or
+/// a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// See `embed_with`. Additionally:
+/// * `StegError::UnsupportedOption`: If `options.output_format()` does not use 8-bit samples.
+pub fn embed_image(
+    image: &RgbImage,
+    input: &[u8],
+    extension: &str,
+    options: &EmbedOptions,
+) -> StegResult<RgbImage> {
+    if CarrierKind::for_format(options.output_format()) != CarrierKind::Rgb8 {
+        return Err(StegError::UnsupportedOption(format!(
+            "{:?} output does not use 8-bit samples, use embed_with for it instead",
+            options.output_format()
         )));
     }
 
-    if !LOSSLESS_FORMATS.contains(&format) {
-        return Err(StegError::UnsupportedFormat(format!(
-            "Format {:?} is not supported for embedding",
-            format
+    let container = DynamicImage::ImageRgb8(image.clone());
+
+    with_threads(options.threads, || {
+        embed_decoded(input, extension, &container, options).map(|carrier| carrier.plane)
+    })
+}
+
+/// Embeds data into an already decoded image of any color type, returning the image with
+/// the embedded content.
+///
+/// This is `embed_with` without the decoding and encoding steps, for callers working
+/// with the `image` crate. The container is converted to the samples of `options.output_format()`
+/// just like a decoded container is by `embed_with`, so the returned image holds 8-bit
+/// RGB samples, or 32-bit float ones for HDR and OpenEXR output, whatever the color type
+/// of `container`. The options are validated like by `embed_image`.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the data to be embedded.
+/// * `extension`: The file extension of the input data (e.g., "txt", "jpg").
+/// * `container`: The decoded container image.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `DynamicImage` with the embedded content if successful,
+/// or a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// See `embed_with`.
+pub fn embed_dynamic(
+    input: &[u8],
+    extension: &str,
+    container: &DynamicImage,
+    options: &EmbedOptions,
+) -> StegResult<DynamicImage> {
+    with_threads(options.threads, || {
+        embed_decoded(input, extension, container, options).map(Carrier::into_image)
+    })
+}
+
+/// Embeds the data into a carrier of a decoded container like `embed_carrier`, verifying
+/// the carrier instead of the encoded output when requested.
+fn embed_decoded(
+    input: &[u8],
+    extension: &str,
+    container: &DynamicImage,
+    options: &EmbedOptions,
+) -> StegResult<Carrier> {
+    let order = KeyedPermutation::new(options.seed);
+    let (carrier, report) = embed_carrier(input, extension, container, None, options, &order)?;
+
+    if options.verify_after_embed {
+        check_cancelled(options.cancel.as_ref())?;
+        let data = verify_plane(&carrier.plane, extension, report.lsbs, &order)?;
+        check_verified(&data, input)?;
+        debug!("Verified the output");
+        milestone(options.progress.as_ref(), Phase::Verify);
+    }
+
+    Ok(carrier)
+}
+
+/// Builds the payload for a planned embedding and writes it into an 8-bit plane,
+/// returning the number of bits flipped. An existing payload it overwrites is added to
+/// `warnings`.
+fn embed_plane(
+    image: &mut RgbImage,
+    input: &[u8],
+    extension: &str,
+    plan: &EmbedPlan,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+    warnings: &mut Vec<Warning>,
+) -> StegResult<usize> {
+    let total = build_payload(
+        input,
+        extension,
+        options.hash,
+        fingerprint(image, plan.lsbs),
+    )?;
+
+    if let Some(message) = check_existing(image, plan.lsbs, order, options.overwrite_policy)? {
+        debug!("{}, overwriting it", message);
+        warnings.push(Warning::PayloadOverwritten(message));
+    }
+
+    embed_bytes(image, &total, plan.lsbs, order, options)
+}
+
+/// Builds the header for a planned embedding and streams the data from `input` into an
+/// 8-bit plane, returning the number of bits flipped, like `embed_plane`.
+///
+/// The header comes first in the order but depends on the checksum of the data, so its
+/// positions are kept aside and written once all the data has been read.
+#[instrument(level = "debug", skip_all, fields(data_bytes = plan.data_bytes, lsbs = plan.lsbs))]
+fn embed_stream(
+    image: &mut RgbImage,
+    input: &mut impl Read,
+    extension: &str,
+    plan: &EmbedPlan,
+    options: &EmbedOptions,
+    order: &impl PixelOrder,
+    warnings: &mut Vec<Warning>,
+) -> StegResult<usize> {
+    let lsbs = plan.lsbs;
+    let fingerprint = fingerprint(image, lsbs);
+
+    if let Some(message) = check_existing(image, lsbs, order, options.overwrite_policy)? {
+        debug!("{}, overwriting it", message);
+        warnings.push(Warning::PayloadOverwritten(message));
+    }
+
+    let header_bits = (plan.payload_bytes - plan.data_bytes) * BITS_PER_BYTE;
+    let capacity_bits = capacity_bits(image.width(), image.height(), lsbs)?;
+    let mut positions = order.positions(capacity_bits, (plan.payload_bytes * BITS_PER_BYTE) as u64);
+    let header_positions = positions.by_ref().take(header_bits).collect::<Vec<_>>();
+
+    let mut hasher = select_hasher(options.hash);
+    let mut buffer = vec![0; READ_CHUNK_SIZE.min(plan.data_bytes)];
+    let mut remaining = plan.data_bytes;
+    let mut bits_flipped = 0;
+    let progress = Tracker::new(
+        options.progress.as_ref(),
+        options.cancel.as_ref(),
+        Phase::Embed,
+        (plan.payload_bytes * BITS_PER_BYTE) as u64,
+    );
+
+    while remaining > 0 {
+        progress.check()?;
+        let chunk = &mut buffer[..remaining.min(READ_CHUNK_SIZE)];
+        input.read_exact(chunk)?;
+        hasher.update(chunk);
+        bits_flipped += write_bits(image, chunk, lsbs, &mut positions);
+        remaining -= chunk.len();
+        progress.advance((chunk.len() * BITS_PER_BYTE) as u64);
+    }
+
+    let mut header = Vec::with_capacity(header_size(options.hash, extension.len()));
+    write_header(
+        &mut header,
+        plan.data_bytes,
+        extension,
+        options.hash,
+        fingerprint,
+        &hasher.finalize_reset(),
+    )?;
+    bits_flipped += write_bits(image, &header, lsbs, &mut header_positions.into_iter());
+    progress.advance(header_bits as u64);
+
+    Ok(bits_flipped)
+}
+
+/// Checks that an encoded output extracts back to the input it was embedded from.
+fn verify(
+    output: &[u8],
+    input: &[u8],
+    extension: &str,
+    lsbs: usize,
+    format: Format,
+    order: &impl PixelOrder,
+) -> StegResult<()> {
+    let data = verify_extraction(output, extension, lsbs, format, order)?;
+
+    check_verified(&data, input)
+}
+
+/// Compares the data extracted by a verification with the input.
+fn check_verified(data: &[u8], input: &[u8]) -> StegResult<()> {
+    if data != input {
+        let offset = data
+            .iter()
+            .zip(input)
+            .position(|(a, b)| a != b)
+            .unwrap_or(data.len().min(input.len()));
+        return Err(StegError::SelfVerificationFailed(format!(
+            "the data differs from the input at byte {} ({} bytes extracted, {} expected)",
+            offset,
+            data.len(),
+            input.len()
         )));
     }
 
-    let total = build_payload(input, extension, hash)?;
+    Ok(())
+}
+
+/// Extracts an encoded output again, checking its extension, and returns its data.
+fn verify_extraction(
+    output: &[u8],
+    extension: &str,
+    lsbs: usize,
+    format: Format,
+    order: &impl PixelOrder,
+) -> StegResult<Vec<u8>> {
+    let carrier = decode_as(output, format).map_err(|e| {
+        StegError::SelfVerificationFailed(format!("decoding the output failed: {}", e))
+    })?;
+
+    verify_plane(&carrier.plane, extension, lsbs, order)
+}
+
+/// Extracts an output plane again, checking its extension, and returns its data.
+fn verify_plane(
+    plane: &RgbImage,
+    extension: &str,
+    lsbs: usize,
+    order: &impl PixelOrder,
+) -> StegResult<Vec<u8>> {
+    let (data, ext) = extract_plane(plane, lsbs, order).map_err(|e| {
+        StegError::SelfVerificationFailed(format!("extracting the output failed: {}", e))
+    })?;
+
+    if ext != extension {
+        return Err(StegError::SelfVerificationFailed(format!(
+            "the extension was extracted as {:?} instead of {:?}",
+            ext, extension
+        )));
+    }
+
+    Ok(data)
+}
+
+/// Validates an embedding and computes its parameters from the container dimensions.
+pub(crate) fn plan(
+    input_len: usize,
+    extension: &str,
+    (width, height): (u32, u32),
+    options: &EmbedOptions,
+) -> StegResult<EmbedPlan> {
+    let EmbedOptions {
+        lsbs, hash, seed, ..
+    } = *options;
+    let format = options.output_format();
+
+    check_output_format(format)?;
+    check_output_dimensions(format, (width, height))?;
+
+    let max_lsbs = CarrierKind::for_format(format).max_lsbs();
+    if let LsbSelection::Fixed(lsbs) = lsbs {
+        check_lsbs(lsbs, max_lsbs)?;
+    }
+
+    if extension.len() > u8::MAX as usize {
+        return Err(StegError::ExtensionTooLong {
+            len: extension.len(),
+            max: u8::MAX as usize,
+        });
+    }
+
+    // The most bits that can be used, as the header must fit whichever `lsbs` is selected
+    let most_lsbs = match lsbs {
+        LsbSelection::Fixed(lsbs) => lsbs,
+        LsbSelection::Auto => max_lsbs,
+    };
+    check_dimensions(
+        (width, height),
+        most_lsbs,
+        header_size(hash, extension.len()),
+    )?;
+
+    let length_size = core::mem::size_of::<u32>();
+    let total_len = input_len
+        .checked_add(header_size(hash, extension.len()))
+        .filter(|&total_len| total_len - length_size <= u32::MAX as usize)
+        .ok_or_else(|| {
+            StegError::CalculationOverflow(format!(
+                "Payload length exceeds maximum size: {} bytes of data",
+                input_len
+            ))
+        })?;
 
-    let total_len = total.len();
     // Potential overflow when calculating total_len_bits
     let total_len_bits = total_len.checked_mul(BITS_PER_BYTE).ok_or_else(|| {
         StegError::CalculationOverflow(format!(
@@ -72,74 +1562,328 @@ pub fn embed(
         ))
     })?;
 
+    let lsbs = match lsbs {
+        LsbSelection::Fixed(lsbs) => lsbs,
+        LsbSelection::Auto => {
+            let samples = capacity_bits(width, height, 1)?;
+            if samples == 0 || (total_len_bits as u64).div_ceil(samples) > max_lsbs as u64 {
+                return Err(insufficient_capacity(
+                    total_len_bits as u64,
+                    samples * max_lsbs as u64,
+                    max_lsbs,
+                ));
+            }
+            let lsbs = (total_len_bits as u64).div_ceil(samples).max(1) as usize;
+            debug!("Selected lsbs: {}", lsbs);
+            lsbs
+        }
+    };
+
+    let capacity_bits = capacity_bits(width, height, lsbs)?;
+
+    if total_len_bits as u64 > capacity_bits {
+        return Err(insufficient_capacity(
+            total_len_bits as u64,
+            capacity_bits,
+            lsbs,
+        ));
+    }
+
+    Ok(EmbedPlan {
+        data_bytes: input_len,
+        payload_bytes: total_len,
+        lsbs,
+        width,
+        height,
+        capacity_bits,
+        utilization: total_len_bits as f64 / capacity_bits as f64 * 100.0,
+        hash,
+        seed,
+        format,
+        existing_payload: None,
+    })
+}
+
+/// Plans an embedding like `plan`, upscaling the container if it is too small and
+/// the resize policy allows it.
+fn plan_resized(
+    input_len: usize,
+    extension: &str,
+    (width, height): (u32, u32),
+    options: &EmbedOptions,
+) -> StegResult<EmbedPlan> {
+    let original = plan(input_len, extension, (width, height), options);
+
+    let ResizePolicy::UpscaleToFit { max_scale, .. } = options.resize_policy else {
+        return original;
+    };
+    let Err(StegError::InsufficientCapacity { .. }) = original else {
+        return original;
+    };
+
+    let lsbs = match options.lsbs {
+        LsbSelection::Fixed(lsbs) => lsbs,
+        LsbSelection::Auto => CarrierKind::for_format(options.output_format()).max_lsbs(),
+    };
+    // Cannot overflow, as `plan` only failed on the capacity
+    let total_len_bits = (input_len + header_size(options.hash, extension.len())) * BITS_PER_BYTE;
+    let pixels = pixels_for_bits(total_len_bits as u64, lsbs) as f64;
+
+    let scale = (pixels / (width as f64 * height as f64)).sqrt();
+    let size = (
+        (width as f64 * scale).ceil(),
+        (height as f64 * scale).ceil(),
+    );
+    let scale = f64::max(size.0 / width as f64, size.1 / height as f64);
+
+    if !scale.is_finite() || scale > max_scale as f64 {
+        debug!(
+            "Fitting the payload would need upscaling by {:.3}, more than the maximum of {}",
+            scale, max_scale
+        );
+        return original;
+    }
+
     debug!(
-        "Preparing to embed: {} bytes ({} bits)",
-        total_len, total_len_bits
+        "Upscaling the container from {}x{} to {}x{}",
+        width, height, size.0, size.1
     );
-    debug!("Data: {} bytes", input.len());
 
-    let image = decode(container)?;
+    plan(
+        input_len,
+        extension,
+        (size.0 as u32, size.1 as u32),
+        options,
+    )
+}
+
+/// Builds the carrier for a planned embedding, resizing the container if needed.
+fn planned_carrier(container: &DynamicImage, plan: &EmbedPlan, options: &EmbedOptions) -> Carrier {
+    let kind = CarrierKind::for_format(options.output_format());
 
-    let capacity_bits = image.len() * lsbs;
+    match options.resize_policy {
+        ResizePolicy::UpscaleToFit { filter, .. }
+            if (plan.width, plan.height) != (container.width(), container.height()) =>
+        {
+            Carrier::new(
+                container.resize_exact(plan.width, plan.height, filter),
+                kind,
+            )
+        }
+        _ => Carrier::new(container.clone(), kind),
+    }
+}
 
-    if total_len_bits > capacity_bits {
-        return Err(StegError::InsufficientCapacity(format!(
-            "Container is too small to hold the data: {} bits required, {} bits available",
-            total_len_bits, capacity_bits
-        )));
+/// Looks for a payload the embedding would destroy, as required by `policy`.
+///
+/// Returns a description of the payload found under `OverwritePolicy::Warn`.
+fn check_existing(
+    image: &RgbImage,
+    lsbs: usize,
+    order: &impl PixelOrder,
+    policy: OverwritePolicy,
+) -> StegResult<Option<String>> {
+    if policy == OverwritePolicy::Allow {
+        return Ok(None);
+    }
+
+    let Ok(header) =
+        PayloadReader::new(image, lsbs, order).and_then(|mut reader| read_header(&mut reader))
+    else {
+        return Ok(None);
+    };
+
+    let message = format!(
+        "Container already holds a {} byte payload with extension {:?} and a {:?} checksum",
+        header.length, header.extension, header.hash
+    );
+    match policy {
+        OverwritePolicy::Error => Err(StegError::ExistingPayload(message)),
+        _ => Ok(Some(message)),
+    }
+}
+
+/// An index of an embeddable bit or a payload bit, stored no wider than the container needs.
+pub(crate) trait BitIndex: Copy + Ord + Send + Sync {
+    fn from_u64(index: u64) -> Self;
+    fn to_u64(self) -> u64;
+}
+
+impl BitIndex for u32 {
+    fn from_u64(index: u64) -> Self {
+        index as u32
     }
 
-    let image = embed_bytes(image, total, lsbs, seed);
+    fn to_u64(self) -> u64 {
+        self as u64
+    }
+}
 
-    let output = encode(image, format)?;
+impl BitIndex for u64 {
+    fn from_u64(index: u64) -> Self {
+        index
+    }
 
-    Ok(output)
+    fn to_u64(self) -> u64 {
+        self
+    }
 }
 
-fn embed_bytes(mut image: RgbImage, total: Vec<u8>, lsbs: usize, seed: u64) -> RgbImage {
-    let capacity_bits = image.len() * lsbs;
+#[instrument(level = "debug", skip_all, fields(bytes = total.len(), lsbs = lsbs))]
+fn embed_bytes(
+    image: &mut RgbImage,
+    total: &[u8],
+    lsbs: usize,
+    order: &impl PixelOrder,
+    options: &EmbedOptions,
+) -> StegResult<usize> {
+    let capacity_bits = capacity_bits(image.width(), image.height(), lsbs)?;
+    let total_len_bits = (total.len() * BITS_PER_BYTE) as u64;
+    let inverse_ord = InverseOrder::new(order, capacity_bits, total_len_bits);
+    milestone(options.progress.as_ref(), Phase::Order);
+
+    let progress = Tracker::new(
+        options.progress.as_ref(),
+        options.cancel.as_ref(),
+        Phase::Embed,
+        total_len_bits,
+    );
+    progress.check()?;
+    let bits_flipped = inverse_ord.embed(image, 0, total, lsbs, &progress);
+    // Cancelled chunks were skipped, leaving the payload incomplete
+    progress.check()?;
 
-    let order = generate_order(seed, capacity_bits);
+    Ok(bits_flipped)
+}
 
-    let total_len_bits = total.len() * BITS_PER_BYTE;
+/// The payload bits paired with their positions in the container, sorted by position,
+/// so that any part of the container can be written on its own.
+pub(crate) enum InverseOrder {
+    Narrow(Vec<(u32, u32)>),
+    Wide(Vec<(u64, u64)>),
+}
 
+impl InverseOrder {
+    /// Generates the positions of the first `total_len_bits` bits of `order`.
+    #[instrument(
+        name = "order",
+        level = "debug",
+        skip_all,
+        fields(capacity_bits = capacity_bits, bits = total_len_bits)
+    )]
+    pub(crate) fn new(order: &impl PixelOrder, capacity_bits: u64, total_len_bits: u64) -> Self {
+        // Pairs of `u32` take half the memory of pairs of `u64`, and every payload bit
+        // index is below `capacity_bits`
+        if u32::try_from(capacity_bits).is_ok() {
+            debug!("Using 32-bit bit indices");
+            InverseOrder::Narrow(inverse_order(order, capacity_bits, total_len_bits))
+        } else {
+            debug!("Using 64-bit bit indices");
+            InverseOrder::Wide(inverse_order(order, capacity_bits, total_len_bits))
+        }
+    }
+
+    /// Writes the payload bits that fall into `samples`, the part of the container
+    /// starting at sample `first_sample`, returning the number of bits flipped.
+    ///
+    /// The bits written are reported to `progress` once per chunk, and the chunks left
+    /// once the operation is cancelled are skipped.
+    pub(crate) fn embed(
+        &self,
+        samples: &mut [u8],
+        first_sample: usize,
+        total: &[u8],
+        lsbs: usize,
+        progress: &Tracker,
+    ) -> usize {
+        match self {
+            InverseOrder::Narrow(inverse_ord) => {
+                embed_band(samples, first_sample, inverse_ord, total, lsbs, progress)
+            }
+            InverseOrder::Wide(inverse_ord) => {
+                embed_band(samples, first_sample, inverse_ord, total, lsbs, progress)
+            }
+        }
+    }
+}
+
+fn inverse_order<I: BitIndex>(
+    order: &impl PixelOrder,
+    capacity_bits: u64,
+    total_len_bits: u64,
+) -> Vec<(I, I)> {
     let mut inverse_ord = order
-        .iter()
-        .take(total_len_bits)
+        .positions(capacity_bits, total_len_bits)
         .enumerate()
-        .map(|(i, x)| (x, i))
+        .map(|(i, x)| (I::from_u64(x), I::from_u64(i as u64)))
         .collect::<Vec<_>>();
-    inverse_ord.par_sort_by_key(|(x, _)| *x);
+    parallel(|| inverse_ord.par_sort_by_key(|(x, _)| *x));
+    inverse_ord
+}
 
-    image
-        .par_chunks_mut(CHUNK_SIZE)
+fn embed_band<I: BitIndex>(
+    samples: &mut [u8],
+    first_sample: usize,
+    inverse_ord: &[(I, I)],
+    total: &[u8],
+    lsbs: usize,
+    progress: &Tracker,
+) -> usize {
+    parallel(|| embed_samples(samples, first_sample, inverse_ord, total, lsbs, progress))
+}
+
+fn embed_samples<I: BitIndex>(
+    samples: &mut [u8],
+    first_sample: usize,
+    inverse_ord: &[(I, I)],
+    total: &[u8],
+    lsbs: usize,
+    progress: &Tracker,
+) -> usize {
+    let chunk_size = chunk_size(samples.len());
+
+    samples
+        .par_chunks_mut(chunk_size)
         .enumerate()
-        .for_each(|(index, chunk)| {
-            let start = index * CHUNK_SIZE * lsbs;
-            let end = start + CHUNK_SIZE * lsbs - 1; // The end should be inclusive so that
-            // the upper bound is correct
+        .map(|(index, chunk)| {
+            if progress.is_cancelled() {
+                return 0;
+            }
 
-            let (lower, upper) = bounds(&inverse_ord, start, end);
+            let chunk_start = first_sample + index * chunk_size;
+            let start = (chunk_start * lsbs) as u64;
+            // The end should be inclusive so that the upper bound is correct, and within
+            // the capacity so that it fits into the index type
+            let end = start + (chunk.len() * lsbs) as u64 - 1;
+
+            let (lower, upper) = bounds(inverse_ord, I::from_u64(start), I::from_u64(end));
+            let mut flipped = 0;
 
             for (bit_index, bit_index_seq) in &inverse_ord[lower..upper] {
+                // The payload is in memory, so its bit indices fit into a `usize`
+                let (bit_index, bit_index_seq) =
+                    (bit_index.to_u64(), bit_index_seq.to_u64() as usize);
                 let byte_index = bit_index_seq / BITS_PER_BYTE;
                 let bit_offset = bit_index_seq % BITS_PER_BYTE;
 
                 let byte = total[byte_index];
                 let bit = (byte >> (BITS_PER_BYTE - 1 - bit_offset)) & 1;
 
-                let bit_in_chunk = bit_index / lsbs % CHUNK_SIZE;
-                let bit_in_channel = bit_index % lsbs;
+                let bit_in_chunk = (bit_index / lsbs as u64) as usize - chunk_start;
+                let bit_in_channel = (bit_index % lsbs as u64) as usize;
 
                 let mask = !(1 << bit_in_channel);
+                flipped += ((chunk[bit_in_chunk] >> bit_in_channel) & 1 != bit) as usize;
                 chunk[bit_in_chunk] = (chunk[bit_in_chunk] & mask) | (bit << bit_in_channel);
             }
-        });
+            progress.advance((upper - lower) as u64);
 
-    image
+            flipped
+        })
+        .sum()
 }
 
-fn bounds(inverse_ord: &[(usize, usize)], start: usize, end: usize) -> (usize, usize) {
+fn bounds<I: BitIndex>(inverse_ord: &[(I, I)], start: I, end: I) -> (usize, usize) {
     let lower = inverse_ord
         .binary_search_by(|&(x, _)| match x.cmp(&start) {
             Ordering::Equal => Ordering::Greater,
@@ -155,42 +1899,23 @@ fn bounds(inverse_ord: &[(usize, usize)], start: usize, end: usize) -> (usize, u
     (lower, upper)
 }
 
-fn generate_order(seed: u64, capacity_bits: usize) -> IndexVec {
-    let mut rng = Pcg64Mcg::seed_from_u64(seed);
-
-    // The `amount` parameter must be the same as `total_len_bits` for reproducibility
-    sample(&mut rng, capacity_bits, capacity_bits)
-}
-
-fn build_payload(input: &[u8], extension: &str, hash: Hash) -> StegResult<Vec<u8>> {
-    let ext_len: u8 = extension.len().try_into().map_err(|_| {
-        StegError::ExtensionTooLong(format!(
-            "Extension length exceeds maximum size: {}",
-            extension.len()
-        ))
-    })?;
-
-    let hash_flag = hash as u8;
-
-    let mut hasher = select_hasher(hash);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let checksum = use_hasher(&mut *hasher, input);
+    #[test]
+    fn test_inverse_order_is_narrow_below_u32_max() {
+        let order = KeyedPermutation::new(42);
+        let is_narrow = |capacity_bits| {
+            matches!(
+                InverseOrder::new(&order, capacity_bits, 1024),
+                InverseOrder::Narrow(_)
+            )
+        };
 
-    let payload = [
-        ext_len.to_le_bytes().as_ref(),
-        extension.as_bytes(),
-        hash_flag.to_le_bytes().as_ref(),
-        checksum.as_ref(),
-        input,
-    ]
-    .concat();
-
-    let payload_len: u32 = payload.len().try_into().map_err(|_| {
-        StegError::CalculationOverflow(format!(
-            "Payload length exceeds maximum size: {} bytes",
-            payload.len()
-        ))
-    })?;
-
-    Ok([payload_len.to_le_bytes().as_ref(), &payload].concat())
+        // A 48 megapixel container at 8 lsbs
+        assert!(is_narrow(8000 * 6000 * 3 * 8));
+        assert!(is_narrow(u32::MAX as u64));
+        assert!(!is_narrow(u32::MAX as u64 + 1));
+    }
 }
@@ -1,88 +1,583 @@
-use std::fmt;
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{FromUtf8Error, String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use strum::IntoStaticStr;
+use thiserror::Error;
+
+use super::consts::{BITS_PER_BYTE, EMBEDDABLE_CHANNELS};
+#[cfg(feature = "std")]
+use super::format::Format;
 
 /// Represents the possible errors that can occur during steganography operations.
-#[derive(Debug)]
+///
+/// Variants carry the values that caused them, so that callers can react to an error
+/// without parsing its message. `kind` groups them into a few stable categories, and
+/// new variants may be added in any release.
+///
+/// `code` names each variant in snake case, such as `"checksum_mismatch"`. Codes are
+/// stable: a variant keeps its code across releases.
+///
+/// The variants holding errors of image codecs and I/O, and `UnsupportedOutputFormat`,
+/// only exist with the `std` feature.
+#[derive(Debug, Error, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+#[non_exhaustive]
 pub enum StegError {
     /// Error indicating an invalid LSB (Least Significant Bit) value was encountered.
-    InvalidLsbValue(String),
+    #[error("Invalid LSBs value: {given}, lsbs must be between 1 and {max} inclusive")]
+    InvalidLsbValue {
+        /// The number of bits per channel that was asked for.
+        given: usize,
+        /// The largest number of bits per channel the operation supports.
+        max: usize,
+    },
     /// Error originating from the underlying image processing library.
-    ImageProcessing(image::ImageError),
+    #[cfg(feature = "std")]
+    #[error("Image processing error: {0}")]
+    ImageProcessing(#[from] image::ImageError),
     /// Error during the detection of the image format.
+    #[error("Image format detection error: {0}")]
     FormatDetection(String),
     /// Error indicating that the file extension is too long to be embedded.
-    ExtensionTooLong(String),
+    #[error("Extension too long: {len} bytes, at most {max} bytes are allowed")]
+    ExtensionTooLong {
+        /// The length of the extension, in bytes.
+        len: usize,
+        /// The longest extension that can be embedded, in bytes.
+        max: usize,
+    },
     /// Error indicating that the container image does not have enough capacity to hold the payload.
-    InsufficientCapacity(String),
+    #[error(
+        "Insufficient container capacity: {required_bits} bits required, {available_bits} bits available, you need at least {missing_pixels} more pixels"
+    )]
+    InsufficientCapacity {
+        /// The bits the operation needs.
+        required_bits: u64,
+        /// The bits the container offers.
+        available_bits: u64,
+        /// The pixels the container lacks to offer `required_bits`, at the same `lsbs`.
+        missing_pixels: u64,
+    },
     /// Error occurring during the parsing of the payload data.
+    #[error("Failed to parse payload: {0}")]
     PayloadParse(String),
+    /// Error indicating that the extension read from a payload header is not valid UTF-8.
+    #[error("Failed to parse payload: invalid UTF-8 sequence in extension")]
+    InvalidExtensionUtf8 {
+        /// The error of the conversion, holding the bytes of the extension.
+        #[from]
+        source: FromUtf8Error,
+    },
     /// Error indicating a mismatch in checksums, suggesting data corruption.
+    #[error("Checksum mismatch")]
     ChecksumMismatch,
+    /// Error indicating a checksum mismatch caused by changes to the container after embedding,
+    /// such as re-saving it with different pixel values.
+    #[error(
+        "Container modified: the pixels of the image were changed after embedding, \
+         e.g. by re-saving it with color management or another editor"
+    )]
+    ContainerModified,
+    /// Error indicating a checksum mismatch while the rest of the container is intact,
+    /// so only the embedded bits were corrupted.
+    #[error(
+        "Payload corrupted: the embedded bits were changed, but the rest of the image is intact"
+    )]
+    PayloadCorrupted {
+        /// The checksum stored in the payload.
+        expected: Vec<u8>,
+        /// The checksum of the extracted data.
+        actual: Vec<u8>,
+    },
+    /// Error indicating that the image holds no payload under the given parameters.
+    #[error("No payload found: the image holds no valid header under these lsbs and seed")]
+    NoPayloadFound {
+        /// Why the header read from the image is not valid.
+        #[source]
+        cause: Box<StegError>,
+    },
+    /// Error indicating that the image holds no payload under the given seed, while its
+    /// least significant bits look like they hold one.
+    #[error(
+        "No payload found under this seed, though the least significant bits of the image \
+         look like they hold one"
+    )]
+    WrongSeedSuspected {
+        /// Why the header read with the seed is not valid.
+        #[source]
+        cause: Box<StegError>,
+    },
+    /// Error indicating that the image holds the valid header of a payload longer than
+    /// its capacity, as if it was cut short.
+    #[error(
+        "Payload truncated: the payload needs {required_bits} bits, but the image only holds {available_bits}"
+    )]
+    PayloadTruncated {
+        /// The bits the payload needs, from its length field.
+        required_bits: u64,
+        /// The bits the image offers.
+        available_bits: u64,
+    },
     /// Error due to a numeric calculation overflow.
+    #[error("Numeric calculation overflow: {0}")]
     CalculationOverflow(String),
     /// Error indicating that the calculated capacity exceeds the maximum value of `usize`.
+    #[error("Capacity exceeds system limit (usize::MAX): {0}")]
     CapacityExceedsUsizeMax(String),
-    /// Error occurring during the parsing of a hash flag.
-    HashFlagParse(String),
+    /// Error indicating that the hash flag read from a payload header is unknown.
+    #[error("Failed to parse hash flag: {flag:#04x}")]
+    HashFlagParse {
+        /// The flag byte that was read.
+        flag: u8,
+    },
     /// Error indicating that the image format is not supported.
+    #[error("Unsupported image format: {0}")]
     UnsupportedFormat(String),
+    /// Error indicating that an embedding cannot be written in the requested format.
+    #[cfg(feature = "std")]
+    #[error(
+        "Unsupported output format {format:?}: {reason}, supported formats are: {}",
+        format_list(.supported)
+    )]
+    UnsupportedOutputFormat {
+        /// The format that was asked for, as given, such as `"jpg"` or `"Jpeg"`.
+        format: String,
+        /// Why it cannot be written, such as that it is lossy.
+        reason: String,
+        /// The formats an embedding can be written in, see
+        /// `image::supported_output_formats`.
+        supported: Vec<Format>,
+    },
+    /// Error indicating that a watermark message is too long to be tiled.
+    #[error("Watermark too long: {len} bytes, at most {max} bytes are allowed")]
+    WatermarkTooLong {
+        /// The length of the message, in bytes.
+        len: usize,
+        /// The longest message a watermark can hold, in bytes.
+        max: usize,
+    },
+    /// Error indicating that no valid watermark could be found in the image.
+    #[error("Watermark not found: no block holds a valid watermark")]
+    WatermarkNotFound,
+    /// Error indicating that the container already holds a payload that embedding would destroy.
+    #[error("Existing payload: {0}")]
+    ExistingPayload(String),
+    /// Error indicating that two images that should be compared have different dimensions.
+    #[error(
+        "Dimension mismatch: original is {}x{} pixels, modified is {}x{} pixels",
+        .expected.0, .expected.1, .actual.0, .actual.1
+    )]
+    DimensionMismatch {
+        /// The width and height of the original image.
+        expected: (u32, u32),
+        /// The width and height of the image compared against it.
+        actual: (u32, u32),
+    },
+    /// Error indicating that a buffer of raw pixels does not match the given dimensions.
+    #[error(
+        "Buffer size mismatch: {len} bytes do not hold {width}x{height} RGB pixels, which take {} bytes",
+        rgb_len(*.width, *.height)
+    )]
+    BufferSizeMismatch {
+        /// The width the pixels were given for.
+        width: u32,
+        /// The height the pixels were given for.
+        height: u32,
+        /// The length of the buffer, in bytes.
+        len: usize,
+    },
+    /// Error indicating that two images that should be compared have different color types.
+    #[error("Color type mismatch: {0}")]
+    ColorTypeMismatch(String),
+    /// Error indicating that a requested bit plane does not exist.
+    #[error(
+        "Invalid bit plane: channel {channel} bit {bit}, channels must be below {} and bits below {}",
+        EMBEDDABLE_CHANNELS,
+        BITS_PER_BYTE
+    )]
+    InvalidBitPlane {
+        /// The channel that was asked for, counting from 0 for red.
+        channel: usize,
+        /// The bit that was asked for, counting from 0 for the least significant one.
+        bit: u8,
+    },
+    /// Error indicating that an analysis was asked to use empty blocks.
+    #[error("Invalid block size: blocks must have at least one row, got {block_rows}")]
+    InvalidBlockSize {
+        /// The number of rows per block that was asked for.
+        block_rows: usize,
+    },
+    /// Error indicating that an embedded image did not extract back to its input.
+    #[error("Self-verification failed: {0}")]
+    SelfVerificationFailed(String),
+    /// Error indicating that an embedding changed more than the bits it was allowed to.
+    #[error(
+        "Unexpected change: pixel ({x}, {y}) channel {channel} changed from {original:#010b} to {modified:#010b}, outside the lowest {lsbs} bits"
+    )]
+    UnexpectedChange {
+        /// The column of the first pixel that changed too much.
+        x: u32,
+        /// The row of the first pixel that changed too much.
+        y: u32,
+        /// The channel that changed, counting from 0 for red.
+        channel: usize,
+        /// The sample before the embedding.
+        original: u8,
+        /// The sample after the embedding.
+        modified: u8,
+        /// The number of lowest bits that were allowed to change.
+        lsbs: usize,
+    },
+    /// Error indicating that an option is not supported by the requested operation.
+    #[error("Unsupported option: {0}")]
+    UnsupportedOption(String),
+    /// Error indicating that the thread pool requested for an operation could not be created.
+    #[error("Failed to create thread pool: {0}")]
+    ThreadPool(String),
+    /// Error indicating that an embedded payload is longer than an extraction allows.
+    #[error("Payload too large: {len} bytes exceed the maximum of {max} bytes")]
+    PayloadTooLarge {
+        /// The length of the payload, in bytes.
+        len: u64,
+        /// The longest payload the extraction allows, in bytes.
+        max: u64,
+    },
+    /// Error indicating that the operation was stopped through its `CancelToken`.
+    #[error("Operation cancelled")]
+    Cancelled,
+    /// Error indicating that a requested range of the embedded data lies past its end.
+    #[error(
+        "Range out of bounds: {len} bytes at offset {offset} exceed the {data_len} bytes of data"
+    )]
+    OutOfRange {
+        /// The offset of the range into the data, in bytes.
+        offset: usize,
+        /// The length of the range, in bytes.
+        len: usize,
+        /// The length of the embedded data, in bytes.
+        data_len: usize,
+    },
     /// General I/O error.
-    Io(std::io::Error),
+    #[cfg(feature = "std")]
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The category of a `StegError`, for choosing how to react to it, such as which
+/// exception to raise or which status code to answer with.
+///
+/// The categories and their names are stable, while the variants of `StegError` within
+/// each may grow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An argument or option is out of range or not supported by the operation.
+    InvalidArgument,
+    /// An image format cannot be read, detected or written.
+    UnsupportedFormat,
+    /// The container is too small for the operation.
+    InsufficientCapacity,
+    /// A size exceeds what the platform or the operation allows.
+    LimitExceeded,
+    /// No payload or watermark was found in the image.
+    NotFound,
+    /// A payload was found, but its checksum does not match.
+    Corrupted,
+    /// The container already holds a payload that the operation would destroy.
+    ExistingPayload,
+    /// Two images that were compared do not match.
+    Mismatch,
+    /// An image could not be decoded or encoded.
+    Image,
+    /// Reading or writing failed.
+    Io,
+    /// The operation was cancelled.
+    Cancelled,
+    /// The library failed in a way it should not, such as an output that does not
+    /// extract back to its input.
+    Internal,
 }
 
-impl fmt::Display for StegError {
+impl ErrorKind {
+    /// A stable name of the kind in snake case, such as `"insufficient_capacity"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::InvalidArgument => "invalid_argument",
+            ErrorKind::UnsupportedFormat => "unsupported_format",
+            ErrorKind::InsufficientCapacity => "insufficient_capacity",
+            ErrorKind::LimitExceeded => "limit_exceeded",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Corrupted => "corrupted",
+            ErrorKind::ExistingPayload => "existing_payload",
+            ErrorKind::Mismatch => "mismatch",
+            ErrorKind::Image => "image",
+            ErrorKind::Io => "io",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl StegError {
+    /// The category of the error.
+    pub fn kind(&self) -> ErrorKind {
         match self {
-            StegError::InvalidLsbValue(msg) => write!(f, "Invalid LSBs value: {}", msg),
-            StegError::ImageProcessing(err) => write!(f, "Image processing error: {}", err),
-            StegError::FormatDetection(msg) => write!(f, "Image format detection error: {}", msg),
-            StegError::ExtensionTooLong(msg) => write!(f, "Extension too long: {}", msg),
-            StegError::InsufficientCapacity(msg) => {
-                write!(f, "Insufficient container capacity: {}", msg)
-            }
-            StegError::PayloadParse(msg) => write!(f, "Failed to parse payload: {}", msg),
-            StegError::ChecksumMismatch => write!(f, "Checksum mismatch"),
-            StegError::CalculationOverflow(msg) => {
-                write!(f, "Numeric calculation overflow: {}", msg)
-            }
-            StegError::CapacityExceedsUsizeMax(msg) => {
-                write!(f, "Capacity exceeds system limit (usize::MAX): {}", msg)
+            StegError::InvalidLsbValue { .. }
+            | StegError::ExtensionTooLong { .. }
+            | StegError::WatermarkTooLong { .. }
+            | StegError::BufferSizeMismatch { .. }
+            | StegError::InvalidBitPlane { .. }
+            | StegError::InvalidBlockSize { .. }
+            | StegError::UnsupportedOption(_)
+            | StegError::OutOfRange { .. } => ErrorKind::InvalidArgument,
+            StegError::FormatDetection(_) | StegError::UnsupportedFormat(_) => {
+                ErrorKind::UnsupportedFormat
             }
-            StegError::Io(err) => write!(f, "I/O error: {}", err),
-            StegError::HashFlagParse(msg) => write!(f, "Failed to parse hash flag: {}", msg),
-            StegError::UnsupportedFormat(msg) => write!(f, "Unsupported image format: {}", msg),
+            #[cfg(feature = "std")]
+            StegError::UnsupportedOutputFormat { .. } => ErrorKind::UnsupportedFormat,
+            StegError::InsufficientCapacity { .. } => ErrorKind::InsufficientCapacity,
+            StegError::CalculationOverflow(_)
+            | StegError::CapacityExceedsUsizeMax(_)
+            | StegError::PayloadTooLarge { .. } => ErrorKind::LimitExceeded,
+            StegError::PayloadParse(_)
+            | StegError::InvalidExtensionUtf8 { .. }
+            | StegError::HashFlagParse { .. }
+            | StegError::NoPayloadFound { .. }
+            | StegError::WrongSeedSuspected { .. }
+            | StegError::WatermarkNotFound => ErrorKind::NotFound,
+            StegError::ChecksumMismatch
+            | StegError::ContainerModified
+            | StegError::PayloadCorrupted { .. }
+            | StegError::PayloadTruncated { .. } => ErrorKind::Corrupted,
+            StegError::ExistingPayload(_) => ErrorKind::ExistingPayload,
+            StegError::DimensionMismatch { .. }
+            | StegError::ColorTypeMismatch(_)
+            | StegError::UnexpectedChange { .. } => ErrorKind::Mismatch,
+            #[cfg(feature = "std")]
+            StegError::ImageProcessing(_) => ErrorKind::Image,
+            #[cfg(feature = "std")]
+            StegError::Io(_) => ErrorKind::Io,
+            StegError::Cancelled => ErrorKind::Cancelled,
+            StegError::SelfVerificationFailed(_) | StegError::ThreadPool(_) => ErrorKind::Internal,
         }
     }
-}
 
-impl std::error::Error for StegError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    /// The description of the variants that carry one instead of structured values, such
+    /// as the reason of `UnsupportedFormat`.
+    pub fn detail(&self) -> Option<&str> {
         match self {
-            StegError::ImageProcessing(err) => Some(err),
-            StegError::Io(err) => Some(err),
+            StegError::FormatDetection(msg)
+            | StegError::PayloadParse(msg)
+            | StegError::CalculationOverflow(msg)
+            | StegError::CapacityExceedsUsizeMax(msg)
+            | StegError::UnsupportedFormat(msg)
+            | StegError::ExistingPayload(msg)
+            | StegError::ColorTypeMismatch(msg)
+            | StegError::SelfVerificationFailed(msg)
+            | StegError::UnsupportedOption(msg)
+            | StegError::ThreadPool(msg) => Some(msg),
             _ => None,
         }
     }
-}
 
-/// Converts an `image::ImageError` into a `StegError::ImageProcessing` variant.
-impl From<image::ImageError> for StegError {
-    fn from(err: image::ImageError) -> Self {
-        StegError::ImageProcessing(err)
+    /// The bits an operation needed and the bits the container offered, if the error is
+    /// `InsufficientCapacity`.
+    pub fn capacity_shortfall(&self) -> Option<(u64, u64)> {
+        match *self {
+            StegError::InsufficientCapacity {
+                required_bits,
+                available_bits,
+                ..
+            } => Some((required_bits, available_bits)),
+            _ => None,
+        }
     }
-}
 
-/// Converts a `std::io::Error` into a `StegError::Io` variant.
-impl From<std::io::Error> for StegError {
-    fn from(err: std::io::Error) -> Self {
-        StegError::Io(err)
+    /// The stable identifier of the variant in snake case, such as `"checksum_mismatch"`.
+    pub fn code(&self) -> &'static str {
+        self.into()
     }
-}
 
-/// Converts a `std::string::FromUtf8Error` into a `StegError::PayloadParse` variant.
-impl From<std::string::FromUtf8Error> for StegError {
-    fn from(err: std::string::FromUtf8Error) -> Self {
-        StegError::PayloadParse(format!("Invalid UTF-8 sequence in extension: {}", err))
+    /// A summary of the error for recording it, such as in JSON with the `serde` feature.
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            code: self.code(),
+            kind: self.kind(),
+            message: self.to_string(),
+            details: self.details(),
+        }
     }
+
+    /// The values the error carries, by name.
+    fn details(&self) -> BTreeMap<&'static str, ErrorDetail> {
+        use ErrorDetail::{Number, Text};
+
+        let details: Vec<(&'static str, ErrorDetail)> = match self {
+            StegError::InvalidLsbValue { given, max } => {
+                vec![
+                    ("given", Number(*given as u64)),
+                    ("max", Number(*max as u64)),
+                ]
+            }
+            StegError::ExtensionTooLong { len, max } | StegError::WatermarkTooLong { len, max } => {
+                vec![("len", Number(*len as u64)), ("max", Number(*max as u64))]
+            }
+            StegError::InsufficientCapacity {
+                required_bits,
+                available_bits,
+                missing_pixels,
+            } => vec![
+                ("required_bits", Number(*required_bits)),
+                ("available_bits", Number(*available_bits)),
+                ("missing_pixels", Number(*missing_pixels)),
+            ],
+            StegError::PayloadTruncated {
+                required_bits,
+                available_bits,
+            } => vec![
+                ("required_bits", Number(*required_bits)),
+                ("available_bits", Number(*available_bits)),
+            ],
+            StegError::HashFlagParse { flag } => vec![("flag", Number(*flag as u64))],
+            StegError::DimensionMismatch { expected, actual } => vec![
+                ("expected_width", Number(expected.0 as u64)),
+                ("expected_height", Number(expected.1 as u64)),
+                ("actual_width", Number(actual.0 as u64)),
+                ("actual_height", Number(actual.1 as u64)),
+            ],
+            StegError::BufferSizeMismatch { width, height, len } => vec![
+                ("width", Number(*width as u64)),
+                ("height", Number(*height as u64)),
+                ("len", Number(*len as u64)),
+            ],
+            StegError::InvalidBitPlane { channel, bit } => vec![
+                ("channel", Number(*channel as u64)),
+                ("bit", Number(*bit as u64)),
+            ],
+            StegError::InvalidBlockSize { block_rows } => {
+                vec![("block_rows", Number(*block_rows as u64))]
+            }
+            StegError::UnexpectedChange {
+                x,
+                y,
+                channel,
+                original,
+                modified,
+                lsbs,
+            } => vec![
+                ("x", Number(*x as u64)),
+                ("y", Number(*y as u64)),
+                ("channel", Number(*channel as u64)),
+                ("original", Number(*original as u64)),
+                ("modified", Number(*modified as u64)),
+                ("lsbs", Number(*lsbs as u64)),
+            ],
+            StegError::PayloadTooLarge { len, max } => {
+                vec![("len", Number(*len)), ("max", Number(*max))]
+            }
+            StegError::OutOfRange {
+                offset,
+                len,
+                data_len,
+            } => vec![
+                ("offset", Number(*offset as u64)),
+                ("len", Number(*len as u64)),
+                ("data_len", Number(*data_len as u64)),
+            ],
+            StegError::PayloadCorrupted { expected, actual } => vec![
+                ("expected", Text(hex(expected))),
+                ("actual", Text(hex(actual))),
+            ],
+            #[cfg(feature = "std")]
+            StegError::UnsupportedOutputFormat {
+                format,
+                reason,
+                supported,
+            } => vec![
+                ("format", Text(format.clone())),
+                ("reason", Text(reason.clone())),
+                ("supported", Text(format_list(supported))),
+            ],
+            StegError::NoPayloadFound { cause } | StegError::WrongSeedSuspected { cause } => {
+                vec![("cause", Text(cause.to_string()))]
+            }
+            #[cfg(feature = "std")]
+            StegError::ImageProcessing(err) => vec![("source", Text(err.to_string()))],
+            #[cfg(feature = "std")]
+            StegError::Io(err) => vec![("source", Text(err.to_string()))],
+            StegError::InvalidExtensionUtf8 { source } => {
+                vec![("source", Text(source.to_string()))]
+            }
+            _ => self
+                .detail()
+                .map(|detail| vec![("detail", Text(detail.to_string()))])
+                .unwrap_or_default(),
+        };
+
+        details.into_iter().collect()
+    }
+}
+
+/// A summary of a `StegError` for logs and job records, serialized as
+/// `{ code, kind, message, details }` with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ErrorReport {
+    /// The stable identifier of the variant, as returned by `StegError::code`.
+    pub code: &'static str,
+    /// The category of the error.
+    pub kind: ErrorKind,
+    /// The human-readable message of the error.
+    pub message: String,
+    /// The values the error carries, by name, such as `required_bits` for
+    /// `insufficient_capacity`, or the `detail` of variants holding a description.
+    pub details: BTreeMap<&'static str, ErrorDetail>,
+}
+
+/// A value carried by a `StegError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(untagged))]
+pub enum ErrorDetail {
+    /// A count, size, position or other integer.
+    Number(u64),
+    /// A description, or the message of the underlying error.
+    Text(String),
+}
+
+/// Lists formats by name, such as `"Png, WebP"`.
+#[cfg(feature = "std")]
+fn format_list(formats: &[Format]) -> String {
+    formats
+        .iter()
+        .map(|format| format!("{:?}", format))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats a checksum as lowercase hexadecimal.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The length of `width` x `height` RGB pixels, in bytes, which cannot overflow.
+fn rgb_len(width: u32, height: u32) -> u128 {
+    width as u128 * height as u128 * EMBEDDABLE_CHANNELS as u128
 }
 
 /// A type alias for `Result<T, StegError>`, used for functions that can return a `StegError`.
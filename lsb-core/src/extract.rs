@@ -1,14 +1,40 @@
-use log::debug;
-use rand::{prelude::*, seq::index::sample};
-use rand_pcg::Pcg64Mcg;
-use rayon::prelude::*;
+use std::{
+    fmt,
+    io::{self, Write},
+    ops::RangeInclusive,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering::Relaxed},
+    },
+};
 
-use super::{consts::*, error::*, hash::*, image::*};
+use tracing::{Span, debug, instrument};
+
+use super::{
+    analysis::plane_embedding_rate,
+    cancel::*,
+    capacity::{capacity_bits, check_dimensions, check_lsbs, insufficient_capacity},
+    consts::*,
+    embed::{BitIndex, EmbedOptions, LsbSelection},
+    error::*,
+    format::Format,
+    hash::*,
+    image::*,
+    order::*,
+    par::*,
+    payload::*,
+    progress::*,
+    simd::gather_lsb1,
+    stego::{Stego, StegoContainer},
+    threads::{parallel, with_threads},
+    warning::Warning,
+};
 
 /// Extracts data embedded in an image using LSB steganography.
 ///
 /// This function attempts to read the payload length, then the payload itself,
-/// which includes the original file extension, hash flag, checksum, and the hidden data.
+/// which includes the original file extension, hash flag, container fingerprint, checksum,
+/// and the hidden data.
 /// It verifies the checksum before returning the data.
 ///
 /// # Arguments
@@ -26,164 +52,1555 @@ use super::{consts::*, error::*, hash::*, image::*};
 /// # Errors
 ///
 /// This function can return errors for various reasons, including:
-/// * `StegError::InsufficientCapacity`: If the image is too small to contain valid metadata or payload.
-/// * `StegError::HashFlagParse`: If the hash flag read from the image is invalid.
-/// * `StegError::ChecksumMismatch`: If the checksum of the extracted data does not match the embedded checksum.
+/// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+/// * `StegError::InsufficientCapacity`: If the image is too small to contain any payload.
+/// * `StegError::NoPayloadFound`: If no valid header is found under `lsbs` and `seed`. Its
+///   source tells why, such as `StegError::HashFlagParse` for an unknown hash flag or
+///   `StegError::InvalidExtensionUtf8` for an extension that is not valid UTF-8.
+/// * `StegError::WrongSeedSuspected`: If no valid header is found, but the least
+///   significant bits of the image look like they hold a payload.
+/// * `StegError::PayloadTruncated`: If the header is valid, but the payload is longer than
+///   the image holds, such as after cropping.
+/// * `StegError::ContainerModified`: If the checksum does not match because the image was
+///   changed after embedding.
+/// * `StegError::PayloadCorrupted`: If the checksum does not match, but only the embedded
+///   bits were changed.
+/// * `StegError::ChecksumMismatch`: If the checksum of data embedded without a container
+///   fingerprint does not match the embedded checksum.
 /// * Errors from the `image` crate during image decoding.
-/// * `std::string::FromUtf8Error` if the extracted extension bytes are not valid UTF-8.
 pub fn extract(input: &[u8], lsbs: usize, seed: u64) -> StegResult<(Vec<u8>, String)> {
-    let image = decode(input)?;
+    let options = ExtractOptions::new().lsbs(lsbs).seed(seed);
 
-    let length = extract_length(&image, lsbs, seed)?;
+    extract_with(input, &options).map(|extracted| (extracted.data, extracted.extension))
+}
 
-    extract_payload(&image, length, lsbs, seed)
+/// The parameters of an extraction operation, mirroring `EmbedOptions`.
+///
+/// `ExtractOptions::from(&embed_options)` gives the options that extract what
+/// `embed_options` embeds, so the parameters both sides must agree on are not repeated.
+#[derive(Clone)]
+pub struct ExtractOptions {
+    /// The number of least significant bits per color channel used during embedding (1-8),
+    /// or `LsbSelection::Auto` to try every value like `extract_auto`.
+    pub lsbs: LsbSelection,
+    /// The 64-bit seed used for the pseudo-random number generator during embedding.
+    pub seed: u64,
+    /// Whether to check the data against its checksum. Without it, data whose bits were
+    /// corrupted is returned as it is.
+    ///
+    /// `LsbSelection::Auto` tells the right `lsbs` value by its checksum, so it always
+    /// verifies the data.
+    pub verify: bool,
+    /// The longest payload to read, in bytes, counting its metadata after the length
+    /// field, or `None` for no limit. Longer payloads fail with
+    /// `StegError::PayloadTooLarge` before they are read.
+    pub max_payload: Option<usize>,
+    /// The number of threads to run the parallel parts of the operation on, like
+    /// `EmbedOptions::threads`.
+    pub threads: Option<usize>,
+    /// A callback invoked as the extraction progresses, or `None` to report nothing.
+    ///
+    /// Extractions report `Phase::Decode` if they decode the container, then
+    /// `Phase::Extract` about once per chunk of the payload.
+    pub progress: Option<ProgressCallback>,
+    /// A token that stops the extraction with `StegError::Cancelled` once cancelled, or
+    /// `None` to always run it to completion.
+    pub cancel: Option<CancelToken>,
+    /// Whether to fall back to the `SeededShuffle` of images embedded before
+    /// `KeyedPermutation` when no keyed payload is found with `LsbSelection::Auto` as
+    /// well.
+    ///
+    /// A fixed `lsbs` always falls back, so existing images keep extracting. The shuffle
+    /// generates a permutation of every embeddable bit of the container, so `Auto` only
+    /// falls back with this set: a wrong seed or an image without a payload would
+    /// otherwise cost memory and time in proportion to the container, for every `lsbs`
+    /// tried.
+    pub legacy_order: bool,
 }
 
-fn extract_payload(
+impl fmt::Debug for ExtractOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractOptions")
+            .field("lsbs", &self.lsbs)
+            .field("seed", &self.seed)
+            .field("verify", &self.verify)
+            .field("max_payload", &self.max_payload)
+            .field("threads", &self.threads)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .field("cancel", &self.cancel)
+            .field("legacy_order", &self.legacy_order)
+            .finish()
+    }
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            lsbs: LsbSelection::default(),
+            seed: 42,
+            verify: true,
+            max_payload: None,
+            threads: None,
+            progress: None,
+            cancel: None,
+            legacy_order: false,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Starts from the default options, which match the defaults of `EmbedOptions`.
+    ///
+    /// Like those of `EmbedOptions`, every option has a method of the same name that
+    /// sets it.
+    pub fn new() -> Self {
+        ExtractOptions::default()
+    }
+
+    /// Sets how many least significant bits per channel were used.
+    pub fn lsbs(mut self, lsbs: impl Into<LsbSelection>) -> Self {
+        self.lsbs = lsbs.into();
+        self
+    }
+
+    /// Sets the seed of the pixel order.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets whether the data is checked against its checksum.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Sets the longest payload to read, or `None` for no limit.
+    pub fn max_payload(mut self, max_payload: impl Into<Option<usize>>) -> Self {
+        self.max_payload = max_payload.into();
+        self
+    }
+
+    /// Sets the number of threads of the pool created for every operation, or `None` to
+    /// use the pool of the calling thread.
+    pub fn threads(mut self, threads: impl Into<Option<usize>>) -> Self {
+        self.threads = threads.into();
+        self
+    }
+
+    /// Sets the callback invoked as the extraction progresses.
+    pub fn progress(mut self, progress: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Sets the token that stops the extraction once cancelled.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Sets whether images embedded before `KeyedPermutation` are read with
+    /// `LsbSelection::Auto` as well.
+    pub fn legacy_order(mut self, legacy_order: bool) -> Self {
+        self.legacy_order = legacy_order;
+        self
+    }
+
+    /// The legacy order to fall back to: always with a fixed `lsbs`, and with
+    /// `LsbSelection::Auto` only if `legacy_order` is set.
+    fn legacy_shuffle(&self) -> Option<SeededShuffle> {
+        (self.legacy_order || matches!(self.lsbs, LsbSelection::Fixed(_)))
+            .then(|| SeededShuffle::new(self.seed))
+    }
+
+    /// Rejects a fixed `lsbs` that no carrier can hold, before anything is decoded.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::InvalidLsbValue`: If `lsbs` is fixed and not between 1 and 8 inclusive.
+    pub(crate) fn check_lsbs(&self) -> StegResult<()> {
+        match self.lsbs {
+            LsbSelection::Fixed(lsbs) => check_lsbs(lsbs, BITS_PER_BYTE),
+            LsbSelection::Auto => Ok(()),
+        }
+    }
+}
+
+impl From<&EmbedOptions> for ExtractOptions {
+    fn from(options: &EmbedOptions) -> Self {
+        ExtractOptions {
+            lsbs: options.lsbs,
+            seed: options.seed,
+            threads: options.threads,
+            progress: options.progress.clone(),
+            cancel: options.cancel.clone(),
+            ..ExtractOptions::default()
+        }
+    }
+}
+
+/// A payload extracted by `extract_with`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extracted {
+    /// The extracted data.
+    pub data: Vec<u8>,
+    /// The original file extension.
+    pub extension: String,
+    /// The number of least significant bits per channel the payload was found with.
+    pub lsbs: usize,
+    /// The hashing algorithm of the checksum.
+    pub hash: Hash,
+    /// What the caller may want to know about the extraction, in the order it was found.
+    pub warnings: Vec<Warning>,
+}
+
+/// Extracts data embedded in an image, as configured by `options`.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the image data from which to extract content.
+/// * `options`: The parameters of the extraction.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `Extracted` payload, if successful.
+///
+/// # Errors
+///
+/// See `extract`. Additionally:
+/// * `StegError::PayloadTooLarge`: If the payload is longer than `max_payload`.
+pub fn extract_with(input: &[u8], options: &ExtractOptions) -> StegResult<Extracted> {
+    options.check_lsbs()?;
+
+    let container = StegoContainer::new(input)?;
+    milestone(options.progress.as_ref(), Phase::Decode);
+    check_cancelled(options.cancel.as_ref())?;
+
+    extract_decoded(container.image(), container.format(), options)
+}
+
+/// Extracts data embedded in an already decoded image of any color type, as configured
+/// by `options`.
+///
+/// This is `extract_with` without the decoding step, for callers working with the
+/// `image` crate. Without a format to tell how the image was encoded, it is read as an
+/// 8-bit RGB image, like a `StegoContainer::from_image`. Images embedded for HDR or
+/// OpenEXR output must be extracted from their encoded bytes instead.
+///
+/// # Arguments
+///
+/// * `container`: The decoded image from which to extract content.
+/// * `options`: The parameters of the extraction.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `Extracted` payload, if successful.
+///
+/// # Errors
+///
+/// See `extract_with`.
+pub fn extract_dynamic(
+    container: &DynamicImage,
+    options: &ExtractOptions,
+) -> StegResult<Extracted> {
+    extract_decoded(container, None, options)
+}
+
+/// Extracts the payload of a decoded container, whose carrier follows `format` if known.
+///
+/// Every extraction from a `DynamicImage` goes through here, so this is where its color
+/// type is converted to a carrier.
+fn extract_decoded(
+    container: &DynamicImage,
+    format: Option<Format>,
+    options: &ExtractOptions,
+) -> StegResult<Extracted> {
+    let kind = format.map_or(CarrierKind::Rgb8, CarrierKind::for_format);
+    let carrier = Carrier::new(container.clone(), kind);
+    debug!("Carrier: {:?}", carrier.kind());
+
+    extract_carrier(&carrier, options)
+}
+
+/// Extracts data embedded in an already decoded image, as configured by `options`.
+///
+/// This is `extract_with` without the decoding step, for callers that hold decoded
+/// images, such as those returned by `embed_image`. The image is read as an 8-bit
+/// image, so `LsbSelection::Auto` tries every `lsbs` value from 1 to 8.
+///
+/// # Arguments
+///
+/// * `image`: The image from which to extract content.
+/// * `options`: The parameters of the extraction.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `Extracted` payload, if successful.
+///
+/// # Errors
+///
+/// See `extract_with`.
+pub fn extract_image(image: &RgbImage, options: &ExtractOptions) -> StegResult<Extracted> {
+    extract_plane_with(image, CarrierKind::Rgb8.max_lsbs(), options)
+}
+
+/// Extracts the payload of a carrier, like `extract_with`.
+pub(crate) fn extract_carrier(
+    carrier: &Carrier,
+    options: &ExtractOptions,
+) -> StegResult<Extracted> {
+    let mut extracted = extract_plane_with(&carrier.plane, carrier.kind().max_lsbs(), options)?;
+    extracted.warnings.splice(0..0, carrier_warnings(carrier));
+
+    Ok(extracted)
+}
+
+/// The warnings extracting from a carrier raises, whatever its payload.
+fn carrier_warnings(carrier: &Carrier) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    if carrier.dropped_alpha() {
+        warnings.push(Warning::AlphaDropped);
+    }
+
+    warnings
+}
+
+/// Extracts the payload of a plane holding up to `max_lsbs` bits per sample.
+fn extract_plane_with(
+    plane: &RgbImage,
+    max_lsbs: usize,
+    options: &ExtractOptions,
+) -> StegResult<Extracted> {
+    options.check_lsbs()?;
+
+    with_threads(options.threads, || extract_lsbs(plane, max_lsbs, options))
+}
+
+/// Extracts the payload of a plane like `extract_plane_with`, in the pool of the
+/// calling thread.
+#[instrument(
+    name = "extract",
+    skip_all,
+    fields(width = plane.width(), height = plane.height(), lsbs = tracing::field::Empty)
+)]
+fn extract_lsbs(
+    plane: &RgbImage,
+    max_lsbs: usize,
+    options: &ExtractOptions,
+) -> StegResult<Extracted> {
+    match options.lsbs {
+        LsbSelection::Fixed(lsbs) => {
+            Span::current().record("lsbs", lsbs);
+            extract_image_with(plane, lsbs, options)
+                .map_err(|err| diagnose_missing_payload(plane, err))
+        }
+        LsbSelection::Auto => {
+            let options = ExtractOptions {
+                verify: true,
+                ..options.clone()
+            };
+
+            let mut first_error = None;
+            for lsbs in 1..=max_lsbs {
+                check_cancelled(options.cancel.as_ref())?;
+                match extract_image_with(plane, lsbs, &options) {
+                    Ok(extracted) => {
+                        debug!("Found payload with lsbs: {}", lsbs);
+                        Span::current().record("lsbs", lsbs);
+                        return Ok(extracted);
+                    }
+                    Err(err) => {
+                        first_error.get_or_insert(err);
+                    }
+                }
+            }
+
+            let err = first_error.expect("every carrier supports at least one lsbs value");
+            Err(diagnose_missing_payload(plane, err))
+        }
+    }
+}
+
+/// Extracts the payload of an already decoded image with a known `lsbs`.
+fn extract_image_with(
+    image: &RgbImage,
+    lsbs: usize,
+    options: &ExtractOptions,
+) -> StegResult<Extracted> {
+    let (keyed, shuffle) = (
+        KeyedPermutation::new(options.seed),
+        options.legacy_shuffle(),
+    );
+    let (header, mut reader) = read_seeded_header(image, lsbs, &keyed, shuffle.as_ref())?;
+    check_max_payload(header.length, options)?;
+
+    let (data, extension) = extract_payload(&mut reader, header.length, options)?;
+    Ok(Extracted {
+        data,
+        extension,
+        lsbs,
+        hash: header.hash,
+        warnings: Vec::new(),
+    })
+}
+
+/// Checks the length of a payload against `options.max_payload`.
+fn check_max_payload(length: usize, options: &ExtractOptions) -> StegResult<()> {
+    if let Some(max_payload) = options.max_payload
+        && length > max_payload
+    {
+        return Err(StegError::PayloadTooLarge {
+            len: length as u64,
+            max: max_payload as u64,
+        });
+    }
+
+    Ok(())
+}
+
+/// Extracts data embedded in an already decoded image, like `extract`.
+///
+/// # Arguments
+///
+/// * `image`: The image from which to extract content.
+/// * `lsbs`: The number of least significant bits per color channel used during embedding (1-8).
+/// * `seed`: The 64-bit seed used for the pseudo-random number generator during embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing a tuple `(Vec<u8>, String)` of the extracted data and the
+/// original file extension, if successful.
+///
+/// # Errors
+///
+/// See `extract`.
+pub fn extract_from_image(
     image: &RgbImage,
-    length: usize,
     lsbs: usize,
     seed: u64,
-) -> Result<(Vec<u8>, String), StegError> {
+) -> StegResult<(Vec<u8>, String)> {
+    let options = ExtractOptions::new().lsbs(lsbs).seed(seed);
+    options.check_lsbs()?;
+
+    extract_image_with(image, lsbs, &options).map(|extracted| (extracted.data, extracted.extension))
+}
+
+/// Extracts data embedded in a buffer of raw interleaved RGB8 pixels, like
+/// `extract_from_image`.
+///
+/// This is the counterpart of `embed_raw_rgb`, for pipelines that do not use `image`
+/// types.
+///
+/// # Arguments
+///
+/// * `pixels`: The RGB8 samples from which to extract content.
+/// * `width`: The width of the image, in pixels.
+/// * `height`: The height of the image, in pixels.
+/// * `lsbs`: The number of least significant bits per color channel used during embedding (1-8).
+/// * `seed`: The 64-bit seed used for the pseudo-random number generator during embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing a tuple `(Vec<u8>, String)` of the extracted data and the
+/// original file extension, if successful.
+///
+/// # Errors
+///
+/// See `extract`. Additionally:
+/// * `StegError::BufferSizeMismatch`: If `pixels` does not hold exactly `width` x `height`
+///   pixels.
+pub fn extract_raw_rgb(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    lsbs: usize,
+    seed: u64,
+) -> StegResult<(Vec<u8>, String)> {
+    extract_from_image(&raw_rgb_image(pixels, width, height)?, lsbs, seed)
+}
+
+/// Extracts `len` bytes of the data embedded in an image, starting `offset` bytes into
+/// the data, without reading the rest of it.
+///
+/// Only the header and the requested bytes are read, and the positions of the bytes in
+/// between are skipped rather than generated, so reading a small part of a large
+/// payload is cheap. The checksum covers the whole data, so it cannot be verified: the
+/// bytes are returned as they are, even if they were corrupted.
+///
+/// Only payloads placed by a `KeyedPermutation` are read, as the positions of a
+/// `SeededShuffle` cannot be skipped without generating the whole permutation.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the image data from which to extract content.
+/// * `offset`: The offset of the first byte to read, from the start of the data.
+/// * `len`: The number of bytes to read.
+/// * `lsbs`: The number of least significant bits per color channel used during embedding (1-8).
+/// * `seed`: The 64-bit seed used for the pseudo-random number generator during embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `len` bytes of the data, if successful.
+///
+/// # Errors
+///
+/// See `extract`, except for the checksum errors. Additionally:
+/// * `StegError::OutOfRange`: If the range ends past the end of the data.
+pub fn extract_range(
+    input: &[u8],
+    offset: usize,
+    len: usize,
+    lsbs: usize,
+    seed: u64,
+) -> StegResult<Vec<u8>> {
+    check_lsbs(lsbs, BITS_PER_BYTE)?;
+
+    let carrier = StegoContainer::new(input)?.carrier(None);
+    debug!("Carrier: {:?}", carrier.kind());
+
+    let keyed = KeyedPermutation::new(seed);
+    let (header, mut reader) = read_seeded_header(&carrier.plane, lsbs, &keyed, None)
+        .map_err(|err| diagnose_missing_payload(&carrier.plane, err))?;
+    let Layout { start, end, .. } = read_layout(&mut reader, header.length)?;
+
+    let data_len = end - start;
+    if offset
+        .checked_add(len)
+        .is_none_or(|range_end| range_end > data_len)
+    {
+        return Err(StegError::OutOfRange {
+            offset,
+            len,
+            data_len,
+        });
+    }
+
+    reader.seek(start + offset);
+    reader.read(start + offset, len)
+}
+
+/// Whether the checksum of a payload matches its data, as found by `inspect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumStatus {
+    /// The checksum matches the data.
+    Valid,
+    /// The checksum does not match, and the payload holds no fingerprint to tell why.
+    Mismatch,
+    /// The checksum does not match, as the container was changed after embedding.
+    ContainerModified,
+    /// The checksum does not match, while the rest of the container is intact.
+    Corrupted,
+    /// The checksum was not verified, as `verify` of the options is off.
+    Unverified,
+}
+
+/// Metadata of a payload found by `inspect`, without its data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    /// The original file extension.
+    pub extension: String,
+    /// The number of data bytes.
+    pub data_bytes: u64,
+    /// The number of least significant bits per channel the payload was found with.
+    pub lsbs: usize,
+    /// The hashing algorithm of the checksum.
+    pub hash: Hash,
+    /// Whether the checksum matches the data.
+    pub checksum: ChecksumStatus,
+    /// What the caller may want to know about the inspection, in the order it was found.
+    pub warnings: Vec<Warning>,
+}
+
+/// Reads the metadata of the payload embedded in an image, without extracting its data.
+///
+/// Only the header is read, unless `options.verify` is on, in which case the data is
+/// hashed in chunks to verify the checksum, without holding it in memory. A checksum
+/// that does not match is reported in the `Inspection` rather than as an error. With
+/// `LsbSelection::Auto`, the fewest bits whose payload has a valid checksum are picked,
+/// or else the fewest with a valid header.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the image data to inspect.
+/// * `options`: The parameters of the extraction the payload would be read with.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `Inspection` of the payload, if successful.
+///
+/// # Errors
+///
+/// See `extract`, except for the checksum errors.
+pub fn inspect(input: &[u8], options: &ExtractOptions) -> StegResult<Inspection> {
+    options.check_lsbs()?;
+
+    let container = StegoContainer::new(input)?;
+    let carrier = container.carrier(None);
+    debug!("Carrier: {:?}", carrier.kind());
+
+    let lsbs = match options.lsbs {
+        LsbSelection::Fixed(lsbs) => lsbs..=lsbs,
+        LsbSelection::Auto => 1..=carrier.kind().max_lsbs(),
+    };
+    let mut inspection = with_threads(options.threads, || {
+        let (mut first_found, mut first_error) = (None, None);
+        for lsbs in lsbs {
+            check_cancelled(options.cancel.as_ref())?;
+            match inspect_image(&carrier.plane, lsbs, options) {
+                Ok(inspection) if inspection.checksum == ChecksumStatus::Valid => {
+                    return Ok(inspection);
+                }
+                Ok(inspection) => {
+                    first_found.get_or_insert(inspection);
+                }
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        match (first_found, first_error) {
+            (Some(inspection), _) => Ok(inspection),
+            (None, err) => Err(diagnose_missing_payload(
+                &carrier.plane,
+                err.expect("every carrier supports at least one lsbs value"),
+            )),
+        }
+    })?;
+    inspection.warnings.splice(0..0, carrier_warnings(&carrier));
+
+    Ok(inspection)
+}
+
+/// Inspects the payload of an already decoded image with a known `lsbs`.
+fn inspect_image(
+    image: &RgbImage,
+    lsbs: usize,
+    options: &ExtractOptions,
+) -> StegResult<Inspection> {
+    let (keyed, shuffle) = (
+        KeyedPermutation::new(options.seed),
+        options.legacy_shuffle(),
+    );
+    let (header, mut reader) = read_seeded_header(image, lsbs, &keyed, shuffle.as_ref())?;
+
+    let Layout { start, end, .. } = read_layout(&mut reader, header.length)?;
+
+    let checksum = if options.verify {
+        reader.seek(0);
+        match stream_payload(&mut reader, header.length, io::sink(), options) {
+            Ok(_) => ChecksumStatus::Valid,
+            Err(StegError::ChecksumMismatch) => ChecksumStatus::Mismatch,
+            Err(StegError::ContainerModified) => ChecksumStatus::ContainerModified,
+            Err(StegError::PayloadCorrupted { .. }) => ChecksumStatus::Corrupted,
+            Err(err) => return Err(err),
+        }
+    } else {
+        ChecksumStatus::Unverified
+    };
+
+    Ok(Inspection {
+        extension: header.extension,
+        data_bytes: (end - start) as u64,
+        lsbs,
+        hash: header.hash,
+        checksum,
+        warnings: Vec::new(),
+    })
+}
+
+/// Metadata of a payload extracted by `extract_to_writer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractInfo {
+    /// The original file extension.
+    pub extension: String,
+    /// The number of data bytes written.
+    pub data_bytes: u64,
+    /// The hashing algorithm the checksum was verified with.
+    pub hash: Hash,
+    /// What the caller may want to know about the extraction, in the order it was found.
+    pub warnings: Vec<Warning>,
+}
+
+/// Extracts data embedded in an image like `extract`, streaming it into `writer`
+/// instead of returning it.
+///
+/// The data is read and hashed in chunks, so memory use does not grow with the size
+/// of the payload beyond the decoded image. The checksum can only be verified once all
+/// the data has been read, so `writer` may already have received the data, or part of
+/// it, when an error is returned. Callers writing to a file should write to a temporary
+/// one and only keep it on success.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the image data from which to extract content.
+/// * `writer`: The sink the extracted data is written to.
+/// * `lsbs`: The number of least significant bits per color channel used during embedding (1-8).
+/// * `seed`: The 64-bit seed used for the pseudo-random number generator during embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `ExtractInfo` of the payload, if successful.
+///
+/// # Errors
+///
+/// See `extract`. Failures of `writer` are returned as `StegError::Io`.
+pub fn extract_to_writer<W: Write>(
+    input: &[u8],
+    writer: W,
+    lsbs: usize,
+    seed: u64,
+) -> StegResult<ExtractInfo> {
+    Stego::builder()
+        .lsbs(lsbs)
+        .seed(seed)
+        .build()
+        .extract_to_writer(&StegoContainer::new(input)?, writer)
+}
+
+/// Streams the payload of a carrier into `writer`, like `extract_to_writer`.
+///
+/// With `LsbSelection::Auto`, the payload is first extracted as a whole to find its
+/// `lsbs`, so memory use grows with its size.
+pub(crate) fn extract_carrier_to_writer<W: Write>(
+    carrier: &Carrier,
+    mut writer: W,
+    options: &ExtractOptions,
+) -> StegResult<ExtractInfo> {
+    options.check_lsbs()?;
+
+    let mut info = with_threads(options.threads, || match options.lsbs {
+        LsbSelection::Fixed(lsbs) => extract_image_to_writer(&carrier.plane, writer, lsbs, options)
+            .map_err(|err| diagnose_missing_payload(&carrier.plane, err)),
+        LsbSelection::Auto => {
+            let extracted = extract_lsbs(&carrier.plane, carrier.kind().max_lsbs(), options)?;
+            writer.write_all(&extracted.data)?;
+
+            Ok(ExtractInfo {
+                extension: extracted.extension,
+                data_bytes: extracted.data.len() as u64,
+                hash: extracted.hash,
+                warnings: extracted.warnings,
+            })
+        }
+    })?;
+    info.warnings.splice(0..0, carrier_warnings(carrier));
+
+    Ok(info)
+}
+
+/// Streams the data embedded in an already decoded image with a known `lsbs` into
+/// `writer`.
+#[instrument(
+    name = "extract",
+    skip_all,
+    fields(width = image.width(), height = image.height(), lsbs = lsbs)
+)]
+fn extract_image_to_writer<W: Write>(
+    image: &RgbImage,
+    writer: W,
+    lsbs: usize,
+    options: &ExtractOptions,
+) -> StegResult<ExtractInfo> {
+    let (keyed, shuffle) = (
+        KeyedPermutation::new(options.seed),
+        options.legacy_shuffle(),
+    );
+    let (header, mut reader) = read_seeded_header(image, lsbs, &keyed, shuffle.as_ref())?;
+    check_max_payload(header.length, options)?;
+
+    stream_payload(&mut reader, header.length, writer, options)
+}
+
+/// Extracts data embedded with a custom `PixelOrder`, such as by `embed_with_order`.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the image data from which to extract content.
+/// * `lsbs`: The number of least significant bits per color channel used during embedding (1-8).
+/// * `order`: The order the payload bits were placed into the container with.
+///
+/// # Returns
+///
+/// A `StegResult` containing a tuple `(Vec<u8>, String)` where the `Vec<u8>` is the
+/// extracted data and the `String` is the original file extension, if successful.
+///
+/// # Errors
+///
+/// See `extract`.
+pub fn extract_with_order(
+    input: &[u8],
+    lsbs: usize,
+    order: &impl PixelOrder,
+) -> StegResult<(Vec<u8>, String)> {
+    check_lsbs(lsbs, BITS_PER_BYTE)?;
+
+    let carrier = decode(input, None)?;
+    debug!("Carrier: {:?}", carrier.kind());
+
+    extract_plane(&carrier.plane, lsbs, order)
+}
+
+/// Extracts the data and extension from an already decoded plane.
+#[instrument(
+    name = "extract",
+    skip_all,
+    fields(width = image.width(), height = image.height(), lsbs = lsbs)
+)]
+pub(crate) fn extract_plane(
+    image: &RgbImage,
+    lsbs: usize,
+    order: &impl PixelOrder,
+) -> StegResult<(Vec<u8>, String)> {
+    let mut reader = PayloadReader::new(image, lsbs, order)?;
+    let header = read_header(&mut reader)?;
+
+    extract_payload(&mut reader, header.length, &ExtractOptions::new())
+}
+
+/// Extracts data embedded with an unknown number of least significant bits.
+///
+/// Every `lsbs` value the image supports is tried in increasing order, and the first
+/// one whose payload passes the checksum is returned. This finds the value chosen by
+/// `LsbSelection::Auto`, since it always picks the smallest one that fits.
+///
+/// # Arguments
+///
+/// * `input`: A slice of bytes representing the image data from which to extract content.
+/// * `seed`: The 64-bit seed used for the pseudo-random number generator during embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing a tuple `(Vec<u8>, String, usize)` with the extracted data,
+/// the original file extension and the `lsbs` value it was found with, if successful.
+///
+/// # Errors
+///
+/// The error of the attempt with `lsbs` = 1 when no attempt succeeds, or errors from
+/// the `image` crate during image decoding.
+pub fn extract_auto(input: &[u8], seed: u64) -> StegResult<(Vec<u8>, String, usize)> {
+    let carrier = StegoContainer::new(input)?.carrier(None);
+    debug!("Carrier: {:?}", carrier.kind());
+
+    extract_auto_carrier(&carrier, seed)
+}
+
+/// Tries every `lsbs` value the carrier supports, like `extract_auto`.
+fn extract_auto_carrier(carrier: &Carrier, seed: u64) -> StegResult<(Vec<u8>, String, usize)> {
+    let options = ExtractOptions::new().lsbs(LsbSelection::Auto).seed(seed);
+
+    extract_carrier(carrier, &options)
+        .map(|extracted| (extracted.data, extracted.extension, extracted.lsbs))
+}
+
+/// A set of parameters under which `recover` found a plausible payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveryHit {
+    /// The number of least significant bits per channel.
+    pub lsbs: usize,
+    /// The seed of the pixel order.
+    pub seed: u64,
+    /// The original file extension.
+    pub extension: String,
+    /// The hashing algorithm of the checksum.
+    pub hash: Hash,
+    /// The length of the embedded data and its metadata after the length field, in bytes.
+    pub length: usize,
+    /// Whether the checksum of the data was verified.
+    pub verified: bool,
+    /// How likely these are the right parameters, between 0 and 1.
+    pub confidence: f64,
+    /// The extracted data, if the checksum was verified.
+    pub data: Option<Vec<u8>>,
+}
+
+/// Searches for the parameters an image was embedded with, when they are not known exactly.
+///
+/// Every combination of `lsbs` and seed is tried in parallel. A candidate is dropped as
+/// soon as its header is implausible, so most wrong guesses cost only a few bytes of
+/// reading. Payloads with a plausible header are checked against their checksum, unless
+/// they are longer than `limit` bytes, in which case they are reported unverified with
+/// a lower confidence.
+///
+/// Only the `KeyedPermutation` of every seed is tried, as a `SeededShuffle` would
+/// generate a permutation of the whole container for every candidate.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the image data.
+/// * `lsbs_range`: The `lsbs` values to try.
+/// * `seeds`: The seeds to try.
+/// * `limit`: The longest payload, in bytes, whose checksum is verified, or `None` to
+///   verify every candidate.
+///
+/// # Returns
+///
+/// The candidates with a plausible payload, the most likely first. Images that cannot
+/// be decoded have no candidates.
+pub fn recover(
+    container: &[u8],
+    lsbs_range: RangeInclusive<usize>,
+    seeds: impl Iterator<Item = u64>,
+    limit: Option<usize>,
+) -> Vec<RecoveryHit> {
+    let Ok(carrier) = decode(container, None) else {
+        return Vec::new();
+    };
+    let lsbs_range =
+        *lsbs_range.start().max(&1)..=*lsbs_range.end().min(&carrier.kind().max_lsbs());
+
+    let candidates = seeds
+        .flat_map(|seed| lsbs_range.clone().map(move |lsbs| (lsbs, seed)))
+        .collect::<Vec<_>>();
+
+    let mut hits = candidates
+        .into_par_iter()
+        .filter_map(|(lsbs, seed)| {
+            let keyed = KeyedPermutation::new(seed);
+            let (header, mut reader) =
+                read_seeded_header(&carrier.plane, lsbs, &keyed, None).ok()?;
+
+            let mut hit = RecoveryHit {
+                lsbs,
+                seed,
+                extension: header.extension,
+                hash: header.hash,
+                length: header.length,
+                verified: false,
+                confidence: 0.0,
+                data: None,
+            };
+
+            if limit.is_none_or(|limit| header.length <= limit) {
+                let (data, _) =
+                    extract_payload(&mut reader, header.length, &ExtractOptions::new()).ok()?;
+                hit.verified = true;
+                hit.confidence = 1.0;
+                hit.data = Some(data);
+            } else {
+                // Extensions are usually short and alphanumeric, unlike random bytes
+                let typical = !hit.extension.is_empty()
+                    && hit.extension.len() <= 8
+                    && hit.extension.bytes().all(|b| b.is_ascii_alphanumeric());
+                hit.confidence = if typical { 0.5 } else { 0.25 };
+            }
+
+            Some(hit)
+        })
+        .collect::<Vec<_>>();
+
+    hits.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then(a.lsbs.cmp(&b.lsbs))
+            .then(a.seed.cmp(&b.seed))
+    });
+    debug!("Recovered {} candidates", hits.len());
+
+    hits
+}
+
+/// The metadata stored in front of the embedded data.
+pub(crate) struct Header {
+    /// The length of everything after the length field, in bytes.
+    pub(crate) length: usize,
+    /// The original file extension.
+    pub(crate) extension: String,
+    /// The hashing algorithm used for the checksum.
+    pub(crate) hash: Hash,
+    /// Whether the payload is marked as embedded with a `KeyedPermutation` of its seed.
+    pub(crate) keyed_order: bool,
+}
+
+/// Reads the header of a payload embedded with a seed, detecting its order, and returns
+/// it along with a reader in that order.
+///
+/// Without `shuffle`, the header is read with `keyed` only, and its error is returned
+/// as it is. With it, a header read with `keyed` is only trusted if it carries
+/// `KEYED_ORDER_FLAG`. Otherwise the image is read with `shuffle`, so images embedded
+/// before `KeyedPermutation` still extract. When both fail, the error of `keyed` is
+/// returned, as that is the order of current images.
+fn read_seeded_header<'a>(
+    image: &'a RgbImage,
+    lsbs: usize,
+    keyed: &'a KeyedPermutation,
+    shuffle: Option<&'a SeededShuffle>,
+) -> StegResult<(Header, PayloadReader<'a>)> {
+    let mut reader = PayloadReader::new(image, lsbs, keyed)?;
+    let Some(shuffle) = shuffle else {
+        return read_header(&mut reader).map(|header| (header, reader));
+    };
+    let keyed_error = match read_header(&mut reader) {
+        Ok(header) if header.keyed_order => return Ok((header, reader)),
+        Ok(_) => None,
+        Err(err) => Some(err),
+    };
+
+    // The shuffle stores a permutation of every embeddable bit, which needs them to be
+    // addressable
+    let capacity_bits = capacity_bits(image.width(), image.height(), lsbs)?;
+    if usize::try_from(capacity_bits).is_err() {
+        return Err(keyed_error.unwrap_or_else(|| {
+            StegError::CapacityExceedsUsizeMax(format!(
+                "The seeded shuffle of {} bits exceeds the address space of this platform",
+                capacity_bits
+            ))
+        }));
+    }
+
+    debug!("No keyed header, falling back to the seeded shuffle");
+    let mut reader = PayloadReader::new(image, lsbs, shuffle)?;
+    match read_header(&mut reader) {
+        Ok(header) => Ok((header, reader)),
+        // A truncated payload is more telling than a missing one
+        Err(err @ StegError::PayloadTruncated { .. }) => Err(err),
+        Err(err) => Err(keyed_error.unwrap_or(err)),
+    }
+}
+
+/// The longest extension a truncated payload is recognized by, as extensions are
+/// usually short, unlike random bytes.
+const TYPICAL_EXTENSION_LEN: usize = 8;
+
+/// The embedding rate estimated by sample pair analysis from which an image without a
+/// payload under the given seed is suspected to hold one under another seed.
+const WRONG_SEED_MIN_EMBEDDING_RATE: f64 = 0.1;
+
+/// The length of the reads from which `gather_sorted` is faster than `gather`, as
+/// sorting the positions only pays off when there are many of them.
+const SORTED_READ_MIN_BYTES: usize = 1024 * 1024;
+
+/// The number of samples of a bucket read at once by `gather_sorted`, few enough to stay
+/// in the cache of a core.
+const GATHER_BUCKET_SAMPLES: usize = 64 * 1024;
+
+/// Reads and parses only the header of an embedded payload.
+///
+/// This is much cheaper than a full extraction, and is used to tell whether an
+/// image plausibly holds a payload under the `lsbs` and order of `reader`. The
+/// payload can be extracted with the same `reader` afterwards.
+///
+/// # Errors
+///
+/// * `StegError::PayloadTruncated`: If the length field exceeds the capacity, but the rest
+///   of the header is valid.
+/// * `StegError::NoPayloadFound`: If the header is not valid otherwise.
+pub(crate) fn read_header(reader: &mut PayloadReader) -> StegResult<Header> {
+    let length = extract_length(reader)?;
     let length_size = core::mem::size_of::<u32>();
 
-    let payload = read_bytes(image, length + length_size, lsbs, seed)?;
-    let payload = &payload[length_size..];
+    let required_bits = (length as u64 + length_size as u64) * BITS_PER_BYTE as u64;
+    if required_bits > reader.capacity_bits {
+        return Err(truncation_error(reader, required_bits));
+    }
+
+    let prefix = reader.read(length_size, length.min(MAX_HEADER_SIZE - length_size))?;
+    let ParsedHeader {
+        extension,
+        hash,
+        keyed_order,
+        rest,
+        ..
+    } = parse_header(&prefix).map_err(no_payload)?;
+
+    let hash_length = hash.digest_size();
+    if rest.len() < hash_length {
+        return Err(no_payload(StegError::PayloadParse(format!(
+            "Checksum of {} bytes exceeds the payload",
+            hash_length
+        ))));
+    }
+
+    Ok(Header {
+        length,
+        extension,
+        hash,
+        keyed_order,
+    })
+}
+
+/// Extracts the payload of `length` bytes after the length field, reporting the bits
+/// read to the progress callback of `options` once per chunk.
+fn extract_payload(
+    reader: &mut PayloadReader,
+    length: usize,
+    options: &ExtractOptions,
+) -> Result<(Vec<u8>, String), StegError> {
+    let length_size = core::mem::size_of::<u32>();
 
-    let ext_len = payload[0] as usize;
-    let payload = &payload[1..];
-    let extension = String::from_utf8(payload[0..ext_len].into())?;
-    debug!("Extension: {} ({} bytes)", extension, ext_len);
-    let payload = &payload[ext_len..];
+    let progress = Tracker::new(
+        options.progress.as_ref(),
+        options.cancel.as_ref(),
+        Phase::Extract,
+        (length * BITS_PER_BYTE) as u64,
+    );
+    let payload = reader.read_tracked(length_size, length, &progress)?;
 
-    let hash_flag = payload[0];
-    let hash = Hash::from_repr(hash_flag).ok_or(StegError::HashFlagParse(format!(
-        "Failed to parse hash: {}",
-        hash_flag
-    )))?;
-    let payload = &payload[1..];
-    debug!("Hash: {:?}", hash);
+    let ParsedHeader {
+        extension,
+        hash,
+        fingerprint: expected_fingerprint,
+        rest: payload,
+        ..
+    } = parse_header(&payload)?;
 
     let mut hasher = select_hasher(hash);
     let hash_length = hasher.output_size();
-    let hash_val = &payload[..hash_length];
-    let payload = &payload[hash_length..];
+    if payload.len() < hash_length {
+        return Err(StegError::PayloadParse(format!(
+            "Checksum of {} bytes exceeds the payload",
+            hash_length
+        )));
+    }
+    let (hash_val, payload) = payload.split_at(hash_length);
 
-    let checksum = use_hasher(&mut *hasher, payload);
-    if *checksum != *hash_val {
-        return Err(StegError::ChecksumMismatch);
+    if options.verify {
+        let actual = use_hasher(&mut *hasher, payload);
+        if *actual != *hash_val {
+            return Err(checksum_error(
+                reader,
+                expected_fingerprint,
+                hash_val,
+                &actual,
+            ));
+        }
     }
 
     Ok((payload.to_vec(), extension))
 }
 
-fn extract_length(image: &RgbImage, lsbs: usize, seed: u64) -> StegResult<usize> {
-    let capacity_bytes = image.len();
+/// The metadata of a payload and where its data lies, read without reading the data.
+struct Layout {
+    extension: String,
+    hash: Hash,
+    checksum: Vec<u8>,
+    fingerprint: Option<Vec<u8>>,
+    /// The offset of the first data byte, from the start of the length field.
+    start: usize,
+    /// The offset right after the last data byte, from the start of the length field.
+    end: usize,
+}
 
+/// Reads the metadata of a payload of `length` bytes, up to where its data starts.
+fn read_layout(reader: &mut PayloadReader, length: usize) -> StegResult<Layout> {
     let length_size = core::mem::size_of::<u32>();
-    if capacity_bytes < length_size {
-        return Err(StegError::InsufficientCapacity(format!(
-            "Container is too small to hold the data: {} bytes available",
-            capacity_bytes
+
+    let prefix = reader.read(length_size, length.min(MAX_HEADER_SIZE - length_size))?;
+    let ParsedHeader {
+        extension,
+        hash,
+        fingerprint,
+        rest,
+        ..
+    } = parse_header(&prefix)?;
+
+    let hash_length = hash.digest_size();
+    if rest.len() < hash_length {
+        return Err(StegError::PayloadParse(format!(
+            "Checksum of {} bytes exceeds the payload",
+            hash_length
         )));
     }
-    let length = read_bytes(image, length_size, lsbs, seed)?;
+
+    Ok(Layout {
+        extension,
+        hash,
+        checksum: rest[..hash_length].to_vec(),
+        fingerprint: fingerprint.map(<[u8]>::to_vec),
+        start: length_size + prefix.len() - rest.len() + hash_length,
+        end: length_size + length,
+    })
+}
+
+/// Streams a payload of `length` bytes into `writer` in chunks, verifying its checksum
+/// once all of it has been written.
+fn stream_payload(
+    reader: &mut PayloadReader,
+    length: usize,
+    mut writer: impl Write,
+    options: &ExtractOptions,
+) -> StegResult<ExtractInfo> {
+    let Layout {
+        extension,
+        hash,
+        checksum: expected_checksum,
+        fingerprint: expected_fingerprint,
+        start,
+        end,
+    } = read_layout(reader, length)?;
+    let mut hasher = select_hasher(hash);
+    debug!("Streaming {} bytes of data", end - start);
+
+    let progress = Tracker::new(
+        options.progress.as_ref(),
+        options.cancel.as_ref(),
+        Phase::Extract,
+        ((end - start) * BITS_PER_BYTE) as u64,
+    );
+    for offset in (start..end).step_by(READ_CHUNK_SIZE) {
+        progress.check()?;
+        let chunk = reader.read(offset, READ_CHUNK_SIZE.min(end - offset))?;
+        // Positions are only kept for the current chunk
+        reader.discard(offset + chunk.len());
+
+        hasher.update(&chunk);
+        writer.write_all(&chunk)?;
+        progress.advance((chunk.len() * BITS_PER_BYTE) as u64);
+    }
+
+    if options.verify {
+        let actual = hasher.finalize_reset();
+        if *actual != *expected_checksum {
+            return Err(checksum_error(
+                reader,
+                expected_fingerprint.as_deref(),
+                &expected_checksum,
+                &actual,
+            ));
+        }
+    }
+
+    Ok(ExtractInfo {
+        extension,
+        data_bytes: (end - start) as u64,
+        hash,
+        warnings: Vec::new(),
+    })
+}
+
+/// Tells apart why the `expected` checksum of a payload does not match the `actual`
+/// one, using its fingerprint.
+fn checksum_error(
+    reader: &PayloadReader,
+    expected_fingerprint: Option<&[u8]>,
+    expected: &[u8],
+    actual: &[u8],
+) -> StegError {
+    match expected_fingerprint {
+        None => StegError::ChecksumMismatch,
+        Some(fingerprint_val) if *fingerprint_val != fingerprint(reader.image, reader.lsbs) => {
+            StegError::ContainerModified
+        }
+        Some(_) => StegError::PayloadCorrupted {
+            expected: expected.to_vec(),
+            actual: actual.to_vec(),
+        },
+    }
+}
+
+/// Reads the length field of a payload, which may exceed the capacity of the image.
+fn extract_length(reader: &mut PayloadReader) -> StegResult<usize> {
+    // `PayloadReader::new` checked that the container holds at least a header
+    let length_size = core::mem::size_of::<u32>();
+    let length = reader.read(0, length_size)?;
     let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
     debug!("Length: {} bytes", length);
-    if length + length_size > capacity_bytes {
-        return Err(StegError::InsufficientCapacity(format!(
-            "Container is too small to hold the data: {} bytes required, {} bytes available",
-            length + length_size,
-            capacity_bytes
-        )));
-    }
 
     Ok(length)
 }
 
-fn read_bytes(
-    container: &image::RgbImage,
-    length: usize,
-    lsbs: usize,
-    seed: u64,
-) -> StegResult<Vec<u8>> {
-    let width = container.width() as usize;
-    let height = container.height() as usize;
-
-    // Potential overflow when calculating width_bits
-    let width_bits = width
-        .checked_mul(EMBEDDABLE_CHANNELS)
-        .and_then(|res| res.checked_mul(lsbs))
-        .ok_or_else(|| {
-            StegError::CalculationOverflow(format!(
-            "Overflow calculating width_bits: width ({}) * EMBEDDABLE_CHANNELS ({}) * lsbs ({})",
-            width, EMBEDDABLE_CHANNELS, lsbs
-        ))
-        })?;
+/// Tells apart a payload cut short from bytes that hold no payload, when the length
+/// field of a payload needs `required_bits`, more than the image holds.
+///
+/// The payload counts as cut short if the part of its header the image still holds is
+/// valid, with a typical extension, which random bytes rarely are.
+fn truncation_error(reader: &mut PayloadReader, required_bits: u64) -> StegError {
+    let available_bits = reader.capacity_bits;
+    let length_size = core::mem::size_of::<u32>();
 
-    // Potential overflow when calculating capacity_bits
-    let capacity_bits = width_bits.checked_mul(height).ok_or_else(|| {
-        StegError::CalculationOverflow(format!(
-            "Overflow calculating capacity_bits: width_bits ({}) * height ({})",
-            width_bits, height
-        ))
-    })?;
+    let available = usize::try_from(available_bits / BITS_PER_BYTE as u64)
+        .unwrap_or(usize::MAX)
+        .saturating_sub(length_size);
+    let plausible = reader
+        .read(length_size, available.min(MAX_HEADER_SIZE - length_size))
+        .is_ok_and(|prefix| {
+            parse_header(&prefix).is_ok_and(|header| {
+                header.extension.len() <= TYPICAL_EXTENSION_LEN
+                    && header.extension.bytes().all(|b| b.is_ascii_alphanumeric())
+            })
+        });
 
-    // Potential overflow when calculating length_bits
-    let length_bits = length.checked_mul(BITS_PER_BYTE).ok_or_else(|| {
-        StegError::CalculationOverflow(format!(
-            "Overflow calculating length_bits: length ({}) * BITS_PER_BYTE ({})",
-            length, BITS_PER_BYTE
+    if plausible {
+        StegError::PayloadTruncated {
+            required_bits,
+            available_bits,
+        }
+    } else {
+        no_payload(insufficient_capacity(
+            required_bits,
+            available_bits,
+            reader.lsbs,
         ))
-    })?;
+    }
+}
 
-    if length_bits > capacity_bits {
-        return Err(StegError::InsufficientCapacity(format!(
-            "Container is too small to hold the data: {} bits required, {} bits available",
-            length_bits, capacity_bits
-        )));
+/// Wraps why a header is not valid into `StegError::NoPayloadFound`.
+fn no_payload(cause: StegError) -> StegError {
+    StegError::NoPayloadFound {
+        cause: Box::new(cause),
     }
+}
 
-    let mut rng = Pcg64Mcg::seed_from_u64(seed);
-    // The `amount` parameter must be the same as `length` fro reproducibility
-    let order = sample(&mut rng, capacity_bits, capacity_bits);
+/// Tells a payload that is missing from one embedded with another seed, by whether the
+/// least significant bits of `image` look like they hold one.
+fn diagnose_missing_payload(image: &RgbImage, err: StegError) -> StegError {
+    match err {
+        StegError::NoPayloadFound { cause }
+            if plane_embedding_rate(image) >= WRONG_SEED_MIN_EMBEDDING_RATE =>
+        {
+            debug!("The image looks embedded, suspecting a wrong seed");
+            StegError::WrongSeedSuspected { cause }
+        }
+        err => err,
+    }
+}
 
-    let mut output = vec![0; length];
+/// The positions of an order from some payload bit on.
+type Positions<'a> = Box<dyn Iterator<Item = u64> + 'a>;
 
-    output.par_chunks_mut(CHUNK_SIZE).enumerate().try_for_each(|(index, chunk)| -> StegResult<()> {
-        for (byte_index, byte) in chunk.iter_mut().enumerate() {
-            let byte_index = index * CHUNK_SIZE + byte_index;
+/// Reads the bytes of a payload in the order they were embedded in.
+///
+/// The positions of the order are generated only once, however many reads there are,
+/// and kept for the bytes read so far, so reading the header and then the whole payload
+/// costs no more than reading the whole payload. Streaming reads `discard` the
+/// positions they are done with instead, and reads of a part of the payload `seek` past
+/// the positions before it.
+pub(crate) struct PayloadReader<'a> {
+    image: &'a RgbImage,
+    lsbs: usize,
+    capacity_bits: u64,
+    positions: Positions<'a>,
+    /// Starts the positions of the order at a payload bit.
+    positions_from: Box<dyn Fn(u64) -> Positions<'a> + 'a>,
+    /// The positions generated so far and not discarded, in payload order.
+    generated: Vec<u64>,
+    /// The number of positions discarded from the front of `generated`.
+    discarded: usize,
+}
 
-            for bit_offset in 0..BITS_PER_BYTE {
-                // Potential overflow when calculating bit_index
-                let bit_index_seq = byte_index.checked_mul(BITS_PER_BYTE)
-                    .and_then(|res| res.checked_add(bit_offset))
-                    .ok_or_else(|| StegError::CalculationOverflow(format!(
-                        "Overflow calculating sequential bit_index: byte_index ({}) * BITS_PER_BYTE ({}) + bit_offset ({})",
-                        byte_index, BITS_PER_BYTE, bit_offset
-                    )))?;
+impl<'a> PayloadReader<'a> {
+    /// Creates a reader for the payload embedded in `image` under `lsbs` and `order`.
+    pub(crate) fn new(
+        image: &'a RgbImage,
+        lsbs: usize,
+        order: &'a impl PixelOrder,
+    ) -> StegResult<Self> {
+        check_dimensions(image.dimensions(), lsbs, MIN_HEADER_SIZE)?;
+        let capacity_bits = capacity_bits(image.width(), image.height(), lsbs)?;
+        let positions_from = move |start| -> Positions<'a> {
+            Box::new(order.positions_from(capacity_bits, start, capacity_bits))
+        };
 
-                let bit_index = order.index(bit_index_seq);
+        Ok(PayloadReader {
+            image,
+            lsbs,
+            capacity_bits,
+            positions: positions_from(0),
+            positions_from: Box::new(positions_from),
+            generated: Vec::new(),
+            discarded: 0,
+        })
+    }
 
-                let y = bit_index / width_bits;
+    /// Moves on to the bytes from `offset`, without generating the positions of the bytes
+    /// before it where the order allows. Like discarded bytes, the bytes before `offset`
+    /// cannot be read anymore.
+    pub(crate) fn seek(&mut self, offset: usize) {
+        let bits = offset * BITS_PER_BYTE;
 
-                let x_bit = bit_index % width_bits;
-                let x = x_bit / (EMBEDDABLE_CHANNELS * lsbs);
+        self.positions = (self.positions_from)(bits as u64);
+        self.generated.clear();
+        self.discarded = bits;
+    }
 
-                let bit_in_pixel = x_bit % (EMBEDDABLE_CHANNELS * lsbs);
+    /// Forgets the positions of the bytes before `offset`, which cannot be read anymore.
+    pub(crate) fn discard(&mut self, offset: usize) {
+        let bits = (offset * BITS_PER_BYTE)
+            .saturating_sub(self.discarded)
+            .min(self.generated.len());
 
-                let channel = bit_in_pixel / lsbs;
-                let bit_in_channel = bit_in_pixel % lsbs;
+        self.generated.drain(..bits);
+        self.discarded += bits;
+    }
 
-                let pixel = container.get_pixel(x as u32, y as u32);
+    /// Reads `length` bytes, starting `offset` bytes into the payload.
+    pub(crate) fn read(&mut self, offset: usize, length: usize) -> StegResult<Vec<u8>> {
+        self.read_tracked(offset, length, &Tracker::new(None, None, Phase::Extract, 0))
+    }
 
-                let bit = (pixel[channel] >> bit_in_channel) & 1;
-                *byte = (*byte << 1) | bit;
-            }
+    /// Reads `length` bytes like `read`, reporting the bits read to `progress` once per
+    /// chunk, and failing with `StegError::Cancelled` if the operation was cancelled.
+    #[instrument(
+        name = "read_bytes",
+        level = "debug",
+        skip_all,
+        fields(offset = offset, length = length)
+    )]
+    pub(crate) fn read_tracked(
+        &mut self,
+        offset: usize,
+        length: usize,
+        progress: &Tracker,
+    ) -> StegResult<Vec<u8>> {
+        // Potential overflow when calculating the bit range
+        let (start_bits, end_bits) = offset
+            .checked_add(length)
+            .and_then(|end| end.checked_mul(BITS_PER_BYTE))
+            .map(|end_bits| (offset * BITS_PER_BYTE, end_bits))
+            .ok_or_else(|| {
+                StegError::CalculationOverflow(format!(
+                    "Overflow calculating length_bits: (offset ({}) + length ({})) * BITS_PER_BYTE ({})",
+                    offset, length, BITS_PER_BYTE
+                ))
+            })?;
+
+        if end_bits as u64 > self.capacity_bits {
+            return Err(insufficient_capacity(
+                end_bits as u64,
+                self.capacity_bits,
+                self.lsbs,
+            ));
         }
 
-        Ok(())
-    })?;
+        assert!(
+            start_bits >= self.discarded,
+            "Reading bytes whose positions were discarded"
+        );
+
+        let generated_bits = self.discarded + self.generated.len();
+        if generated_bits < end_bits {
+            let missing = end_bits - generated_bits;
+            self.generated.extend(self.positions.by_ref().take(missing));
+        }
+        let positions = &self.generated[start_bits - self.discarded..end_bits - self.discarded];
+
+        let mut output = vec![0; length];
+        if length < SORTED_READ_MIN_BYTES {
+            gather(self.image, positions, self.lsbs, &mut output, progress);
+        } else if u32::try_from(self.capacity_bits).is_ok() {
+            // Every position is below `capacity_bits`, so they fit in 32 bits
+            gather_sorted::<u32>(
+                self.image.as_raw(),
+                positions,
+                self.lsbs,
+                &mut output,
+                progress,
+            );
+        } else {
+            gather_sorted::<u64>(
+                self.image.as_raw(),
+                positions,
+                self.lsbs,
+                &mut output,
+                progress,
+            );
+        }
+        // Cancelled chunks were skipped, leaving the output incomplete
+        progress.check()?;
 
-    Ok(output)
+        Ok(output)
+    }
+}
+
+/// Reads one byte into `output` for every 8 `positions`, most significant bit first,
+/// visiting the positions in payload order.
+fn gather(image: &RgbImage, positions: &[u64], lsbs: usize, output: &mut [u8], progress: &Tracker) {
+    // Cannot overflow: `capacity_bits`, a multiple of it, fits into a `u64`
+    let width_bits = image.width() as u64 * (EMBEDDABLE_CHANNELS * lsbs) as u64;
+    let pixel_bits = (EMBEDDABLE_CHANNELS * lsbs) as u64;
+
+    parallel(|| {
+        let chunk_size = chunk_size(output.len());
+
+        output
+            .par_chunks_mut(chunk_size)
+            .zip(positions.par_chunks(chunk_size * BITS_PER_BYTE))
+            .for_each(|(chunk, positions)| {
+                if progress.is_cancelled() {
+                    return;
+                }
+                if lsbs == 1 && gather_lsb1(image.as_raw(), positions, chunk) {
+                    progress.advance(positions.len() as u64);
+                    return;
+                }
+
+                for (byte, positions) in chunk.iter_mut().zip(positions.chunks_exact(BITS_PER_BYTE))
+                {
+                    for &bit_index in positions {
+                        let y = bit_index / width_bits;
+
+                        let x_bit = bit_index % width_bits;
+                        let x = x_bit / pixel_bits;
+
+                        let bit_in_pixel = (x_bit % pixel_bits) as usize;
+
+                        let channel = bit_in_pixel / lsbs;
+                        let bit_in_channel = bit_in_pixel % lsbs;
+
+                        let pixel = image.get_pixel(x as u32, y as u32);
+
+                        let bit = (pixel[channel] >> bit_in_channel) & 1;
+                        *byte = (*byte << 1) | bit;
+                    }
+                }
+                progress.advance(positions.len() as u64);
+            });
+    });
+}
+
+/// Reads one byte into `output` for every 8 `positions`, most significant bit first.
+///
+/// Like embedding, the positions are paired with their bits and grouped by the part of
+/// the container they fall into, so that the container is read a cache-sized bucket at
+/// a time instead of at random. A counting sort into buckets is enough for that, and
+/// much cheaper than fully sorting the positions.
+fn gather_sorted<I: BitIndex>(
+    samples: &[u8],
+    positions: &[u64],
+    lsbs: usize,
+    output: &mut [u8],
+    progress: &Tracker,
+) {
+    // The samples are in memory, so their indices fit into a `usize`
+    let bucket_of = |bit_index: u64| (bit_index / lsbs as u64) as usize / GATHER_BUCKET_SAMPLES;
+
+    let mut starts = vec![0; samples.len().div_ceil(GATHER_BUCKET_SAMPLES) + 1];
+    for &bit_index in positions {
+        starts[bucket_of(bit_index) + 1] += 1;
+    }
+    for bucket in 1..starts.len() {
+        starts[bucket] += starts[bucket - 1];
+    }
+
+    let mut inverse_ord = vec![(I::from_u64(0), I::from_u64(0)); positions.len()];
+    let mut next = starts.clone();
+    for (bit_index_seq, &bit_index) in positions.iter().enumerate() {
+        let slot = &mut next[bucket_of(bit_index)];
+        inverse_ord[*slot] = (I::from_u64(bit_index), I::from_u64(bit_index_seq as u64));
+        *slot += 1;
+    }
+
+    let bytes = output.iter().map(|_| AtomicU8::new(0)).collect::<Vec<_>>();
+    parallel(|| {
+        starts.par_windows(2).for_each(|bucket| {
+            if progress.is_cancelled() {
+                return;
+            }
+            for &(bit_index, bit_index_seq) in &inverse_ord[bucket[0]..bucket[1]] {
+                let (bit_index, bit_index_seq) =
+                    (bit_index.to_u64(), bit_index_seq.to_u64() as usize);
+                let sample = samples[(bit_index / lsbs as u64) as usize];
+                let bit = (sample >> (bit_index % lsbs as u64)) & 1;
+                let shift = BITS_PER_BYTE - 1 - bit_index_seq % BITS_PER_BYTE;
+                bytes[bit_index_seq / BITS_PER_BYTE].fetch_or(bit << shift, Relaxed);
+            }
+            progress.advance((bucket[1] - bucket[0]) as u64);
+        });
+    });
+
+    for (byte, bits) in output.iter_mut().zip(bytes) {
+        *byte = bits.into_inner();
+    }
 }
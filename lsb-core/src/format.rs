@@ -0,0 +1,143 @@
+use image::ImageFormat;
+
+use super::{error::*, image::is_lossless};
+
+/// The MIME type of JPEG XL, which the `image` crate does not know.
+const JXL_MIME_TYPE: &str = "image/jxl";
+/// The file extensions of JPEG XL, which the `image` crate does not know.
+const JXL_EXTENSIONS: [&str; 1] = ["jxl"];
+
+/// The image formats a container can be decoded from or an embedding written in.
+///
+/// This mirrors `image::ImageFormat`, so that the public API does not change with every
+/// release of the `image` crate, and adds JPEG XL, which the `image` crate has no codec
+/// for. Only the lossless formats can be written, see `image::LOSSLESS_FORMATS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+    Pnm,
+    Tiff,
+    Tga,
+    Dds,
+    Bmp,
+    Ico,
+    Hdr,
+    OpenExr,
+    Farbfeld,
+    Avif,
+    Qoi,
+    Pcx,
+    /// JPEG XL, decoded and encoded with the `jxl` feature. Outputs are always lossless.
+    Jxl,
+}
+
+impl Format {
+    /// Determines the format from a file extension, such as `"png"` or `"EXR"`.
+    ///
+    /// Unlike `image::output_format_from_extension`, this also returns lossy formats.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        if extension.eq_ignore_ascii_case(JXL_EXTENSIONS[0]) {
+            return Some(Format::Jxl);
+        }
+        ImageFormat::from_extension(extension).and_then(|format| format.try_into().ok())
+    }
+
+    /// Determines the format from a MIME type, such as `"image/png"`.
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        if mime_type == JXL_MIME_TYPE {
+            return Some(Format::Jxl);
+        }
+        ImageFormat::from_mime_type(mime_type).and_then(|format| format.try_into().ok())
+    }
+
+    /// The MIME type of the format.
+    pub fn mime_type(self) -> &'static str {
+        match ImageFormat::try_from(self) {
+            Ok(format) => format.to_mime_type(),
+            Err(_) => JXL_MIME_TYPE,
+        }
+    }
+
+    /// The file extensions of the format, the most common one first.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match ImageFormat::try_from(self) {
+            Ok(format) => format.extensions_str(),
+            Err(_) => &JXL_EXTENSIONS,
+        }
+    }
+
+    /// Returns whether the format stores samples losslessly, so that embedded bits
+    /// survive encoding.
+    pub fn is_lossless(self) -> bool {
+        is_lossless(self)
+    }
+}
+
+impl TryFrom<Format> for ImageFormat {
+    type Error = StegError;
+
+    /// Converts to a format of the `image` crate, failing with
+    /// `StegError::UnsupportedFormat` for JPEG XL, which it has no codec for.
+    fn try_from(format: Format) -> StegResult<Self> {
+        Ok(match format {
+            Format::Png => ImageFormat::Png,
+            Format::Jpeg => ImageFormat::Jpeg,
+            Format::Gif => ImageFormat::Gif,
+            Format::WebP => ImageFormat::WebP,
+            Format::Pnm => ImageFormat::Pnm,
+            Format::Tiff => ImageFormat::Tiff,
+            Format::Tga => ImageFormat::Tga,
+            Format::Dds => ImageFormat::Dds,
+            Format::Bmp => ImageFormat::Bmp,
+            Format::Ico => ImageFormat::Ico,
+            Format::Hdr => ImageFormat::Hdr,
+            Format::OpenExr => ImageFormat::OpenExr,
+            Format::Farbfeld => ImageFormat::Farbfeld,
+            Format::Avif => ImageFormat::Avif,
+            Format::Qoi => ImageFormat::Qoi,
+            Format::Pcx => ImageFormat::Pcx,
+            Format::Jxl => {
+                return Err(StegError::UnsupportedFormat(
+                    "The image crate has no JPEG XL codec".to_string(),
+                ));
+            }
+        })
+    }
+}
+
+impl TryFrom<ImageFormat> for Format {
+    type Error = StegError;
+
+    /// Converts a format of the `image` crate, failing with `StegError::UnsupportedFormat`
+    /// for formats added to it after this crate was released.
+    fn try_from(format: ImageFormat) -> StegResult<Self> {
+        Ok(match format {
+            ImageFormat::Png => Format::Png,
+            ImageFormat::Jpeg => Format::Jpeg,
+            ImageFormat::Gif => Format::Gif,
+            ImageFormat::WebP => Format::WebP,
+            ImageFormat::Pnm => Format::Pnm,
+            ImageFormat::Tiff => Format::Tiff,
+            ImageFormat::Tga => Format::Tga,
+            ImageFormat::Dds => Format::Dds,
+            ImageFormat::Bmp => Format::Bmp,
+            ImageFormat::Ico => Format::Ico,
+            ImageFormat::Hdr => Format::Hdr,
+            ImageFormat::OpenExr => Format::OpenExr,
+            ImageFormat::Farbfeld => Format::Farbfeld,
+            ImageFormat::Avif => Format::Avif,
+            ImageFormat::Qoi => Format::Qoi,
+            ImageFormat::Pcx => Format::Pcx,
+            format => {
+                return Err(StegError::UnsupportedFormat(format!(
+                    "Format {:?} is not known to this version of lsb-core",
+                    format
+                )));
+            }
+        })
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use super::{
+    cancel::check_cancelled,
+    embed::{EmbedOptions, EmbedReport, decode_container, embed_reader_inner},
+    error::*,
+    extract::{ExtractInfo, ExtractOptions, extract_carrier_to_writer},
+    format::Format,
+    image::*,
+    order::KeyedPermutation,
+    progress::*,
+    stego::StegoContainer,
+    warning::Warning,
+};
+
+/// The extension of data embedded from a file without one.
+const DEFAULT_EXTENSION: &str = "bin";
+
+/// Embeds a file into a container image file, writing the result to another file.
+///
+/// The extension of `input` is embedded along with its data, or `"bin"` if it has none,
+/// which the report warns of with `Warning::ExtensionDefaulted`, and the output format is detected from the extension of `output`, overriding
+/// `options.format`. An `output` without an extension keeps `options.format`, or the format
+/// of the container if that is unset. The input is streamed into the embedding rather than read into
+/// memory. If the embedding fails, no file is left behind at `output`.
+///
+/// # Arguments
+///
+/// * `input`: The file to embed.
+/// * `container`: The container image file.
+/// * `output`: The file the embedded image is written to.
+/// * `options`: The parameters of the embedding.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `EmbedReport` of the embedding, if successful.
+///
+/// # Errors
+///
+/// See `embed_with`. Additionally:
+/// * `StegError::UnsupportedFormat`: If the extension of `output` is unknown or belongs
+///   to a lossy format.
+/// * `StegError::Io`: If a file cannot be read or written, naming its path.
+pub fn embed_path(
+    input: &Path,
+    container: &Path,
+    output: &Path,
+    options: &EmbedOptions,
+) -> StegResult<EmbedReport> {
+    let start = Instant::now();
+
+    let options = match output_format_from_path(output)? {
+        Some(format) => options.clone().format(format),
+        None => options.clone(),
+    };
+    let extension = input.extension().and_then(OsStr::to_str);
+
+    let container = fs::read(container).map_err(|e| path_error("read", container, e))?;
+    let container = decode_container(&container, &options)?;
+
+    let input_file = File::open(input).map_err(|e| path_error("read", input, e))?;
+    let input_len = input_file
+        .metadata()
+        .map_err(|e| path_error("read", input, e))?
+        .len();
+    let reader = WithPath::new(BufReader::new(input_file), input, "read");
+
+    let writer = File::create(output).map_err(|e| path_error("write", output, e))?;
+    let mut writer = WithPath::new(BufWriter::new(writer), output, "write");
+
+    let result = embed_reader_inner(
+        reader,
+        input_len,
+        extension.unwrap_or(DEFAULT_EXTENSION),
+        &container,
+        &options,
+        &KeyedPermutation::new(options.seed),
+        &mut writer,
+    )
+    .and_then(|report| {
+        writer.flush()?;
+        Ok(report)
+    });
+    // Don't leave an empty or partial image behind
+    if result.is_err() {
+        let _ = fs::remove_file(output);
+    }
+
+    let mut report = result?;
+    report.elapsed = start.elapsed();
+    if extension.is_none() {
+        let warning = Warning::ExtensionDefaulted(DEFAULT_EXTENSION.to_string());
+        report.warnings.insert(0, warning);
+    }
+
+    Ok(report)
+}
+
+/// Extracts the data embedded in a container image file into a file in `output_dir`.
+///
+/// The file is named after the container, with the embedded extension in place of its
+/// own, e.g. `secret.txt` for a `secret.png` container holding a text file. The data is
+/// streamed into a `.part` file first, which is only renamed once its checksum is
+/// verified, so a failed extraction leaves nothing behind.
+///
+/// # Arguments
+///
+/// * `container`: The container image file.
+/// * `output_dir`: The directory the extracted file is written to.
+/// * `options`: The parameters of the extraction.
+///
+/// # Returns
+///
+/// A `StegResult` containing the path of the extracted file and the `ExtractInfo` of the
+/// extraction, if successful.
+///
+/// # Errors
+///
+/// See `extract_with`. Additionally:
+/// * `StegError::Io`: If a file cannot be read or written, naming its path.
+pub fn extract_path(
+    container: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> StegResult<(PathBuf, ExtractInfo)> {
+    options.check_lsbs()?;
+
+    let carrier = {
+        let bytes = fs::read(container).map_err(|e| path_error("read", container, e))?;
+        StegoContainer::new(&bytes)?.carrier(None)
+    };
+    milestone(options.progress.as_ref(), Phase::Decode);
+    check_cancelled(options.cancel.as_ref())?;
+
+    let stem = container.file_stem().unwrap_or(OsStr::new("extracted"));
+    let output_file = |extension: &str| {
+        let mut name = stem.to_os_string();
+        if !extension.is_empty() {
+            name.push(".");
+            name.push(extension);
+        }
+        output_dir.join(name)
+    };
+
+    // The extension is only known once the data is written, and the data only verified
+    // once all of it is
+    let partial = output_file("part");
+    let writer = File::create(&partial).map_err(|e| path_error("write", &partial, e))?;
+    let mut writer = WithPath::new(BufWriter::new(writer), &partial, "write");
+
+    let info = extract_carrier_to_writer(&carrier, &mut writer, options)
+        .and_then(|info| {
+            writer.flush()?;
+            Ok(info)
+        })
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        })?;
+
+    let output = output_file(&info.extension);
+    fs::rename(&partial, &output).map_err(|e| path_error("write", &output, e))?;
+
+    Ok((output, info))
+}
+
+/// Determines the output format from the extension of a path, if it has one.
+fn output_format_from_path(path: &Path) -> StegResult<Option<Format>> {
+    path.extension()
+        .map(|extension| output_format_from_extension(&extension.to_string_lossy()))
+        .transpose()
+}
+
+/// Names the path an I/O error happened on.
+fn path_error(action: &str, path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(
+        err.kind(),
+        format!("Failed to {} {}: {}", action, path.display(), err),
+    )
+}
+
+/// Names the path of a reader or writer in its I/O errors.
+struct WithPath<'a, T> {
+    inner: T,
+    path: &'a Path,
+    action: &'static str,
+}
+
+impl<'a, T> WithPath<'a, T> {
+    fn new(inner: T, path: &'a Path, action: &'static str) -> Self {
+        WithPath {
+            inner,
+            path,
+            action,
+        }
+    }
+
+    fn map_err(&self, err: io::Error) -> io::Error {
+        path_error(self.action, self.path, err)
+    }
+}
+
+impl<T: Read> Read for WithPath<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|e| self.map_err(e))
+    }
+}
+
+impl<T: Write> Write for WithPath<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(|e| self.map_err(e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(|e| self.map_err(e))
+    }
+}
+
+impl<T: Seek> Seek for WithPath<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos).map_err(|e| self.map_err(e))
+    }
+}
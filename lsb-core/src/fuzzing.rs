@@ -0,0 +1,28 @@
+use super::{
+    error::*,
+    hash::Hash,
+    payload::{ParsedHeader, parse_header},
+};
+
+/// Parses the metadata at the start of a payload, after the length field, the way
+/// extraction does.
+///
+/// # Arguments
+///
+/// * `payload`: The bytes of a payload after the length field.
+///
+/// # Returns
+///
+/// A `StegResult` containing the extension, the hash and whether the payload holds a
+/// fingerprint, along with the checksum and data following the metadata, if successful.
+pub fn parse_payload_header(payload: &[u8]) -> StegResult<(String, Hash, bool, &[u8])> {
+    let ParsedHeader {
+        extension,
+        hash,
+        fingerprint,
+        rest,
+        ..
+    } = parse_header(payload)?;
+
+    Ok((extension, hash, fingerprint.is_some(), rest))
+}
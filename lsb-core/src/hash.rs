@@ -1,20 +1,25 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+};
+use core::{fmt, str::FromStr};
+
 use digest::DynDigest;
-pub use strum::ParseError;
-use strum::{EnumString, FromRepr};
+use strum::FromRepr;
+use thiserror::Error;
+
+use super::consts::{CHUNK_SIZE, FINGERPRINT_SIZE};
 
 /// Represents the available hashing algorithms.
 ///
 /// This enum is used to specify which hashing algorithm to use for various operations.
 /// It derives several traits for convenience, including:
 /// - `Debug`, `Clone`, `Copy`, `PartialEq`, `Eq`: Standard Rust traits.
-/// - `EnumString`: To allow parsing from string representations (e.g., "SHA256").
 /// - `FromRepr`: To allow conversion from its underlying integer representation.
 ///
-/// The `strum(serialize_all = "UPPERCASE")` attribute ensures that string representations
-/// (e.g., for parsing or display) use uppercase names like "BLAKE3", "SHA256", etc.
+/// Names are parsed with `Hash::parse`, which every frontend shares.
 /// The `repr(u8)` attribute specifies that the enum is represented by an 8-bit unsigned integer.
-#[derive(Debug, Clone, Copy, EnumString, FromRepr)]
-#[strum(serialize_all = "UPPERCASE")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
 #[repr(u8)]
 pub enum Hash {
     Blake3 = 0,
@@ -23,6 +28,99 @@ pub enum Hash {
     Sha1 = 3,
 }
 
+/// Every name `Hash::parse` accepts, in lowercase, the canonical name of each algorithm
+/// first.
+pub const HASH_NAMES: [&str; 10] = [
+    "blake3", "blake-3", "sha256", "sha-256", "sha2-256", "sha512", "sha-512", "sha2-512", "sha1",
+    "sha-1",
+];
+
+impl Hash {
+    /// Every hashing algorithm, the default first.
+    pub fn all() -> &'static [Hash] {
+        &[Hash::Blake3, Hash::Sha256, Hash::Sha512, Hash::Sha1]
+    }
+
+    /// The canonical name of the algorithm, such as "sha256", which `parse` accepts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hash::Blake3 => "blake3",
+            Hash::Sha256 => "sha256",
+            Hash::Sha512 => "sha512",
+            Hash::Sha1 => "sha1",
+        }
+    }
+
+    /// The size of a checksum of the algorithm, in bytes, which every payload embedded
+    /// with it spends on its checksum.
+    pub fn digest_size(&self) -> usize {
+        match self {
+            Hash::Blake3 => 32,
+            Hash::Sha256 => 32,
+            Hash::Sha512 => 64,
+            Hash::Sha1 => 20,
+        }
+    }
+
+    /// Parses the name of a hashing algorithm.
+    ///
+    /// Names are matched ignoring ASCII case, with underscores counting as hyphens, so
+    /// "SHA256", "sha-256", "SHA2_256" and "Blake3" are all accepted. See `HASH_NAMES`.
+    ///
+    /// # Errors
+    ///
+    /// * `HashParseError`: If `name` is none of `HASH_NAMES`.
+    pub fn parse(name: &str) -> Result<Hash, HashParseError> {
+        let normalized = name.to_ascii_lowercase().replace('_', "-");
+
+        match normalized.as_str() {
+            "blake3" | "blake-3" => Ok(Hash::Blake3),
+            "sha256" | "sha-256" | "sha2-256" => Ok(Hash::Sha256),
+            "sha512" | "sha-512" | "sha2-512" => Ok(Hash::Sha512),
+            "sha1" | "sha-1" => Ok(Hash::Sha1),
+            _ => Err(HashParseError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Hash {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Hash::parse(s)
+    }
+}
+
+/// The error of parsing a name that is not one of a hashing algorithm.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "Unknown hash algorithm {name:?}, expected one of: {}",
+    HASH_NAMES.join(", ")
+)]
+pub struct HashParseError {
+    name: String,
+}
+
+impl HashParseError {
+    /// The name that was given.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The names that are accepted, ignoring ASCII case.
+    pub fn accepted(&self) -> &'static [&'static str] {
+        &HASH_NAMES
+    }
+}
+
 /// Updates the given hasher with data and returns the resulting hash.
 ///
 /// This function takes a mutable reference to a dynamic digest object (`DynDigest`),
@@ -66,3 +164,48 @@ pub(crate) fn select_hasher(hash: Hash) -> Box<dyn DynDigest> {
         Sha1 => Box::new(sha1::Sha1::default()),
     }
 }
+
+/// Computes a short fingerprint of the bits of the samples of a plane that an embedding
+/// leaves alone.
+///
+/// Every sample has its lowest `lsbs` bits masked off before hashing with BLAKE3, so
+/// the fingerprint is the same before and after embedding, but changes when the
+/// container itself is modified, e.g. by re-saving it with color management.
+pub(crate) fn fingerprint(samples: &[u8], lsbs: usize) -> [u8; FINGERPRINT_SIZE] {
+    let mut fingerprinter = Fingerprinter::new(lsbs);
+    fingerprinter.update(samples);
+    fingerprinter.finish()
+}
+
+/// Computes a `fingerprint` incrementally, from the samples of consecutive rows.
+pub(crate) struct Fingerprinter {
+    hasher: blake3::Hasher,
+    mask: u8,
+}
+
+impl Fingerprinter {
+    pub(crate) fn new(lsbs: usize) -> Self {
+        Fingerprinter {
+            hasher: blake3::Hasher::new(),
+            mask: u8::MAX.checked_shl(lsbs as u32).unwrap_or(0),
+        }
+    }
+
+    /// Adds the next samples, in the order of an `RgbImage`.
+    pub(crate) fn update(&mut self, samples: &[u8]) {
+        let mut masked = [0; CHUNK_SIZE];
+
+        for chunk in samples.chunks(CHUNK_SIZE) {
+            for (masked, &sample) in masked.iter_mut().zip(chunk) {
+                *masked = sample & self.mask;
+            }
+            self.hasher.update(&masked[..chunk.len()]);
+        }
+    }
+
+    pub(crate) fn finish(self) -> [u8; FINGERPRINT_SIZE] {
+        let mut fingerprint = [0; FINGERPRINT_SIZE];
+        fingerprint.copy_from_slice(&self.hasher.finalize().as_bytes()[..FINGERPRINT_SIZE]);
+        fingerprint
+    }
+}
@@ -1,32 +1,1019 @@
-use std::io::Cursor;
+use std::io::{BufRead, Cursor, Seek, Write};
 
-use super::error::StegResult;
-use image::ImageReader;
-pub use image::{ImageFormat, RgbImage};
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+
+use super::{
+    capacity::required_dimensions,
+    consts::{BITS_PER_BYTE, EMBEDDABLE_CHANNELS},
+    error::{StegError, StegResult},
+    format::Format,
+    hash::Hash,
+    jxl::{decode_jxl, is_jxl, jxl_dimensions, write_jxl},
+    metadata::{Metadata, write_tiff_metadata},
+    plane::check_rgb_len,
+    zune::decode_zune,
+};
+pub use image::{DynamicImage, GrayImage, RgbImage, imageops::FilterType};
+use image::{
+    ImageFormat, ImageReader, Rgb, Rgb32FImage,
+    error::{EncodingError, ImageError},
+};
+use tiff::{
+    TiffError, TiffResult,
+    encoder::{
+        Rational, TiffEncoder,
+        colortype::RGB8,
+        compression::{Compression, Deflate, DeflateLevel, Lzw, Uncompressed},
+    },
+    tags::ResolutionUnit,
+};
+use tracing::{Span, field, instrument};
+
+/// The version of the encoded outputs, bumped whenever a release changes the bytes it
+/// writes for the same embedding.
+///
+/// PNG, WebP and TIFF outputs are written with every parameter of their encoders set by
+/// this crate rather than left to upstream defaults: the compression and filter of
+/// `OutputOptions` and a single `IDAT` chunk for PNG, the predictor transform of
+/// lossless WebP, and strips of about 1 MB, the Deflate level and a resolution without
+/// unit for TIFF. Embedding the same data into the same container with the same options
+/// therefore gives byte-identical files in these formats for as long as this stays the
+/// same, which the `data/golden-v*` files are checked against.
+///
+/// What is not guaranteed: the compressed bytes if an upgrade of the Deflate or LZW
+/// compressors changes how they compress, although the pixels and the layout of chunks
+/// and tags stay the same, and the bytes of the other formats, which have no encoding
+/// parameters and are written by the `image` crate. Their pixels are guaranteed.
+pub const FORMAT_OUTPUT_VERSION: u32 = 1;
+
+/// The size TIFF outputs split their image data into, in bytes of samples per strip.
+const TIFF_STRIP_BYTES: u64 = 1_000_000;
+
+/// The largest width and height of ICO images, whose directory stores them in a byte.
+const ICO_MAX_SIZE: u32 = 256;
+
+/// The largest width and height of WebP outputs. Lossless WebP stores up to 16384 in 14
+/// bits, but the `image-webp` decoder rejects the 16384-pixel images its encoder writes.
+const WEBP_MAX_SIZE: u32 = 16383;
+
+/// The most pixels the `qoi` crate encodes or decodes.
+const QOI_MAX_PIXELS: u64 = 400_000_000;
+
+/// The size of the file header and info header of BMP outputs.
+const BMP_HEADER_BYTES: u64 = 54;
 
 /// A list of image formats considered lossless and suitable for embedding.
-pub const LOSSLESS_FORMATS: [ImageFormat; 10] = [
-    ImageFormat::Png,
-    ImageFormat::WebP,
-    ImageFormat::Pnm,
-    ImageFormat::Tiff,
-    ImageFormat::Tga,
-    ImageFormat::Bmp,
-    ImageFormat::Ico,
-    ImageFormat::Hdr,
-    ImageFormat::Farbfeld,
-    ImageFormat::Qoi,
+///
+/// JPEG XL is only among them with the `jxl` feature.
+pub const LOSSLESS_FORMATS: &[Format] = &[
+    Format::Png,
+    Format::WebP,
+    Format::Pnm,
+    Format::Tiff,
+    Format::Tga,
+    Format::Bmp,
+    Format::Ico,
+    Format::Hdr,
+    Format::OpenExr,
+    Format::Farbfeld,
+    Format::Qoi,
+    #[cfg(feature = "jxl")]
+    Format::Jxl,
 ];
 
-pub(crate) fn decode(container: &[u8]) -> StegResult<RgbImage> {
-    let container_reader = ImageReader::new(Cursor::new(container)).with_guessed_format()?;
-    let image = container_reader.decode()?.to_rgb8();
+/// Returns whether `format` stores samples losslessly, so that embedded bits survive encoding.
+pub fn is_lossless(format: Format) -> bool {
+    LOSSLESS_FORMATS.contains(&format)
+}
+
+/// Returns the image formats an embedding can be written in.
+pub fn supported_output_formats() -> &'static [Format] {
+    LOSSLESS_FORMATS
+}
+
+/// Determines the output format from a file extension, such as `"png"` or `"EXR"`.
+///
+/// # Errors
+///
+/// `StegError::UnsupportedOutputFormat` if the extension is unknown or belongs to a lossy
+/// format. The error lists the supported formats.
+pub fn output_format_from_extension(extension: &str) -> StegResult<Format> {
+    let format = Format::from_extension(extension)
+        .ok_or_else(|| unsupported_output_format(extension, "it is not a known image format"))?;
+
+    check_output_format_as(format, extension)?;
+
+    Ok(format)
+}
+
+/// Determines the output format from a file extension or a format name, such as `"png"`,
+/// `".EXR"` or `"OpenExr"`, in any case.
+///
+/// The bindings and the command-line tool parse the formats they are given with it, so
+/// that they reject the same formats with the same message.
+///
+/// # Errors
+///
+/// `StegError::UnsupportedOutputFormat` if the format is unknown or cannot hold an
+/// embedding. The error lists the supported formats.
+pub fn parse_output_format(format: &str) -> StegResult<Format> {
+    let name = format.strip_prefix('.').unwrap_or(format);
+    let parsed = Format::from_extension(name)
+        .or_else(|| {
+            supported_output_formats()
+                .iter()
+                .copied()
+                .find(|supported| format!("{:?}", supported).eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| unsupported_output_format(format, "it is not a known image format"))?;
+
+    check_output_format_as(parsed, format)?;
+
+    Ok(parsed)
+}
+
+/// Returns an error listing the supported formats unless `format` is lossless.
+pub(crate) fn check_output_format(format: Format) -> StegResult<()> {
+    check_output_format_as(format, &format!("{:?}", format))
+}
+
+/// Like `check_output_format`, naming the format as it was `given` in the error.
+fn check_output_format_as(format: Format, given: &str) -> StegResult<()> {
+    if format == Format::Jxl && !is_lossless(format) {
+        return Err(unsupported_output_format(
+            given,
+            "it needs the `jxl` feature of lsb-core",
+        ));
+    }
+    if !is_lossless(format) {
+        return Err(unsupported_output_format(
+            given,
+            "it is lossy, which would destroy the payload",
+        ));
+    }
+
+    Ok(())
+}
+
+/// The error for an output `format` that cannot be written, for `reason`.
+fn unsupported_output_format(format: &str, reason: &str) -> StegError {
+    StegError::UnsupportedOutputFormat {
+        format: format.to_string(),
+        reason: reason.to_string(),
+        supported: supported_output_formats().to_vec(),
+    }
+}
+
+/// Returns an error naming the limit unless `format` can store an image of `width` by
+/// `height` pixels, so that embeddings fail before any bits are written rather than when
+/// encoding the output.
+///
+/// HDR and Farbfeld outputs are limited to what their decoders in the `image` crate
+/// accept, as larger ones could not be extracted from again.
+pub(crate) fn check_output_dimensions(
+    format: Format,
+    (width, height): (u32, u32),
+) -> StegResult<()> {
+    let pixels = u64::from(width) * u64::from(height);
+
+    let limit = match format {
+        Format::Ico if width > ICO_MAX_SIZE || height > ICO_MAX_SIZE => {
+            format!("ICO images are at most {0}x{0} pixels", ICO_MAX_SIZE)
+        }
+        Format::WebP if width > WEBP_MAX_SIZE || height > WEBP_MAX_SIZE => {
+            format!("WebP images are at most {0}x{0} pixels", WEBP_MAX_SIZE)
+        }
+        Format::Tga if width > u32::from(u16::MAX) || height > u32::from(u16::MAX) => {
+            format!("TGA images are at most {0}x{0} pixels", u16::MAX)
+        }
+        Format::Png if width > i32::MAX as u32 || height > i32::MAX as u32 => {
+            format!("PNG images are at most {0}x{0} pixels", i32::MAX)
+        }
+        Format::Qoi if pixels > QOI_MAX_PIXELS => {
+            format!("QOI images have at most {} pixels", QOI_MAX_PIXELS)
+        }
+        // Rows of 24-bit pixels are padded to 4 bytes, and the file size is a 32-bit field
+        Format::Bmp
+            if BMP_HEADER_BYTES
+                + (u64::from(width) * 3).next_multiple_of(4) * u64::from(height)
+                > u64::from(u32::MAX) =>
+        {
+            "BMP files of 24-bit pixels are at most 4 GiB".to_string()
+        }
+        Format::Hdr if pixels.checked_mul(3).is_none() => {
+            "HDR images are only decoded if their 8-bit RGB samples fit in 2^64 bytes".to_string()
+        }
+        Format::Farbfeld if pixels.checked_mul(8).is_none() => {
+            "Farbfeld images are only decoded if their 16-bit RGBA samples fit in 2^64 bytes"
+                .to_string()
+        }
+        _ => return Ok(()),
+    };
+
+    Err(StegError::UnsupportedFormat(format!(
+        "The output would be {}x{} pixels, too large for {:?}: {}",
+        width, height, format, limit
+    )))
+}
+
+/// How hard PNG outputs are compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngCompression {
+    /// Compresses with fdeflate, several times faster than zlib at the cost of somewhat
+    /// larger files.
+    Fast,
+    /// Compresses with zlib at its default level.
+    Default,
+    /// Compresses with zlib at its highest level, for the smallest files.
+    Best,
+}
+
+/// The filter PNG outputs apply to every row before compressing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngFilter {
+    /// Leaves the rows as they are.
+    NoFilter,
+    /// Subtracts the pixel to the left.
+    Sub,
+    /// Subtracts the pixel above.
+    Up,
+    /// Subtracts the average of the pixels to the left and above.
+    Avg,
+    /// Subtracts whichever of the pixels to the left, above and above left predicts best.
+    Paeth,
+    /// Picks the filter that suits each row best, which compresses better than any single
+    /// filter but takes longer.
+    Adaptive,
+}
+
+/// How TIFF outputs are compressed. Every choice is lossless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TiffCompression {
+    /// Stores the samples as they are, for the fastest encoding and the largest files.
+    #[default]
+    None,
+    /// Compresses with LZW, which most TIFF readers support.
+    Lzw,
+    /// Compresses with Deflate, which usually gives smaller files than LZW.
+    Deflate,
+}
+
+/// The parameters of encoding an output image. Formats other than PNG and TIFF ignore
+/// them.
+///
+/// Every setting decodes to the same pixels, so they only trade encoding time for file
+/// size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputOptions {
+    /// How hard PNG outputs are compressed.
+    pub png_compression: PngCompression,
+    /// The filter PNG outputs apply to their rows.
+    pub png_filter: PngFilter,
+    /// Whether PNG outputs are interlaced with Adam7, so that viewers can show them
+    /// progressively while loading. Interlaced outputs are somewhat larger, and banded
+    /// embeddings cannot write them.
+    pub png_interlaced: bool,
+    /// How TIFF outputs are compressed.
+    pub tiff_compression: TiffCompression,
+    /// Whether outputs keep the metadata of the container, so that they do not stand
+    /// out by lacking it: the ancillary chunks of PNG containers, such as their color
+    /// profile, text and physical pixel size, and the EXIF and XMP of JPEG, PNG, WebP and
+    /// TIFF containers. PNG, WebP and TIFF outputs can hold it, banded embeddings never
+    /// keep it.
+    pub keep_metadata: bool,
+}
+
+impl Default for OutputOptions {
+    /// Compresses PNG outputs with fdeflate and an adaptive filter, or the `Sub` filter
+    /// with the `fast-png` feature, without interlacing them, leaves TIFF outputs
+    /// uncompressed and keeps the metadata of the container.
+    fn default() -> Self {
+        OutputOptions {
+            png_compression: PngCompression::Fast,
+            png_filter: if cfg!(feature = "fast-png") {
+                PngFilter::Sub
+            } else {
+                PngFilter::Adaptive
+            },
+            png_interlaced: false,
+            tiff_compression: TiffCompression::default(),
+            keep_metadata: true,
+        }
+    }
+}
+
+impl OutputOptions {
+    /// Starts from the default options.
+    pub fn new() -> Self {
+        OutputOptions::default()
+    }
+
+    /// Sets how hard PNG outputs are compressed.
+    pub fn png_compression(mut self, png_compression: PngCompression) -> Self {
+        self.png_compression = png_compression;
+        self
+    }
+
+    /// Sets the filter PNG outputs apply to their rows.
+    pub fn png_filter(mut self, png_filter: PngFilter) -> Self {
+        self.png_filter = png_filter;
+        self
+    }
+
+    /// Sets whether PNG outputs are interlaced with Adam7.
+    pub fn png_interlaced(mut self, png_interlaced: bool) -> Self {
+        self.png_interlaced = png_interlaced;
+        self
+    }
+
+    /// Sets how TIFF outputs are compressed.
+    pub fn tiff_compression(mut self, tiff_compression: TiffCompression) -> Self {
+        self.tiff_compression = tiff_compression;
+        self
+    }
+
+    /// Sets whether outputs keep the metadata of the container.
+    pub fn keep_metadata(mut self, keep_metadata: bool) -> Self {
+        self.keep_metadata = keep_metadata;
+        self
+    }
+}
+
+/// The look of a container made by `generate_container`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoiseStyle {
+    /// Every sample is uniformly random.
+    #[default]
+    Uniform,
+    /// Smooth diagonal gradients with moderate noise on top, somewhat like a photo.
+    GradientNoise,
+}
+
+/// Generates a synthetic container image.
+///
+/// The image is fully determined by its arguments, so the same container can be
+/// generated again instead of stored.
+///
+/// # Arguments
+///
+/// * `width`: The width of the image, in pixels.
+/// * `height`: The height of the image, in pixels.
+/// * `style`: The look of the image.
+/// * `seed`: A 64-bit seed for the pseudo-random number generator of the noise.
+///
+/// # Returns
+///
+/// The generated `RgbImage`.
+pub fn generate_container(width: u32, height: u32, style: NoiseStyle, seed: u64) -> RgbImage {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+
+    match style {
+        NoiseStyle::Uniform => RgbImage::from_fn(width, height, |_, _| Rgb(rng.random())),
+        NoiseStyle::GradientNoise => {
+            let scale = |position: u32, length: u32| position * u8::MAX as u32 / length.max(1);
+            RgbImage::from_fn(width, height, |x, y| {
+                let base = [
+                    scale(x, width),
+                    scale(y, height),
+                    scale(x + y, width + height),
+                ];
+                Rgb(base.map(|b| (b as i32 + rng.random_range(-16..=16)).clamp(0, 255) as u8))
+            })
+        }
+    }
+}
+
+/// Generates a synthetic container that is just large enough to hold a payload.
+///
+/// The container is sized with `required_dimensions`, so it is as close to a square
+/// as possible.
+///
+/// # Arguments
+///
+/// * `payload_len`: The length, in bytes, of the data to embed.
+/// * `lsbs`: The number of least significant bits per channel to embed with (1-8).
+/// * `hash`: The hashing algorithm that will be used for the checksum.
+/// * `ext`: The extension that will be embedded with the data.
+/// * `style`: The look of the image.
+/// * `seed`: A 64-bit seed for the pseudo-random number generator of the noise.
+///
+/// # Returns
+///
+/// A `StegResult` containing the generated `RgbImage` if successful.
+///
+/// # Errors
+///
+/// See `required_dimensions`.
+pub fn generate_container_for(
+    payload_len: usize,
+    lsbs: usize,
+    hash: Hash,
+    ext: &str,
+    style: NoiseStyle,
+    seed: u64,
+) -> StegResult<RgbImage> {
+    let (width, height) = required_dimensions(payload_len, lsbs, hash, ext)?;
+
+    Ok(generate_container(width, height, style, seed))
+}
+
+/// The smallest RGBE exponent whose normalized values are still normal `f32`s.
+const MIN_RGBE_EXPONENT: u8 = 3;
+/// The mantissa bit that keeps an RGBE pixel normalized.
+const RGBE_NORMALIZED_BIT: u8 = 1 << (BITS_PER_BYTE - 1);
+
+/// Describes which bits of a container's samples carry the embedded data.
+///
+/// Every kind exposes its samples as an 8-bit `RgbImage` plane, so the bit
+/// scattering code does not need to know about floating-point containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CarrierKind {
+    /// Plain 8-bit samples, used as they are.
+    Rgb8,
+    /// Radiance HDR samples: the 8-bit mantissas of their RGBE encoding.
+    Rgbe,
+    /// 32-bit float samples: the low 8 bits of their mantissas.
+    Float32,
+}
+
+impl CarrierKind {
+    /// Selects the carrier kind that survives encoding to `format`.
+    pub(crate) fn for_format(format: Format) -> Self {
+        match format {
+            Format::Hdr => CarrierKind::Rgbe,
+            Format::OpenExr => CarrierKind::Float32,
+            _ => CarrierKind::Rgb8,
+        }
+    }
+
+    /// The largest number of least significant bits per sample this kind can hold.
+    pub(crate) fn max_lsbs(self) -> usize {
+        match self {
+            // The top mantissa bit must stay set, or the encoder renormalizes the pixel.
+            CarrierKind::Rgbe => BITS_PER_BYTE - 1,
+            CarrierKind::Rgb8 | CarrierKind::Float32 => BITS_PER_BYTE,
+        }
+    }
+}
+
+/// A decoded container, split into the plane that carries the data and the
+/// samples needed to rebuild the full image afterwards.
+pub(crate) struct Carrier {
+    kind: CarrierKind,
+    /// The 8-bit plane the data is embedded into and extracted from.
+    pub(crate) plane: RgbImage,
+    /// The original float samples, for the kinds whose plane is only part of a sample.
+    samples: Option<Rgb32FImage>,
+    /// Whether the decoded image had an alpha channel, which the carrier dropped.
+    dropped_alpha: bool,
+}
+
+impl Carrier {
+    /// Splits a decoded image into a carrier of the given kind.
+    pub(crate) fn new(image: DynamicImage, kind: CarrierKind) -> Self {
+        let dropped_alpha = image.color().has_alpha();
+
+        match kind {
+            CarrierKind::Rgb8 => Carrier {
+                kind,
+                plane: image.into_rgb8(),
+                samples: None,
+                dropped_alpha,
+            },
+            CarrierKind::Rgbe => {
+                let samples = image.into_rgb32f();
+                let plane = RgbImage::from_fn(samples.width(), samples.height(), |x, y| {
+                    Rgb(to_rgbe8(samples.get_pixel(x, y)).0)
+                });
+                Carrier {
+                    kind,
+                    plane,
+                    samples: Some(samples),
+                    dropped_alpha,
+                }
+            }
+            CarrierKind::Float32 => {
+                let samples = image.into_rgb32f();
+                let plane = RgbImage::from_fn(samples.width(), samples.height(), |x, y| {
+                    Rgb(samples.get_pixel(x, y).0.map(|s| s.to_bits() as u8))
+                });
+                Carrier {
+                    kind,
+                    plane,
+                    samples: Some(samples),
+                    dropped_alpha,
+                }
+            }
+        }
+    }
+
+    /// The carrier kind.
+    pub(crate) fn kind(&self) -> CarrierKind {
+        self.kind
+    }
+
+    /// Whether the decoded image had an alpha channel, which the carrier dropped.
+    pub(crate) fn dropped_alpha(&self) -> bool {
+        self.dropped_alpha
+    }
+
+    /// Merges the plane back into the samples, producing the image to encode.
+    pub(crate) fn into_image(self) -> DynamicImage {
+        let Carrier {
+            kind,
+            plane,
+            samples,
+            ..
+        } = self;
+
+        match (kind, samples) {
+            (CarrierKind::Rgbe, Some(mut samples)) => {
+                for (sample, mantissas) in samples.pixels_mut().zip(plane.pixels()) {
+                    let (_, exponent) = to_rgbe8(sample);
+                    *sample = from_rgbe8(mantissas.0, exponent);
+                }
+                DynamicImage::ImageRgb32F(samples)
+            }
+            (CarrierKind::Float32, Some(mut samples)) => {
+                for (sample, low) in samples.pixels_mut().zip(plane.pixels()) {
+                    for (s, &l) in sample.0.iter_mut().zip(low.0.iter()) {
+                        *s = f32::from_bits(s.to_bits() & !0xFF | u32::from(l));
+                    }
+                }
+                DynamicImage::ImageRgb32F(samples)
+            }
+            _ => DynamicImage::ImageRgb8(plane),
+        }
+    }
+}
+
+/// Converts a float pixel to RGBE exactly like the `image` crate's HDR encoder does.
+fn to_rgbe8(pixel: &Rgb<f32>) -> ([u8; 3], u8) {
+    let [r, g, b] = pixel.0;
+    let max = f32::max(r, f32::max(g, b));
+    if max <= 0.0 {
+        return ([0; 3], 0);
+    }
+
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = f32::powi(2.0, exponent);
+    let mantissas = pixel.0.map(|s| f32::trunc(s / scale * 256.0) as u8);
+
+    (mantissas, (exponent + 128) as u8)
+}
+
+/// Converts RGBE back to a float pixel exactly like the `image` crate's HDR decoder does.
+///
+/// Pixels with a zero exponent would decode to black, so when data was written into
+/// their mantissas they are given the smallest exponent that keeps them normalized.
+fn from_rgbe8(mut mantissas: [u8; 3], mut exponent: u8) -> Rgb<f32> {
+    if exponent == 0 {
+        if mantissas == [0; 3] {
+            return Rgb([0.0; 3]);
+        }
+        exponent = MIN_RGBE_EXPONENT;
+        mantissas = mantissas.map(|m| m | RGBE_NORMALIZED_BIT);
+    }
+
+    let scale = f32::exp2(f32::from(exponent) - (128.0 + 8.0));
+    Rgb(mantissas.map(|m| scale * f32::from(m)))
+}
+
+/// Decodes a container into a carrier.
+///
+/// The carrier kind follows `output` when given (embedding), or the detected
+/// format of the container otherwise (extracting).
+pub(crate) fn decode(container: &[u8], output: Option<Format>) -> StegResult<Carrier> {
+    let (image, format) = decode_detected(container)?;
+    let kind = output
+        .or(format)
+        .map_or(CarrierKind::Rgb8, CarrierKind::for_format);
+    Ok(Carrier::new(image, kind))
+}
+
+/// Decodes an image known to be in `format` into a carrier.
+///
+/// Unlike `decode`, this works for formats that cannot be guessed from their contents.
+pub(crate) fn decode_as(container: &[u8], format: Format) -> StegResult<Carrier> {
+    let image = decode_image_as(container, format)?;
+    Ok(Carrier::new(image, CarrierKind::for_format(format)))
+}
+
+/// Decodes a container into a carrier for `output`, resizing it to `width` x `height` first.
+pub(crate) fn decode_resized(
+    container: &[u8],
+    output: Format,
+    (width, height): (u32, u32),
+    filter: FilterType,
+) -> StegResult<Carrier> {
+    let image = decode_image(container)?.resize_exact(width, height, filter);
+    Ok(Carrier::new(image, CarrierKind::for_format(output)))
+}
+
+/// The format `reader` detected, if it is one this crate knows.
+pub(crate) fn detected_format<R: BufRead + Seek>(reader: &ImageReader<R>) -> Option<Format> {
+    reader.format().and_then(|format| format.try_into().ok())
+}
+
+/// Decodes an image, keeping its original color type and sample depth.
+pub(crate) fn decode_image(container: &[u8]) -> StegResult<DynamicImage> {
+    decode_detected(container).map(|(image, _)| image)
+}
+
+/// Decodes an image like `decode_image`, along with the format detected from its contents.
+#[instrument(
+    name = "decode",
+    level = "debug",
+    skip_all,
+    fields(
+        bytes = container.len(),
+        format = field::Empty,
+        width = field::Empty,
+        height = field::Empty
+    )
+)]
+pub(crate) fn decode_detected(container: &[u8]) -> StegResult<(DynamicImage, Option<Format>)> {
+    let (image, format) = if is_jxl(container) {
+        (decode_jxl(container)?, Some(Format::Jxl))
+    } else if let Some(image) = decode_zune(container)? {
+        (image, Some(Format::Jpeg))
+    } else {
+        let container_reader = ImageReader::new(Cursor::new(container)).with_guessed_format()?;
+        let format = detected_format(&container_reader);
+        (container_reader.decode()?, format)
+    };
+
+    record_decoded(&image, format);
+    Ok((image, format))
+}
+
+/// Decodes an image known to be in `format`, keeping its original color type and sample
+/// depth.
+#[instrument(
+    name = "decode",
+    level = "debug",
+    skip_all,
+    fields(
+        bytes = container.len(),
+        format = field::Empty,
+        width = field::Empty,
+        height = field::Empty
+    )
+)]
+pub(crate) fn decode_image_as(container: &[u8], format: Format) -> StegResult<DynamicImage> {
+    let image = if format == Format::Jxl {
+        decode_jxl(container)?
+    } else if format == Format::Jpeg
+        && let Some(image) = decode_zune(container)?
+    {
+        image
+    } else {
+        ImageReader::with_format(Cursor::new(container), format.try_into()?).decode()?
+    };
+
+    record_decoded(&image, Some(format));
     Ok(image)
 }
 
-pub(crate) fn encode(image: RgbImage, format: ImageFormat) -> StegResult<Vec<u8>> {
-    let mut output = Vec::new();
-    let mut cursor = Cursor::new(&mut output);
-    image.write_to(&mut cursor, format)?;
-    Ok(output)
+/// Records the format and dimensions of a decoded image on the current `decode` span.
+fn record_decoded(image: &DynamicImage, format: Option<Format>) {
+    let span = Span::current();
+    span.record("format", field::debug(format));
+    span.record("width", image.width());
+    span.record("height", image.height());
+}
+
+/// Copies raw interleaved RGB8 pixels into an image, checking that there are exactly
+/// `width` x `height` of them.
+pub(crate) fn raw_rgb_image(pixels: &[u8], width: u32, height: u32) -> StegResult<RgbImage> {
+    check_rgb_len(pixels.len(), width, height)?;
+    Ok(RgbImage::from_raw(width, height, pixels.to_vec()).expect("the length was checked"))
+}
+
+/// Detects the format of an encoded image from its contents, without decoding it.
+pub(crate) fn guess_format(container: &[u8]) -> Option<Format> {
+    if is_jxl(container) {
+        return Some(Format::Jxl);
+    }
+
+    ImageReader::new(Cursor::new(container))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| detected_format(&reader))
+}
+
+/// Reads the dimensions of a container without decoding its pixels.
+pub(crate) fn dimensions(container: &[u8]) -> StegResult<(u32, u32)> {
+    if is_jxl(container) {
+        return jxl_dimensions(container);
+    }
+
+    let container_reader = ImageReader::new(Cursor::new(container)).with_guessed_format()?;
+    Ok(container_reader.into_dimensions()?)
+}
+
+pub(crate) fn encode(
+    carrier: Carrier,
+    format: Format,
+    options: &OutputOptions,
+    metadata: &Metadata,
+) -> StegResult<Vec<u8>> {
+    let mut output = Cursor::new(Vec::new());
+    encode_to(carrier, format, options, metadata, &mut output)?;
+    Ok(output.into_inner())
+}
+
+/// Encodes a carrier straight into `writer`, without buffering the whole output.
+///
+/// PNG, WebP and TIFF outputs carry `metadata` unless `options` opt out of it.
+#[instrument(
+    name = "encode",
+    level = "debug",
+    skip_all,
+    fields(format = ?format, width = carrier.plane.width(), height = carrier.plane.height())
+)]
+pub(crate) fn encode_to<W: Write + Seek>(
+    carrier: Carrier,
+    format: Format,
+    options: &OutputOptions,
+    metadata: &Metadata,
+    writer: &mut W,
+) -> StegResult<()> {
+    let image = match (format, carrier.into_image()) {
+        // ICO decoders only accept the PNG images embedded in them as RGBA
+        (Format::Ico, image) => DynamicImage::ImageRgba8(image.into_rgba8()),
+        // Farbfeld only stores 16-bit RGBA, which maps back to the same 8-bit samples
+        (Format::Farbfeld, image) => DynamicImage::ImageRgba16(image.into_rgba16()),
+        (_, image) => image,
+    };
+    let metadata = if options.keep_metadata {
+        metadata
+    } else {
+        &Metadata::default()
+    };
+
+    // The encoders whose parameters the `image` crate would choose are driven directly,
+    // so that the output stays as `FORMAT_OUTPUT_VERSION` documents
+    match (format, image) {
+        (Format::Png, image) => write_png(&image.into_rgb8(), options, metadata, writer),
+        (Format::WebP, image) => write_webp(&image.into_rgb8(), metadata, writer),
+        (Format::Jxl, image) => write_jxl(&image.into_rgb8(), writer),
+        (Format::Tiff, DynamicImage::ImageRgb8(plane)) => {
+            write_tiff(&plane, options.tiff_compression, metadata, writer)
+        }
+        (_, image) => {
+            image.write_to(writer, format.try_into()?)?;
+            Ok(())
+        }
+    }
+}
+
+/// Encodes an 8-bit RGB image as PNG with the `png` crate, which unlike the `image`
+/// crate can write the chunks of `metadata`. They go between the header and the image
+/// data, where every chunk is allowed, and the image data goes into a single `IDAT`
+/// chunk.
+///
+/// The `png` crate cannot interlace, so interlaced image data is filtered and compressed
+/// here and written as a chunk of its own.
+fn write_png<W: Write>(
+    plane: &RgbImage,
+    options: &OutputOptions,
+    metadata: &Metadata,
+    writer: W,
+) -> StegResult<()> {
+    let (width, height) = plane.dimensions();
+    let mut writer = png_encoder(writer, width, height, options)
+        .write_header()
+        .map_err(std::io::Error::from)?;
+    for chunk in &metadata.png_chunks {
+        writer
+            .write_chunk(png::chunk::ChunkType(chunk.kind), &chunk.data)
+            .map_err(std::io::Error::from)?;
+    }
+    if options.png_interlaced {
+        writer
+            .write_chunk(png::chunk::IDAT, &adam7_image_data(plane, options)?)
+            .map_err(std::io::Error::from)?;
+    } else {
+        writer
+            .write_image_data(plane.as_raw())
+            .map_err(std::io::Error::from)?;
+    }
+    writer.finish().map_err(std::io::Error::from)?;
+
+    Ok(())
+}
+
+/// The seven passes of Adam7 interlacing: the column and row of their first pixel, and
+/// the steps to the next column and row.
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Builds the zlib stream of the image data of an interlaced PNG: the rows of every
+/// Adam7 pass, each filtered against the row above it in the same pass.
+fn adam7_image_data(plane: &RgbImage, options: &OutputOptions) -> StegResult<Vec<u8>> {
+    let (width, height) = plane.dimensions();
+    let mut filtered = Vec::new();
+
+    for (first_x, first_y, step_x, step_y) in ADAM7_PASSES {
+        let columns = (first_x..width).step_by(step_x as usize);
+        // Passes without columns have no rows either, not even empty ones
+        if columns.len() == 0 {
+            continue;
+        }
+        // The row above the first one of a pass counts as zeros
+        let mut above = vec![0; columns.len() * EMBEDDABLE_CHANNELS];
+
+        for y in (first_y..height).step_by(step_y as usize) {
+            let row = columns
+                .clone()
+                .flat_map(|x| plane.get_pixel(x, y).0)
+                .collect::<Vec<_>>();
+            filter_row(options.png_filter, &above, &row, &mut filtered);
+            above = row;
+        }
+    }
+
+    let level = match options.png_compression {
+        PngCompression::Fast => flate2::Compression::fast(),
+        PngCompression::Default => flate2::Compression::default(),
+        PngCompression::Best => flate2::Compression::best(),
+    };
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), level);
+    encoder.write_all(&filtered)?;
+    Ok(encoder.finish()?)
+}
+
+/// Appends the filter type and the filtered samples of a row of 8-bit RGB pixels to
+/// `output`. Like the `png` crate, the adaptive filter picks the filter whose output has
+/// the smallest sum of absolute values.
+fn filter_row(filter: PngFilter, above: &[u8], row: &[u8], output: &mut Vec<u8>) {
+    let filtered = |kind: u8| {
+        row.iter().enumerate().map(move |(i, &sample)| {
+            let left = i.checked_sub(EMBEDDABLE_CHANNELS).map_or(0, |i| row[i]);
+            let upper_left = i.checked_sub(EMBEDDABLE_CHANNELS).map_or(0, |i| above[i]);
+            let predicted = match kind {
+                0 => 0,
+                1 => left,
+                2 => above[i],
+                3 => ((u16::from(left) + u16::from(above[i])) / 2) as u8,
+                _ => paeth(left, above[i], upper_left),
+            };
+            sample.wrapping_sub(predicted)
+        })
+    };
+
+    let kind = match filter {
+        PngFilter::NoFilter => 0,
+        PngFilter::Sub => 1,
+        PngFilter::Up => 2,
+        PngFilter::Avg => 3,
+        PngFilter::Paeth => 4,
+        PngFilter::Adaptive => (0..=4)
+            .min_by_key(|&kind| {
+                filtered(kind)
+                    .map(|sample| u64::from((sample as i8).unsigned_abs()))
+                    .sum::<u64>()
+            })
+            .expect("there are five filters"),
+    };
+    output.push(kind);
+    output.extend(filtered(kind));
+}
+
+/// Predicts a sample from its neighbors to the left, above and above left, as the
+/// `Paeth` filter of PNG does.
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(above) - i16::from(upper_left);
+    let distance = |sample: u8| (estimate - i16::from(sample)).abs();
+
+    if distance(left) <= distance(above) && distance(left) <= distance(upper_left) {
+        left
+    } else if distance(above) <= distance(upper_left) {
+        above
+    } else {
+        upper_left
+    }
+}
+
+/// Encodes an 8-bit RGB image as lossless WebP with the `image-webp` crate, which unlike
+/// the `image` crate can write EXIF and XMP chunks.
+///
+/// Lossy WebP would destroy the payload, and `image-webp` only encodes losslessly.
+fn write_webp<W: Write>(plane: &RgbImage, metadata: &Metadata, writer: W) -> StegResult<()> {
+    let mut encoder = image_webp::WebPEncoder::new(writer);
+    let mut params = image_webp::EncoderParams::default();
+    params.use_predictor_transform = true;
+    encoder.set_params(params);
+    if let Some(exif) = &metadata.exif {
+        encoder.set_exif_metadata(exif.clone());
+    }
+    if let Some(xmp) = &metadata.xmp {
+        encoder.set_xmp_metadata(xmp.clone());
+    }
+
+    let (width, height) = plane.dimensions();
+    encoder
+        .encode(plane.as_raw(), width, height, image_webp::ColorType::Rgb8)
+        .map_err(|err| match err {
+            image_webp::EncodingError::IoError(err) => StegError::Io(err),
+            err => StegError::ImageProcessing(ImageError::Encoding(EncodingError::new(
+                ImageFormat::WebP.into(),
+                err,
+            ))),
+        })
+}
+
+/// Encodes an 8-bit RGB image as TIFF with the `tiff` crate, which unlike the `image`
+/// crate can compress it and write the tags of `metadata`.
+fn write_tiff<W: Write + Seek>(
+    plane: &RgbImage,
+    compression: TiffCompression,
+    metadata: &Metadata,
+    writer: W,
+) -> StegResult<()> {
+    let mut encoder = TiffEncoder::new(writer).map_err(tiff_error)?;
+
+    match compression {
+        TiffCompression::None => write_tiff_image(&mut encoder, plane, Uncompressed, metadata),
+        TiffCompression::Lzw => write_tiff_image(&mut encoder, plane, Lzw, metadata),
+        TiffCompression::Deflate => write_tiff_image(
+            &mut encoder,
+            plane,
+            Deflate::with_level(DeflateLevel::Balanced),
+            metadata,
+        ),
+    }
+    .map_err(tiff_error)
+}
+
+/// Writes the only image of a TIFF file, with its metadata.
+fn write_tiff_image<W: Write + Seek, D: Compression>(
+    encoder: &mut TiffEncoder<W>,
+    plane: &RgbImage,
+    compression: D,
+    metadata: &Metadata,
+) -> TiffResult<()> {
+    let (width, height) = plane.dimensions();
+    let mut image = encoder.new_image_with_compression::<RGB8, _>(width, height, compression)?;
+    let row_bytes = width as u64 * EMBEDDABLE_CHANNELS as u64;
+    image.rows_per_strip(u32::try_from(TIFF_STRIP_BYTES.div_ceil(row_bytes.max(1)))?)?;
+    image.resolution(ResolutionUnit::None, Rational { n: 1, d: 1 });
+    // The metadata may replace the resolution
+    write_tiff_metadata(image.encoder(), metadata)?;
+    image.write_data(plane.as_raw())
+}
+
+/// Converts an error of the TIFF encoder like the `image` crate does.
+fn tiff_error(err: TiffError) -> StegError {
+    match err {
+        TiffError::IoError(err) => StegError::Io(err),
+        err => StegError::ImageProcessing(ImageError::Encoding(EncodingError::new(
+            ImageFormat::Tiff.into(),
+            err,
+        ))),
+    }
+}
+
+/// Creates an encoder for 8-bit RGB PNG images, compressing them as `options` ask.
+pub(crate) fn png_encoder<'a, W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    options: &OutputOptions,
+) -> png::Encoder<'a, W> {
+    let mut info = png::Info::with_size(width, height);
+    info.interlaced = options.png_interlaced;
+    let mut encoder = png::Encoder::with_info(writer, info).expect("a still image is valid");
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(match options.png_compression {
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Default => png::Compression::Default,
+        PngCompression::Best => png::Compression::Best,
+    });
+    // Maps the filters like the PNG encoder of the `image` crate, which PNG outputs were
+    // written with before
+    let (filter, adaptive_filter) = match options.png_filter {
+        PngFilter::NoFilter => (
+            png::FilterType::NoFilter,
+            png::AdaptiveFilterType::NonAdaptive,
+        ),
+        PngFilter::Sub => (png::FilterType::Sub, png::AdaptiveFilterType::NonAdaptive),
+        PngFilter::Up => (png::FilterType::Up, png::AdaptiveFilterType::NonAdaptive),
+        PngFilter::Avg => (png::FilterType::Avg, png::AdaptiveFilterType::NonAdaptive),
+        PngFilter::Paeth => (png::FilterType::Paeth, png::AdaptiveFilterType::NonAdaptive),
+        PngFilter::Adaptive => (png::FilterType::Sub, png::AdaptiveFilterType::Adaptive),
+    };
+    encoder.set_filter(filter);
+    encoder.set_adaptive_filter(adaptive_filter);
+    encoder
 }
@@ -0,0 +1,162 @@
+use super::error::*;
+
+#[cfg(feature = "jxl")]
+pub(crate) use codec::{decode_jxl, jxl_dimensions, write_jxl};
+
+/// The signatures JPEG XL files start with: a bare codestream, or the ISOBMFF container
+/// that wraps one.
+const SIGNATURES: [&[u8]; 2] = [
+    &[0xff, 0x0a],
+    &[
+        0x00, 0x00, 0x00, 0x0c, b'J', b'X', b'L', b' ', 0x0d, 0x0a, 0x87, 0x0a,
+    ],
+];
+
+/// Returns whether an encoded image is JPEG XL, which the `image` crate cannot detect.
+pub(crate) fn is_jxl(container: &[u8]) -> bool {
+    SIGNATURES
+        .iter()
+        .any(|signature| container.starts_with(signature))
+}
+
+/// The error of every JPEG XL operation without the `jxl` feature.
+#[cfg(not(feature = "jxl"))]
+fn missing_codec() -> StegError {
+    StegError::UnsupportedFormat(
+        "JPEG XL needs the `jxl` feature of lsb-core, which this build lacks".to_string(),
+    )
+}
+
+/// Fails to decode a JPEG XL image, which needs the `jxl` feature.
+#[cfg(not(feature = "jxl"))]
+pub(crate) fn decode_jxl(_container: &[u8]) -> StegResult<image::DynamicImage> {
+    Err(missing_codec())
+}
+
+/// Fails to read the dimensions of a JPEG XL image, which needs the `jxl` feature.
+#[cfg(not(feature = "jxl"))]
+pub(crate) fn jxl_dimensions(_container: &[u8]) -> StegResult<(u32, u32)> {
+    Err(missing_codec())
+}
+
+/// Fails to encode a JPEG XL image, which needs the `jxl` feature.
+#[cfg(not(feature = "jxl"))]
+pub(crate) fn write_jxl<W: std::io::Write>(_plane: &image::RgbImage, _writer: W) -> StegResult<()> {
+    Err(missing_codec())
+}
+
+#[cfg(feature = "jxl")]
+mod codec {
+    use std::io::{Cursor, Write};
+
+    use image::{
+        DynamicImage, ImageBuffer, RgbImage,
+        error::{DecodingError, EncodingError, ImageError, ImageFormatHint},
+    };
+    use jxl_oxide::{FrameBufferSample, ImageStream, JxlImage};
+    use zune_core::{bit_depth::BitDepth, colorspace::ColorSpace, options::EncoderOptions};
+    use zune_jpegxl::JxlSimpleEncoder;
+
+    use super::*;
+
+    /// The effort of the encoder, pinned to the default of `zune-core` so that outputs
+    /// stay the same as it changes.
+    const EFFORT: u8 = 4;
+
+    /// Decodes a JPEG XL image, keeping its color type and, up to 16 bits, its sample
+    /// depth. Color images with floating-point samples decode to 32-bit floats.
+    pub(crate) fn decode_jxl(container: &[u8]) -> StegResult<DynamicImage> {
+        let image = read(container)?;
+        let render = image.render_frame(0).map_err(decoding_error)?;
+        let mut stream = render.stream();
+        let (width, height) = (stream.width(), stream.height());
+
+        let bit_depth = image.image_header().metadata.bit_depth;
+        let float = bit_depth.bits_per_sample() > 16
+            || matches!(bit_depth, jxl_oxide::image::BitDepth::FloatSample { .. });
+        let wide = bit_depth.bits_per_sample() > 8;
+
+        let decoded = match (stream.channels(), float, wide) {
+            (1, _, false) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageLuma8),
+            (1, _, true) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageLuma16),
+            (2, _, false) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageLumaA8),
+            (2, _, true) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageLumaA16),
+            (3, false, false) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageRgb8),
+            (3, false, true) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageRgb16),
+            (3, true, _) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageRgb32F),
+            (4, false, false) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageRgba8),
+            (4, false, true) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageRgba16),
+            (4, true, _) => ImageBuffer::from_raw(width, height, samples(&mut stream))
+                .map(DynamicImage::ImageRgba32F),
+            _ => None,
+        };
+
+        decoded.ok_or_else(|| {
+            StegError::UnsupportedFormat(format!(
+                "JPEG XL images with {} channels are not supported",
+                stream.channels()
+            ))
+        })
+    }
+
+    /// Reads the dimensions of a JPEG XL image, without rendering its pixels.
+    pub(crate) fn jxl_dimensions(container: &[u8]) -> StegResult<(u32, u32)> {
+        let image = read(container)?;
+        Ok((image.width(), image.height()))
+    }
+
+    /// Parses a JPEG XL image, leaving its frames to be rendered.
+    fn read(container: &[u8]) -> StegResult<JxlImage> {
+        JxlImage::builder()
+            .read(Cursor::new(container))
+            .map_err(decoding_error)
+    }
+
+    /// Renders every sample of `stream`, interleaved.
+    fn samples<S: FrameBufferSample + Default + Clone>(stream: &mut ImageStream) -> Vec<S> {
+        let (width, height) = (stream.width() as usize, stream.height() as usize);
+        let mut samples = vec![S::default(); width * height * stream.channels() as usize];
+        stream.write_to_buffer(&mut samples);
+        samples
+    }
+
+    fn decoding_error(err: Box<dyn std::error::Error + Send + Sync>) -> StegError {
+        StegError::ImageProcessing(ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("JPEG XL".to_string()),
+            err,
+        )))
+    }
+
+    /// Encodes an 8-bit RGB image as JPEG XL with `zune-jpegxl`, whose only mode is
+    /// lossless, so the payload always survives.
+    pub(crate) fn write_jxl<W: Write>(plane: &RgbImage, writer: W) -> StegResult<()> {
+        let (width, height) = plane.dimensions();
+        let options = EncoderOptions::new(
+            width as usize,
+            height as usize,
+            ColorSpace::RGB,
+            BitDepth::Eight,
+        )
+        .set_effort(EFFORT);
+
+        JxlSimpleEncoder::new(plane.as_raw(), options)
+            .encode(writer)
+            .map_err(|err| {
+                StegError::ImageProcessing(ImageError::Encoding(EncodingError::new(
+                    ImageFormatHint::Name("JPEG XL".to_string()),
+                    err,
+                )))
+            })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! The layout of payloads in the bits of a container, as a reference for readers written
+//! without this crate.
+//!
+//! A payload is a header followed by the data, stored as follows:
+//! 1. The fields of the header are written in the order of `HEADER_FIELDS`. The length
+//!    field is a `u32` in `LENGTH_BYTE_ORDER`, counting every byte after itself.
+//! 2. The bits of every byte are stored in `BIT_ORDER`, the most significant first.
+//! 3. The `i`-th bit of the payload is stored at the `i`-th position of the
+//!    `KeyedPermutation` of the seed, a Feistel network keyed by `RNG_ALGORITHM`.
+//!    Position `p` is bit `p % lsbs` of channel sample `p / lsbs`, with samples laid
+//!    out row by row, pixel by pixel, red, green then blue.
+//!
+//! Every payload embedded since `FORMAT_VERSION` 1 is read back the same way. The
+//! `data/layout-v*` files are checked against this layout, so a change of it must bump
+//! `FORMAT_VERSION`, record new files and keep reading the old ones, the way payloads
+//! without `KEYED_ORDER_FLAG` are still read with the `SeededShuffle` they were
+//! embedded with.
+
+pub use super::consts::{FINGERPRINT_FLAG, FINGERPRINT_SIZE, KEYED_ORDER_FLAG};
+
+/// The version of the layout of payloads, bumped whenever embedding changes which bits of
+/// a container hold what.
+///
+/// This is unrelated to `image::FORMAT_OUTPUT_VERSION`, which covers how the pixels are
+/// encoded into an output file.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A field of the header that precedes the data of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderField {
+    /// The number of bytes after the length field, `LENGTH_SIZE` bytes.
+    Length,
+    /// The length of the extension, a byte.
+    ExtensionLength,
+    /// The extension of the data, in UTF-8.
+    Extension,
+    /// The `Hash` of the checksum in the low bits, along with `FINGERPRINT_FLAG` and
+    /// `KEYED_ORDER_FLAG`, a byte.
+    HashFlag,
+    /// The fingerprint of the container bits the embedding leaves alone,
+    /// `FINGERPRINT_SIZE` bytes, only present with `FINGERPRINT_FLAG`.
+    Fingerprint,
+    /// The checksum of the data, of the size of the digest of its `Hash`.
+    Checksum,
+}
+
+/// The fields of the header, in the order they are stored.
+pub const HEADER_FIELDS: [HeaderField; 6] = [
+    HeaderField::Length,
+    HeaderField::ExtensionLength,
+    HeaderField::Extension,
+    HeaderField::HashFlag,
+    HeaderField::Fingerprint,
+    HeaderField::Checksum,
+];
+
+/// The size of the length field, in bytes.
+pub const LENGTH_SIZE: usize = core::mem::size_of::<u32>();
+
+/// The byte order of the length field.
+pub const LENGTH_BYTE_ORDER: &str = "little-endian";
+
+/// The order the bits of a payload byte are stored in.
+pub const BIT_ORDER: &str = "msb-first";
+
+/// The generator the keys of the four rounds of a `KeyedPermutation` are drawn from,
+/// seeded with `seed_from_u64` of `rand`.
+pub const RNG_ALGORITHM: &str = "pcg64mcg";
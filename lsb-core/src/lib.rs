@@ -6,26 +6,179 @@
 //!    - Length of the original file extension (1 byte).
 //!    - Original file extension.
 //!    - Hash algorithm flag (1 byte).
+//!    - Fingerprint of the container bits the embedding leaves alone (8 bytes).
 //!    - Checksum of the input data.
 //! 2. Prepending the total length of this combined payload (4 bytes, little-endian).
 //! 3. Encoding this final data into the LSBs of the container image's color channels.
 //!    A pseudo-random pixel order is used based on a seed for embedding.
+//!    Each position of the order is computed on its own by a `KeyedPermutation`, so
+//!    embedding and extraction only ever generate as many positions as the payload
+//!    has bits, however large the container. The hash flag marks payloads placed this
+//!    way. Payloads without the mark were embedded with a `SeededShuffle`, which
+//!    generates a permutation of the whole container, so they are read with a fixed
+//!    `lsbs`, or with `ExtractOptions::legacy_order` set. The `suite_legacy_order`
+//!    benchmark compares the two orders with a small payload in a 50 megapixel
+//!    container.
+//!    Floating-point outputs (HDR, OpenEXR) keep their samples and embed into the
+//!    LSBs of the mantissas instead.
 //!
 //! The extraction process reverses these steps, using the same seed to read bits
 //! in the correct order, verify the checksum, and retrieve the original data and extension.
+//! When the checksum does not match, the fingerprint tells whether the container was
+//! modified after embedding or only the payload was corrupted.
+//!
+//! Operations are instrumented with `tracing`. Embeddings run in an `embed` span holding
+//! the `order`, `embed_bytes` and `encode` spans of their phases, and extractions in an
+//! `extract` span holding a `read_bytes` span per read. Containers are decoded in a
+//! `decode` span before either starts. Without a `tracing` subscriber, the events and
+//! spans are emitted as `log` records instead, for loggers such as `env_logger`.
+//!
+//! Everything that touches images, files or threads needs the default `std` feature.
+//! Without it, with `alloc` instead, the crate is `no_std` and only holds the payload
+//! framing, the orders of `order` and the `Plane` of samples in memory they are
+//! embedded into, for targets with an allocator but no operating system.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("lsb-core needs the `alloc` feature, which `std` enables");
+
+extern crate alloc;
 
+/// Module for async wrappers that run operations on tokio's blocking thread pool. Only
+/// available with the `async` feature, the only one that pulls in tokio.
+#[cfg(feature = "async")]
+pub mod aio;
+/// Module for steganalysis of images that may hold embedded data.
+#[cfg(feature = "std")]
+pub mod analysis;
+/// Module for embedding into very large PNG containers a band of rows at a time.
+#[cfg(feature = "std")]
+mod band;
+/// Module for stopping operations from another thread.
+#[cfg(feature = "std")]
+mod cancel;
+/// Module for calculating how much data fits into a container.
+mod capacity;
 /// Module for constants used throughout the crate.
 mod consts;
+/// Module for finding exactly which samples and bits differ between two images.
+#[cfg(feature = "std")]
+pub mod diff;
 /// Module for embedding data into images using LSB steganography.
+#[cfg(feature = "std")]
 mod embed;
 /// Module for error handling in steganography operations.
 pub mod error;
 /// Module for extracting data from images using LSB steganography.
+#[cfg(feature = "std")]
 mod extract;
+/// Module for the image formats of containers and embeddings.
+#[cfg(feature = "std")]
+mod format;
+/// Module for embedding and extracting between files on disk.
+#[cfg(feature = "std")]
+mod fs;
+/// Module for the entry points of the fuzz targets into private parts of the crate. Only
+/// compiled under `cargo fuzz`, which sets `cfg(fuzzing)`.
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing;
 /// Module for hashing functionalities used in steganography.
 pub mod hash;
 /// Module for image handling, including decoding and encoding images.
+#[cfg(feature = "std")]
 pub mod image;
+/// Module for JPEG XL, which the `image` crate has no codec for.
+#[cfg(feature = "std")]
+mod jxl;
+/// Module for the layout of payloads in containers and the version it is compatible with.
+pub mod layout;
+/// Module for carrying the metadata of containers over into outputs.
+#[cfg(feature = "std")]
+mod metadata;
+/// Module for measuring how much embedding degraded an image.
+#[cfg(feature = "std")]
+pub mod metrics;
+/// Module for the orders in which payload bits are placed into a container.
+pub mod order;
+/// Module for the parallel iterators of the crate, which run sequentially without the
+/// `parallel` feature.
+#[cfg(feature = "std")]
+mod par;
+/// Module for building and parsing the payloads embedded into containers.
+mod payload;
+/// Module for embedding into and extracting from planes of samples in memory.
+mod plane;
+/// Module for the types most programs need, to be glob imported.
+#[cfg(feature = "std")]
+pub mod prelude;
+/// Module for reporting the progress of long operations.
+#[cfg(feature = "std")]
+mod progress;
+/// Module for the SIMD path of extraction.
+#[cfg(feature = "std")]
+mod simd;
+/// Module for a steganography session that bundles its parameters and caches decoded containers.
+#[cfg(feature = "std")]
+mod stego;
+/// Module for destroying embedded payloads by randomizing the LSBs.
+#[cfg(feature = "std")]
+mod strip;
+/// Module for running the parallel sections of an operation in a pool of its own.
+#[cfg(feature = "std")]
+mod threads;
+/// Module for the non-fatal conditions returned along with the results of operations.
+#[cfg(feature = "std")]
+mod warning;
+/// Module for crop-robust watermarking by tiling a short message across the image.
+#[cfg(feature = "std")]
+pub mod watermark;
+/// Module for decoding JPEG containers with zune-jpeg directly, with the `zune` feature.
+#[cfg(feature = "std")]
+mod zune;
 
-pub use embed::embed;
-pub use extract::extract;
+#[cfg(feature = "std")]
+pub use analysis::{bit_plane, chi_square_analysis, estimate_embedding_rate, risk_report};
+#[cfg(feature = "std")]
+pub use band::embed_banded;
+#[cfg(feature = "std")]
+pub use cancel::CancelToken;
+#[cfg(feature = "std")]
+pub use capacity::{capacity, capacity_table, capacity_with};
+pub use capacity::{capacity_for_dimensions, required_dimensions, required_pixels};
+#[cfg(feature = "std")]
+pub use diff::{
+    amplified_change_map, assert_only_lsbs_changed, assert_only_lsbs_changed_with, change_map,
+    diff, diff_with,
+};
+#[cfg(feature = "std")]
+pub use embed::{
+    EmbedOptions, EmbedOutcome, EmbedPlan, EmbedReport, LsbSelection, OverwritePolicy,
+    ResizePolicy, embed, embed_chunks, embed_dynamic, embed_from_reader, embed_image,
+    embed_into_image, embed_raw_rgb, embed_to_writer, embed_with, embed_with_order,
+    embed_with_report, plan_embed,
+};
+#[cfg(feature = "std")]
+pub use extract::{
+    ChecksumStatus, ExtractInfo, ExtractOptions, Extracted, Inspection, RecoveryHit, extract,
+    extract_auto, extract_dynamic, extract_from_image, extract_image, extract_range,
+    extract_raw_rgb, extract_to_writer, extract_with, extract_with_order, inspect, recover,
+};
+#[cfg(feature = "std")]
+pub use format::Format;
+#[cfg(feature = "std")]
+pub use fs::{embed_path, extract_path};
+pub use plane::Plane;
+#[cfg(feature = "std")]
+pub use progress::{Phase, Progress, ProgressCallback};
+#[cfg(feature = "std")]
+pub use simd::{set_simd_enabled, simd_enabled};
+#[cfg(feature = "std")]
+pub use stego::{Stego, StegoBuilder, StegoContainer};
+#[cfg(feature = "std")]
+pub use strip::strip;
+#[cfg(feature = "std")]
+pub use warning::Warning;
+#[cfg(feature = "std")]
+pub use watermark::{embed_watermark, extract_watermark};
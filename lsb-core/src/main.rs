@@ -1,43 +1,1072 @@
 mod cli;
+mod passphrase;
 
 use clap::CommandFactory;
 use clap_complete::generate;
 use cli::*;
-use lsb_core::{embed, extract};
-use std::{error::Error, fs, io, path::PathBuf};
+use lsb_core::{
+    ChecksumStatus, EmbedOptions, ExtractOptions, LsbSelection, OverwritePolicy,
+    analysis::RiskRating,
+    capacity_with, embed_path, embed_with_report,
+    error::{ErrorKind, StegError},
+    extract_path, extract_with,
+    hash::Hash,
+    image::output_format_from_extension,
+    inspect, plan_embed, risk_report,
+};
+use passphrase::{ENCRYPTED_EXTENSION, read_passphrase};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::{
+    collections::HashSet,
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use tracing::warn;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::init();
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            if let Some(hint) = err.downcast_ref::<StegError>().and_then(hint) {
+                eprintln!("Hint: {}", hint);
+            }
+            ExitCode::from(exit_code(err.as_ref()))
+        }
+    }
+}
+
+/// What to try next after an extraction error, for the errors a user can act on.
+fn hint(err: &StegError) -> Option<&'static str> {
+    match err {
+        StegError::NoPayloadFound { .. } => Some(
+            "The image holds no payload under these settings. Check that it is the embedded \
+             image, and pass the --lsbs and --seed, or the passphrase, it was embedded with",
+        ),
+        StegError::WrongSeedSuspected { .. } => Some(
+            "The image looks embedded, but not with this seed. Pass the seed it was embedded \
+             with using --seed, or its passphrase, and make sure the image was not cropped \
+             or resized",
+        ),
+        StegError::PayloadTruncated { .. } => Some(
+            "The image holds only the start of the payload. It was probably cropped after \
+             embedding, so extract from the original output instead",
+        ),
+        StegError::PayloadCorrupted { .. } => Some(
+            "Some embedded bits were changed, e.g. by a transfer or a lossy format. \
+             Extract from the original output instead",
+        ),
+        StegError::ContainerModified => Some(
+            "The image was edited or re-encoded after embedding. Extract from the original \
+             output, and only share it in lossless formats",
+        ),
+        _ => None,
+    }
+}
+
+/// The exit status of a failed command, telling apart the kinds of library errors so
+/// that scripts need not parse the message.
+fn exit_code(err: &(dyn Error + 'static)) -> u8 {
+    match err.downcast_ref::<StegError>().map(StegError::kind) {
+        Some(ErrorKind::InvalidArgument) => 2,
+        Some(ErrorKind::UnsupportedFormat) => 3,
+        Some(ErrorKind::InsufficientCapacity | ErrorKind::LimitExceeded) => 4,
+        Some(ErrorKind::NotFound) => 5,
+        Some(ErrorKind::Corrupted) => 6,
+        Some(ErrorKind::Io) => 7,
+        _ => 1,
+    }
+}
+
+/// Whether a path stands for stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Reads a file, or all of stdin for `-`.
+fn read_input(path: &Path, what: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    if is_stdio(path) {
+        io::stdin().read_to_end(&mut bytes)
+    } else {
+        fs::File::open(path).and_then(|mut file| file.read_to_end(&mut bytes))
+    }
+    .map_err(|e| format!("Failed to read {}: {}", what, e))?;
+
+    Ok(bytes)
+}
+
+/// Writes `bytes` to a file, or to stdout for `-`.
+fn write_output(path: &Path, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    if is_stdio(path) {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+    } else {
+        fs::write(path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// The file name `extract_path` gives the payload of `container`, which is "extracted"
+/// for a container read from stdin.
+fn payload_name(container: &Path, extension: &str) -> OsString {
+    let mut name = match is_stdio(container) {
+        true => OsStr::new("extracted"),
+        false => container.file_stem().unwrap_or(OsStr::new("extracted")),
+    }
+    .to_os_string();
+    if !extension.is_empty() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
+}
+
+/// Parses a seed written in decimal, or in hexadecimal with a `0x` prefix, ignoring
+/// surrounding whitespace.
+fn parse_seed(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Reads the seed from --seed-file or --seed-env, falling back to --seed. Errors never
+/// quote the contents, which are meant to stay secret.
+fn read_seed(cli: &Cli) -> Result<SeedSelection, Box<dyn Error>> {
+    let (text, source) = if let Some(path) = &cli.seed_file {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the seed file {}: {}", path.display(), e))?;
+        (text, format!("The seed file {}", path.display()))
+    } else if let Some(var) = &cli.seed_env {
+        let text = env::var(var).map_err(|e| match e {
+            env::VarError::NotPresent => format!("The environment variable {} is not set", var),
+            env::VarError::NotUnicode(_) => {
+                format!("The environment variable {} is not UTF-8", var)
+            }
+        })?;
+        (text, format!("The environment variable {}", var))
+    } else {
+        return Ok(cli.seed);
+    };
+
+    parse_seed(&text).map(SeedSelection::Fixed).ok_or_else(|| {
+        format!(
+            "{} does not hold a decimal or 0x-prefixed hexadecimal 64-bit number",
+            source
+        )
+        .into()
+    })
+}
+
+/// The seed of a command that reads a payload, which has to be the one it was embedded
+/// with.
+fn fixed_seed(seed: SeedSelection) -> Result<u64, Box<dyn Error>> {
+    match seed {
+        SeedSelection::Fixed(seed) => Ok(seed),
+        SeedSelection::Random => {
+            Err("A random seed is only made up when embedding. Pass the seed it printed".into())
+        }
+    }
+}
+
+/// Writes `seed` to a new file at `path` that only its owner can read, or truncates an
+/// existing one and takes its permissions away from others.
+fn write_seed(path: &Path, seed: u64) -> Result<(), Box<dyn Error>> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let write = || -> io::Result<()> {
+        let mut file = options.open(path)?;
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        writeln!(file, "{}", seed)
+    };
+    write().map_err(|e| format!("Failed to write the seed to {}: {}", path.display(), e).into())
+}
+
+/// Refuses to write binary data to stdout when it is a terminal, unless forced to.
+fn check_tty(force_tty: bool) -> Result<(), Box<dyn Error>> {
+    if io::stdout().is_terminal() && !force_tty {
+        return Err(
+            "Refusing to write binary data to a terminal, redirect stdout or pass \
+                    --force-tty"
+                .into(),
+        );
+    }
+
+    Ok(())
+}
+
+/// The containers of a batch embedding and where their outputs go.
+struct Batch {
+    input: PathBuf,
+    containers: Vec<PathBuf>,
+    output_dir: PathBuf,
+    output_template: String,
+    seed_per_file: Option<SeedPerFile>,
+    manifest: Option<PathBuf>,
+    /// Whether the seeds may be printed, which they may not when read from a file or
+    /// the environment.
+    show_seeds: bool,
+}
+
+/// The embedding into one container of a batch.
+struct BatchEntry {
+    container: PathBuf,
+    input: PathBuf,
+    output: PathBuf,
+    seed: u64,
+}
+
+/// Fills the `{stem}`, `{name}`, `{ext}` and `{index}` placeholders of a template with
+/// those of the container at `index`.
+fn fill_template(template: &str, container: &Path, index: usize) -> String {
+    let part = |part: Option<&OsStr>| part.unwrap_or_default().to_string_lossy().into_owned();
+
+    template
+        .replace("{stem}", &part(container.file_stem()))
+        .replace("{name}", &part(container.file_name()))
+        .replace("{ext}", &part(container.extension()))
+        .replace("{index}", &index.to_string())
+}
+
+/// Embeds into every container of a batch, `jobs` at a time, carrying on past the ones
+/// that fail and exiting with 1 if any did.
+fn embed_batch(
+    batch: &Batch,
+    options: &EmbedOptions,
+    jobs: Option<usize>,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let mut outputs = HashSet::new();
+    let entries: Vec<_> = batch
+        .containers
+        .iter()
+        .enumerate()
+        .map(|(index, container)| {
+            let input = fill_template(&batch.input.to_string_lossy(), container, index);
+            let output =
+                batch
+                    .output_dir
+                    .join(fill_template(&batch.output_template, container, index));
+            let seed = match batch.seed_per_file {
+                None => options.seed,
+                Some(SeedPerFile::Index) => options.seed.wrapping_add(index as u64),
+                Some(SeedPerFile::Name) => {
+                    let name = container.file_name().unwrap_or_default();
+                    let hash = blake3::hash(name.as_encoded_bytes());
+                    let offset = u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap());
+                    options.seed.wrapping_add(offset)
+                }
+            };
+
+            BatchEntry {
+                container: container.clone(),
+                input: PathBuf::from(input),
+                output,
+                seed,
+            }
+        })
+        .collect();
+    // Outputs written over each other would pass for embeddings they are not
+    for entry in &entries {
+        if !outputs.insert(&entry.output) {
+            return Err(format!(
+                "Several containers would be written to {}, use placeholders in \
+                 --output-template",
+                entry.output.display()
+            )
+            .into());
+        }
+    }
+    fs::create_dir_all(&batch.output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", batch.output_dir.display(), e))?;
+
+    let embed_entry = |entry: &BatchEntry| {
+        let options = options.clone().seed(entry.seed);
+        let result = embed_path(&entry.input, &entry.container, &entry.output, &options);
+        match &result {
+            Ok(report) => {
+                for warning in &report.warnings {
+                    eprintln!("Warning: {}: {}", entry.container.display(), warning);
+                }
+                print!(
+                    "OK {} -> {}",
+                    entry.container.display(),
+                    entry.output.display()
+                );
+                match batch.show_seeds {
+                    true => println!(" (seed {})", entry.seed),
+                    false => println!(),
+                }
+            }
+            Err(err) => println!("FAIL {}: {}", entry.container.display(), err),
+        }
+        result
+    };
+    #[cfg(feature = "parallel")]
+    let results: Vec<_> = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?
+        .install(|| entries.par_iter().map(embed_entry).collect());
+    #[cfg(not(feature = "parallel"))]
+    let results: Vec<_> = {
+        let _ = jobs;
+        entries.iter().map(embed_entry).collect()
+    };
+
+    let failed: Vec<_> = entries
+        .iter()
+        .zip(&results)
+        .filter_map(|(entry, result)| Some((entry, result.as_ref().err()?)))
+        .collect();
+    if !failed.is_empty() {
+        eprintln!("Failed:");
+        for (entry, err) in &failed {
+            eprintln!("  {}: {}", entry.container.display(), err);
+        }
+    }
+    println!(
+        "{} embedded, {} failed",
+        entries.len() - failed.len(),
+        failed.len()
+    );
+
+    if let Some(manifest) = &batch.manifest {
+        let files: Vec<_> = entries
+            .iter()
+            .zip(&results)
+            .map(|(entry, result)| {
+                let mut file = serde_json::json!({
+                    "container": entry.container.display().to_string(),
+                    "input": entry.input.display().to_string(),
+                    "output": entry.output.display().to_string(),
+                    "seed": entry.seed,
+                });
+                match result {
+                    Ok(report) => file["lsbs"] = report.lsbs.into(),
+                    Err(err) => file["error"] = err.to_string().into(),
+                }
+                file
+            })
+            .collect();
+        let manifest_json = serde_json::json!({ "files": files });
+        fs::write(manifest, serde_json::to_string_pretty(&manifest_json)?)
+            .map_err(|e| format!("Failed to write {}: {}", manifest.display(), e))?;
+    }
+
+    Ok(match failed.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    })
+}
+
+/// The size of a tar header, and the unit file contents are padded to.
+const TAR_BLOCK: u64 = 512;
+
+/// Collects the entries under `dir` with paths relative to it, sorted so that the same
+/// directory always gives the same archive.
+///
+/// Symlinks are listed, not followed, and anything that is neither a file, a directory
+/// nor a symlink is skipped with a warning.
+fn archive_entries(dir: &Path, relative: &Path) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+    let mut children = fs::read_dir(dir.join(relative))?
+        .map(|entry| entry.map(|entry| relative.join(entry.file_name())))
+        .collect::<io::Result<Vec<_>>>()?;
+    children.sort();
+
+    let mut entries = Vec::new();
+    for path in children {
+        let metadata = fs::symlink_metadata(dir.join(&path))?;
+        if metadata.is_dir() {
+            entries.push((path.clone(), metadata));
+            entries.extend(archive_entries(dir, &path)?);
+        } else if metadata.is_file() || metadata.is_symlink() {
+            entries.push((path, metadata));
+        } else {
+            warn!("Skipping {}, which is not a file", path.display());
+        }
+    }
+    Ok(entries)
+}
+
+/// Archives the directory `dir` as a tar for embedding into `container`.
+///
+/// Only relative paths, contents, symlink targets and whether files are executable are
+/// kept: owners, times and other permission bits are normalized, so that the archive
+/// does not leak them.
+///
+/// # Errors
+///
+/// * `StegError::InsufficientCapacity`: If the archive cannot fit into the container,
+///   which is checked from the sizes of the files before any of them is read.
+/// * Errors reading the directory.
+fn archive_dir(
+    dir: &Path,
+    container: &[u8],
+    lsbs: LsbSelection,
+    hash: Hash,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let entries = archive_entries(dir, Path::new(""))
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+
+    // A header for each entry, the padded contents of files and two empty blocks at
+    // the end. Paths too long for a header need more, so this is a lower bound
+    let estimate = entries
+        .iter()
+        .filter(|(_, metadata)| metadata.is_file())
+        .map(|(_, metadata)| metadata.len().next_multiple_of(TAR_BLOCK))
+        .sum::<u64>()
+        + TAR_BLOCK * (entries.len() as u64 + 2);
+    let lsbs = match lsbs {
+        LsbSelection::Fixed(lsbs) => lsbs,
+        LsbSelection::Auto => 8,
+    };
+    let capacity = capacity_with(container, lsbs, hash, "tar".len())? as u64;
+    if estimate > capacity {
+        let required_bits = estimate.saturating_mul(8);
+        // Every pixel holds `lsbs` bits in each of its 3 channels
+        return Err(StegError::InsufficientCapacity {
+            required_bits,
+            available_bits: capacity * 8,
+            missing_pixels: (required_bits - capacity * 8).div_ceil(3 * lsbs as u64),
+        }
+        .into());
+    }
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.mode(tar::HeaderMode::Deterministic);
+    builder.follow_symlinks(false);
+    for (path, _) in &entries {
+        builder.append_path_with_name(dir.join(path), path)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// An entry of a payload, as listed by `extract --list`.
+struct ListEntry {
+    path: String,
+    size: u64,
+    kind: &'static str,
+    target: Option<String>,
+}
+
+/// Prints the files of the payload of `input`, with the entries of a tar archive if it
+/// was embedded from a directory.
+///
+/// Only the header is read for a single file. An archive is read in full to list it,
+/// but its checksum is never verified, which a warning points out.
+fn list_payload(
+    input: &[u8],
+    container: &Path,
+    options: &ExtractOptions,
+    json: bool,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let options = options.clone().verify(false);
+    let inspection = inspect(input, &options)?;
+    for warning in &inspection.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let mut entries = Vec::new();
+    if inspection.extension == "tar" {
+        let extracted = extract_with(input, &options)?;
+        let mut archive = tar::Archive::new(extracted.data.as_slice());
+        for entry in archive
+            .entries()
+            .map_err(|e| format!("Failed to read archive: {}", e))?
+        {
+            let entry = entry.map_err(|e| format!("Failed to read archive: {}", e))?;
+            let header = entry.header();
+            let kind = match header.entry_type() {
+                tar::EntryType::Directory => "directory",
+                tar::EntryType::Symlink => "symlink",
+                _ => "file",
+            };
+            entries.push(ListEntry {
+                path: entry.path()?.display().to_string(),
+                size: header.size()?,
+                kind,
+                target: entry
+                    .link_name()?
+                    .map(|target| target.display().to_string()),
+            });
+        }
+    } else {
+        // Named like `extract` would name the file
+        let path = payload_name(container, &inspection.extension);
+        entries.push(ListEntry {
+            path: path.to_string_lossy().into_owned(),
+            size: inspection.data_bytes,
+            kind: "file",
+            target: None,
+        });
+    }
+
+    let files = entries.iter().filter(|entry| entry.kind == "file");
+    let (count, total) = (
+        files.clone().count(),
+        files.map(|entry| entry.size).sum::<u64>(),
+    );
+    if json {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let mut value = serde_json::json!({
+                    "path": entry.path,
+                    "size": entry.size,
+                    "type": entry.kind,
+                });
+                if let Some(target) = &entry.target {
+                    value["target"] = target.as_str().into();
+                }
+                value
+            })
+            .collect();
+        let listing = serde_json::json!({
+            "extension": inspection.extension,
+            "entries": entries,
+            "files": count,
+            "total_bytes": total,
+            "verified": false,
+        });
+        println!("{}", listing);
+    } else {
+        for entry in &entries {
+            match (entry.kind, &entry.target) {
+                ("directory", _) => println!("{:>10}  {}/", "", entry.path),
+                (_, Some(target)) => println!("{:>10}  {} -> {}", "", entry.path, target),
+                _ => println!("{:>10}  {}", entry.size, entry.path),
+            }
+        }
+        let files = match count {
+            1 => "file",
+            _ => "files",
+        };
+        println!("Total: {} {}, {} bytes", count, files, total);
+    }
+    eprintln!("Warning: The checksum was not verified; extract the payload to verify it");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Expands the glob patterns among `paths`, keeping the other paths as they are.
+///
+/// A path that exists is never read as a pattern, even if it contains `*`, `?` or `[`.
+fn expand_globs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        match path.to_str() {
+            Some(pattern) if pattern.contains(['*', '?', '[']) && !path.exists() => {
+                let matches = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+                if matches.is_empty() {
+                    return Err(format!("No files match {}", pattern).into());
+                }
+                files.extend(matches);
+            }
+            _ => files.push(path.clone()),
+        }
+    }
+
+    Ok(files)
+}
+
+/// Extracts the payload of a container in memory, returning its extension and size once
+/// its checksum is verified.
+fn verify(
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<(String, usize), Box<dyn Error + Send + Sync>> {
+    let input = fs::read(path).map_err(|e| format!("Failed to read container: {}", e))?;
+    let extracted = extract_with(&input, options)?;
+
+    Ok((extracted.extension, extracted.data.len()))
+}
+
+fn run() -> Result<ExitCode, Box<dyn Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
 
     let cli = Cli::parse();
+    let seed = read_seed(&cli)?;
+
+    #[cfg(feature = "parallel")]
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
 
     match cli.command {
         Commands::Embed {
-            container,
+            containers,
             input,
             output,
+            output_dir,
+            output_template,
+            seed_per_file,
+            manifest,
+            #[cfg(feature = "parallel")]
+            jobs,
             hash,
+            force_overwrite,
+            no_risk_check,
+            dry_run,
+            verify,
+            passphrase,
+            seed_out,
         } => {
-            let output = PathBuf::from(output);
-            let format = image::ImageFormat::from_path(&output)?;
-            let ext = input.extension().and_then(|s| s.to_str()).unwrap_or("bin");
+            let passphrase = read_passphrase(&passphrase, true)?;
+            let seed = match seed {
+                SeedSelection::Fixed(_) if seed_out.is_some() => {
+                    return Err("--seed-out needs --seed random".into());
+                }
+                SeedSelection::Fixed(seed) => seed,
+                SeedSelection::Random => {
+                    let seed = rand::random();
+                    // Recorded before embedding, as the output is useless without it
+                    if let Some(path) = &seed_out {
+                        write_seed(path, seed)?;
+                    }
+                    eprintln!("Seed: {} (generated, extracting needs it)", seed);
+                    seed
+                }
+            };
+            let options = EmbedOptions::new()
+                .lsbs(cli.lsbs)
+                .hash(hash)
+                .seed(passphrase.as_ref().map_or(seed, |p| p.seed()))
+                .overwrite_policy(if force_overwrite {
+                    OverwritePolicy::Allow
+                } else {
+                    OverwritePolicy::Warn
+                })
+                .verify_after_embed(verify);
 
-            let container =
-                fs::read(container).map_err(|e| format!("Failed to read container: {}", e))?;
-            let input = fs::read(&input).map_err(|e| format!("Failed to read input: {}", e))?;
+            if let Some(output_dir) = output_dir {
+                if passphrase.is_some() {
+                    return Err("A passphrase cannot be used with --output-dir".into());
+                }
+                let batch = Batch {
+                    input,
+                    containers: expand_globs(&containers)?,
+                    output_dir,
+                    output_template,
+                    seed_per_file,
+                    manifest,
+                    show_seeds: cli.seed_file.is_none() && cli.seed_env.is_none(),
+                };
+                #[cfg(not(feature = "parallel"))]
+                let jobs = None;
+                return embed_batch(&batch, &options, jobs);
+            }
+            let [container] = <[PathBuf; 1]>::try_from(containers)
+                .map_err(|_| "Embedding into several containers needs --output-dir")?;
+
+            let output = output.unwrap_or_else(|| {
+                let extension = container
+                    .extension()
+                    .and_then(|s| s.to_str())
+                    .filter(|s| output_format_from_extension(s).is_ok())
+                    .unwrap_or_default();
+                PathBuf::from("embedded").with_extension(extension)
+            });
+            // Without an extension, the output keeps the format of the container
+            let format = output
+                .extension()
+                .map(|s| output_format_from_extension(s.to_str().unwrap_or_default()))
+                .transpose()?;
+            // Directories are embedded as tar archives of them
+            let is_dir = !is_stdio(&input) && input.is_dir();
+            let ext = match is_dir {
+                true => "tar",
+                false => input.extension().and_then(|s| s.to_str()).unwrap_or("bin"),
+            };
+
+            let options = options.format(format);
+
+            if is_stdio(&input) && is_stdio(&container) {
+                return Err(
+                    "Only one of the input and the container can be read from stdin".into(),
+                );
+            }
+            if is_stdio(&output) && !dry_run {
+                check_tty(cli.force_tty)?;
+            }
+            // Only the risk check, the dry run and the standard streams need the container
+            // in memory
+            let in_memory = passphrase.is_some()
+                || is_dir
+                || is_stdio(&input)
+                || is_stdio(&container)
+                || is_stdio(&output);
+            let container_bytes = (!no_risk_check || dry_run || in_memory)
+                .then(|| read_input(&container, "container"))
+                .transpose()?;
+            let input_bytes = match &container_bytes {
+                Some(container) if is_dir => Some(archive_dir(&input, container, cli.lsbs, hash)?),
+                _ => in_memory.then(|| read_input(&input, "input")).transpose()?,
+            };
+            let (input_bytes, ext) = match (&passphrase, input_bytes) {
+                (Some(passphrase), Some(input)) => {
+                    (Some(passphrase.encrypt(&input, ext)?), ENCRYPTED_EXTENSION)
+                }
+                (_, input_bytes) => (input_bytes, ext),
+            };
+
+            if let Some(container) = &container_bytes
+                && (!no_risk_check || dry_run)
+            {
+                let data_len = match &input_bytes {
+                    Some(input) => input.len(),
+                    None => {
+                        let input_len = fs::metadata(&input)
+                            .map_err(|e| format!("Failed to read input: {}", e))?
+                            .len();
+                        // Lengths that do not fit are rejected by the capacity checks
+                        usize::try_from(input_len).unwrap_or(usize::MAX)
+                    }
+                };
+
+                if !no_risk_check {
+                    let lsbs = match cli.lsbs {
+                        LsbSelection::Fixed(lsbs) => lsbs,
+                        LsbSelection::Auto => (1..=8)
+                            .find(|&lsbs| {
+                                capacity_with(container, lsbs, hash, ext.len())
+                                    .is_ok_and(|capacity| capacity >= data_len)
+                            })
+                            .unwrap_or(8),
+                    };
+                    // Invalid settings are reported by the embedding itself
+                    if let Ok(report) = risk_report(container, data_len, lsbs)
+                        && report.rating == RiskRating::High
+                    {
+                        warn!("The embedding is likely to be detected:");
+                        for reason in &report.reasons {
+                            warn!("  {}", reason);
+                        }
+                    }
+                }
+
+                if dry_run {
+                    let plan = plan_embed(data_len, ext, container, &options)?;
+                    println!("Payload: {} bytes", plan.payload_bytes);
+                    println!("LSBs: {}", plan.lsbs);
+                    println!("Capacity: {} bits", plan.capacity_bits);
+                    println!("Utilization: {:.2}%", plan.utilization);
+                    if let Some(existing) = plan.existing_payload {
+                        println!("Overwrites: {}", existing);
+                    }
+                    return Ok(ExitCode::SUCCESS);
+                }
+            }
+
+            let report = match (&input_bytes, &container_bytes) {
+                (Some(input), Some(container)) => {
+                    let (image, report) = embed_with_report(input, ext, container, &options)?;
+                    write_output(&output, &image)?;
+                    report
+                }
+                _ => embed_path(&input, &container, &output, &options)?,
+            };
+            for warning in &report.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            // With the image written to stdout, the report goes to stderr
+            let mut info: Box<dyn Write> = match is_stdio(&output) {
+                true => Box::new(io::stderr()),
+                false => Box::new(io::stdout()),
+            };
+            writeln!(info, "Payload: {} bytes", report.payload_bytes)?;
+            writeln!(info, "LSBs: {}", report.lsbs)?;
+            writeln!(info, "Capacity: {} bits", report.capacity_bits)?;
+            writeln!(info, "Utilization: {:.2}%", report.utilization)?;
+            writeln!(
+                info,
+                "Flipped: {} of {} bits",
+                report.bits_flipped, report.bits_written
+            )?;
+        }
+        Commands::Extract {
+            container,
+            output_dir,
+            stdout,
+            unpack,
+            list,
+            json,
+            legacy_order,
+            passphrase,
+        } => {
+            let passphrase = read_passphrase(&passphrase, false)?;
+            let seed = fixed_seed(seed)?;
+            let options = ExtractOptions::new()
+                .lsbs(cli.lsbs)
+                .seed(passphrase.as_ref().map_or(seed, |p| p.seed()))
+                .legacy_order(legacy_order);
+            // Encrypted payloads are only ever extracted in memory
+            let extract = || -> Result<_, Box<dyn Error>> {
+                let input = read_input(&container, "container")?;
+                let mut extracted = match (extract_with(&input, &options), &passphrase) {
+                    (Err(err), Some(_)) if err.kind() == ErrorKind::NotFound => {
+                        return Err(
+                            "Wrong passphrase: no payload was embedded with it under these \
+                             settings"
+                                .into(),
+                        );
+                    }
+                    (result, _) => result?,
+                };
+                if let Some(passphrase) = &passphrase
+                    && extracted.extension == ENCRYPTED_EXTENSION
+                {
+                    (extracted.data, extracted.extension) = passphrase.decrypt(&extracted.data)?;
+                }
+                Ok(extracted)
+            };
+
+            if list {
+                if passphrase.is_some() {
+                    return Err("--list does not decrypt payloads".into());
+                }
+                let input = read_input(&container, "container")?;
+                return list_payload(&input, &container, &options, json);
+            }
+
+            if unpack {
+                if is_stdio(&output_dir) {
+                    return Err("--unpack writes a directory, not stdout".into());
+                }
+                let extracted = extract()?;
+                for warning in &extracted.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                if extracted.extension != "tar" {
+                    return Err(format!(
+                        "The payload is a .{} file, not a directory",
+                        extracted.extension
+                    )
+                    .into());
+                }
 
-            let embedded = embed(&input, ext, &container, cli.lsbs, hash, cli.seed, format)?;
+                let output = output_dir.join(payload_name(&container, ""));
+                if output.exists() {
+                    return Err(format!("{} already exists", output.display()).into());
+                }
+                // Entries escaping the directory are skipped by `unpack`
+                let mut archive = tar::Archive::new(extracted.data.as_slice());
+                archive.set_preserve_mtime(false);
+                archive
+                    .unpack(&output)
+                    .map_err(|e| format!("Failed to unpack {}: {}", output.display(), e))?;
+                println!("{}", output.display());
+                return Ok(ExitCode::SUCCESS);
+            }
+            let to_stdout = stdout || is_stdio(&output_dir);
 
-            fs::write(&output, embedded).map_err(|e| format!("Failed to write output: {}", e))?;
+            if passphrase.is_none() && !is_stdio(&container) && !to_stdout {
+                let (output, info) = extract_path(&container, &output_dir, &options)?;
+                for warning in &info.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                println!("{}", output.display());
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            // --stdout asks for the data on a terminal
+            if to_stdout && !stdout {
+                check_tty(cli.force_tty)?;
+            }
+            let extracted = extract()?;
+            for warning in &extracted.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+
+            if to_stdout {
+                write_output(Path::new("-"), &extracted.data)?;
+                eprintln!("Extension: {}", extracted.extension);
+                eprintln!("Size: {} bytes", extracted.data.len());
+            } else {
+                let output = output_dir.join(payload_name(&container, &extracted.extension));
+                write_output(&output, &extracted.data)?;
+                println!("{}", output.display());
+            }
         }
-        Commands::Extract { container, output } => {
+        Commands::Capacity {
+            image,
+            hash,
+            all,
+            json,
+        } => {
             let container =
-                fs::read(container).map_err(|e| format!("Failed to read container: {}", e))?;
+                fs::read(&image).map_err(|e| format!("Failed to read container: {}", e))?;
+
+            match cli.lsbs {
+                LsbSelection::Fixed(lsbs) if !all => {
+                    let capacity = capacity_with(&container, lsbs, hash, 0)?;
+                    if json {
+                        println!("{{\"lsbs\":{},\"bytes\":{}}}", lsbs, capacity);
+                    } else {
+                        println!("Capacity: {} bytes", capacity);
+                    }
+                }
+                _ => {
+                    let mut table = Vec::new();
+                    for lsbs in 1..=8 {
+                        match capacity_with(&container, lsbs, hash, 0) {
+                            Ok(capacity) => table.push((lsbs, capacity)),
+                            // Too few bits for even an empty payload
+                            Err(StegError::InsufficientCapacity { .. }) => table.push((lsbs, 0)),
+                            Err(err) => return Err(err.into()),
+                        }
+                    }
+
+                    if json {
+                        let rows: Vec<_> = table
+                            .iter()
+                            .map(|(lsbs, capacity)| {
+                                format!("{{\"lsbs\":{},\"bytes\":{}}}", lsbs, capacity)
+                            })
+                            .collect();
+                        println!("[{}]", rows.join(","));
+                    } else {
+                        println!("LSBs  Capacity");
+                        for (lsbs, capacity) in &table {
+                            println!("{:>4}  {} bytes", lsbs, capacity);
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Inspect {
+            container,
+            json,
+            legacy_order,
+        } => {
+            let options = ExtractOptions::new()
+                .lsbs(cli.lsbs)
+                .seed(fixed_seed(seed)?)
+                .legacy_order(legacy_order);
+            let input =
+                fs::read(&container).map_err(|e| format!("Failed to read container: {}", e))?;
+
+            let inspection = match inspect(&input, &options) {
+                Ok(inspection) => inspection,
+                Err(
+                    err @ (StegError::NoPayloadFound { .. } | StegError::WrongSeedSuspected { .. }),
+                ) => {
+                    if json {
+                        println!("{}", serde_json::json!({ "present": false }));
+                    } else {
+                        println!("No payload detected (or wrong seed)");
+                    }
+                    return Ok(ExitCode::from(exit_code(&err)));
+                }
+                Err(err) => return Err(err.into()),
+            };
+            for warning in &inspection.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+
+            let checksum = match inspection.checksum {
+                ChecksumStatus::Valid => "valid",
+                ChecksumStatus::Mismatch => "mismatch",
+                ChecksumStatus::ContainerModified => "container modified",
+                ChecksumStatus::Corrupted => "corrupted",
+                ChecksumStatus::Unverified => "unverified",
+            };
+            if json {
+                let report = serde_json::json!({
+                    "present": true,
+                    "extension": inspection.extension,
+                    "bytes": inspection.data_bytes,
+                    "lsbs": inspection.lsbs,
+                    "hash": inspection.hash.name(),
+                    "checksum": checksum,
+                });
+                println!("{}", report);
+            } else {
+                println!("Payload: present");
+                println!("Extension: {}", inspection.extension);
+                println!("Size: {} bytes", inspection.data_bytes);
+                println!("LSBs: {}", inspection.lsbs);
+                println!("Hash: {}", inspection.hash);
+                println!("Checksum: {}", checksum);
+            }
+
+            if inspection.checksum != ChecksumStatus::Valid {
+                // The exit status of extracting the payload
+                return Ok(ExitCode::from(exit_code(&StegError::ChecksumMismatch)));
+            }
+        }
+        Commands::Verify {
+            images,
+            json,
+            legacy_order,
+        } => {
+            let options = ExtractOptions::new()
+                .lsbs(cli.lsbs)
+                .seed(fixed_seed(seed)?)
+                .legacy_order(legacy_order);
+            let files = expand_globs(&images)?;
 
-            let (data, ext) = extract(&container, cli.lsbs, cli.seed)?;
+            #[cfg(feature = "parallel")]
+            let results: Vec<_> = files
+                .par_iter()
+                .map(|path| verify(path, &options))
+                .collect();
+            #[cfg(not(feature = "parallel"))]
+            let results: Vec<_> = files.iter().map(|path| verify(path, &options)).collect();
+            let failed = results.iter().filter(|result| result.is_err()).count();
 
-            let output = output.with_extension(ext);
-            fs::write(&output, data).map_err(|e| format!("Failed to write output: {}", e))?;
+            if json {
+                let files: Vec<_> = files
+                    .iter()
+                    .zip(&results)
+                    .map(|(path, result)| match result {
+                        Ok((extension, bytes)) => serde_json::json!({
+                            "path": path.display().to_string(),
+                            "pass": true,
+                            "extension": extension,
+                            "bytes": bytes,
+                        }),
+                        Err(err) => serde_json::json!({
+                            "path": path.display().to_string(),
+                            "pass": false,
+                            "error": err.to_string(),
+                        }),
+                    })
+                    .collect();
+                let report = serde_json::json!({
+                    "files": files,
+                    "passed": results.len() - failed,
+                    "failed": failed,
+                });
+                println!("{}", report);
+            } else {
+                for (path, result) in files.iter().zip(&results) {
+                    match result {
+                        Ok(_) => println!("PASS {}", path.display()),
+                        Err(err) => println!("FAIL {}: {}", path.display(), err),
+                    }
+                }
+                println!("{} passed, {} failed", results.len() - failed, failed);
+            }
+
+            if failed > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
         }
         Commands::Completion { shell } => {
             let mut cmd = Cli::command();
@@ -47,5 +1076,5 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,565 @@
+use std::{
+    borrow::Cow,
+    io::{Seek, Write},
+};
+
+use tiff::{
+    TiffResult,
+    encoder::{DirectoryEncoder, TiffKind, TiffValue},
+    tags::{Tag, Type},
+};
+
+use super::format::Format;
+
+/// The metadata of a container that outputs carry over, so that they do not stand out
+/// by lacking it and, for the orientation, are not shown rotated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Metadata {
+    /// The ancillary chunks for PNG outputs: those of a PNG container, or its EXIF and
+    /// XMP for other containers.
+    pub(crate) png_chunks: Vec<PngChunk>,
+    /// The EXIF of the container: a TIFF header and directories, without image data.
+    pub(crate) exif: Option<Vec<u8>>,
+    /// The XMP packet of the container.
+    pub(crate) xmp: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Reads the metadata of a container in `format`, skipping whatever is malformed.
+    ///
+    /// Only JPEG, PNG, WebP and TIFF containers are read, with a light parse of their
+    /// structure that does not decode any pixels.
+    pub(crate) fn read(container: &[u8], format: Option<Format>) -> Self {
+        let (exif, xmp) = match format {
+            Some(Format::Png) => {
+                let png_chunks = ancillary_png_chunks(container);
+                return Metadata {
+                    exif: png_exif(&png_chunks),
+                    xmp: png_xmp(&png_chunks),
+                    png_chunks,
+                };
+            }
+            Some(Format::Jpeg) => jpeg_metadata(container),
+            Some(Format::WebP) => webp_metadata(container),
+            Some(Format::Tiff) => tiff_metadata(container),
+            _ => (None, None),
+        };
+
+        let mut png_chunks = Vec::new();
+        if let Some(exif) = &exif {
+            png_chunks.push(PngChunk {
+                kind: *b"eXIf",
+                data: exif.clone(),
+            });
+        }
+        if let Some(xmp) = &xmp {
+            png_chunks.push(PngChunk {
+                kind: *b"iTXt",
+                data: [XMP_ITXT_HEADER, xmp].concat(),
+            });
+        }
+
+        Metadata {
+            png_chunks,
+            exif,
+            xmp,
+        }
+    }
+}
+
+/// The first bytes of every PNG file.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The known ancillary PNG chunks that stay true of an image whose samples changed.
+///
+/// `iCCP`, `cHRM`, `gAMA`, `sRGB` and `tIME` are marked unsafe to copy, but only
+/// because they would be wrong for different colors, which the LSBs are not. Those
+/// describing the sample depth, palette or transparency (`sBIT`, `bKGD`, `hIST`,
+/// `sPLT`, `tRNS`) and animations are dropped, as the output is 8-bit RGB.
+const COPIED_PNG_CHUNKS: [&[u8; 4]; 10] = [
+    b"iCCP", b"sRGB", b"gAMA", b"cHRM", b"pHYs", b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf",
+];
+
+/// An ancillary chunk of a PNG container, to be written into a PNG output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PngChunk {
+    pub(crate) kind: [u8; 4],
+    pub(crate) data: Vec<u8>,
+}
+
+/// Collects the ancillary chunks of a PNG container that are still valid for an 8-bit
+/// RGB output, in their order in the container.
+///
+/// This only walks the chunk stream, without decoding anything. Chunks with a wrong
+/// CRC are skipped, and a truncated stream ends the walk, as decoders do.
+pub(crate) fn ancillary_png_chunks(container: &[u8]) -> Vec<PngChunk> {
+    let Some(mut rest) = container.strip_prefix(&PNG_SIGNATURE) else {
+        return Vec::new();
+    };
+
+    let mut chunks = Vec::new();
+    let mut grayscale = false;
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let Some(crc) = rest.get(8 + length..12 + length) else {
+            break;
+        };
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let data = &rest[8..8 + length];
+        rest = &rest[12 + length..];
+
+        // The CRC covers the type and the data
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&kind);
+        hasher.update(data);
+        if hasher.finalize() != u32::from_be_bytes(crc.try_into().unwrap()) {
+            continue;
+        }
+
+        let copied = match &kind {
+            b"IEND" => break,
+            // Color types 0 and 4, whose ICC profiles do not apply to RGB
+            b"IHDR" => {
+                grayscale = matches!(data.get(9), Some(0 | 4));
+                false
+            }
+            b"iCCP" => !grayscale,
+            kind => {
+                COPIED_PNG_CHUNKS.contains(&kind)
+                    // Unknown ancillary chunks marked safe to copy into an image whose
+                    // critical chunks changed
+                    || (kind[0].is_ascii_lowercase() && kind[3].is_ascii_lowercase())
+            }
+        };
+        if copied {
+            chunks.push(PngChunk {
+                kind,
+                data: data.to_vec(),
+            });
+        }
+    }
+
+    chunks
+}
+
+/// The keyword of the PNG text chunk holding XMP, followed by the flags and the empty
+/// language tags of an uncompressed `iTXt` chunk.
+const XMP_ITXT_HEADER: &[u8] = b"XML:com.adobe.xmp\0\0\0\0\0";
+
+/// The EXIF of a PNG container, from its `eXIf` chunk.
+fn png_exif(chunks: &[PngChunk]) -> Option<Vec<u8>> {
+    chunks
+        .iter()
+        .find(|chunk| &chunk.kind == b"eXIf")
+        .map(|chunk| chunk.data.clone())
+}
+
+/// The XMP of a PNG container, from its uncompressed `iTXt` chunk.
+fn png_xmp(chunks: &[PngChunk]) -> Option<Vec<u8>> {
+    chunks
+        .iter()
+        .filter(|chunk| &chunk.kind == b"iTXt")
+        .find_map(|chunk| {
+            let data = chunk.data.strip_prefix(b"XML:com.adobe.xmp\0\0")?;
+            // Skips the compression method and the language tag and translated keyword
+            let mut fields = data.get(1..)?.splitn(3, |&byte| byte == 0);
+            let (_, _, xmp) = (fields.next()?, fields.next()?, fields.next()?);
+            Some(xmp.to_vec())
+        })
+}
+
+/// The prefix of the JPEG `APP1` segment holding EXIF.
+const JPEG_EXIF_PREFIX: &[u8] = b"Exif\0\0";
+/// The prefix of the JPEG `APP1` segment holding XMP.
+const JPEG_XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// The EXIF and XMP of a JPEG container, from its `APP1` segments.
+///
+/// Only the segments before the image data are walked, where they belong.
+fn jpeg_metadata(container: &[u8]) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    let (mut exif, mut xmp) = (None, None);
+    let Some(mut rest) = container.strip_prefix(&[0xff, 0xd8]) else {
+        return (exif, xmp);
+    };
+
+    while let [0xff, marker, tail @ ..] = rest {
+        // Fill bytes may come before a marker
+        if *marker == 0xff {
+            rest = &rest[1..];
+            continue;
+        }
+
+        rest = tail;
+        match marker {
+            // Markers without a segment
+            0x01 | 0xd0..=0xd7 => {}
+            // The start of the image data, or the end of an image without any
+            0xda | 0xd9 => break,
+            _ => {
+                let Some(&[high, low]) = rest.get(..2) else {
+                    break;
+                };
+                let length = u16::from_be_bytes([high, low]) as usize;
+                let Some(segment) = rest.get(2..length.max(2)) else {
+                    break;
+                };
+                if *marker == 0xe1 {
+                    if let Some(data) = segment.strip_prefix(JPEG_EXIF_PREFIX) {
+                        exif.get_or_insert_with(|| data.to_vec());
+                    } else if let Some(data) = segment.strip_prefix(JPEG_XMP_PREFIX) {
+                        xmp.get_or_insert_with(|| data.to_vec());
+                    }
+                }
+                rest = &rest[length.max(2)..];
+            }
+        }
+    }
+
+    (exif, xmp)
+}
+
+/// The EXIF and XMP of a WebP container, from its `EXIF` and `XMP ` chunks.
+fn webp_metadata(container: &[u8]) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    let (mut exif, mut xmp) = (None, None);
+    if container.get(..4) != Some(b"RIFF") || container.get(8..12) != Some(b"WEBP") {
+        return (exif, xmp);
+    }
+
+    let mut rest = &container[12..];
+    while rest.len() >= 8 {
+        let length = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        let Some(data) = rest.get(8..8 + length) else {
+            break;
+        };
+        match &rest[..4] {
+            // Some writers keep the prefix of the JPEG segment
+            b"EXIF" => exif = Some(data.strip_prefix(JPEG_EXIF_PREFIX).unwrap_or(data).to_vec()),
+            b"XMP " => xmp = Some(data.to_vec()),
+            _ => {}
+        }
+        // Chunks are padded to an even length
+        rest = rest.get(8 + length + length % 2..).unwrap_or_default();
+    }
+
+    (exif, xmp)
+}
+
+/// The EXIF and XMP of a TIFF container, from the tags of its first directory.
+///
+/// The EXIF is rebuilt into a TIFF structure of its own, as the container holds it
+/// among the tags describing its image data.
+fn tiff_metadata(container: &[u8]) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    let Some(mut fields) = read_exif(container) else {
+        return (None, None);
+    };
+
+    let xmp = fields
+        .iter()
+        .position(|field| field.tag == XMP_TAG)
+        .map(|index| fields.remove(index).value);
+    let exif = (!fields.is_empty()).then(|| write_exif(&fields));
+
+    (exif, xmp)
+}
+
+/// The TIFF tag holding XMP.
+const XMP_TAG: u16 = 700;
+/// The TIFF tags pointing to directories of EXIF, GPS and interoperability tags.
+const SUB_IFD_TAGS: [u16; 3] = [34665, 34853, 40965];
+/// How deeply directories may point to further directories: EXIF to interoperability.
+const MAX_SUB_IFD_DEPTH: u8 = 2;
+
+/// The TIFF tags that describe how the image data is stored, or point into data that is
+/// not copied, rather than describing the image.
+///
+/// Outputs are written with tags of their own for these, and `MakerNote` is dropped as
+/// it usually holds offsets that no longer point to its data once moved.
+const DROPPED_TAGS: &[u16] = &[
+    254, 255, 256, 257, 258, 259, 262, 263, 266, 273, 277, 278, 279, 280, 281, 284, 317, 320, 322,
+    323, 324, 325, 330, 338, 339, 340, 341, 347, 513, 514, 529, 530, 532, 37500,
+];
+
+/// A field of a TIFF directory, with its value in native byte order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: Vec<u8>,
+    /// The directory the field points to, for the tags in `SUB_IFD_TAGS`.
+    sub_ifd: Option<Vec<Field>>,
+}
+
+/// The size in bytes of a value of a TIFF field type, and of the numbers making it up,
+/// which are swapped to change the byte order.
+const fn type_size(kind: u16) -> Option<(usize, usize)> {
+    match kind {
+        // BYTE, ASCII, SBYTE, UNDEFINED
+        1 | 2 | 6 | 7 => Some((1, 1)),
+        // SHORT, SSHORT
+        3 | 8 => Some((2, 2)),
+        // LONG, SLONG, FLOAT, IFD
+        4 | 9 | 11 | 13 => Some((4, 4)),
+        // RATIONAL, SRATIONAL
+        5 | 10 => Some((8, 4)),
+        // DOUBLE
+        12 => Some((8, 8)),
+        _ => None,
+    }
+}
+
+/// Reads the first directory of a TIFF structure, such as EXIF, with the directories
+/// it points to.
+fn read_exif(exif: &[u8]) -> Option<Vec<Field>> {
+    let big_endian = match exif.get(..4)? {
+        b"II*\0" => false,
+        b"MM\0*" => true,
+        _ => return None,
+    };
+    let reader = ExifReader { exif, big_endian };
+    reader.ifd(reader.u32(4)? as usize, MAX_SUB_IFD_DEPTH)
+}
+
+/// Reads the directories of a TIFF structure in either byte order.
+struct ExifReader<'a> {
+    exif: &'a [u8],
+    big_endian: bool,
+}
+
+impl ExifReader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        let mut bytes: [u8; N] = self
+            .exif
+            .get(offset..offset.checked_add(N)?)?
+            .try_into()
+            .ok()?;
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Some(bytes)
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        self.bytes(offset).map(u16::from_le_bytes)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        self.bytes(offset).map(u32::from_le_bytes)
+    }
+
+    /// Reads the directory at `offset`, skipping the fields it cannot read and those in
+    /// `DROPPED_TAGS`.
+    fn ifd(&self, offset: usize, depth: u8) -> Option<Vec<Field>> {
+        let count = self.u16(offset)? as usize;
+        let mut fields = Vec::with_capacity(count);
+
+        for entry in (0..count).map(|index| offset + 2 + index * 12) {
+            let (Some(tag), Some(kind), Some(count)) =
+                (self.u16(entry), self.u16(entry + 2), self.u32(entry + 4))
+            else {
+                break;
+            };
+            let Some((size, unit)) = type_size(kind) else {
+                continue;
+            };
+            if DROPPED_TAGS.contains(&tag) {
+                continue;
+            }
+
+            let Some(len) = (count as usize).checked_mul(size) else {
+                continue;
+            };
+            // Values of up to 4 bytes are stored in the entry itself
+            let start = match len {
+                0..=4 => entry + 8,
+                _ => match self.u32(entry + 8) {
+                    Some(start) => start as usize,
+                    None => continue,
+                },
+            };
+            let Some(bytes) = start
+                .checked_add(len)
+                .and_then(|end| self.exif.get(start..end))
+            else {
+                continue;
+            };
+
+            let mut value = bytes.to_vec();
+            if self.big_endian != cfg!(target_endian = "big") {
+                value.chunks_mut(unit).for_each(<[u8]>::reverse);
+            }
+
+            let sub_ifd = if SUB_IFD_TAGS.contains(&tag) && count == 1 && size == 4 {
+                let Some(sub_ifd) = depth
+                    .checked_sub(1)
+                    .and_then(|depth| self.ifd(self.u32(entry + 8)? as usize, depth))
+                else {
+                    continue;
+                };
+                Some(sub_ifd)
+            } else {
+                None
+            };
+
+            fields.push(Field {
+                tag,
+                kind,
+                count,
+                value,
+                sub_ifd,
+            });
+        }
+
+        Some(fields)
+    }
+}
+
+/// Writes fields into a TIFF structure in native byte order, like EXIF.
+fn write_exif(fields: &[Field]) -> Vec<u8> {
+    let header: &[u8] = if cfg!(target_endian = "big") {
+        b"MM\0*"
+    } else {
+        b"II*\0"
+    };
+    [header, &8u32.to_ne_bytes(), &write_ifd(fields, 8)].concat()
+}
+
+/// Writes fields as a directory starting at `offset` of a TIFF structure in native byte
+/// order, followed by their values and the directories they point to.
+fn write_ifd(fields: &[Field], offset: u32) -> Vec<u8> {
+    let mut fields: Vec<_> = fields.iter().collect();
+    fields.sort_by_key(|field| field.tag);
+
+    let mut entries = Vec::with_capacity(2 + fields.len() * 12 + 4);
+    entries.extend((fields.len() as u16).to_ne_bytes());
+    // Values and directories go after the directory, which ends with the offset of the
+    // next one
+    let data_offset = offset + 2 + fields.len() as u32 * 12 + 4;
+    let mut data = Vec::new();
+
+    for field in fields {
+        entries.extend(field.tag.to_ne_bytes());
+        entries.extend(field.kind.to_ne_bytes());
+        entries.extend(field.count.to_ne_bytes());
+
+        // Every offset falls on a word boundary
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        let value_offset = data_offset + data.len() as u32;
+        match &field.sub_ifd {
+            Some(sub_ifd) => {
+                entries.extend(value_offset.to_ne_bytes());
+                data.extend(write_ifd(sub_ifd, value_offset));
+            }
+            None if field.value.len() <= 4 => {
+                entries.extend(&field.value);
+                entries.extend(&[0; 4][field.value.len()..]);
+            }
+            None => {
+                entries.extend(value_offset.to_ne_bytes());
+                data.extend(&field.value);
+            }
+        }
+    }
+    entries.extend(0u32.to_ne_bytes());
+
+    [entries, data].concat()
+}
+
+/// Writes the EXIF and XMP of `metadata` as tags of the TIFF directory being encoded,
+/// which must not have written its image data yet.
+///
+/// The directories EXIF points to are written right away, as raw data at the current
+/// end of the file.
+pub(crate) fn write_tiff_metadata<W: Write + Seek, K: TiffKind>(
+    directory: &mut DirectoryEncoder<W, K>,
+    metadata: &Metadata,
+) -> TiffResult<()> {
+    let fields = metadata
+        .exif
+        .as_deref()
+        .and_then(read_exif)
+        .unwrap_or_default();
+
+    for field in fields {
+        let Some(sub_ifd) = &field.sub_ifd else {
+            write_field(directory, field.tag, field.kind, field.count, &field.value)?;
+            continue;
+        };
+
+        // Directories start on a word boundary
+        let mut offset = directory.write_data(&[][..] as &[u8])?;
+        if offset % 2 == 1 {
+            offset = directory.write_data(0u8)? + 1;
+        }
+        // A directory past what classic TIFF can address is left out
+        let Ok(offset) = u32::try_from(offset) else {
+            continue;
+        };
+        directory.write_data(&write_ifd(sub_ifd, offset)[..])?;
+        write_field(directory, field.tag, field.kind, 1, &offset.to_ne_bytes())?;
+    }
+
+    if let Some(xmp) = &metadata.xmp {
+        directory.write_tag(Tag::from_u16_exhaustive(XMP_TAG), &xmp[..])?;
+    }
+
+    Ok(())
+}
+
+/// A field value of any type, already in native byte order, as the TIFF encoder writes.
+struct RawValue<'a, const KIND: u16> {
+    count: u32,
+    value: &'a [u8],
+}
+
+impl<const KIND: u16> TiffValue for RawValue<'_, KIND> {
+    const BYTE_LEN: u8 = match type_size(KIND) {
+        Some((size, _)) => size as u8,
+        None => 1,
+    };
+    const FIELD_TYPE: Type = match KIND {
+        1 => Type::BYTE,
+        2 => Type::ASCII,
+        3 => Type::SHORT,
+        4 => Type::LONG,
+        5 => Type::RATIONAL,
+        6 => Type::SBYTE,
+        8 => Type::SSHORT,
+        9 => Type::SLONG,
+        10 => Type::SRATIONAL,
+        11 => Type::FLOAT,
+        12 => Type::DOUBLE,
+        13 => Type::IFD,
+        _ => Type::UNDEFINED,
+    };
+
+    fn count(&self) -> usize {
+        self.count as usize
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.value)
+    }
+}
+
+/// Writes a field of any type as a tag of the TIFF directory being encoded.
+fn write_field<W: Write + Seek, K: TiffKind>(
+    directory: &mut DirectoryEncoder<W, K>,
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: &[u8],
+) -> TiffResult<()> {
+    let tag = Tag::from_u16_exhaustive(tag);
+    macro_rules! write_as {
+        ($($kind:literal)*) => {
+            match kind {
+                $($kind => directory.write_tag(tag, RawValue::<$kind> { count, value }),)*
+                _ => Ok(()),
+            }
+        };
+    }
+    write_as!(1 2 3 4 5 6 7 8 9 10 11 12 13)
+}
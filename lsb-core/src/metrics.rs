@@ -0,0 +1,209 @@
+use image::Rgb;
+
+use super::{consts::*, diff::check_dimensions, error::*, image::*, par::*};
+
+/// The largest value an 8-bit sample can take.
+const MAX_SAMPLE: f64 = u8::MAX as f64;
+/// The side length of the square windows SSIM is computed over.
+const SSIM_WINDOW: usize = 8;
+/// The constant stabilizing the luminance term of SSIM, `(0.01 * MAX_SAMPLE)^2`.
+const SSIM_C1: f64 = (0.01 * MAX_SAMPLE) * (0.01 * MAX_SAMPLE);
+/// The constant stabilizing the contrast and structure term of SSIM, `(0.03 * MAX_SAMPLE)^2`.
+const SSIM_C2: f64 = (0.03 * MAX_SAMPLE) * (0.03 * MAX_SAMPLE);
+
+/// Measures of the difference between an image and a modified copy of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityReport {
+    /// The mean squared error over all color channel samples.
+    pub mse: f64,
+    /// The peak signal-to-noise ratio in decibels, or infinity for identical images.
+    pub psnr_db: f64,
+    /// The largest absolute difference between two corresponding samples.
+    pub max_abs_diff: u8,
+}
+
+/// Computes the MSE and PSNR between an image and a modified copy of it.
+///
+/// # Arguments
+///
+/// * `original`: The image before modification, e.g. the container.
+/// * `modified`: The image after modification, e.g. the output of `embed`.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `QualityReport` if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+pub fn quality_metrics(original: &RgbImage, modified: &RgbImage) -> StegResult<QualityReport> {
+    check_dimensions(original.dimensions(), modified.dimensions())?;
+
+    let chunk_size = chunk_size(original.len());
+    let (squared_error, max_abs_diff) = original
+        .par_chunks(chunk_size)
+        .zip(modified.par_chunks(chunk_size))
+        .map(|(a, b)| {
+            a.iter().zip(b).fold((0u64, 0u8), |(sum, max), (&a, &b)| {
+                let diff = a.abs_diff(b);
+                (sum + u64::from(diff) * u64::from(diff), max.max(diff))
+            })
+        })
+        .reduce(|| (0, 0), |(s1, m1), (s2, m2)| (s1 + s2, m1.max(m2)));
+
+    let mse = if original.is_empty() {
+        0.0
+    } else {
+        squared_error as f64 / original.len() as f64
+    };
+
+    Ok(QualityReport {
+        mse,
+        psnr_db: 10.0 * (MAX_SAMPLE * MAX_SAMPLE / mse).log10(),
+        max_abs_diff,
+    })
+}
+
+/// Computes the MSE and PSNR between two encoded images, as `quality_metrics` does.
+///
+/// The images are decoded like `extract` decodes its input, so floating-point
+/// formats are compared on the samples that carry embedded data.
+///
+/// # Arguments
+///
+/// * `original`: A slice of bytes representing the image before modification.
+/// * `modified`: A slice of bytes representing the image after modification.
+///
+/// # Returns
+///
+/// A `StegResult` containing the `QualityReport` if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+/// * Errors from the `image` crate during image decoding.
+pub fn quality_metrics_from_bytes(original: &[u8], modified: &[u8]) -> StegResult<QualityReport> {
+    let (original, modified) = decode_pair(original, modified)?;
+
+    quality_metrics(&original, &modified)
+}
+
+/// Computes the structural similarity (SSIM) index between an image and a modified copy of it.
+///
+/// This follows Wang et al. (2004) on the Rec. 601 luma of the images: the index is
+/// computed over every 8x8 window, with population statistics, and averaged. Images
+/// smaller than a window in a direction use a single window spanning that direction.
+///
+/// # Arguments
+///
+/// * `original`: The image before modification, e.g. the container.
+/// * `modified`: The image after modification, e.g. the output of `embed`.
+///
+/// # Returns
+///
+/// A `StegResult` containing the SSIM index, 1 for identical images and lower the more
+/// they differ, if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+pub fn ssim(original: &RgbImage, modified: &RgbImage) -> StegResult<f64> {
+    check_dimensions(original.dimensions(), modified.dimensions())?;
+
+    let (width, height) = (original.width() as usize, original.height() as usize);
+    if width == 0 || height == 0 {
+        return Ok(1.0);
+    }
+
+    let (x, y) = (luma(original), luma(modified));
+    let (window_width, window_height) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let columns = width - window_width + 1;
+    let rows = height - window_height + 1;
+
+    // The rows are summed in order, as floating-point addition is not associative and a
+    // parallel sum would depend on how the rows are split between threads.
+    let row_totals = (0..rows)
+        .into_par_iter()
+        .map(|top| {
+            (0..columns)
+                .map(|left| window_ssim(&x, &y, width, (left, top), (window_width, window_height)))
+                .sum::<f64>()
+        })
+        .collect::<Vec<_>>();
+    let total = row_totals.into_iter().sum::<f64>();
+
+    Ok(total / (rows * columns) as f64)
+}
+
+/// Computes the SSIM index of the window at `(left, top)` of two luma planes `width` wide.
+fn window_ssim(
+    x: &[f64],
+    y: &[f64],
+    width: usize,
+    (left, top): (usize, usize),
+    (window_width, window_height): (usize, usize),
+) -> f64 {
+    let values = |image| window(image, width, (left, top), (window_width, window_height));
+    let n = (window_width * window_height) as f64;
+
+    let mean_x = values(x).sum::<f64>() / n;
+    let mean_y = values(y).sum::<f64>() / n;
+    let (var_x, var_y, cov) =
+        values(x)
+            .zip(values(y))
+            .fold((0.0, 0.0, 0.0), |(var_x, var_y, cov), (a, b)| {
+                let (da, db) = (a - mean_x, b - mean_y);
+                (var_x + da * da, var_y + db * db, cov + da * db)
+            });
+    let (var_x, var_y, cov) = (var_x / n, var_y / n, cov / n);
+
+    ((2.0 * mean_x * mean_y + SSIM_C1) * (2.0 * cov + SSIM_C2))
+        / ((mean_x * mean_x + mean_y * mean_y + SSIM_C1) * (var_x + var_y + SSIM_C2))
+}
+
+/// Iterates over the values of a window of a luma plane `width` wide.
+fn window(
+    image: &[f64],
+    width: usize,
+    (left, top): (usize, usize),
+    (window_width, window_height): (usize, usize),
+) -> impl Iterator<Item = f64> + '_ {
+    (top..top + window_height).flat_map(move |row| {
+        let start = row * width + left;
+        image[start..start + window_width].iter().copied()
+    })
+}
+
+/// Computes the SSIM index between two encoded images, as `ssim` does.
+///
+/// # Arguments
+///
+/// * `original`: A slice of bytes representing the image before modification.
+/// * `modified`: A slice of bytes representing the image after modification.
+///
+/// # Returns
+///
+/// A `StegResult` containing the SSIM index if successful.
+///
+/// # Errors
+///
+/// * `StegError::DimensionMismatch`: If the images do not have the same dimensions.
+/// * Errors from the `image` crate during image decoding.
+pub fn ssim_from_bytes(original: &[u8], modified: &[u8]) -> StegResult<f64> {
+    let (original, modified) = decode_pair(original, modified)?;
+
+    ssim(&original, &modified)
+}
+
+/// Converts an image to its Rec. 601 luma, row by row.
+pub(crate) fn luma(image: &RgbImage) -> Vec<f64> {
+    image
+        .pixels()
+        .map(|&Rgb([r, g, b])| 0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b))
+        .collect()
+}
+
+/// Decodes two images for comparison.
+fn decode_pair(original: &[u8], modified: &[u8]) -> StegResult<(RgbImage, RgbImage)> {
+    Ok((decode(original, None)?.plane, decode(modified, None)?.plane))
+}
@@ -0,0 +1,286 @@
+use rand::{prelude::*, seq::index::sample};
+use rand_pcg::Pcg64Mcg;
+use tracing::instrument;
+
+/// Decides which embeddable bits of a container hold which bits of the payload.
+///
+/// Embedding and extraction both go through the same `PixelOrder`, so they cannot
+/// drift apart: the `i`-th bit of the payload is always stored at the `i`-th
+/// position returned by `positions`.
+///
+/// Positions index the embeddable bits of the container in raster order: bit
+/// `p` is bit `p % lsbs` of channel sample `p / lsbs`, with samples laid out
+/// row by row, pixel by pixel, channel by channel.
+///
+/// Positions are `u64` on every platform, so that a container embedded on one platform
+/// is read back in the same order on any other, including 32-bit ones where the number
+/// of embeddable bits of a large container does not fit in a `usize`.
+pub trait PixelOrder: Sync {
+    /// Returns the positions of the first `needed` payload bits.
+    ///
+    /// Every position must be below `capacity_bits`, and no position may repeat.
+    /// The result must only depend on `self`, `capacity_bits` and `needed`, and
+    /// asking for fewer bits must return a prefix of asking for more.
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64>;
+
+    /// Returns the positions of the payload bits from `start` up to `needed`, the same
+    /// as skipping the first `start` positions of `positions(capacity_bits, needed)`.
+    ///
+    /// Orders that compute every position on their own override this to start right
+    /// away, so that a part of a payload can be read without generating the positions
+    /// before it.
+    fn positions_from(
+        &self,
+        capacity_bits: u64,
+        start: u64,
+        needed: u64,
+    ) -> impl Iterator<Item = u64> {
+        self.positions(capacity_bits, needed)
+            .skip(usize::try_from(start).unwrap_or(usize::MAX))
+    }
+}
+
+/// The number of rounds of the Feistel network behind `KeyedPermutation`.
+const FEISTEL_ROUNDS: usize = 4;
+
+/// Scatters the payload over the whole container using a permutation keyed by a seed.
+///
+/// This is the order `embed` and `extract` use. Every position is computed on its own
+/// by a small Feistel network over the next even power of two, cycle-walking until it
+/// falls below `capacity_bits`. Unlike `SeededShuffle`, nothing is stored per
+/// embeddable bit, so memory only grows with the payload.
+#[derive(Debug, Clone)]
+pub struct KeyedPermutation {
+    keys: [u64; FEISTEL_ROUNDS],
+}
+
+impl KeyedPermutation {
+    /// Creates the permutation for a 64-bit seed.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg64Mcg::seed_from_u64(seed);
+        KeyedPermutation {
+            keys: core::array::from_fn(|_| rng.random()),
+        }
+    }
+
+    /// Returns the position of the `index`-th payload bit among `capacity_bits` bits.
+    ///
+    /// `index` must be below `capacity_bits`.
+    pub fn position(&self, capacity_bits: u64, index: u64) -> u64 {
+        self.position_in(&Domain::new(capacity_bits), index)
+    }
+
+    /// Returns the position of the `index`-th payload bit in `domain`.
+    fn position_in(&self, domain: &Domain, index: u64) -> u64 {
+        if domain.capacity_bits <= 1 {
+            return index;
+        }
+
+        // The network permutes the whole domain, so walking the cycle from a position
+        // below `capacity_bits` always leads back below it, at most a few steps away
+        let mut position = index;
+        loop {
+            position = self.encrypt(position, domain);
+            if position < domain.capacity_bits {
+                return position;
+            }
+        }
+    }
+
+    /// Permutes a value of the domain.
+    fn encrypt(&self, value: u64, domain: &Domain) -> u64 {
+        let Domain {
+            half_bits, mask, ..
+        } = *domain;
+        let (mut left, mut right) = (value >> half_bits, value & mask);
+
+        for &key in &self.keys {
+            (left, right) = (right, left ^ (mix(right ^ key) & mask));
+        }
+
+        (left << half_bits) | right
+    }
+
+    /// Returns the positions of the payload bits from `start` up to `needed` in `domain`.
+    fn positions_in(&self, domain: Domain, start: u64, needed: u64) -> impl Iterator<Item = u64> {
+        (start..needed.min(domain.capacity_bits)).map(move |index| self.position_in(&domain, index))
+    }
+}
+
+/// The bounds of the Feistel network of `KeyedPermutation` for a capacity: the next even
+/// power of two, split into two halves of `half_bits` bits.
+#[derive(Debug, Clone, Copy)]
+struct Domain {
+    capacity_bits: u64,
+    half_bits: u32,
+    /// The bits of a half.
+    mask: u64,
+}
+
+impl Domain {
+    fn new(capacity_bits: u64) -> Self {
+        let domain_bits = u64::BITS - capacity_bits.saturating_sub(1).leading_zeros();
+        let half_bits = domain_bits.div_ceil(2);
+        Domain {
+            capacity_bits,
+            half_bits,
+            mask: (1 << half_bits) - 1,
+        }
+    }
+}
+
+impl From<u64> for KeyedPermutation {
+    fn from(seed: u64) -> Self {
+        KeyedPermutation::new(seed)
+    }
+}
+
+impl PixelOrder for KeyedPermutation {
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        self.positions_from(capacity_bits, 0, needed)
+    }
+
+    fn positions_from(
+        &self,
+        capacity_bits: u64,
+        start: u64,
+        needed: u64,
+    ) -> impl Iterator<Item = u64> {
+        self.positions_in(Domain::new(capacity_bits), start, needed)
+    }
+}
+
+/// A `KeyedPermutation` prepared for the capacity of the containers it is meant for.
+///
+/// This is a handle to share between many embeddings and extractions of containers
+/// with the same dimensions and seed, e.g. by passing one `Order` to `embed_with_order`
+/// and `extract_with_order` from a pool of workers. It derives the round keys of the
+/// permutation and the bounds of its Feistel network once, instead of on every call,
+/// and holds nothing per embeddable bit, as every position is computed on its own.
+///
+/// The positions are identical to those of `KeyedPermutation::new(seed)`, so images
+/// can be extracted with `extract` as usual. Containers of any other capacity are
+/// handled correctly too, by computing their bounds on every call.
+#[derive(Debug, Clone)]
+pub struct Order {
+    permutation: KeyedPermutation,
+    domain: Domain,
+}
+
+impl Order {
+    /// Creates the order of a 64-bit seed for containers with `capacity_bits`
+    /// embeddable bits, i.e. width * height * 3 * lsbs.
+    pub fn new(seed: u64, capacity_bits: u64) -> Self {
+        Order {
+            permutation: KeyedPermutation::new(seed),
+            domain: Domain::new(capacity_bits),
+        }
+    }
+
+    /// The number of embeddable bits the order is meant for.
+    pub fn capacity_bits(&self) -> u64 {
+        self.domain.capacity_bits
+    }
+
+    /// The bounds of the network for `capacity_bits`, precomputed for the capacity of
+    /// the order.
+    fn domain(&self, capacity_bits: u64) -> Domain {
+        if capacity_bits == self.domain.capacity_bits {
+            self.domain
+        } else {
+            Domain::new(capacity_bits)
+        }
+    }
+}
+
+impl PixelOrder for Order {
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        self.positions_from(capacity_bits, 0, needed)
+    }
+
+    fn positions_from(
+        &self,
+        capacity_bits: u64,
+        start: u64,
+        needed: u64,
+    ) -> impl Iterator<Item = u64> {
+        self.permutation
+            .positions_in(self.domain(capacity_bits), start, needed)
+    }
+}
+
+/// The SplitMix64 finalizer, used as the round function of `KeyedPermutation`.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Scatters the payload over the whole container using a seeded shuffle.
+///
+/// This was the order of `embed` before `KeyedPermutation`, and extractions only fall
+/// back to it for images without `KeyedPermutation`'s header flag, with a fixed `lsbs`
+/// or `ExtractOptions::legacy_order` set. It samples a complete permutation of the
+/// embeddable bits with a `Pcg64Mcg` generator, and takes its prefix, so it needs
+/// memory for every embeddable bit of the container. The sampling runs in a
+/// `seeded_shuffle` span.
+///
+/// # Panics
+///
+/// `positions` panics if `capacity_bits` does not fit in a `usize`, as such a
+/// permutation cannot be stored.
+#[derive(Debug, Clone)]
+pub struct SeededShuffle(Pcg64Mcg);
+
+impl SeededShuffle {
+    /// Creates the shuffle for a 64-bit seed.
+    pub fn new(seed: u64) -> Self {
+        SeededShuffle(Pcg64Mcg::seed_from_u64(seed))
+    }
+}
+
+impl From<u64> for SeededShuffle {
+    fn from(seed: u64) -> Self {
+        SeededShuffle::new(seed)
+    }
+}
+
+impl PixelOrder for SeededShuffle {
+    #[instrument(name = "seeded_shuffle", skip(self))]
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        let mut rng = self.0.clone();
+
+        let capacity_bits = usize::try_from(capacity_bits)
+            .expect("The capacity of a seeded shuffle must fit in a usize");
+        let needed = usize::try_from(needed).unwrap_or(usize::MAX);
+
+        // The `amount` parameter must be the same as `capacity_bits` for reproducibility
+        sample(&mut rng, capacity_bits, capacity_bits)
+            .into_iter()
+            .take(needed)
+            .map(|position| position as u64)
+    }
+}
+
+/// Writes the payload into the embeddable bits in raster order, starting at the
+/// top-left corner.
+///
+/// This needs no seed and is cheap, but concentrates all changes at the top of the
+/// image, so it is mostly useful for debugging and comparisons.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sequential;
+
+impl PixelOrder for Sequential {
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        0..needed.min(capacity_bits)
+    }
+
+    fn positions_from(
+        &self,
+        capacity_bits: u64,
+        start: u64,
+        needed: u64,
+    ) -> impl Iterator<Item = u64> {
+        start..needed.min(capacity_bits)
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::{current_num_threads, prelude::*};
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) use sequential::*;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    use std::{
+        iter::{Enumerate, Filter, FilterMap, Map, Zip},
+        slice::{Chunks, ChunksMut, Iter, IterMut, Windows},
+    };
+
+    use image::{ImageBuffer, Pixel, buffer::Pixels};
+
+    /// There is only the calling thread.
+    pub(crate) fn current_num_threads() -> usize {
+        1
+    }
+
+    /// A sequential iterator standing in for a rayon parallel iterator.
+    ///
+    /// The adaptors whose rayon version differs from the standard one are inherent
+    /// methods, which take precedence over those of `Iterator`, and the others keep the
+    /// wrapper so that they can be chained.
+    pub(crate) struct Sequential<I>(I);
+
+    impl<I: Iterator> Iterator for Sequential<I> {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<I::Item> {
+            self.0.next()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.0.size_hint()
+        }
+    }
+
+    impl<I: Iterator> Sequential<I> {
+        pub(crate) fn map<B, F: FnMut(I::Item) -> B>(self, f: F) -> Sequential<Map<I, F>> {
+            Sequential(self.0.map(f))
+        }
+
+        pub(crate) fn filter<P: FnMut(&I::Item) -> bool>(
+            self,
+            predicate: P,
+        ) -> Sequential<Filter<I, P>> {
+            Sequential(self.0.filter(predicate))
+        }
+
+        pub(crate) fn filter_map<B, F: FnMut(I::Item) -> Option<B>>(
+            self,
+            f: F,
+        ) -> Sequential<FilterMap<I, F>> {
+            Sequential(self.0.filter_map(f))
+        }
+
+        pub(crate) fn enumerate(self) -> Sequential<Enumerate<I>> {
+            Sequential(self.0.enumerate())
+        }
+
+        pub(crate) fn zip<J: IntoIterator>(self, other: J) -> Sequential<Zip<I, J::IntoIter>> {
+            Sequential(self.0.zip(other))
+        }
+
+        /// Folds the items starting from `identity()`, like `ParallelIterator::reduce`.
+        pub(crate) fn reduce<ID, OP>(self, identity: ID, op: OP) -> I::Item
+        where
+            ID: Fn() -> I::Item,
+            OP: Fn(I::Item, I::Item) -> I::Item,
+        {
+            self.0.fold(identity(), op)
+        }
+
+        /// Finds the first item matching `predicate`, like `ParallelIterator::find_first`.
+        pub(crate) fn find_first<P: FnMut(&I::Item) -> bool>(
+            mut self,
+            predicate: P,
+        ) -> Option<I::Item> {
+            self.0.find(predicate)
+        }
+    }
+
+    pub(crate) trait ParallelSlice<T> {
+        fn par_iter(&self) -> Sequential<Iter<'_, T>>;
+        fn par_chunks(&self, chunk_size: usize) -> Sequential<Chunks<'_, T>>;
+        fn par_windows(&self, window_size: usize) -> Sequential<Windows<'_, T>>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_iter(&self) -> Sequential<Iter<'_, T>> {
+            Sequential(self.iter())
+        }
+
+        fn par_chunks(&self, chunk_size: usize) -> Sequential<Chunks<'_, T>> {
+            Sequential(self.chunks(chunk_size))
+        }
+
+        fn par_windows(&self, window_size: usize) -> Sequential<Windows<'_, T>> {
+            Sequential(self.windows(window_size))
+        }
+    }
+
+    pub(crate) trait ParallelSliceMut<T> {
+        fn par_iter_mut(&mut self) -> Sequential<IterMut<'_, T>>;
+        fn par_chunks_mut(&mut self, chunk_size: usize) -> Sequential<ChunksMut<'_, T>>;
+        fn par_sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, f: F);
+    }
+
+    impl<T> ParallelSliceMut<T> for [T] {
+        fn par_iter_mut(&mut self) -> Sequential<IterMut<'_, T>> {
+            Sequential(self.iter_mut())
+        }
+
+        fn par_chunks_mut(&mut self, chunk_size: usize) -> Sequential<ChunksMut<'_, T>> {
+            Sequential(self.chunks_mut(chunk_size))
+        }
+
+        fn par_sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, f: F) {
+            self.sort_by_key(f);
+        }
+    }
+
+    pub(crate) trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Sequential<Self::IntoIter> {
+            Sequential(self.into_iter())
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    /// Stands in for the `par_pixels` the `image` crate provides with its rayon feature.
+    pub(crate) trait ParallelPixels<P: Pixel> {
+        fn par_pixels(&self) -> Sequential<Pixels<'_, P>>;
+    }
+
+    impl<P: Pixel> ParallelPixels<P> for ImageBuffer<P, Vec<P::Subpixel>> {
+        fn par_pixels(&self) -> Sequential<Pixels<'_, P>> {
+            Sequential(self.pixels())
+        }
+    }
+}
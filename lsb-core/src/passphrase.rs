@@ -0,0 +1,157 @@
+//! Passphrases, from which the command-line tool derives the seed and the key that
+//! encrypts the payload, so that neither has to be typed as an argument.
+
+use crate::cli::PassphraseArgs;
+use argon2::Argon2;
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, Nonce,
+    aead::{Aead, NewAead},
+};
+use std::{env, error::Error, fs};
+
+/// The extension embedded for encrypted payloads, whose own extension is encrypted
+/// along with their data.
+pub const ENCRYPTED_EXTENSION: &str = "enc";
+
+/// The salt of the derivation. It is fixed, as the seed has to be derived before
+/// anything can be read from the container.
+const SALT: &[u8] = b"lsb-core passphrase v1";
+/// The version of the layout of encrypted payloads, their first byte.
+const VERSION: u8 = 1;
+const NONCE_SIZE: usize = 12;
+
+/// The seed and the key derived from a passphrase.
+pub struct Passphrase {
+    seed: u64,
+    key: [u8; 32],
+}
+
+impl Passphrase {
+    /// Derives the seed and the key from `passphrase` with Argon2id.
+    ///
+    /// # Errors
+    ///
+    /// * If the passphrase is empty.
+    pub fn derive(passphrase: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if passphrase.is_empty() {
+            return Err("The passphrase is empty".into());
+        }
+
+        let mut output = [0; 40];
+        Argon2::default()
+            .hash_password_into(passphrase, SALT, &mut output)
+            .map_err(|e| format!("Failed to derive from the passphrase: {}", e))?;
+        let (seed, key) = output.split_at(8);
+        Ok(Passphrase {
+            seed: u64::from_le_bytes(seed.try_into().unwrap()),
+            key: key.try_into().unwrap(),
+        })
+    }
+
+    /// The seed that picks the positions of the payload, in place of `--seed`.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Encrypts `data` and its `extension` with ChaCha20-Poly1305 under a random nonce.
+    ///
+    /// # Returns
+    ///
+    /// The version, the nonce and the ciphertext of the extension's length, the
+    /// extension and the data, to be embedded with `ENCRYPTED_EXTENSION`.
+    ///
+    /// # Errors
+    ///
+    /// * If the extension is longer than 255 bytes.
+    pub fn encrypt(&self, data: &[u8], extension: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let extension_len =
+            u8::try_from(extension.len()).map_err(|_| "The extension is too long")?;
+        let plaintext = [&[extension_len][..], extension.as_bytes(), data].concat();
+
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| "Failed to encrypt the payload")?;
+        Ok([&[VERSION][..], &nonce, &ciphertext].concat())
+    }
+
+    /// Decrypts a payload written by `encrypt`.
+    ///
+    /// # Returns
+    ///
+    /// The data and its extension.
+    ///
+    /// # Errors
+    ///
+    /// * If the payload is not encrypted with this version, or with this passphrase.
+    pub fn decrypt(&self, payload: &[u8]) -> Result<(Vec<u8>, String), Box<dyn Error>> {
+        let (version, nonce, ciphertext) = match payload {
+            [version, rest @ ..] if rest.len() >= NONCE_SIZE => {
+                let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+                (*version, nonce, ciphertext)
+            }
+            _ => return Err("The encrypted payload is truncated".into()),
+        };
+        if version != VERSION {
+            return Err(format!("Unsupported encrypted payload version {}", version).into());
+        }
+
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Wrong passphrase: the payload does not decrypt with it")?;
+        let (&extension_len, rest) = plaintext
+            .split_first()
+            .ok_or("The encrypted payload is truncated")?;
+        if rest.len() < usize::from(extension_len) {
+            return Err("The encrypted payload is truncated".into());
+        }
+        let (extension, data) = rest.split_at(usize::from(extension_len));
+        let extension = String::from_utf8(extension.to_vec())
+            .map_err(|_| "The encrypted extension is not UTF-8")?;
+        Ok((data.to_vec(), extension))
+    }
+}
+
+/// Reads the passphrase selected by `args`, if any, and derives from it.
+///
+/// A prompt reads from the terminal without echoing, and asks a second time when
+/// `confirm` is set. A file holds the passphrase with an optional trailing newline.
+///
+/// # Errors
+///
+/// * If the passphrase cannot be read, is empty, or does not match its confirmation.
+pub fn read_passphrase(
+    args: &PassphraseArgs,
+    confirm: bool,
+) -> Result<Option<Passphrase>, Box<dyn Error>> {
+    let passphrase = if args.passphrase_prompt {
+        let passphrase = rpassword::prompt_password("Passphrase: ")
+            .map_err(|e| format!("Failed to read the passphrase: {}", e))?;
+        if confirm
+            && rpassword::prompt_password("Confirm passphrase: ")
+                .map_err(|e| format!("Failed to read the passphrase: {}", e))?
+                != passphrase
+        {
+            return Err("The passphrases do not match".into());
+        }
+        passphrase.into_bytes()
+    } else if let Some(var) = &args.passphrase_env {
+        env::var_os(var)
+            .ok_or_else(|| format!("The environment variable {} is not set", var))?
+            .into_encoded_bytes()
+    } else if let Some(path) = &args.passphrase_file {
+        let mut passphrase =
+            fs::read(path).map_err(|e| format!("Failed to read the passphrase: {}", e))?;
+        if passphrase.ends_with(b"\n") {
+            passphrase.pop();
+            if passphrase.ends_with(b"\r") {
+                passphrase.pop();
+            }
+        }
+        passphrase
+    } else {
+        return Ok(None);
+    };
+
+    Passphrase::derive(&passphrase).map(Some)
+}
@@ -0,0 +1,154 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use tracing::debug;
+
+use super::{consts::*, error::*, hash::*};
+
+/// The most bytes a header can span: the length field, the extension length,
+/// the longest extension, the hash flag, the fingerprint and the longest checksum.
+#[cfg(feature = "std")]
+pub(crate) const MAX_HEADER_SIZE: usize =
+    core::mem::size_of::<u32>() + 1 + u8::MAX as usize + 1 + FINGERPRINT_SIZE + MAX_DIGEST_SIZE;
+
+/// The size of the longest checksum, that of SHA-512.
+#[cfg(feature = "std")]
+const MAX_DIGEST_SIZE: usize = 64;
+
+/// The fewest bytes a header can span: the length field, the extension length, the hash
+/// flag and the shortest checksum, without an extension or a fingerprint.
+pub(crate) const MIN_HEADER_SIZE: usize = core::mem::size_of::<u32>() + 1 + 1 + MIN_DIGEST_SIZE;
+
+/// The size of the shortest checksum, that of SHA-1.
+const MIN_DIGEST_SIZE: usize = 20;
+
+/// Returns the number of bytes `build_payload` stores in front of the data.
+pub(crate) fn header_size(hash: Hash, ext_len: usize) -> usize {
+    core::mem::size_of::<u32>() + 1 + ext_len + 1 + FINGERPRINT_SIZE + hash.digest_size()
+}
+
+/// Builds the payload, header and data, in a single allocation of its exact size.
+pub(crate) fn build_payload(
+    input: &[u8],
+    extension: &str,
+    hash: Hash,
+    fingerprint: [u8; FINGERPRINT_SIZE],
+) -> StegResult<Vec<u8>> {
+    let checksum = use_hasher(&mut *select_hasher(hash), input);
+
+    let mut payload = Vec::with_capacity(header_size(hash, extension.len()) + input.len());
+    write_header(
+        &mut payload,
+        input.len(),
+        extension,
+        hash,
+        fingerprint,
+        &checksum,
+    )?;
+    payload.extend_from_slice(input);
+    Ok(payload)
+}
+
+/// Appends everything the payload holds before the data, from the length field to the
+/// checksum, to `payload`.
+///
+/// The header is `header_size` bytes long, so `payload` can be allocated for the
+/// whole payload beforehand.
+pub(crate) fn write_header(
+    payload: &mut Vec<u8>,
+    input_len: usize,
+    extension: &str,
+    hash: Hash,
+    fingerprint: [u8; FINGERPRINT_SIZE],
+    checksum: &[u8],
+) -> StegResult<()> {
+    let ext_len: u8 = extension
+        .len()
+        .try_into()
+        .map_err(|_| StegError::ExtensionTooLong {
+            len: extension.len(),
+            max: u8::MAX as usize,
+        })?;
+
+    let hash_flag = hash as u8 | FINGERPRINT_FLAG | KEYED_ORDER_FLAG;
+
+    // The length field counts everything after itself
+    let payload_len =
+        (header_size(hash, extension.len()) - core::mem::size_of::<u32>()).checked_add(input_len);
+    let payload_len: u32 = payload_len
+        .and_then(|len| len.try_into().ok())
+        .ok_or_else(|| {
+            StegError::CalculationOverflow(format!(
+                "Payload length exceeds maximum size: {} bytes of data",
+                input_len
+            ))
+        })?;
+
+    payload.extend_from_slice(&payload_len.to_le_bytes());
+    payload.push(ext_len);
+    payload.extend_from_slice(extension.as_bytes());
+    payload.push(hash_flag);
+    payload.extend_from_slice(&fingerprint);
+    payload.extend_from_slice(checksum);
+    Ok(())
+}
+
+/// The metadata at the start of a payload, after the length field.
+pub(crate) struct ParsedHeader<'a> {
+    pub(crate) extension: String,
+    pub(crate) hash: Hash,
+    pub(crate) keyed_order: bool,
+    /// The container fingerprint, absent in payloads embedded before fingerprints.
+    pub(crate) fingerprint: Option<&'a [u8]>,
+    /// The checksum and data following the metadata.
+    pub(crate) rest: &'a [u8],
+}
+
+/// Parses the extension, hash flag and container fingerprint at the start of a payload.
+pub(crate) fn parse_header(payload: &[u8]) -> StegResult<ParsedHeader<'_>> {
+    let (&ext_len, payload) = payload
+        .split_first()
+        .ok_or_else(|| StegError::PayloadParse("Missing extension length".to_string()))?;
+    let ext_len = ext_len as usize;
+
+    if payload.len() < ext_len {
+        return Err(StegError::PayloadParse(format!(
+            "Extension of {} bytes exceeds the payload",
+            ext_len
+        )));
+    }
+    let (extension, payload) = payload.split_at(ext_len);
+    let extension = String::from_utf8(extension.into())?;
+    debug!("Extension: {} ({} bytes)", extension, ext_len);
+
+    let (&hash_flag, payload) = payload
+        .split_first()
+        .ok_or_else(|| StegError::PayloadParse("Missing hash flag".to_string()))?;
+    let hash = Hash::from_repr(hash_flag & !(FINGERPRINT_FLAG | KEYED_ORDER_FLAG))
+        .ok_or(StegError::HashFlagParse { flag: hash_flag })?;
+    debug!("Hash: {:?}", hash);
+
+    let (fingerprint, payload) = if hash_flag & FINGERPRINT_FLAG != 0 {
+        if payload.len() < FINGERPRINT_SIZE {
+            return Err(StegError::PayloadParse(format!(
+                "Fingerprint of {} bytes exceeds the payload",
+                FINGERPRINT_SIZE
+            )));
+        }
+        let (fingerprint, payload) = payload.split_at(FINGERPRINT_SIZE);
+        (Some(fingerprint), payload)
+    } else {
+        (None, payload)
+    };
+
+    Ok(ParsedHeader {
+        extension,
+        hash,
+        keyed_order: hash_flag & KEYED_ORDER_FLAG != 0,
+        fingerprint,
+        rest: payload,
+    })
+}
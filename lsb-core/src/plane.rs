@@ -0,0 +1,332 @@
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+
+use super::{
+    capacity::{capacity_bits, check_dimensions, check_lsbs, insufficient_capacity},
+    consts::*,
+    error::*,
+    hash::*,
+    order::*,
+    payload::*,
+};
+
+/// A plane of interleaved RGB8 samples in memory, such as a framebuffer, to embed into
+/// and extract from without decoding or encoding an image.
+///
+/// The samples hold `width` x `height` pixels row by row, each as its red, green and
+/// blue samples, like the buffers of `embed_raw_rgb` and `extract_raw_rgb`. Payloads
+/// are laid out as described by `layout`, so a plane embedded here extracts with
+/// `extract_raw_rgb`, and the other way around.
+///
+/// This is the part of the crate that builds without the `std` feature, for targets
+/// with an allocator but no operating system. It runs on the calling thread, and does
+/// not check for an existing payload or report progress like the functions built on
+/// images do.
+#[derive(Debug, Clone)]
+pub struct Plane<S> {
+    samples: S,
+    width: u32,
+    height: u32,
+}
+
+impl<S: AsRef<[u8]>> Plane<S> {
+    /// Wraps the samples of `width` x `height` RGB pixels.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::BufferSizeMismatch`: If `samples` does not hold exactly `width` x
+    ///   `height` pixels.
+    pub fn new(samples: S, width: u32, height: u32) -> StegResult<Self> {
+        check_rgb_len(samples.as_ref().len(), width, height)?;
+        Ok(Plane {
+            samples,
+            width,
+            height,
+        })
+    }
+
+    /// The width and height of the plane, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The samples of the plane.
+    pub fn samples(&self) -> &[u8] {
+        self.samples.as_ref()
+    }
+
+    /// Returns the samples of the plane.
+    pub fn into_samples(self) -> S {
+        self.samples
+    }
+
+    /// The number of bits the plane holds with `lsbs` bits per sample.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+    /// * `StegError::CalculationOverflow`: If the number of bits does not fit into a `u64`.
+    pub fn capacity_bits(&self, lsbs: usize) -> StegResult<u64> {
+        check_lsbs(lsbs, BITS_PER_BYTE)?;
+        capacity_bits(self.width, self.height, lsbs)
+    }
+
+    /// Reads `len` bytes stored in `order`, starting `offset` bytes into it, most
+    /// significant bit first.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+    /// * `StegError::InsufficientCapacity`: If the bytes end past the capacity of the plane.
+    /// * `StegError::CalculationOverflow`: If the end of the bytes overflows.
+    pub fn read_bytes(
+        &self,
+        lsbs: usize,
+        order: &impl PixelOrder,
+        offset: usize,
+        len: usize,
+    ) -> StegResult<Vec<u8>> {
+        let (capacity_bits, start_bits, end_bits) = self.bit_range(lsbs, offset, len)?;
+        let mut positions = order.positions_from(capacity_bits, start_bits, end_bits);
+
+        let mut output = vec![0; len];
+        read_bits(self.samples(), &mut output, lsbs, &mut positions);
+        Ok(output)
+    }
+
+    /// Extracts the data embedded with `lsbs` and `seed`, and its extension.
+    ///
+    /// Payloads embedded before `KeyedPermutation` are read with the `SeededShuffle`
+    /// they were embedded with, like `extract` does.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+    /// * `StegError::InsufficientCapacity`: If the plane cannot hold even a header.
+    /// * `StegError::NoPayloadFound`: If the plane holds no valid header.
+    /// * `StegError::ContainerModified`: If the checksum does not match and the samples
+    ///   were changed after embedding.
+    /// * `StegError::PayloadCorrupted`: If the checksum does not match, while the rest of
+    ///   the samples are intact.
+    /// * `StegError::ChecksumMismatch`: If the checksum of a payload without a fingerprint
+    ///   does not match.
+    pub fn extract(&self, lsbs: usize, seed: u64) -> StegResult<(Vec<u8>, String)> {
+        check_dimensions(self.dimensions(), lsbs, MIN_HEADER_SIZE)?;
+
+        let payload = match self.read_payload(lsbs, &KeyedPermutation::new(seed)) {
+            Ok(payload) if parse_header(&payload).is_ok_and(|header| header.keyed_order) => payload,
+            keyed => {
+                // The shuffle stores a permutation of every embeddable bit
+                let capacity_bits = capacity_bits(self.width, self.height, lsbs)?;
+                let shuffle = match usize::try_from(capacity_bits) {
+                    Ok(_) => self.read_payload(lsbs, &SeededShuffle::new(seed)),
+                    Err(_) => Err(StegError::CapacityExceedsUsizeMax(format!(
+                        "The seeded shuffle of {} bits exceeds the address space of this platform",
+                        capacity_bits
+                    ))),
+                };
+                match (keyed, shuffle) {
+                    (_, Ok(payload)) => payload,
+                    (Err(err), Err(_)) | (Ok(_), Err(err)) => return Err(err),
+                }
+            }
+        };
+
+        self.check_payload(&payload, lsbs)
+    }
+
+    /// Extracts the data embedded with `lsbs` in `order`, and its extension, like
+    /// `extract`.
+    ///
+    /// # Errors
+    ///
+    /// See `extract`.
+    pub fn extract_with_order(
+        &self,
+        lsbs: usize,
+        order: &impl PixelOrder,
+    ) -> StegResult<(Vec<u8>, String)> {
+        check_dimensions(self.dimensions(), lsbs, MIN_HEADER_SIZE)?;
+
+        let payload = self.read_payload(lsbs, order)?;
+        self.check_payload(&payload, lsbs)
+    }
+
+    /// Checks a payload read with `lsbs` against its checksum, and returns its data and
+    /// extension.
+    fn check_payload(&self, payload: &[u8], lsbs: usize) -> StegResult<(Vec<u8>, String)> {
+        let ParsedHeader {
+            extension,
+            hash,
+            fingerprint: expected_fingerprint,
+            rest,
+            ..
+        } = parse_header(payload)?;
+
+        let mut hasher = select_hasher(hash);
+        if rest.len() < hasher.output_size() {
+            return Err(StegError::PayloadParse(format!(
+                "Checksum of {} bytes exceeds the payload",
+                hasher.output_size()
+            )));
+        }
+        let (checksum, data) = rest.split_at(hasher.output_size());
+
+        let actual = use_hasher(&mut *hasher, data);
+        if *actual != *checksum {
+            return Err(match expected_fingerprint {
+                None => StegError::ChecksumMismatch,
+                Some(expected) if *expected != fingerprint(self.samples(), lsbs) => {
+                    StegError::ContainerModified
+                }
+                Some(_) => StegError::PayloadCorrupted {
+                    expected: checksum.to_vec(),
+                    actual: actual.to_vec(),
+                },
+            });
+        }
+
+        Ok((data.to_vec(), extension))
+    }
+
+    /// Reads the payload after the length field, as long as the length field says.
+    fn read_payload(&self, lsbs: usize, order: &impl PixelOrder) -> StegResult<Vec<u8>> {
+        let length_size = core::mem::size_of::<u32>();
+        let length = self.read_bytes(lsbs, order, 0, length_size)?;
+        let length = u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize;
+
+        let payload = self
+            .read_bytes(lsbs, order, length_size, length)
+            .map_err(|cause| StegError::NoPayloadFound {
+                cause: Box::new(cause),
+            })?;
+        parse_header(&payload).map_err(|cause| StegError::NoPayloadFound {
+            cause: Box::new(cause),
+        })?;
+        Ok(payload)
+    }
+
+    /// Checks `lsbs` and that `len` bytes from `offset` fit, returning the capacity and
+    /// the range of bits of the bytes.
+    fn bit_range(&self, lsbs: usize, offset: usize, len: usize) -> StegResult<(u64, u64, u64)> {
+        let capacity_bits = self.capacity_bits(lsbs)?;
+
+        let end_bits = offset
+            .checked_add(len)
+            .and_then(|end| (end as u64).checked_mul(BITS_PER_BYTE as u64))
+            .ok_or_else(|| {
+                StegError::CalculationOverflow(format!(
+                    "Overflow calculating the end of {} bytes at offset {}",
+                    len, offset
+                ))
+            })?;
+        if end_bits > capacity_bits {
+            return Err(insufficient_capacity(end_bits, capacity_bits, lsbs));
+        }
+
+        Ok((capacity_bits, (offset * BITS_PER_BYTE) as u64, end_bits))
+    }
+}
+
+impl<S: AsRef<[u8]> + AsMut<[u8]>> Plane<S> {
+    /// Writes `bytes` in `order`, starting `offset` bytes into it, most significant bit
+    /// first, returning the number of bits flipped.
+    ///
+    /// # Errors
+    ///
+    /// See `read_bytes`. The plane is left untouched when an error is returned.
+    pub fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+        lsbs: usize,
+        order: &impl PixelOrder,
+        offset: usize,
+    ) -> StegResult<usize> {
+        let (capacity_bits, start_bits, end_bits) = self.bit_range(lsbs, offset, bytes.len())?;
+        let mut positions = order.positions_from(capacity_bits, start_bits, end_bits);
+
+        Ok(write_bits(
+            self.samples.as_mut(),
+            bytes,
+            lsbs,
+            &mut positions,
+        ))
+    }
+
+    /// Embeds data and its extension with `lsbs`, `hash` and `seed`, so that `extract`
+    /// and the other extractions of the crate read it back.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8 inclusive.
+    /// * `StegError::ExtensionTooLong`: If the extension is longer than 255 bytes.
+    /// * `StegError::InsufficientCapacity`: If the payload does not fit into the plane.
+    /// * `StegError::CalculationOverflow`: If the payload is longer than 4 GiB.
+    ///
+    /// The plane is left untouched when an error is returned.
+    pub fn embed(
+        &mut self,
+        input: &[u8],
+        extension: &str,
+        lsbs: usize,
+        hash: Hash,
+        seed: u64,
+    ) -> StegResult<()> {
+        check_lsbs(lsbs, BITS_PER_BYTE)?;
+        let fingerprint = fingerprint(self.samples(), lsbs);
+        let payload = build_payload(input, extension, hash, fingerprint)?;
+
+        self.write_bytes(&payload, lsbs, &KeyedPermutation::new(seed), 0)?;
+        Ok(())
+    }
+}
+
+/// Checks that a buffer of `len` bytes holds exactly `width` x `height` RGB pixels.
+pub(crate) fn check_rgb_len(len: usize, width: u32, height: u32) -> StegResult<()> {
+    // Cannot overflow, unlike in `u64` at the largest dimensions
+    let expected = width as u128 * height as u128 * EMBEDDABLE_CHANNELS as u128;
+    if len as u128 != expected {
+        return Err(StegError::BufferSizeMismatch { width, height, len });
+    }
+    Ok(())
+}
+
+/// Writes the bits of `bytes` at the next positions of an order, returning the number
+/// of bits flipped.
+pub(crate) fn write_bits(
+    samples: &mut [u8],
+    bytes: &[u8],
+    lsbs: usize,
+    positions: &mut impl Iterator<Item = u64>,
+) -> usize {
+    let mut flipped = 0;
+
+    for &byte in bytes {
+        for (bit_offset, bit_index) in positions.by_ref().take(BITS_PER_BYTE).enumerate() {
+            let bit = (byte >> (BITS_PER_BYTE - 1 - bit_offset)) & 1;
+            // The sample is in memory, so its index fits into a `usize`
+            let sample = &mut samples[(bit_index / lsbs as u64) as usize];
+            let bit_in_channel = (bit_index % lsbs as u64) as usize;
+
+            flipped += ((*sample >> bit_in_channel) & 1 != bit) as usize;
+            *sample = (*sample & !(1 << bit_in_channel)) | (bit << bit_in_channel);
+        }
+    }
+
+    flipped
+}
+
+/// Reads one byte into `output` for every 8 next positions of an order, most
+/// significant bit first.
+fn read_bits(
+    samples: &[u8],
+    output: &mut [u8],
+    lsbs: usize,
+    positions: &mut impl Iterator<Item = u64>,
+) {
+    for byte in output {
+        for bit_index in positions.by_ref().take(BITS_PER_BYTE) {
+            let sample = samples[(bit_index / lsbs as u64) as usize];
+            *byte = (*byte << 1) | ((sample >> (bit_index % lsbs as u64)) & 1);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! The types most programs need, so that `use lsb_core::prelude::*;` covers the
+//! options and results of the common operations.
+
+pub use super::{
+    cancel::CancelToken,
+    embed::{EmbedOptions, EmbedOutcome, EmbedReport, LsbSelection, OverwritePolicy, ResizePolicy},
+    error::{StegError, StegResult},
+    extract::{ExtractOptions, Extracted},
+    format::Format,
+    hash::Hash,
+    image::{OutputOptions, PngCompression, PngFilter, TiffCompression},
+    progress::{Phase, Progress},
+    stego::{Stego, StegoContainer},
+    warning::Warning,
+};
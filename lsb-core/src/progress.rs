@@ -0,0 +1,116 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{cancel::*, error::*};
+
+/// A stage of an embedding or extraction, as reported to a `ProgressCallback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The container was decoded.
+    Decode,
+    /// The positions of the payload bits were generated. Streamed embeddings generate
+    /// them as they go, so they do not report this phase.
+    Order,
+    /// Payload bits are being written into the container.
+    Embed,
+    /// Payload bits are being read from the container.
+    Extract,
+    /// The output image was encoded.
+    Encode,
+    /// The output was extracted again and matched the input.
+    Verify,
+}
+
+/// How far an operation has got, passed to its `ProgressCallback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The stage the operation is in.
+    pub phase: Phase,
+    /// The work done in the phase so far, out of `total`. `Phase::Embed` and
+    /// `Phase::Extract` count payload bits, and the other phases are reported once
+    /// they are done, as 1 out of 1.
+    pub completed: u64,
+    /// The work the phase takes in all.
+    pub total: u64,
+}
+
+/// A callback invoked at coarse milestones of an operation, and about once per chunk
+/// while payload bits are written or read.
+///
+/// The callback may be invoked from any thread of the pool the operation runs in, but
+/// never concurrently by the same phase, whose `completed` values only ever grow. When
+/// `LsbSelection::Auto` tries another value after a failed one, `Phase::Extract` starts
+/// over. The callback runs in the middle of the operation, so it should return quickly,
+/// e.g. by only updating a progress bar.
+pub type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Reports that a phase done in one step has completed.
+pub(crate) fn milestone(callback: Option<&ProgressCallback>, phase: Phase) {
+    if let Some(callback) = callback {
+        callback(Progress {
+            phase,
+            completed: 1,
+            total: 1,
+        });
+    }
+}
+
+/// Reports the progress of a phase made of many parts, which may complete on any thread,
+/// and tells the parts whether the operation was cancelled.
+pub(crate) struct Tracker<'a> {
+    callback: Option<&'a ProgressCallback>,
+    cancel: Option<&'a CancelToken>,
+    phase: Phase,
+    total: u64,
+    completed: Mutex<u64>,
+}
+
+impl<'a> Tracker<'a> {
+    /// Creates a tracker of `phase`, which takes `total` work in all.
+    pub(crate) fn new(
+        callback: Option<&'a ProgressCallback>,
+        cancel: Option<&'a CancelToken>,
+        phase: Phase,
+        total: u64,
+    ) -> Self {
+        Tracker {
+            callback,
+            cancel,
+            phase,
+            total,
+            completed: Mutex::new(0),
+        }
+    }
+
+    /// Reports that `amount` more work was done.
+    pub(crate) fn advance(&self, amount: u64) {
+        let Some(callback) = self.callback else {
+            return;
+        };
+        if amount == 0 {
+            return;
+        }
+
+        // Reporting under the lock keeps the values in order across threads
+        let mut completed = self
+            .completed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *completed += amount;
+        callback(Progress {
+            phase: self.phase,
+            completed: *completed,
+            total: self.total,
+        });
+    }
+
+    /// Returns whether the operation was cancelled, so that the remaining parts can be
+    /// skipped.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Fails with `StegError::Cancelled` if the operation was cancelled.
+    pub(crate) fn check(&self) -> StegResult<()> {
+        check_cancelled(self.cancel)
+    }
+}
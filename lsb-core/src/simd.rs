@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::consts::BITS_PER_BYTE;
+
+/// Whether the SIMD path may be used at all.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables the SIMD path of extraction.
+///
+/// It is enabled by default, and only used when the CPU supports it (AVX2 on x86-64),
+/// with `lsbs` set to 1, for reads of less than 1 MiB at a time. Its output is identical to the scalar code, so this is only
+/// useful for debugging and for comparing the two.
+pub fn set_simd_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether the SIMD path is enabled and supported by the CPU.
+pub fn simd_enabled() -> bool {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// Reads one byte into `output` for every 8 `positions` of 1-bit samples, most
+/// significant bit first.
+///
+/// Returns `false` without reading anything if the SIMD path is not available.
+pub(crate) fn gather_lsb1(samples: &[u8], positions: &[u64], output: &mut [u8]) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if simd_enabled() && i32::try_from(samples.len()).is_ok() {
+        // SAFETY: AVX2 support was just detected
+        unsafe { gather_lsb1_avx2(samples, positions, output) };
+        return true;
+    }
+
+    let _ = (samples, positions, output);
+    false
+}
+
+/// Gathers 8 samples at a time, moving their lowest bits into a byte.
+///
+/// Each gather loads 4 bytes, so positions among the last 3 samples are read one by one.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn gather_lsb1_avx2(samples: &[u8], positions: &[u64], output: &mut [u8]) {
+    let limit = samples.len().saturating_sub(4) as u64;
+
+    for (byte, positions) in output.iter_mut().zip(positions.chunks_exact(BITS_PER_BYTE)) {
+        if positions.iter().any(|&position| position > limit) {
+            *byte = positions.iter().fold(0, |byte, &position| {
+                (byte << 1) | (samples[position as usize] & 1)
+            });
+            continue;
+        }
+
+        let indices: [i32; 8] = std::array::from_fn(|lane| positions[lane] as i32);
+        // SAFETY: every index is within the samples, including the 3 bytes after it
+        let gathered = unsafe {
+            let indices = _mm256_loadu_si256(indices.as_ptr().cast());
+            _mm256_i32gather_epi32::<1>(samples.as_ptr().cast(), indices)
+        };
+
+        // Lane 0 holds the most significant bit, but ends up in the lowest bit of the mask
+        let mask = _mm256_movemask_ps(_mm256_castsi256_ps(_mm256_slli_epi32::<31>(gathered)));
+        *byte = (mask as u8).reverse_bits();
+    }
+}
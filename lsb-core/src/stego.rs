@@ -0,0 +1,335 @@
+use std::io::{Read, Seek, Write};
+
+use tracing::debug;
+
+use super::{
+    capacity::usable_bytes,
+    embed::{
+        EmbedOptions, EmbedPlan, LsbSelection, OverwritePolicy, ResizePolicy, embed_inner,
+        embed_inner_to, embed_reader_inner, plan_for,
+    },
+    error::*,
+    extract::{ExtractInfo, ExtractOptions, extract_carrier, extract_carrier_to_writer},
+    format::Format,
+    hash::Hash,
+    image::*,
+    metadata::Metadata,
+    order::KeyedPermutation,
+};
+
+/// A decoded container, for running several operations on it without decoding it again.
+///
+/// The image keeps its original color type and sample depth, so floating-point
+/// containers can still be embedded into as HDR or OpenEXR.
+#[derive(Debug, Clone)]
+pub struct StegoContainer {
+    image: DynamicImage,
+    format: Option<Format>,
+    metadata: Metadata,
+}
+
+impl StegoContainer {
+    /// Decodes a container, detecting its format from its contents.
+    ///
+    /// # Errors
+    ///
+    /// * Errors from the `image` crate during format detection or image decoding.
+    pub fn new(container: &[u8]) -> StegResult<Self> {
+        let (image, format) = decode_detected(container)?;
+
+        Ok(StegoContainer {
+            image,
+            format,
+            metadata: Metadata::read(container, format),
+        })
+    }
+
+    /// Decodes a container known to be in `format`.
+    ///
+    /// Unlike `new`, this works for formats that cannot be detected from their contents,
+    /// such as TGA.
+    ///
+    /// # Errors
+    ///
+    /// * Errors from the `image` crate during image decoding.
+    pub fn with_format(container: &[u8], format: Format) -> StegResult<Self> {
+        let image = decode_image_as(container, format)?;
+
+        Ok(StegoContainer {
+            image,
+            format: Some(format),
+            metadata: Metadata::read(container, Some(format)),
+        })
+    }
+
+    /// Wraps an already decoded image. Data is extracted from it as from an 8-bit image.
+    pub fn from_image(image: DynamicImage) -> Self {
+        StegoContainer {
+            image,
+            format: None,
+            metadata: Metadata::default(),
+        }
+    }
+
+    /// The decoded image.
+    pub fn image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// The format the container was decoded from, if known.
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+
+    /// The width and height of the container, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.image.width(), self.image.height())
+    }
+
+    /// Builds a carrier whose kind follows `output` when given (embedding), or the format
+    /// of the container otherwise (extracting).
+    pub(crate) fn carrier(&self, output: Option<Format>) -> Carrier {
+        let kind = output
+            .or(self.format)
+            .map_or(CarrierKind::Rgb8, CarrierKind::for_format);
+        Carrier::new(self.image.clone(), kind)
+    }
+
+    /// The metadata of the container, which outputs keep.
+    pub(crate) fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+}
+
+/// A steganography session, bundling the parameters of every operation.
+///
+/// The free functions such as `embed_with` and `extract` are thin wrappers around a
+/// `Stego`. Using one directly with a `StegoContainer` avoids passing the parameters
+/// around and decoding the same container for every step of a workflow.
+#[derive(Debug, Clone, Default)]
+pub struct Stego {
+    options: EmbedOptions,
+}
+
+impl Stego {
+    /// Starts building a session from the default options.
+    pub fn builder() -> StegoBuilder {
+        StegoBuilder::default()
+    }
+
+    /// The parameters of the session.
+    pub fn options(&self) -> &EmbedOptions {
+        &self.options
+    }
+
+    /// Calculates how many bytes of data without an extension fit into a container.
+    ///
+    /// With `LsbSelection::Auto`, this is the capacity at the most `lsbs` the output
+    /// format supports.
+    ///
+    /// # Errors
+    ///
+    /// * `StegError::InvalidLsbValue`: If `lsbs` is not between 1 and 8.
+    /// * `StegError::UnsupportedFormat`: If `lsbs` is `LsbSelection::Auto`, `format` is unset
+    ///   and the container is in a lossy format.
+    /// * `StegError::InsufficientCapacity`: If the container cannot hold even the metadata
+    ///   of an empty payload.
+    /// * `StegError::CalculationOverflow`: If the capacity overflows `usize`.
+    pub fn capacity(&self, container: &StegoContainer) -> StegResult<usize> {
+        let lsbs = match self.options.lsbs {
+            LsbSelection::Fixed(lsbs) => lsbs,
+            LsbSelection::Auto => {
+                let options = self.options.for_container(container.format())?;
+                CarrierKind::for_format(options.output_format()).max_lsbs()
+            }
+        };
+        let (width, height) = container.dimensions();
+
+        usable_bytes(width, height, lsbs, self.options.hash, 0)
+    }
+
+    /// Checks whether an embedding would succeed, and computes its parameters, like
+    /// `plan_embed`.
+    ///
+    /// # Errors
+    ///
+    /// See `embed_with`.
+    pub fn plan(
+        &self,
+        input_len: usize,
+        extension: &str,
+        container: &StegoContainer,
+    ) -> StegResult<EmbedPlan> {
+        plan_for(input_len, extension, container, &self.options)
+    }
+
+    /// Embeds data into a container, like `embed_with`.
+    ///
+    /// # Errors
+    ///
+    /// See `embed_with`.
+    pub fn embed(
+        &self,
+        input: &[u8],
+        extension: &str,
+        container: &StegoContainer,
+    ) -> StegResult<Vec<u8>> {
+        let order = KeyedPermutation::new(self.options.seed);
+
+        embed_inner(input, extension, container, &self.options, &order).map(|(output, _)| output)
+    }
+
+    /// Embeds data into a container, writing the output image into `writer`, like
+    /// `embed_to_writer`.
+    ///
+    /// # Errors
+    ///
+    /// See `embed_to_writer`.
+    pub fn embed_to_writer<W: Write + Seek>(
+        &self,
+        input: &[u8],
+        extension: &str,
+        container: &StegoContainer,
+        writer: W,
+    ) -> StegResult<()> {
+        let order = KeyedPermutation::new(self.options.seed);
+
+        embed_inner_to(input, extension, container, &self.options, &order, writer)
+    }
+
+    /// Embeds data streamed from `input` into a container, writing the output image into
+    /// `writer`, like `embed_from_reader`.
+    ///
+    /// # Errors
+    ///
+    /// See `embed_from_reader`.
+    pub fn embed_from_reader<R: Read, W: Write + Seek>(
+        &self,
+        input: R,
+        input_len: u64,
+        extension: &str,
+        container: &StegoContainer,
+        writer: W,
+    ) -> StegResult<()> {
+        let order = KeyedPermutation::new(self.options.seed);
+
+        embed_reader_inner(
+            input,
+            input_len,
+            extension,
+            container,
+            &self.options,
+            &order,
+            writer,
+        )
+        .map(|_| ())
+    }
+
+    /// Extracts the data and extension embedded in a container, like `extract`.
+    ///
+    /// With `LsbSelection::Auto`, every `lsbs` value is tried like in `extract_auto`.
+    ///
+    /// # Errors
+    ///
+    /// See `extract`.
+    pub fn extract(&self, container: &StegoContainer) -> StegResult<(Vec<u8>, String)> {
+        let carrier = container.carrier(None);
+        debug!("Carrier: {:?}", carrier.kind());
+
+        extract_carrier(&carrier, &ExtractOptions::from(&self.options))
+            .map(|extracted| (extracted.data, extracted.extension))
+    }
+
+    /// Streams the data embedded in a container into `writer`, like `extract_to_writer`.
+    ///
+    /// With `LsbSelection::Auto`, the payload is first extracted as a whole to find its
+    /// `lsbs`, so memory use grows with its size.
+    ///
+    /// # Errors
+    ///
+    /// See `extract_to_writer`.
+    pub fn extract_to_writer<W: Write>(
+        &self,
+        container: &StegoContainer,
+        writer: W,
+    ) -> StegResult<ExtractInfo> {
+        let carrier = container.carrier(None);
+        debug!("Carrier: {:?}", carrier.kind());
+
+        extract_carrier_to_writer(&carrier, writer, &ExtractOptions::from(&self.options))
+    }
+}
+
+impl From<EmbedOptions> for Stego {
+    fn from(options: EmbedOptions) -> Self {
+        Stego { options }
+    }
+}
+
+/// Builds a `Stego` session, starting from the default options.
+#[derive(Debug, Clone, Default)]
+pub struct StegoBuilder {
+    options: EmbedOptions,
+}
+
+impl StegoBuilder {
+    /// Sets how many least significant bits per channel are used.
+    pub fn lsbs(mut self, lsbs: impl Into<LsbSelection>) -> Self {
+        self.options = self.options.lsbs(lsbs);
+        self
+    }
+
+    /// Sets the hashing algorithm of the checksum.
+    pub fn hash(mut self, hash: Hash) -> Self {
+        self.options = self.options.hash(hash);
+        self
+    }
+
+    /// Sets the seed of the pixel order.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options = self.options.seed(seed);
+        self
+    }
+
+    /// Sets the format of the output image, or `None` for the format of the container.
+    pub fn format(mut self, format: impl Into<Option<Format>>) -> Self {
+        self.options = self.options.format(format);
+        self
+    }
+
+    /// Sets how the output image is encoded.
+    pub fn output(mut self, output: OutputOptions) -> Self {
+        self.options = self.options.output(output);
+        self
+    }
+
+    /// Sets what to do when the container already holds a payload.
+    pub fn overwrite_policy(mut self, overwrite_policy: OverwritePolicy) -> Self {
+        self.options = self.options.overwrite_policy(overwrite_policy);
+        self
+    }
+
+    /// Sets what to do when the payload does not fit into the container.
+    pub fn resize_policy(mut self, resize_policy: ResizePolicy) -> Self {
+        self.options = self.options.resize_policy(resize_policy);
+        self
+    }
+
+    /// Sets whether the output is extracted again before it is returned.
+    pub fn verify_after_embed(mut self, verify_after_embed: bool) -> Self {
+        self.options = self.options.verify_after_embed(verify_after_embed);
+        self
+    }
+
+    /// Sets the number of threads of the pool created for every operation, or `None` to
+    /// use the pool of the calling thread.
+    pub fn threads(mut self, threads: impl Into<Option<usize>>) -> Self {
+        self.options = self.options.threads(threads);
+        self
+    }
+
+    /// Finishes the session.
+    pub fn build(self) -> Stego {
+        Stego::from(self.options)
+    }
+}
@@ -0,0 +1,59 @@
+use rand::prelude::*;
+use tracing::debug;
+
+use super::{
+    capacity::check_lsbs, consts::*, error::*, format::Format, image::*, metadata::Metadata, par::*,
+};
+
+/// Destroys any LSB payload in an image by randomizing its low bits.
+///
+/// Every channel of every pixel gets its `lsbs` least significant bits replaced
+/// with fresh random bits, so data hidden there (by this crate or any other LSB
+/// tool) can no longer be recovered, while the visible image is left untouched.
+/// The randomness is not seeded, so stripping the same image twice gives
+/// different results.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the image data to strip.
+/// * `lsbs`: The number of least significant bits to randomize per color channel (1-8,
+///   or 1-7 for HDR output).
+/// * `format`: The `Format` of the output image. Must be a lossless format.
+///
+/// # Returns
+///
+/// A `StegResult` containing a `Vec<u8>` of the stripped image data if successful,
+/// or a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// * `StegError::UnsupportedFormat`: If the specified `format` is not lossless, or cannot
+///   store an image as large as the container.
+/// * `StegError::InvalidLsbValue`: If `lsbs` is outside the range supported by `format`.
+/// * Errors from the `image` crate during image decoding or encoding.
+pub fn strip(container: &[u8], lsbs: usize, format: Format) -> StegResult<Vec<u8>> {
+    check_output_format(format)?;
+
+    let max_lsbs = CarrierKind::for_format(format).max_lsbs();
+    check_lsbs(lsbs, max_lsbs)?;
+
+    let mut carrier = decode(container, Some(format))?;
+    check_output_dimensions(format, carrier.plane.dimensions())?;
+
+    let mask = (u16::MAX << lsbs) as u8;
+    let chunk_size = chunk_size(carrier.plane.len());
+    carrier.plane.par_chunks_mut(chunk_size).for_each(|chunk| {
+        let mut rng = rand::rng();
+        for sample in chunk {
+            *sample = (*sample & mask) | (rng.random::<u8>() & !mask);
+        }
+    });
+    debug!("Randomized {} bits per sample", lsbs);
+
+    encode(
+        carrier,
+        format,
+        &OutputOptions::default(),
+        &Metadata::default(),
+    )
+}
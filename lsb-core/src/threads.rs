@@ -0,0 +1,75 @@
+use super::error::*;
+
+#[cfg(feature = "parallel")]
+pub(crate) use pool::{parallel, with_threads};
+
+/// Runs an operation on the calling thread, the only one without the `parallel` feature.
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn with_threads<T>(
+    _threads: Option<usize>,
+    operation: impl FnOnce() -> StegResult<T>,
+) -> StegResult<T> {
+    operation()
+}
+
+/// Runs a parallel section on the calling thread, the only one without the `parallel`
+/// feature.
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn parallel<T>(section: impl FnOnce() -> T) -> T {
+    section()
+}
+
+#[cfg(feature = "parallel")]
+mod pool {
+    use std::{cell::RefCell, rc::Rc};
+
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+
+    use super::*;
+
+    thread_local! {
+        /// The pool requested by the operation running on this thread, if any.
+        static POOL: RefCell<Option<Rc<ThreadPool>>> = const { RefCell::new(None) };
+    }
+
+    /// Runs an operation whose parallel sections run in a new pool of `threads` threads.
+    ///
+    /// The operation itself runs on the calling thread, so it may use readers and writers
+    /// that cannot be sent to another thread. With `None`, the parallel sections run in the
+    /// pool of the calling thread instead: the global rayon pool, or the pool whose
+    /// `ThreadPool::install` the operation is called from.
+    pub(crate) fn with_threads<T>(
+        threads: Option<usize>,
+        operation: impl FnOnce() -> StegResult<T>,
+    ) -> StegResult<T> {
+        let Some(threads) = threads else {
+            return operation();
+        };
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("lsb-core-{}", index))
+            .build()
+            .map_err(|err| StegError::ThreadPool(err.to_string()))?;
+
+        let _restore = Restore(POOL.replace(Some(Rc::new(pool))));
+        operation()
+    }
+
+    /// Runs a parallel section in the pool requested by the current operation, if any.
+    pub(crate) fn parallel<T: Send>(section: impl FnOnce() -> T + Send) -> T {
+        match POOL.with_borrow(Option::clone) {
+            Some(pool) => pool.install(section),
+            None => section(),
+        }
+    }
+
+    /// Restores the pool of an enclosing operation, even when the inner one panics.
+    struct Restore(Option<Rc<ThreadPool>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            POOL.set(self.0.take());
+        }
+    }
+}
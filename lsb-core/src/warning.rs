@@ -0,0 +1,65 @@
+use core::fmt;
+
+use strum::IntoStaticStr;
+
+use super::format::Format;
+
+/// Something about a successful embedding or extraction that the caller may want to
+/// know about, returned along with its result.
+#[derive(Debug, Clone, PartialEq, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+#[non_exhaustive]
+pub enum Warning {
+    /// The container already held a payload, which the embedding overwrote, as allowed
+    /// by `OverwritePolicy::Warn`.
+    PayloadOverwritten(String),
+    /// The container was upscaled to fit the payload, as allowed by
+    /// `ResizePolicy::UpscaleToFit`.
+    Upscaled { from: (u32, u32), to: (u32, u32) },
+    /// The payload fills more than half of the capacity, which makes it easier to detect
+    /// by steganalysis.
+    HighUtilization { capacity_used: f32 },
+    /// The container has an alpha channel, which was dropped, as only the color channels
+    /// carry data.
+    AlphaDropped,
+    /// The data had no extension, so this one was embedded in its place.
+    ExtensionDefaulted(String),
+    /// The output is in another format than the container.
+    FormatChanged { from: Format, to: Format },
+}
+
+impl Warning {
+    /// Returns a stable identifier of the warning, such as `"high_utilization"`.
+    pub fn code(&self) -> &'static str {
+        self.into()
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::PayloadOverwritten(message) => write!(f, "{}, overwritten", message),
+            Warning::Upscaled { from, to } => write!(
+                f,
+                "The container was upscaled from {}x{} to {}x{} to fit the payload",
+                from.0, from.1, to.0, to.1
+            ),
+            Warning::HighUtilization { capacity_used } => write!(
+                f,
+                "The payload uses {:.1}% of the capacity, which makes it easier to detect",
+                capacity_used * 100.0
+            ),
+            Warning::AlphaDropped => write!(f, "The alpha channel of the container was dropped"),
+            Warning::ExtensionDefaulted(extension) => {
+                write!(
+                    f,
+                    "The data has no extension, so {:?} was embedded",
+                    extension
+                )
+            }
+            Warning::FormatChanged { from, to } => {
+                write!(f, "The {:?} container was written as {:?}", from, to)
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use rand::{prelude::*, seq::index::sample};
+use rand_pcg::Pcg64Mcg;
+use tracing::debug;
+
+use super::{consts::*, error::*, format::Format, hash::*, image::*, metadata::Metadata, par::*};
+
+/// The side length, in pixels, of the square blocks a watermark is tiled into.
+pub const WATERMARK_BLOCK_SIZE: u32 = 64;
+/// The maximum length, in bytes, of a watermark message.
+pub const MAX_WATERMARK_LEN: usize = 64;
+
+/// The number of checksum bytes stored with every copy of the message.
+const CHECKSUM_LEN: usize = 4;
+/// The number of embeddable bits in one block, using one LSB per channel.
+const BLOCK_CAPACITY_BITS: usize =
+    (WATERMARK_BLOCK_SIZE * WATERMARK_BLOCK_SIZE) as usize * EMBEDDABLE_CHANNELS;
+/// The number of bits occupied by the longest possible copy of the message.
+const MAX_COPY_BITS: usize = (1 + MAX_WATERMARK_LEN + CHECKSUM_LEN) * BITS_PER_BYTE;
+
+/// A watermark recovered by `extract_watermark`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    /// The message found in the majority of blocks.
+    pub message: Vec<u8>,
+    /// The fraction of complete blocks that agree on `message`, between 0 and 1.
+    pub confidence: f64,
+}
+
+/// Embeds a short message repeatedly into every block of a container image.
+///
+/// Unlike `embed`, which spreads one large payload over the whole image, the
+/// watermark places a full copy of `message` into each `WATERMARK_BLOCK_SIZE`
+/// square block, at pseudo-random positions that are the same for every block.
+/// This trades capacity for robustness: any complete block that survives a crop
+/// is enough to recover the message.
+///
+/// # Arguments
+///
+/// * `message`: The message to embed, at most `MAX_WATERMARK_LEN` bytes.
+/// * `container`: A slice of bytes representing the container image data.
+/// * `seed`: A 64-bit seed for the pseudo-random number generator that determines bit positions.
+/// * `format`: The `Format` of the output image. Must be a lossless format.
+///
+/// # Returns
+///
+/// A `StegResult` containing a `Vec<u8>` of the watermarked image data if successful,
+/// or a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// * `StegError::WatermarkTooLong`: If `message` is longer than `MAX_WATERMARK_LEN`.
+/// * `StegError::UnsupportedFormat`: If the specified `format` is not lossless, or cannot
+///   store an image as large as the container.
+/// * `StegError::InsufficientCapacity`: If the container is smaller than one block.
+/// * Errors from the `image` crate during image decoding or encoding.
+pub fn embed_watermark(
+    message: &[u8],
+    container: &[u8],
+    seed: u64,
+    format: Format,
+) -> StegResult<Vec<u8>> {
+    if message.len() > MAX_WATERMARK_LEN {
+        return Err(StegError::WatermarkTooLong {
+            len: message.len(),
+            max: MAX_WATERMARK_LEN,
+        });
+    }
+
+    check_output_format(format)?;
+
+    let mut carrier = decode(container, Some(format))?;
+    check_output_dimensions(format, carrier.plane.dimensions())?;
+    let (width, height) = carrier.plane.dimensions();
+    check_holds_block(width, height)?;
+
+    let copy = build_copy(message);
+    let order = block_order(seed);
+
+    for block_y in 0..height / WATERMARK_BLOCK_SIZE {
+        for block_x in 0..width / WATERMARK_BLOCK_SIZE {
+            let origin = (
+                block_x * WATERMARK_BLOCK_SIZE,
+                block_y * WATERMARK_BLOCK_SIZE,
+            );
+
+            for (bit_index_seq, &bit_index) in
+                order.iter().take(copy.len() * BITS_PER_BYTE).enumerate()
+            {
+                let byte = copy[bit_index_seq / BITS_PER_BYTE];
+                let bit = (byte >> (BITS_PER_BYTE - 1 - bit_index_seq % BITS_PER_BYTE)) & 1;
+
+                let (x, y, channel) = locate(origin, bit_index);
+                let pixel = carrier.plane.get_pixel_mut(x, y);
+                pixel[channel] = (pixel[channel] & !1) | bit;
+            }
+        }
+    }
+
+    debug!(
+        "Watermarked {} blocks with {} bytes",
+        (width / WATERMARK_BLOCK_SIZE) * (height / WATERMARK_BLOCK_SIZE),
+        message.len()
+    );
+
+    encode(
+        carrier,
+        format,
+        &OutputOptions::default(),
+        &Metadata::default(),
+    )
+}
+
+/// Recovers a watermark embedded with `embed_watermark`.
+///
+/// The block grid of a cropped image no longer starts at its top-left corner, so
+/// every alignment within a block is tried until one yields a valid copy. All
+/// complete blocks at that alignment are then decoded, and the message found in
+/// the majority of them is returned along with the fraction that agreed.
+///
+/// # Arguments
+///
+/// * `container`: A slice of bytes representing the watermarked image data.
+/// * `seed`: The 64-bit seed used when embedding the watermark.
+///
+/// # Returns
+///
+/// A `StegResult` containing the recovered `Watermark` if successful,
+/// or a `StegError` if an error occurs.
+///
+/// # Errors
+///
+/// * `StegError::InsufficientCapacity`: If the image is smaller than one block.
+/// * `StegError::WatermarkNotFound`: If no block holds a valid copy of a watermark.
+/// * Errors from the `image` crate during image decoding.
+pub fn extract_watermark(container: &[u8], seed: u64) -> StegResult<Watermark> {
+    let carrier = decode(container, None)?;
+    let image = &carrier.plane;
+    let (width, height) = image.dimensions();
+    check_holds_block(width, height)?;
+
+    let order = block_order(seed);
+
+    let offset = (0..WATERMARK_BLOCK_SIZE * WATERMARK_BLOCK_SIZE)
+        .into_par_iter()
+        .map(|i| (i % WATERMARK_BLOCK_SIZE, i / WATERMARK_BLOCK_SIZE))
+        .find_first(|&offset| {
+            block_origins(width, height, offset)
+                .any(|origin| read_copy(image, &order, origin).is_some())
+        })
+        .ok_or_else(|| StegError::WatermarkNotFound)?;
+    debug!("Watermark grid offset: {:?}", offset);
+
+    let origins = block_origins(width, height, offset).collect::<Vec<_>>();
+    let mut votes = HashMap::new();
+    for message in origins
+        .par_iter()
+        .filter_map(|&origin| read_copy(image, &order, origin))
+        .collect::<Vec<_>>()
+    {
+        *votes.entry(message).or_insert(0usize) += 1;
+    }
+
+    let (message, count) = votes
+        .into_iter()
+        .max_by(|(a, x), (b, y)| x.cmp(y).then_with(|| b.cmp(a)))
+        .ok_or_else(|| StegError::WatermarkNotFound)?;
+    debug!("Watermark found in {} of {} blocks", count, origins.len());
+
+    Ok(Watermark {
+        message,
+        confidence: count as f64 / origins.len() as f64,
+    })
+}
+
+/// Rejects images smaller than a block, which cannot hold any copy of a watermark.
+fn check_holds_block(width: u32, height: u32) -> StegResult<()> {
+    if width < WATERMARK_BLOCK_SIZE || height < WATERMARK_BLOCK_SIZE {
+        // The pixels to grow the image by until it holds a block
+        let (block_width, block_height) = (
+            width.max(WATERMARK_BLOCK_SIZE) as u64,
+            height.max(WATERMARK_BLOCK_SIZE) as u64,
+        );
+        return Err(StegError::InsufficientCapacity {
+            required_bits: BLOCK_CAPACITY_BITS as u64,
+            available_bits: 0,
+            missing_pixels: block_width * block_height - width as u64 * height as u64,
+        });
+    }
+
+    Ok(())
+}
+
+/// Builds one copy of the message: its length, the message, and a truncated checksum.
+fn build_copy(message: &[u8]) -> Vec<u8> {
+    let mut hasher = select_hasher(Hash::Blake3);
+    let checksum = use_hasher(&mut *hasher, message);
+
+    [&[message.len() as u8], message, &checksum[..CHECKSUM_LEN]].concat()
+}
+
+/// Generates the bit positions within a block, shared by every block.
+fn block_order(seed: u64) -> Vec<usize> {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+
+    // The `amount` parameter must stay fixed for the positions to be reproducible
+    sample(&mut rng, BLOCK_CAPACITY_BITS, MAX_COPY_BITS).into_vec()
+}
+
+/// Returns the top-left corners of all complete blocks of the grid starting at `offset`.
+fn block_origins(
+    width: u32,
+    height: u32,
+    (offset_x, offset_y): (u32, u32),
+) -> impl Iterator<Item = (u32, u32)> {
+    let columns = width.saturating_sub(offset_x) / WATERMARK_BLOCK_SIZE;
+    let rows = height.saturating_sub(offset_y) / WATERMARK_BLOCK_SIZE;
+
+    (0..rows).flat_map(move |row| {
+        (0..columns).map(move |column| {
+            (
+                offset_x + column * WATERMARK_BLOCK_SIZE,
+                offset_y + row * WATERMARK_BLOCK_SIZE,
+            )
+        })
+    })
+}
+
+/// Maps a bit position within a block to the pixel and channel it lives in.
+fn locate((origin_x, origin_y): (u32, u32), bit_index: usize) -> (u32, u32, usize) {
+    let pixel_index = (bit_index / EMBEDDABLE_CHANNELS) as u32;
+
+    (
+        origin_x + pixel_index % WATERMARK_BLOCK_SIZE,
+        origin_y + pixel_index / WATERMARK_BLOCK_SIZE,
+        bit_index % EMBEDDABLE_CHANNELS,
+    )
+}
+
+/// Reads the bytes at the given positions of a block.
+fn read_block_bytes(
+    image: &RgbImage,
+    order: &[usize],
+    origin: (u32, u32),
+    start: usize,
+    length: usize,
+) -> Vec<u8> {
+    order[start * BITS_PER_BYTE..(start + length) * BITS_PER_BYTE]
+        .chunks(BITS_PER_BYTE)
+        .map(|bits| {
+            bits.iter().fold(0, |byte, &bit_index| {
+                let (x, y, channel) = locate(origin, bit_index);
+                (byte << 1) | (image.get_pixel(x, y)[channel] & 1)
+            })
+        })
+        .collect()
+}
+
+/// Reads and validates the copy of the message in the block at `origin`.
+fn read_copy(image: &RgbImage, order: &[usize], origin: (u32, u32)) -> Option<Vec<u8>> {
+    let length = read_block_bytes(image, order, origin, 0, 1)[0] as usize;
+    if length > MAX_WATERMARK_LEN {
+        return None;
+    }
+
+    let rest = read_block_bytes(image, order, origin, 1, length + CHECKSUM_LEN);
+    let (message, checksum) = rest.split_at(length);
+
+    let mut hasher = select_hasher(Hash::Blake3);
+    let expected = use_hasher(&mut *hasher, message);
+
+    (expected[..CHECKSUM_LEN] == *checksum).then(|| message.to_vec())
+}
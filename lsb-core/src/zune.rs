@@ -0,0 +1,74 @@
+use image::DynamicImage;
+
+use super::error::*;
+
+/// The start of image marker every JPEG file begins with, followed by the first byte of
+/// the next marker.
+#[cfg(feature = "zune")]
+const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8, 0xff];
+
+/// Decodes a JPEG container with `zune-jpeg` directly, without going through the
+/// `image` crate, which uses the same decoder but reads it through more layers.
+///
+/// Only RGB and grayscale outputs are decoded here, as those are the color types
+/// `image` keeps as they are. Other containers, such as CMYK JPEGs, return `None`, to be
+/// decoded by `image` like any other format, so the pixels are always the same as
+/// without the `zune` feature.
+#[cfg(feature = "zune")]
+pub(crate) fn decode_zune(container: &[u8]) -> StegResult<Option<DynamicImage>> {
+    use image::{
+        GrayImage, ImageError, RgbImage,
+        error::{DecodingError, ImageFormatHint},
+    };
+    use zune_jpeg::{
+        JpegDecoder,
+        zune_core::{colorspace::ColorSpace, options::DecoderOptions},
+    };
+
+    if !container.starts_with(JPEG_SIGNATURE) {
+        return Ok(None);
+    }
+
+    let decoding_error = |err: zune_jpeg::errors::DecodeErrors| {
+        StegError::ImageProcessing(ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(image::ImageFormat::Jpeg),
+            err,
+        )))
+    };
+
+    // The options `image` decodes with, which does not limit the dimensions itself
+    let options = DecoderOptions::default()
+        .set_strict_mode(false)
+        .set_max_width(usize::MAX)
+        .set_max_height(usize::MAX);
+    let mut decoder = JpegDecoder::new_with_options(container, options);
+    decoder.decode_headers().map_err(decoding_error)?;
+    let (width, height) = decoder.dimensions().expect("the headers were decoded");
+    let (width, height) = (width as u32, height as u32);
+
+    let image = match decoder.get_output_colorspace() {
+        Some(ColorSpace::RGB) => {
+            let samples = decoder.decode().map_err(decoding_error)?;
+            RgbImage::from_raw(width, height, samples).map(DynamicImage::ImageRgb8)
+        }
+        Some(ColorSpace::Luma) => {
+            let samples = decoder.decode().map_err(decoding_error)?;
+            GrayImage::from_raw(width, height, samples).map(DynamicImage::ImageLuma8)
+        }
+        _ => return Ok(None),
+    };
+
+    image.map(Some).ok_or_else(|| {
+        StegError::ImageProcessing(ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(image::ImageFormat::Jpeg),
+            "The decoded samples do not match the dimensions of the image",
+        )))
+    })
+}
+
+/// Leaves every container to the `image` crate, as `zune-jpeg` is only called directly
+/// with the `zune` feature.
+#[cfg(not(feature = "zune"))]
+pub(crate) fn decode_zune(_container: &[u8]) -> StegResult<Option<DynamicImage>> {
+    Ok(None)
+}
@@ -0,0 +1,114 @@
+// The async wrappers only exist with the `async` feature
+#![cfg(feature = "async")]
+
+use lsb_core::{hash::Hash, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Awaited, not blocked on.";
+
+#[tokio::test]
+async fn test_async_matches_sync() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 48, Format::Png);
+    let options = EmbedOptions::new().lsbs(2).hash(Hash::Sha256).seed(5);
+
+    let embedded = aio::embed(
+        INPUT.to_vec(),
+        "txt".to_string(),
+        container.clone(),
+        options.clone(),
+    )
+    .await?;
+    assert_eq!(embedded, embed_with(INPUT, "txt", &container, &options)?);
+
+    let extracted = aio::extract(embedded.image.clone(), ExtractOptions::from(&options)).await?;
+    assert_eq!(
+        extracted,
+        extract_with(&embedded.image, &ExtractOptions::from(&options))?
+    );
+    assert_eq!(extracted.data, INPUT);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_async_concurrent() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 48, Format::Png);
+
+    let tasks = (0..8u8)
+        .map(|i| {
+            let container = container.clone();
+            tokio::spawn(async move {
+                let options = EmbedOptions::new().seed(i as u64);
+                let embedded =
+                    aio::embed(vec![i; 100], "bin".to_string(), container, options.clone())
+                        .await?
+                        .image;
+                aio::extract(embedded, ExtractOptions::from(&options)).await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (i, task) in tasks.into_iter().enumerate() {
+        assert_eq!(task.await??.data, vec![i as u8; 100]);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_errors() {
+    let result = aio::embed(
+        vec![0; 1 << 20],
+        "bin".to_string(),
+        test_util::encoded_container(16, 16, Format::Png),
+        EmbedOptions::new(),
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(error::StegError::InsufficientCapacity { .. })
+    ));
+
+    let result = aio::extract(
+        test_util::encoded_container(16, 16, Format::Png),
+        ExtractOptions::new(),
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_async_cancel_on_drop() {
+    let cancel = CancelToken::new();
+    let (started, waiting) = std::sync::mpsc::channel();
+    let started = std::sync::Mutex::new(started);
+
+    // The embedding waits in its first report until it is cancelled
+    let token = cancel.clone();
+    let options = EmbedOptions::new()
+        .lsbs(4)
+        .cancel(cancel.clone())
+        .progress(move |_| {
+            let _ = started.lock().unwrap().send(());
+            while !token.is_cancelled() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        });
+    let task = tokio::spawn(aio::embed(
+        vec![0; 100_000],
+        "bin".to_string(),
+        test_util::encoded_container(512, 512, Format::Png),
+        options,
+    ));
+
+    tokio::task::spawn_blocking(move || waiting.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!cancel.is_cancelled());
+
+    task.abort();
+    assert!(task.await.unwrap_err().is_cancelled());
+    assert!(cancel.is_cancelled());
+}
@@ -0,0 +1,177 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, *};
+
+/// A horizontal gradient in red and a vertical one in green.
+fn gradient() -> Vec<u8> {
+    let image = ::image::RgbImage::from_fn(64, 32, |x, y| ::image::Rgb([x as u8, y as u8, 0]));
+
+    let mut output = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut output, ::image::ImageFormat::Png)
+        .unwrap();
+    output.into_inner()
+}
+
+#[test]
+fn test_bit_plane_of_gradient() -> Result<(), Box<dyn std::error::Error>> {
+    let container = gradient();
+
+    let red_lsb = bit_plane(&container, 0, 0)?;
+    assert_eq!(red_lsb.dimensions(), (64, 32));
+    for (x, _, pixel) in red_lsb.enumerate_pixels() {
+        assert_eq!(pixel.0, [if x % 2 == 1 { 255 } else { 0 }]);
+    }
+
+    let green_bit_4 = bit_plane(&container, 1, 4)?;
+    for (_, y, pixel) in green_bit_4.enumerate_pixels() {
+        assert_eq!(pixel.0, [if y & 0b10000 != 0 { 255 } else { 0 }]);
+    }
+
+    assert!(bit_plane(&container, 2, 7)?.iter().all(|&p| p == 0));
+
+    Ok(())
+}
+
+#[test]
+fn test_bit_plane_bounds() {
+    let container = gradient();
+
+    assert!(matches!(
+        bit_plane(&container, 3, 0),
+        Err(StegError::InvalidBitPlane { .. })
+    ));
+    assert!(matches!(
+        bit_plane(&container, 0, 8),
+        Err(StegError::InvalidBitPlane { .. })
+    ));
+}
+
+#[test]
+fn test_chi_square_clean_and_randomized() -> Result<(), Box<dyn std::error::Error>> {
+    let clean = ::image::load_from_memory(include_bytes!("../../data/container.webp"))?;
+    let mut output = std::io::Cursor::new(Vec::new());
+    clean
+        .to_rgb8()
+        .write_to(&mut output, ::image::ImageFormat::Png)?;
+    let clean = output.into_inner();
+    let randomized = strip(&clean, 1, Format::Png)?;
+
+    let before = chi_square_analysis(&clean, 64)?;
+    let after = chi_square_analysis(&randomized, 64)?;
+
+    assert_eq!(before.len(), after.len());
+    assert!(before.iter().all(|&p| p < 0.05), "{:?}", before);
+    assert!(after.iter().all(|&p| p > 0.9), "{:?}", after);
+
+    assert!(matches!(
+        chi_square_analysis(&clean, 0),
+        Err(StegError::InvalidBlockSize { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_embedding_rate_grows_with_payload() -> Result<(), Box<dyn std::error::Error>> {
+    use rand::{RngCore, SeedableRng};
+
+    let clean = ::image::load_from_memory(include_bytes!("../../data/container.webp"))?.to_rgb8();
+    let (width, height) = clean.dimensions();
+    let mut output = std::io::Cursor::new(Vec::new());
+    clean.write_to(&mut output, ::image::ImageFormat::Png)?;
+    let clean = output.into_inner();
+
+    let capacity = capacity_for_dimensions(width, height, 1)?;
+    let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(7);
+
+    let mut estimates = vec![estimate_embedding_rate(&clean)?];
+    for share in [0.1, 0.5, 0.9] {
+        let mut input = vec![0; (capacity as f64 * share) as usize];
+        rng.fill_bytes(&mut input);
+
+        let embedded = embed(
+            &input,
+            "bin",
+            &clean,
+            1,
+            hash::Hash::Blake3,
+            42,
+            Format::Png,
+        )?;
+        estimates.push(estimate_embedding_rate(&embedded)?);
+    }
+
+    assert!(estimates[0] < 0.05, "{:?}", estimates);
+    for (estimate, share) in estimates[1..].iter().zip([0.1, 0.5, 0.9]) {
+        assert!((estimate - share).abs() < 0.1, "{:?}", estimates);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_embedding_rate_of_tiny_image() -> Result<(), Box<dyn std::error::Error>> {
+    let mut output = std::io::Cursor::new(Vec::new());
+    ::image::RgbImage::new(1, 1).write_to(&mut output, ::image::ImageFormat::Png)?;
+
+    assert_eq!(estimate_embedding_rate(&output.into_inner())?, 0.0);
+
+    Ok(())
+}
+
+/// A noisy photo-like image: smooth gradients with sensor noise on top.
+fn noisy_photo() -> Vec<u8> {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(3);
+    let image = ::image::RgbImage::from_fn(256, 192, |x, y| {
+        ::image::Rgb(
+            [x / 2 + 40, y / 2 + 60, (x + y) / 4 + 50]
+                .map(|base| (base as i32 + rng.random_range(-12..=12)).clamp(1, 254) as u8),
+        )
+    });
+
+    let mut output = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut output, ::image::ImageFormat::Png)
+        .unwrap();
+    output.into_inner()
+}
+
+/// A flat screenshot-like image: a few solid panels.
+fn screenshot() -> Vec<u8> {
+    let image = ::image::RgbImage::from_fn(256, 192, |x, y| match (x < 64, y < 32) {
+        (true, _) => ::image::Rgb([40, 44, 52]),
+        (_, true) => ::image::Rgb([200, 200, 205]),
+        _ => ::image::Rgb([250, 250, 250]),
+    });
+
+    let mut output = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut output, ::image::ImageFormat::Png)
+        .unwrap();
+    output.into_inner()
+}
+
+#[test]
+fn test_risk_report_contrasts() -> Result<(), Box<dyn std::error::Error>> {
+    use analysis::RiskRating;
+
+    let photo = risk_report(&noisy_photo(), 1000, 1)?;
+    assert_eq!(photo.rating, RiskRating::Low, "{:?}", photo);
+    assert!(photo.reasons.is_empty());
+
+    let screenshot = screenshot();
+    let capacity = capacity_for_dimensions(256, 192, 1)?;
+    let report = risk_report(&screenshot, capacity * 3 / 4, 1)?;
+    assert_eq!(report.rating, RiskRating::High, "{:?}", report);
+    assert!(report.flatness > 0.9);
+    assert!(report.utilization > 0.5 && report.utilization <= 1.0);
+    assert_eq!(report.reasons.len(), 3);
+
+    // The heuristic is deterministic
+    assert_eq!(report, risk_report(&screenshot, capacity * 3 / 4, 1)?);
+
+    Ok(())
+}
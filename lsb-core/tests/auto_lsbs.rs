@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{
+    error::{ErrorKind, StegError},
+    hash::Hash,
+    *,
+};
+
+mod test_util;
+
+/// A 32x32 container holds 32 * 32 * 3 = 3072 bits, or 384 bytes, per lsbs.
+const SIDE: u32 = 32;
+const BYTES_PER_LSB: usize = (SIDE * SIDE * 3 / 8) as usize;
+/// The length field, extension length, "bin", hash flag, fingerprint and BLAKE3 digest.
+const OVERHEAD: usize = 4 + 1 + 3 + 1 + 8 + 32;
+
+fn options() -> EmbedOptions {
+    EmbedOptions {
+        lsbs: LsbSelection::Auto,
+        hash: Hash::Blake3,
+        seed: 42,
+        format: Some(Format::Png),
+        overwrite_policy: OverwritePolicy::Allow,
+        ..EmbedOptions::default()
+    }
+}
+
+#[test]
+fn test_auto_lsbs_boundaries() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(SIDE, SIDE, Format::Png);
+
+    for (input_len, expected) in [
+        (0, 1),
+        (BYTES_PER_LSB - OVERHEAD, 1),
+        (BYTES_PER_LSB - OVERHEAD + 1, 2),
+        (2 * BYTES_PER_LSB - OVERHEAD, 2),
+        (2 * BYTES_PER_LSB - OVERHEAD + 1, 3),
+        (8 * BYTES_PER_LSB - OVERHEAD, 8),
+    ] {
+        let input = (0..input_len).map(|i| i as u8).collect::<Vec<_>>();
+
+        let embedded = embed_with(&input, "bin", &container, &options())?.image;
+
+        let (data, ext, lsbs) = extract_auto(&embedded, 42)?;
+        assert_eq!(lsbs, expected, "Wrong lsbs for {} bytes", input_len);
+        assert_eq!(data, input);
+        assert_eq!(ext, "bin");
+
+        assert_eq!(extract(&embedded, expected, 42)?.0, input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_lsbs_too_large() {
+    let input = vec![0; 8 * BYTES_PER_LSB - OVERHEAD + 1];
+
+    let result = embed_with(
+        &input,
+        "bin",
+        &test_util::encoded_container(SIDE, SIDE, Format::Png),
+        &options(),
+    );
+
+    assert!(matches!(
+        result,
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+}
+
+#[test]
+fn test_lsb_selection_parse() {
+    assert_eq!(
+        "auto".parse::<LsbSelection>().ok(),
+        Some(LsbSelection::Auto)
+    );
+    assert_eq!(
+        "AUTO".parse::<LsbSelection>().ok(),
+        Some(LsbSelection::Auto)
+    );
+    assert_eq!(
+        "3".parse::<LsbSelection>().ok(),
+        Some(LsbSelection::Fixed(3))
+    );
+    assert_eq!(
+        "three".parse::<LsbSelection>().unwrap_err().kind(),
+        ErrorKind::InvalidArgument
+    );
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "std")]
+
+use std::io::Cursor;
+
+use lsb_core::{error::StegError, image::*, *};
+
+/// Encodes a PNG with the `png` crate, which can write every color type.
+fn png(width: u32, height: u32, color_type: png::ColorType, depth: png::BitDepth) -> Vec<u8> {
+    let sample_size = if depth == png::BitDepth::Sixteen {
+        2
+    } else {
+        1
+    };
+    let data = (0..width as usize * height as usize * color_type.samples() * sample_size)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    let mut output = Vec::new();
+    let mut encoder = png::Encoder::new(&mut output, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(depth);
+    if color_type == png::ColorType::Indexed {
+        encoder.set_palette(
+            (0..=255)
+                .flat_map(|i: u8| [i, 255 - i, i / 2])
+                .collect::<Vec<_>>(),
+        );
+    }
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&data).unwrap();
+    writer.finish().unwrap();
+
+    output
+}
+
+#[test]
+fn test_banded_matches_embed_with() -> Result<(), Box<dyn std::error::Error>> {
+    let input = b"One band at a time".repeat(50);
+
+    for (container, lsbs) in [
+        // Several bands
+        (
+            png(1200, 1400, png::ColorType::Rgb, png::BitDepth::Eight),
+            1,
+        ),
+        (png(300, 200, png::ColorType::Rgba, png::BitDepth::Eight), 2),
+        (
+            png(300, 200, png::ColorType::Grayscale, png::BitDepth::Eight),
+            3,
+        ),
+        (
+            png(300, 200, png::ColorType::Indexed, png::BitDepth::Eight),
+            1,
+        ),
+    ] {
+        let options = EmbedOptions {
+            lsbs: LsbSelection::Fixed(lsbs),
+            ..EmbedOptions::default()
+        };
+
+        let mut banded = Vec::new();
+        embed_banded(
+            &input,
+            "txt",
+            Cursor::new(&container),
+            &options,
+            &mut banded,
+        )?;
+
+        let embedded = embed_with(&input, "txt", &container, &options)?.image;
+        assert_eq!(
+            ::image::load_from_memory(&banded)?.into_rgb8(),
+            ::image::load_from_memory(&embedded)?.into_rgb8()
+        );
+
+        let (data, ext) = extract(&banded, lsbs, 42)?;
+        assert_eq!(data, input);
+        assert_eq!(ext, "txt");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_banded_rejects_unsupported() {
+    let container = png(64, 64, png::ColorType::Rgb, png::BitDepth::Eight);
+    let embed = |container: &[u8], options: &EmbedOptions| {
+        embed_banded(b"data", "txt", Cursor::new(container), options, Vec::new())
+    };
+
+    let options = EmbedOptions {
+        format: Some(Format::Tiff),
+        ..EmbedOptions::default()
+    };
+    assert!(matches!(
+        embed(&container, &options),
+        Err(StegError::UnsupportedFormat(_))
+    ));
+
+    let options = EmbedOptions {
+        overwrite_policy: OverwritePolicy::Warn,
+        ..EmbedOptions::default()
+    };
+    assert!(matches!(
+        embed(&container, &options),
+        Err(StegError::UnsupportedOption(_))
+    ));
+
+    let options = EmbedOptions::new().output(OutputOptions::new().png_interlaced(true));
+    assert!(matches!(
+        embed(&container, &options),
+        Err(StegError::UnsupportedOption(_))
+    ));
+
+    let container = png(64, 64, png::ColorType::Rgb, png::BitDepth::Sixteen);
+    assert!(matches!(
+        embed(&container, &EmbedOptions::default()),
+        Err(StegError::UnsupportedFormat(_))
+    ));
+}
+
+#[test]
+fn test_banded_output_options() -> Result<(), Box<dyn std::error::Error>> {
+    let container = png(300, 200, png::ColorType::Rgb, png::BitDepth::Eight);
+    let input = b"Compressed either way".repeat(20);
+
+    let mut outputs = Vec::new();
+    for png_compression in [PngCompression::Fast, PngCompression::Best] {
+        let options = EmbedOptions::new().output(
+            OutputOptions::new()
+                .png_compression(png_compression)
+                .png_filter(PngFilter::Paeth),
+        );
+        let mut banded = Vec::new();
+        embed_banded(
+            &input,
+            "txt",
+            Cursor::new(&container),
+            &options,
+            &mut banded,
+        )?;
+        assert_eq!(extract(&banded, 1, 42)?.0, input);
+        outputs.push(banded);
+    }
+
+    assert_eq!(
+        ::image::load_from_memory(&outputs[0])?.into_rgb8(),
+        ::image::load_from_memory(&outputs[1])?.into_rgb8()
+    );
+    assert!(outputs[1].len() <= outputs[0].len());
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "std")]
+
+use std::{
+    io::Cursor,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use lsb_core::{error::StegError, *};
+
+mod test_util;
+
+/// A callback that stops at the first report of `phase` until another thread has
+/// cancelled `cancel`, and records the last report.
+fn cancel_at(
+    phase: Phase,
+    cancel: &CancelToken,
+) -> (
+    impl Fn(Progress) + Send + Sync + 'static,
+    Arc<Mutex<Option<Progress>>>,
+) {
+    let (started, waiting) = mpsc::channel::<()>();
+    let (cancelled, confirmed) = mpsc::channel::<()>();
+    let started = Mutex::new(Some(started));
+    let confirmed = Mutex::new(confirmed);
+
+    let token = cancel.clone();
+    thread::spawn(move || {
+        if waiting.recv().is_ok() {
+            token.cancel();
+            let _ = cancelled.send(());
+        }
+    });
+
+    let last = Arc::new(Mutex::new(None));
+    let recorded = last.clone();
+    let callback = move |progress: Progress| {
+        *recorded.lock().unwrap() = Some(progress);
+        if progress.phase == phase
+            && let Some(started) = started.lock().unwrap().take()
+        {
+            started.send(()).unwrap();
+            confirmed.lock().unwrap().recv().unwrap();
+        }
+    };
+
+    (callback, last)
+}
+
+#[test]
+fn test_cancel_embedding() -> Result<(), Box<dyn std::error::Error>> {
+    let input = vec![0x3c; 300_000];
+    let container = test_util::encoded_container(512, 512, Format::Png);
+
+    let cancel = CancelToken::new();
+    let (callback, last) = cancel_at(Phase::Embed, &cancel);
+    let options = EmbedOptions::new()
+        .lsbs(4)
+        .threads(2)
+        .progress(callback)
+        .cancel(cancel.clone());
+
+    let result = embed_with(&input, "bin", &container, &options);
+    assert!(matches!(result, Err(StegError::Cancelled)), "{:?}", result);
+
+    // The remaining chunks were skipped
+    let last = last.lock().unwrap().unwrap();
+    assert_eq!(last.phase, Phase::Embed);
+    assert!(last.completed < last.total, "{:?}", last);
+
+    // A token that is not cancelled changes nothing
+    let options = EmbedOptions::new().lsbs(4).cancel(CancelToken::new());
+    assert_eq!(
+        embed_with(&input, "bin", &container, &options)?.image,
+        embed_with(&input, "bin", &container, &EmbedOptions::new().lsbs(4))?.image
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_cancel_streaming() -> Result<(), Box<dyn std::error::Error>> {
+    let input = vec![0xc3; 300_000];
+    let container = test_util::encoded_container(512, 512, Format::Png);
+
+    let cancel = CancelToken::new();
+    let (callback, _) = cancel_at(Phase::Embed, &cancel);
+    let options = EmbedOptions::new()
+        .lsbs(4)
+        .progress(callback)
+        .cancel(cancel);
+    let mut output = Cursor::new(Vec::new());
+    let result = embed_from_reader(
+        &input[..],
+        input.len() as u64,
+        "bin",
+        &container,
+        &options,
+        &mut output,
+    );
+    assert!(matches!(result, Err(StegError::Cancelled)), "{:?}", result);
+    assert!(output.into_inner().is_empty());
+
+    let embedded = embed_with(&input, "bin", &container, &EmbedOptions::new().lsbs(4))?.image;
+    let cancel = CancelToken::new();
+    let (callback, _) = cancel_at(Phase::Extract, &cancel);
+    let options = EmbedOptions::new()
+        .lsbs(4)
+        .progress(callback)
+        .cancel(cancel);
+    let mut output = Vec::new();
+    let result =
+        Stego::from(options).extract_to_writer(&StegoContainer::new(&embedded)?, &mut output);
+    assert!(matches!(result, Err(StegError::Cancelled)), "{:?}", result);
+    assert!(output.len() < input.len());
+
+    Ok(())
+}
+
+#[test]
+fn test_cancel_extraction() -> Result<(), Box<dyn std::error::Error>> {
+    let input = (0..1_200_000u32)
+        .map(|i| (i % 241) as u8)
+        .collect::<Vec<_>>();
+    let container = test_util::encoded_container(1024, 1024, Format::Png);
+
+    // Long payloads are read in sorted buckets, short ones in payload order
+    for input in [&input[..], &input[..100_000]] {
+        let embedded = embed_with(input, "dat", &container, &EmbedOptions::new().lsbs(4))?.image;
+
+        let cancel = CancelToken::new();
+        let (callback, last) = cancel_at(Phase::Extract, &cancel);
+        let options = ExtractOptions::new()
+            .lsbs(4)
+            .threads(2)
+            .progress(callback)
+            .cancel(cancel);
+        let result = extract_with(&embedded, &options);
+        assert!(matches!(result, Err(StegError::Cancelled)), "{:?}", result);
+
+        let last = last.lock().unwrap().unwrap();
+        assert!(last.completed < last.total, "{:?}", last);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_cancel_before_start() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let embedded = embed_with(b"data", "txt", &container, &EmbedOptions::new())?.image;
+
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(cancel.clone().is_cancelled());
+
+    let result = embed_with(
+        b"data",
+        "txt",
+        &container,
+        &EmbedOptions::new().cancel(cancel.clone()),
+    );
+    assert!(matches!(result, Err(StegError::Cancelled)));
+
+    // Every value tried by `LsbSelection::Auto` is cancelled
+    let options = ExtractOptions::new()
+        .lsbs(LsbSelection::Auto)
+        .cancel(cancel);
+    let result = extract_with(&embedded, &options);
+    assert!(matches!(result, Err(StegError::Cancelled)));
+
+    Ok(())
+}
@@ -0,0 +1,265 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+mod test_util;
+
+#[test]
+fn test_capacity_for_dimensions() -> Result<(), Box<dyn std::error::Error>> {
+    // 100 * 100 * 3 bits, minus 4 + 1 + 1 + 8 bytes of header and a 32 byte BLAKE3 digest
+    assert_eq!(capacity_for_dimensions(100, 100, 1)?, 3704);
+    assert_eq!(capacity_for_dimensions(100, 100, 8)?, 29954);
+    // 4 * 4 * 3 bits cannot hold even the 46 byte header of an empty payload
+    assert!(matches!(
+        capacity_for_dimensions(4, 4, 1),
+        Err(StegError::InsufficientCapacity {
+            required_bits: 368,
+            available_bits: 48,
+            // 123 pixels hold the header
+            missing_pixels: 107,
+        })
+    ));
+    assert!(matches!(
+        capacity_for_dimensions(100, 100, 9),
+        Err(StegError::InvalidLsbValue { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_capacity_overflow() {
+    // u32::MAX * 3 * 8 * u32::MAX bits exceed even a 64-bit usize
+    assert!(matches!(
+        capacity_for_dimensions(u32::MAX, u32::MAX, 8),
+        Err(StegError::CalculationOverflow(_))
+    ));
+}
+
+#[test]
+fn test_capacity_table() -> Result<(), Box<dyn std::error::Error>> {
+    // 100 * 100 * 3 * lsbs / 8 bytes, minus 46 bytes of header with a BLAKE3 digest
+    assert_eq!(
+        capacity_table(&test_util::encoded_container(100, 100, Format::Png))?,
+        [3704, 7454, 11204, 14954, 18704, 22454, 26204, 29954]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_capacity_matches_dimensions() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 48, Format::Png);
+
+    assert_eq!(
+        capacity(&container, 2)?,
+        capacity_for_dimensions(64, 48, 2)?
+    );
+    // A SHA-512 digest is 32 bytes longer than a BLAKE3 one
+    assert_eq!(
+        capacity_with(&container, 2, Hash::Sha512, 3)?,
+        capacity(&container, 2)? - 32 - 3
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_capacity_is_exact() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(40, 30, Format::Png);
+
+    for (lsbs, hash) in [(1, Hash::Sha1), (3, Hash::Sha256)] {
+        let capacity = capacity_with(&container, lsbs, hash, 3)?;
+        let input = vec![0xA5; capacity];
+
+        let embedded = embed(&input, "bin", &container, lsbs, hash, 42, Format::Png)?;
+        assert_eq!(extract(&embedded, lsbs, 42)?.0, input);
+
+        let result = embed(
+            &[input.as_slice(), &[0]].concat(),
+            "bin",
+            &container,
+            lsbs,
+            hash,
+            42,
+            Format::Png,
+        );
+        assert!(matches!(
+            result,
+            Err(StegError::InsufficientCapacity { .. })
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_required_pixels_is_tight() -> Result<(), Box<dyn std::error::Error>> {
+    let input = vec![0x3C; 500];
+
+    for lsbs in [1, 2, 5] {
+        let pixels = required_pixels(input.len(), lsbs, Hash::Sha256, "dat")? as u32;
+
+        let embed_into = |height| {
+            embed(
+                &input,
+                "dat",
+                &test_util::encoded_container(1, height, Format::Png),
+                lsbs,
+                Hash::Sha256,
+                42,
+                Format::Png,
+            )
+        };
+
+        assert!(embed_into(pixels).is_ok());
+        assert!(matches!(
+            embed_into(pixels - 1),
+            // One more pixel would have been enough
+            Err(StegError::InsufficientCapacity { required_bits, available_bits, missing_pixels: 1 })
+                if available_bits == u64::from(pixels - 1) * 3 * lsbs as u64
+                    && required_bits <= available_bits + 3 * lsbs as u64
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_required_dimensions_are_tight() -> Result<(), Box<dyn std::error::Error>> {
+    let input = vec![0x3C; 2000];
+    let (width, height) = required_dimensions(input.len(), 1, Hash::Blake3, "txt")?;
+
+    assert!(width.abs_diff(height) <= 1);
+
+    for (size, fits) in [((width, height), true), ((width, height - 1), false)] {
+        let result = embed(
+            &input,
+            "txt",
+            &test_util::encoded_container(size.0, size.1, Format::Png),
+            1,
+            Hash::Blake3,
+            42,
+            Format::Png,
+        );
+        assert_eq!(result.is_ok(), fits, "{:?}", size);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_at_full_capacity() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+
+    for lsbs in [1, 4, 8] {
+        let input = (0..capacity_with(&container, lsbs, Hash::Blake3, 3)?)
+            .map(|i| (i * 31 % 256) as u8)
+            .collect::<Vec<_>>();
+        let embedded = embed(
+            &input,
+            "bin",
+            &container,
+            lsbs,
+            Hash::Blake3,
+            42,
+            Format::Png,
+        )?;
+
+        assert_eq!(extract(&embedded, lsbs, 42)?.0, input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_length_beyond_bit_capacity() -> Result<(), Box<dyn std::error::Error>> {
+    // 64 * 64 * 3 bits hold 1536 bytes at 1 lsb, but the samples take 12288 bytes
+    let length = 2000u32;
+    let image = ::image::RgbImage::from_fn(64, 64, |x, y| {
+        ::image::Rgb(std::array::from_fn(|channel| {
+            let bit_index = (y * 64 + x) as usize * 3 + channel;
+            let bytes = length.to_le_bytes();
+            bytes
+                .get(bit_index / 8)
+                .map_or(0, |byte| (byte >> (7 - bit_index % 8)) & 1)
+        }))
+    });
+    let mut embedded = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut embedded, ::image::ImageFormat::Png)?;
+
+    // The rest of the header is a valid empty one, so the payload looks cut short
+    let result = extract_with_order(embedded.get_ref(), 1, &order::Sequential);
+    assert!(
+        matches!(
+            result,
+            Err(StegError::PayloadTruncated {
+                available_bits: 12288,
+                ..
+            })
+        ),
+        "{:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_degenerate_payload_lengths() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 24, Format::Png);
+
+    for lsbs in [1, 8] {
+        let capacity = capacity_with(&container, lsbs, Hash::Blake3, 3)?;
+
+        for len in [0, 1, capacity] {
+            let input = vec![0x5A; len];
+            let options = EmbedOptions::new().lsbs(lsbs);
+            let embedded = embed_with(&input, "bin", &container, &options)?.image;
+
+            assert_eq!(
+                extract(&embedded, lsbs, 42)?,
+                (input.clone(), "bin".to_string())
+            );
+            let auto = extract_with(&embedded, &ExtractOptions::new().lsbs(LsbSelection::Auto))?;
+            assert_eq!((auto.data, auto.lsbs), (input.clone(), lsbs));
+
+            let mut writer = Vec::new();
+            let info = extract_to_writer(&embedded, &mut writer, lsbs, 42)?;
+            assert_eq!((writer, info.data_bytes), (input, len as u64));
+            assert_eq!(extract_range(&embedded, len, 0, lsbs, 42)?, []);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_capacity_of_a_container_for_an_empty_payload() -> Result<(), Box<dyn std::error::Error>> {
+    // 123 pixels of 3 bits hold the 46 byte header of an empty payload, 122 do not
+    let fits = test_util::encoded_container(123, 1, Format::Png);
+    assert_eq!(capacity(&fits, 1)?, 0);
+    let embedded = embed(&[], "", &fits, 1, Hash::Blake3, 42, Format::Png)?;
+    assert_eq!(extract(&embedded, 1, 42)?, (Vec::new(), String::new()));
+
+    let too_small = test_util::encoded_container(122, 1, Format::Png);
+    let insufficient = |result: Result<_, StegError>| {
+        matches!(
+            result,
+            Err(StegError::InsufficientCapacity {
+                required_bits: 368,
+                available_bits: 366,
+                missing_pixels: 1,
+            })
+        )
+    };
+    assert!(insufficient(capacity(&too_small, 1).map(drop)));
+    assert!(insufficient(
+        embed(&[], "", &too_small, 1, Hash::Blake3, 42, Format::Png).map(drop)
+    ));
+    // The other numbers of bits still have room
+    assert_eq!(capacity_table(&too_small)?[0], 0);
+    assert_eq!(capacity_table(&too_small)?[1], capacity(&too_small, 2)?);
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+// The chunk size follows the rayon pool, which only exists with the `parallel` feature
+#![cfg(feature = "parallel")]
+
+use lsb_core::{error::StegResult, metrics::quality_metrics_from_bytes, *};
+use rayon::ThreadPoolBuilder;
+
+mod test_util;
+
+#[test]
+fn test_output_independent_of_chunk_size() -> Result<(), Box<dyn std::error::Error>> {
+    // The chunk size follows the size of the thread pool, and is clamped for the small
+    // container and the large one by 1 thread
+    for (width, height, lsbs) in [(40, 30, 2), (300, 300, 1), (1200, 1000, 3)] {
+        let container = test_util::encoded_container(width, height, Format::Pnm);
+        let input = (0..width as usize * height as usize / 4)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect::<Vec<_>>();
+        let options = EmbedOptions {
+            lsbs: LsbSelection::Fixed(lsbs),
+            format: Some(Format::Pnm),
+            ..EmbedOptions::default()
+        };
+
+        let mut results = Vec::new();
+        for threads in [1, 2, 3, 7, 16] {
+            let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+            results.push(pool.install(|| -> StegResult<_> {
+                let (embedded, report) = embed_with_report(&input, "bin", &container, &options)?;
+                let extracted = extract(&embedded, lsbs, 42)?;
+                let diff = diff(&container, &embedded)?;
+                let metrics = quality_metrics_from_bytes(&container, &embedded)?;
+
+                Ok((embedded, report.bits_flipped, extracted, diff, metrics))
+            })?);
+        }
+
+        assert_eq!(results[0].2.0, input);
+        for result in &results[1..] {
+            assert!(
+                *result == results[0],
+                "{}x{} at {} lsbs",
+                width,
+                height,
+                lsbs
+            );
+        }
+    }
+
+    Ok(())
+}
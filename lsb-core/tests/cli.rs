@@ -0,0 +1,1150 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/// A directory of its own for every test, removed when it is dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("lsb-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn write_container(dir: &TempDir) {
+    ::image::RgbImage::from_fn(64, 48, |x, y| {
+        ::image::Rgb([(x * 3) as u8, (y * 5) as u8, (x ^ y) as u8])
+    })
+    .save(dir.0.join("container.png"))
+    .unwrap();
+}
+
+fn lsb_core(dir: &TempDir, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lsb-core"))
+        .current_dir(&dir.0)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs the tool like `lsb_core`, with `stdin` piped into it.
+fn lsb_core_with_stdin(dir: &TempDir, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lsb-core"))
+        .current_dir(&dir.0)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The tool reads all of stdin before writing anything
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_unsupported_output_format_names_the_alternatives() {
+    let dir = TempDir::new("format");
+    write_container(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Hello, world!").unwrap();
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret.txt",
+            "container.png",
+            "--output",
+            "embedded.jpg",
+        ],
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr);
+    assert!(
+        stderr.contains(
+            "Error: Unsupported output format \"jpg\": it is lossy, which would destroy the \
+             payload, supported formats are: Png, WebP"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(!dir.0.join("embedded.jpg").exists());
+}
+
+#[test]
+fn test_empty_file_roundtrip() {
+    let dir = TempDir::new("empty");
+    write_container(&dir);
+    fs::write(dir.0.join("marker.txt"), []).unwrap();
+    fs::create_dir(dir.0.join("out")).unwrap();
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "marker.txt",
+            "container.png",
+            "--output",
+            "embedded.png",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Payload: 49 bytes"));
+
+    let output = lsb_core(&dir, &["extract", "embedded.png", "--output-dir", "out"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        fs::read(dir.0.join("out").join("embedded.txt"))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+/// Copies `input` to `output` with the last data byte of the payload embedded with `lsbs`
+/// and the default seed flipped, leaving the rest of the payload intact.
+fn corrupt_payload(input: &Path, output: &Path, lsbs: usize) {
+    let mut image = ::image::open(input).unwrap().into_rgb8();
+    let (width, height) = image.dimensions();
+
+    let mut plane = lsb_core::Plane::new(image.as_mut(), width, height).unwrap();
+    let order = lsb_core::order::KeyedPermutation::new(42);
+    let length = plane.read_bytes(lsbs, &order, 0, 4).unwrap();
+    let last = 4 + u32::from_le_bytes(length.try_into().unwrap()) as usize - 1;
+    let byte = plane.read_bytes(lsbs, &order, last, 1).unwrap()[0];
+    plane.write_bytes(&[!byte], lsbs, &order, last).unwrap();
+
+    image.save(output).unwrap();
+}
+
+/// A fixture of `data`, from the directory of a test.
+fn fixture(name: &str) -> String {
+    format!("{}/../data/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn test_capacity() {
+    let dir = TempDir::new("capacity");
+    let image = fixture("layout-v1/blake3-l1-16x16.png");
+
+    let output = lsb_core(&dir, &["capacity", &image]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // 96 bytes of LSBs, less 46 bytes of metadata
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Capacity: 50 bytes\n"
+    );
+
+    // Both before and after the subcommand
+    for args in [
+        &["--lsbs", "2", "capacity", &image, "--json"][..],
+        &["capacity", &image, "--json", "--lsbs", "2"],
+    ] {
+        let output = lsb_core(&dir, args);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "{\"lsbs\":2,\"bytes\":146}\n"
+        );
+    }
+
+    let output = lsb_core(&dir, &["capacity", &image, "--hash", "sha-1"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Capacity: 62 bytes\n"
+    );
+}
+
+#[test]
+fn test_capacity_table() {
+    let dir = TempDir::new("capacity-table");
+    let image = fixture("layout-v1/sha256-l1-1x200.png");
+
+    for args in [
+        &["capacity", &image, "--all", "--json"][..],
+        &["capacity", &image, "--lsbs", "auto", "--json"],
+    ] {
+        let output = lsb_core(&dir, args);
+        let rows: Vec<_> = (1..=8)
+            .map(|lsbs| {
+                let bytes = lsb_core::capacity_for_dimensions(1, 200, lsbs).unwrap();
+                format!("{{\"lsbs\":{},\"bytes\":{}}}", lsbs, bytes)
+            })
+            .collect();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("[{}]\n", rows.join(","))
+        );
+    }
+
+    let output = lsb_core(&dir, &["capacity", &image, "--all"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 9, "{}", stdout);
+    assert!(stdout.contains("   1  29 bytes"), "{}", stdout);
+
+    // Too small for even an empty payload
+    let image = fixture("layout-v1/sha1-l8-9x13.png");
+    let output = lsb_core(&dir, &["capacity", &image]);
+    assert_eq!(output.status.code(), Some(4));
+    let output = lsb_core(&dir, &["capacity", &image, "--all"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("   1  0 bytes"));
+}
+
+#[test]
+fn test_capacity_fails_on_undecodable_images() {
+    let dir = TempDir::new("capacity-invalid");
+    fs::write(dir.0.join("image.png"), b"not an image").unwrap();
+
+    for args in [
+        &["capacity", "image.png"][..],
+        &["capacity", "missing.png"],
+        &["capacity", "image.png", "--all", "--json"],
+    ] {
+        let output = lsb_core(&dir, args);
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: "));
+    }
+}
+
+#[test]
+fn test_inspect() {
+    let dir = TempDir::new("inspect");
+    let image = fixture("embedded.png");
+
+    let output = lsb_core(&dir, &["inspect", &image, "--seed", "42"]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Payload: present\nExtension: webp\nSize: 492062 bytes\nLSBs: 1\nHash: blake3\n\
+         Checksum: valid\n"
+    );
+
+    let golden = fixture("golden-v1.png");
+    let output = lsb_core(&dir, &["inspect", &golden, "--lsbs", "2", "--json"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"bytes\":13,\"checksum\":\"valid\",\"extension\":\"txt\",\"hash\":\"blake3\",\
+         \"lsbs\":2,\"present\":true}\n"
+    );
+
+    // Nothing is written
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+}
+
+#[test]
+fn test_inspect_without_payload() {
+    let dir = TempDir::new("inspect-clean");
+
+    for args in [
+        &["inspect", &fixture("container.webp")][..],
+        &[
+            "inspect",
+            &fixture("golden-v1.png"),
+            "--lsbs",
+            "2",
+            "--seed",
+            "7",
+        ],
+    ] {
+        let output = lsb_core(&dir, args);
+        assert_eq!(output.status.code(), Some(5));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "No payload detected (or wrong seed)\n"
+        );
+    }
+
+    let output = lsb_core(&dir, &["inspect", &fixture("golden-v1.png"), "--json"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"present\":false}\n"
+    );
+}
+
+#[test]
+fn test_inspect_corrupted_payload() {
+    let dir = TempDir::new("inspect-corrupted");
+    write_container(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Hello, world!").unwrap();
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret.txt",
+            "container.png",
+            "--output",
+            "embedded.png",
+        ],
+    );
+    assert!(output.status.success());
+
+    corrupt_payload(&dir.0.join("embedded.png"), &dir.0.join("corrupted.png"), 1);
+
+    let output = lsb_core(&dir, &["inspect", "corrupted.png"]);
+    assert_eq!(output.status.code(), Some(6));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Size: 13 bytes\n"), "{}", stdout);
+    assert!(stdout.ends_with("Checksum: corrupted\n"), "{}", stdout);
+}
+
+#[test]
+fn test_legacy_order_with_auto_lsbs() {
+    let dir = TempDir::new("legacy-order");
+    // Embedded before the keyed pixel order
+    let image = fixture("embedded.png");
+
+    let output = lsb_core(&dir, &["inspect", &image, "--lsbs", "auto"]);
+    assert_eq!(output.status.code(), Some(5));
+
+    let output = lsb_core(
+        &dir,
+        &["inspect", &image, "--lsbs", "auto", "--legacy-order"],
+    );
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Size: 492062 bytes\n"));
+
+    let output = lsb_core(
+        &dir,
+        &["verify", &image, "--lsbs", "auto", "--legacy-order"],
+    );
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("1 passed, 0 failed\n"));
+}
+
+#[test]
+fn test_verify() {
+    let dir = TempDir::new("verify");
+    fs::copy(fixture("golden-v1.png"), dir.0.join("good.png")).unwrap();
+    corrupt_payload(&dir.0.join("good.png"), &dir.0.join("bad.png"), 2);
+
+    let output = lsb_core(&dir, &["verify", "good.png", "--lsbs", "2"]);
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "PASS good.png\n1 passed, 0 failed\n"
+    );
+
+    let output = lsb_core(&dir, &["verify", "*.png", "missing.png", "--lsbs", "2"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", stdout);
+    assert!(lines[0].starts_with("FAIL bad.png: "), "{}", stdout);
+    assert_eq!(lines[1], "PASS good.png");
+    assert!(lines[2].starts_with("FAIL missing.png: Failed to read container"));
+    assert_eq!(lines[3], "1 passed, 2 failed");
+
+    // Nothing but the two images
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 2);
+}
+
+#[test]
+fn test_verify_json() {
+    let dir = TempDir::new("verify-json");
+    fs::copy(fixture("golden-v1.png"), dir.0.join("good.png")).unwrap();
+    corrupt_payload(&dir.0.join("good.png"), &dir.0.join("bad.png"), 2);
+
+    let output = lsb_core(
+        &dir,
+        &["verify", "good.png", "bad.png", "--lsbs", "2", "--json"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with(
+            "{\"failed\":1,\"files\":[{\"bytes\":13,\"extension\":\"txt\",\"pass\":true,\
+             \"path\":\"good.png\"},{\"error\":\""
+        ),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.ends_with("\"pass\":false,\"path\":\"bad.png\"}],\"passed\":1}\n"),
+        "{}",
+        stdout
+    );
+
+    // A pattern without matches is an error of its own
+    let output = lsb_core(&dir, &["verify", "*.webp"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No files match *.webp"));
+}
+
+#[test]
+fn test_pipe_through_stdin_and_stdout() {
+    let dir = TempDir::new("pipe");
+    write_container(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Hello, world!").unwrap();
+    let container = fs::read(dir.0.join("container.png")).unwrap();
+
+    // The container from stdin, the image to stdout
+    let output = lsb_core_with_stdin(
+        &dir,
+        &["embed", "secret.txt", "-", "--output", "-"],
+        &container,
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(output.stdout.starts_with(b"\x89PNG"));
+    assert!(stderr.contains("Payload: 62 bytes"), "{}", stderr);
+
+    // The image from stdin, the data to stdout
+    let output = lsb_core_with_stdin(&dir, &["extract", "-", "--output-dir", "-"], &output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"Hello, world!");
+
+    // Only the file of the container is left
+    let mut names: Vec<_> = fs::read_dir(&dir.0)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["container.png", "secret.txt"]);
+}
+
+#[test]
+fn test_pipe_input_from_stdin() {
+    let dir = TempDir::new("pipe-input");
+    write_container(&dir);
+    fs::create_dir(dir.0.join("out")).unwrap();
+
+    let output = lsb_core_with_stdin(
+        &dir,
+        &["embed", "-", "container.png", "--output", "embedded.png"],
+        b"piped data",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Payload: "));
+
+    // Without a file name, the data is embedded as `bin`
+    let output = lsb_core_with_stdin(
+        &dir,
+        &["extract", "-", "--output-dir", "out"],
+        &fs::read(dir.0.join("embedded.png")).unwrap(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let extracted = dir.0.join("out").join("extracted.bin");
+    assert_eq!(fs::read(&extracted).unwrap(), b"piped data");
+
+    // stdin can only be read once
+    let output = lsb_core_with_stdin(&dir, &["embed", "-", "-", "--output", "-"], b"data");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_extract_to_stdout() {
+    let dir = TempDir::new("stdout");
+
+    let output = lsb_core(&dir, &["extract", &fixture("embedded.png"), "--stdout"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout == fs::read(fixture("input.webp")).unwrap());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Extension: webp\nSize: 492062 bytes\n"
+    );
+
+    // Logs go to stderr, and no newline is added
+    let output = Command::new(env!("CARGO_BIN_EXE_lsb-core"))
+        .current_dir(&dir.0)
+        .env("RUST_LOG", "debug")
+        .args([
+            "extract",
+            &fixture("golden-v1.png"),
+            "--stdout",
+            "--lsbs",
+            "2",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Golden output");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("DEBUG"));
+
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+}
+
+/// Writes containers of different sizes into `photos`, and a file that is no image.
+fn write_photos(dir: &TempDir) {
+    fs::create_dir(dir.0.join("photos")).unwrap();
+    for (name, width) in [("a", 40), ("b", 48), ("c", 56)] {
+        ::image::RgbImage::from_fn(width, 32, |x, y| {
+            ::image::Rgb([(x * 5) as u8, (y * 7) as u8, (x + y) as u8])
+        })
+        .save(dir.0.join("photos").join(format!("{}.png", name)))
+        .unwrap();
+    }
+    fs::write(dir.0.join("photos").join("broken.png"), b"not an image").unwrap();
+}
+
+#[test]
+fn test_batch_embed() {
+    let dir = TempDir::new("batch");
+    write_photos(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Stamped everywhere").unwrap();
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret.txt",
+            "photos/*.png",
+            "--output-dir",
+            "out",
+            "--output-template",
+            "{stem}_embedded.png",
+            "--seed-per-file",
+            "index",
+            "--manifest",
+            "manifest.json",
+            "--jobs",
+            "2",
+        ],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(
+        stdout.contains("OK photos/c.png -> out/c_embedded.png (seed 45)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("FAIL photos/broken.png: "), "{}", stdout);
+    assert!(stdout.ends_with("3 embedded, 1 failed\n"), "{}", stdout);
+    assert!(
+        stderr.contains("Failed:\n  photos/broken.png: "),
+        "{}",
+        stderr
+    );
+
+    // The glob is sorted, so broken.png is third
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(dir.0.join("manifest.json")).unwrap()).unwrap();
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files.len(), 4);
+    for (index, file) in files.iter().enumerate() {
+        assert_eq!(file["seed"], 42 + index as u64);
+        let container = file["container"].as_str().unwrap();
+        if container == "photos/broken.png" {
+            assert!(file["error"].is_string());
+            assert!(!dir.0.join("out/broken_embedded.png").exists());
+            continue;
+        }
+
+        let image = fs::read(dir.0.join(file["output"].as_str().unwrap())).unwrap();
+        let (data, extension) = lsb_core::extract(&image, 1, 42 + index as u64).unwrap();
+        assert_eq!(data, b"Stamped everywhere", "{}", container);
+        assert_eq!(extension, "txt");
+        assert_eq!(file["lsbs"], 1);
+    }
+}
+
+#[test]
+fn test_batch_embed_seeds_by_name() {
+    let dir = TempDir::new("batch-name");
+    write_photos(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Seeded by name").unwrap();
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret.txt",
+            "photos/a.png",
+            "photos/b.png",
+            "--output-dir",
+            "out",
+            "--seed-per-file",
+            "name",
+            "--seed",
+            "7",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    for name in ["a.png", "b.png"] {
+        let hash = blake3::hash(name.as_bytes());
+        let seed = 7u64.wrapping_add(u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap()));
+        let image = fs::read(dir.0.join("out").join(name)).unwrap();
+        assert_eq!(
+            lsb_core::extract(&image, 1, seed).unwrap().0,
+            b"Seeded by name"
+        );
+    }
+}
+
+#[test]
+fn test_batch_embed_matched_inputs() {
+    let dir = TempDir::new("batch-matched");
+    write_photos(&dir);
+    fs::create_dir(dir.0.join("payloads")).unwrap();
+    fs::write(dir.0.join("payloads/a.txt"), b"For a").unwrap();
+    fs::write(dir.0.join("payloads/b.md"), b"For b").unwrap();
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "payloads/{stem}.txt",
+            "photos/a.png",
+            "photos/b.png",
+            "--output-dir",
+            "out",
+        ],
+    );
+    // b has no text payload
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("FAIL photos/b.png: "), "{}", stdout);
+
+    let image = fs::read(dir.0.join("out/a.png")).unwrap();
+    assert_eq!(lsb_core::extract(&image, 1, 42).unwrap().0, b"For a");
+
+    // Outputs that would overwrite each other are refused before embedding anything
+    fs::create_dir(dir.0.join("more")).unwrap();
+    fs::copy(dir.0.join("photos/a.png"), dir.0.join("more/a.png")).unwrap();
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "payloads/a.txt",
+            "photos/a.png",
+            "more/a.png",
+            "--output-dir",
+            "twice",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Several containers"));
+    assert!(!dir.0.join("twice").exists());
+
+    // Several containers need an output directory
+    let output = lsb_core(
+        &dir,
+        &["embed", "payloads/a.txt", "photos/a.png", "photos/b.png"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--output-dir"));
+}
+
+#[test]
+fn test_embed_directory() {
+    let dir = TempDir::new("directory");
+    ::image::RgbImage::from_fn(128, 96, |x, y| {
+        ::image::Rgb([(x * 3) as u8, (y * 5) as u8, (x ^ y) as u8])
+    })
+    .save(dir.0.join("container.png"))
+    .unwrap();
+    let secret = dir.0.join("secret");
+    fs::create_dir_all(secret.join("nested/deeper")).unwrap();
+    fs::create_dir(secret.join("empty")).unwrap();
+    fs::write(secret.join("notes.txt"), b"Folders travel too").unwrap();
+    fs::write(secret.join("nested/data.bin"), [7; 700]).unwrap();
+    fs::write(secret.join("nested/deeper/last.md"), b"# Last").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("notes.txt", secret.join("link.txt")).unwrap();
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret",
+            "container.png",
+            "-o",
+            "out.png",
+            "--lsbs",
+            "4",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Without --unpack, the archive itself is extracted
+    let output = lsb_core(&dir, &["extract", "out.png", "--lsbs", "4"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "./out.tar\n");
+
+    let output = lsb_core(
+        &dir,
+        &[
+            "extract", "out.png", "--lsbs", "4", "--unpack", "-o", "unpacked",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let unpacked = dir.0.join("unpacked/out");
+    assert_eq!(
+        fs::read(unpacked.join("notes.txt")).unwrap(),
+        b"Folders travel too"
+    );
+    assert_eq!(
+        fs::read(unpacked.join("nested/data.bin")).unwrap(),
+        [7; 700]
+    );
+    assert_eq!(
+        fs::read(unpacked.join("nested/deeper/last.md")).unwrap(),
+        b"# Last"
+    );
+    assert!(unpacked.join("empty").is_dir());
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(unpacked.join("link.txt")).unwrap(),
+        Path::new("notes.txt")
+    );
+
+    // Unpacking never merges into an existing directory
+    let output = lsb_core(
+        &dir,
+        &[
+            "extract", "out.png", "--lsbs", "4", "--unpack", "-o", "unpacked",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+}
+
+#[test]
+fn test_embed_directory_errors() {
+    let dir = TempDir::new("directory-errors");
+    write_container(&dir);
+    fs::create_dir(dir.0.join("large")).unwrap();
+    fs::write(dir.0.join("large/big.bin"), vec![0; 4096]).unwrap();
+
+    // The sizes of the files already rule the container out
+    let output = lsb_core(&dir, &["embed", "large", "container.png", "-o", "out.png"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(!dir.0.join("out.png").exists());
+
+    // A payload that is no archive cannot be unpacked
+    fs::write(dir.0.join("plain.txt"), b"Just a file").unwrap();
+    let output = lsb_core(
+        &dir,
+        &["embed", "plain.txt", "container.png", "-o", "out.png"],
+    );
+    assert!(output.status.success());
+    let output = lsb_core(&dir, &["extract", "out.png", "--unpack"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a directory"));
+}
+
+#[test]
+fn test_extract_list() {
+    let dir = TempDir::new("list");
+    // An archive of readme.txt, docs/plan.md and data/zeros.bin, at 4 LSBs
+    let archive = fixture("multi-file.png");
+
+    let output = lsb_core(&dir, &["extract", &archive, "--lsbs", "4", "--list"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "            data/\n",
+            "       300  data/zeros.bin\n",
+            "            docs/\n",
+            "        25  docs/plan.md\n",
+            "        24  readme.txt\n",
+            "Total: 3 files, 349 bytes\n",
+        )
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not verified"));
+    // Nothing is written
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+
+    let output = lsb_core(
+        &dir,
+        &["extract", &archive, "--lsbs", "4", "--list", "--json"],
+    );
+    assert!(output.status.success());
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        listing.contains(r#"{"path":"docs/plan.md","size":25,"type":"file"}"#),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains(r#""files":3,"total_bytes":349"#),
+        "{}",
+        listing
+    );
+
+    // A single file is listed under the name extraction gives it
+    let output = lsb_core(
+        &dir,
+        &[
+            "extract",
+            &fixture("golden-v1.png"),
+            "--lsbs",
+            "2",
+            "--list",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "        13  golden-v1.txt\nTotal: 1 file, 13 bytes\n"
+    );
+}
+
+#[test]
+fn test_passphrase() {
+    let dir = TempDir::new("passphrase");
+    write_container(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Keyed by a passphrase").unwrap();
+    fs::write(dir.0.join("passphrase"), b"correct horse\n").unwrap();
+    let with_env = |passphrase: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lsb-core"))
+            .current_dir(&dir.0)
+            .env("LSB_PASSPHRASE", passphrase)
+            .args(args)
+            .args(["--passphrase-env", "LSB_PASSPHRASE"])
+            .output()
+            .unwrap()
+    };
+
+    let output = with_env(
+        "correct horse",
+        &["embed", "secret.txt", "container.png", "-o", "out.png"],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The file holds the same passphrase, and the extension is restored
+    let output = lsb_core(
+        &dir,
+        &["extract", "out.png", "--passphrase-file", "passphrase"],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        fs::read(dir.0.join("out.txt")).unwrap(),
+        b"Keyed by a passphrase"
+    );
+
+    // Neither the default seed nor another passphrase find the payload
+    let output = lsb_core(&dir, &["extract", "out.png", "--stdout"]);
+    assert_eq!(output.status.code(), Some(5));
+    let output = with_env("wrong horse", &["extract", "out.png", "--stdout"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Wrong passphrase"));
+
+    // The passphrase replaces the seed
+    let output = with_env(
+        "correct horse",
+        &["extract", "out.png", "--stdout", "--seed", "1"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let output = with_env("", &["extract", "out.png", "--stdout"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("empty"));
+}
+
+#[test]
+fn test_seed_sources() {
+    let dir = TempDir::new("seed-sources");
+    write_container(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Seeded from a file").unwrap();
+    // A seed that shows up in no other output
+    let seed = 0x5eed_cafe_f00d_u64;
+    fs::write(dir.0.join("seed"), format!("  {}\n", seed)).unwrap();
+    let run = |envs: &[(&str, &str)], args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lsb-core"))
+            .current_dir(&dir.0)
+            .envs(envs.iter().copied())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // The seed stays out of even the most verbose logs
+    let output = run(
+        &[("RUST_LOG", "trace")],
+        &[
+            "embed",
+            "secret.txt",
+            "container.png",
+            "-o",
+            "out.png",
+            "--seed-file",
+            "seed",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for printed in [&output.stdout, &output.stderr] {
+        let printed = String::from_utf8_lossy(printed).to_lowercase();
+        assert!(!printed.contains(&seed.to_string()));
+        assert!(!printed.contains(&format!("{:x}", seed)));
+    }
+
+    // The same seed, in hexadecimal or as an argument
+    let hex = format!("0x{:X}", seed);
+    for (envs, args) in [
+        (
+            vec![("LSB_SEED", hex.as_str())],
+            vec!["--seed-env", "LSB_SEED"],
+        ),
+        (vec![], vec!["--seed", &seed.to_string()]),
+    ] {
+        let output = run(
+            &envs,
+            &[&["extract", "out.png", "--stdout"], &args[..]].concat(),
+        );
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(output.stdout, b"Seeded from a file");
+    }
+
+    // Only one source can be given
+    for args in [
+        &["--seed", "1", "--seed-file", "seed"][..],
+        &["--seed-file", "seed", "--seed-env", "LSB_SEED"],
+        &["--seed-env", "LSB_SEED", "--passphrase-env", "LSB_SEED"],
+    ] {
+        let output = run(&[], &[&["extract", "out.png", "--stdout"], args].concat());
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+
+    // Errors name the source, never its contents
+    fs::write(dir.0.join("malformed"), b"0xnot-a-seed").unwrap();
+    for (envs, args, message) in [
+        (
+            vec![],
+            ["--seed-file", "missing"],
+            "Failed to read the seed file missing",
+        ),
+        (
+            vec![],
+            ["--seed-file", "malformed"],
+            "The seed file malformed does not hold",
+        ),
+        (
+            vec![("LSB_SEED", "-1")],
+            ["--seed-env", "LSB_SEED"],
+            "The environment variable LSB_SEED does not hold",
+        ),
+        (
+            vec![],
+            ["--seed-env", "LSB_UNSET_SEED"],
+            "LSB_UNSET_SEED is not set",
+        ),
+    ] {
+        let output = run(
+            &envs,
+            &[&["extract", "out.png", "--stdout"], &args[..]].concat(),
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr.contains(message), "{}", stderr);
+        assert!(!stderr.contains("not-a-seed"));
+    }
+
+    // Batches keep seeds from files out of their report
+    let output = run(
+        &[],
+        &[
+            "embed",
+            "secret.txt",
+            "container.png",
+            "--output-dir",
+            "batch",
+            "--seed-file",
+            "seed",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "OK container.png -> batch/container.png\n1 embedded, 0 failed\n"
+    );
+}
+
+#[test]
+fn test_random_seed() {
+    let dir = TempDir::new("random-seed");
+    write_container(&dir);
+    fs::write(dir.0.join("secret.txt"), b"Under a fresh seed").unwrap();
+    let reported_seed = |output: &Output| {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let line = stderr.lines().find(|line| line.starts_with("Seed: "));
+        let seed = line.and_then(|line| line.split(' ').nth(1));
+        seed.expect(&stderr).to_string()
+    };
+
+    let mut seeds = Vec::new();
+    for output in ["one.png", "two.png"] {
+        let output = lsb_core(
+            &dir,
+            &[
+                "embed",
+                "secret.txt",
+                "container.png",
+                "-o",
+                output,
+                "--seed",
+                "random",
+            ],
+        );
+        assert!(output.status.success());
+        seeds.push(reported_seed(&output));
+    }
+    assert_ne!(seeds[0], seeds[1]);
+    assert_ne!(
+        fs::read(dir.0.join("one.png")).unwrap(),
+        fs::read(dir.0.join("two.png")).unwrap()
+    );
+
+    for (output, seed) in ["one.png", "two.png"].into_iter().zip(&seeds) {
+        let output = lsb_core(&dir, &["extract", output, "--stdout", "--seed", seed]);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(output.stdout, b"Under a fresh seed");
+    }
+
+    // The written seed is read back by --seed-file
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret.txt",
+            "container.png",
+            "-o",
+            "three.png",
+            "--seed",
+            "random",
+            "--seed-out",
+            "three.seed",
+        ],
+    );
+    assert!(output.status.success());
+    let seed_file = dir.0.join("three.seed");
+    assert_eq!(
+        fs::read_to_string(&seed_file).unwrap(),
+        format!("{}\n", reported_seed(&output))
+    );
+    #[cfg(unix)]
+    assert_eq!(
+        std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&seed_file).unwrap().permissions())
+            & 0o777,
+        0o600
+    );
+    let output = lsb_core(
+        &dir,
+        &[
+            "extract",
+            "three.png",
+            "--stdout",
+            "--seed-file",
+            "three.seed",
+        ],
+    );
+    assert_eq!(output.stdout, b"Under a fresh seed");
+
+    // Extraction needs the seed itself, and only made up seeds are written
+    let output = lsb_core(
+        &dir,
+        &["extract", "one.png", "--stdout", "--seed", "random"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("only made up when embedding"));
+    let output = lsb_core(
+        &dir,
+        &[
+            "embed",
+            "secret.txt",
+            "container.png",
+            "--seed-out",
+            "four.seed",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!dir.0.join("four.seed").exists());
+}
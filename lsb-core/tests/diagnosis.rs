@@ -0,0 +1,93 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, order::Sequential, *};
+
+mod test_util;
+
+/// Data of `len` bytes that looks random to the sample pair analysis.
+fn data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7919 % 251) as u8).collect()
+}
+
+#[test]
+fn test_never_embedded_is_no_payload_found() {
+    let result = extract(&test_util::encoded_container(128, 128, Format::Png), 1, 0);
+
+    assert!(
+        matches!(result, Err(StegError::NoPayloadFound { .. })),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn test_wrong_seed_is_suspected() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(128, 128, Format::Png);
+    let input = data(capacity(&container, 1)? / 2);
+    let embedded = embed(&input, "bin", &container, 1, Hash::Blake3, 42, None)?;
+
+    let result = extract(&embedded, 1, 7);
+    assert!(
+        matches!(result, Err(StegError::WrongSeedSuspected { .. })),
+        "{:?}",
+        result
+    );
+    assert_eq!(extract(&embedded, 1, 42)?.0, input);
+
+    Ok(())
+}
+
+#[test]
+fn test_flipped_bits_are_payload_corrupted() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with_order(
+        &data(256),
+        "bin",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &EmbedOptions::default(),
+        &Sequential,
+    )?;
+
+    // Flip the lowest bit of a sample in the middle of the data
+    let mut image = ::image::load_from_memory(&embedded)?.into_rgb8();
+    image.as_mut()[1000] ^= 1;
+
+    let result = extract_with_order(&test_util::png(&image), 1, &Sequential);
+    let Err(StegError::PayloadCorrupted { expected, actual }) = result else {
+        panic!("{:?}", result);
+    };
+    assert_eq!(expected.len(), 32);
+    assert_eq!(actual.len(), 32);
+    assert_ne!(expected, actual);
+
+    Ok(())
+}
+
+#[test]
+fn test_cropped_image_is_payload_truncated() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with_order(
+        &data(512),
+        "bin",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &EmbedOptions::default(),
+        &Sequential,
+    )?;
+
+    // Keep the top rows, which still hold the header
+    let image = ::image::load_from_memory(&embedded)?.into_rgb8();
+    let cropped = ::image::imageops::crop_imm(&image, 0, 0, 64, 8).to_image();
+
+    let result = extract_with_order(&test_util::png(&cropped), 1, &Sequential);
+    assert!(
+        matches!(
+            result,
+            Err(StegError::PayloadTruncated {
+                required_bits,
+                available_bits: 1536,
+            }) if required_bits > 512 * 8
+        ),
+        "{:?}",
+        result
+    );
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{diff::*, error::StegError, hash::Hash, *};
+
+mod test_util;
+
+#[test]
+fn test_diff_lists_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let original = ::image::RgbImage::from_pixel(4, 3, ::image::Rgb([10, 20, 30]));
+    let mut modified = original.clone();
+    modified.put_pixel(1, 2, ::image::Rgb([11, 20, 30]));
+    modified.put_pixel(3, 0, ::image::Rgb([10, 22, 31]));
+
+    let report = diff_with(&test_util::png(&original), &test_util::png(&modified), 10)?;
+
+    assert_eq!((report.width, report.height, report.channels), (4, 3, 3));
+    assert_eq!(report.changed_pixels, 2);
+    assert_eq!(report.changed_samples, 3);
+    assert_eq!(report.bit_planes, vec![2, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(report.changed_bits(), 3);
+    assert!(!report.truncated);
+    assert_eq!(
+        report.changes[0],
+        SampleChange {
+            x: 3,
+            y: 0,
+            channel: 1,
+            old: 20,
+            new: 22
+        }
+    );
+    assert_eq!(report.changes[2].y, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_of_embedding() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 32, Format::Png);
+    let embedded = embed(
+        b"Only the low planes change",
+        "txt",
+        &container,
+        2,
+        Hash::Sha1,
+        42,
+        Format::Png,
+    )?;
+
+    let report = diff_with(&container, &embedded, 5)?;
+
+    assert!(report.bit_planes[0] > 0 && report.bit_planes[1] > 0);
+    assert!(report.bit_planes[2..].iter().all(|&count| count == 0));
+    assert_eq!(report.changes.len(), 5);
+    assert!(report.truncated);
+    assert!(diff(&container, &embedded)?.changes.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_mismatches() {
+    let rgba = test_util::encode(::image::RgbaImage::new(32, 32), Format::Png);
+
+    assert!(matches!(
+        diff(
+            &test_util::encoded_container(32, 32, Format::Png),
+            &test_util::encoded_container(32, 31, Format::Png)
+        ),
+        Err(StegError::DimensionMismatch { .. })
+    ));
+    assert!(matches!(
+        diff(&test_util::encoded_container(32, 32, Format::Png), &rgba),
+        Err(StegError::ColorTypeMismatch(_))
+    ));
+}
+
+#[test]
+fn test_change_map_stays_in_region() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(40, 40, Format::Png);
+    let input = vec![0xC3; 100];
+
+    // The sequential order fills the first rows of the image and nothing else
+    let embedded = embed_with_order(
+        &input,
+        "bin",
+        &container,
+        &EmbedOptions::default(),
+        &order::Sequential,
+    )?;
+    let rows = ((4 + 1 + 3 + 1 + 8 + 32 + input.len()) * 8).div_ceil(40 * 3) as u32;
+
+    let original = ::image::load_from_memory(&container)?.into_rgb8();
+    let modified = ::image::load_from_memory(&embedded)?.into_rgb8();
+    let map = change_map(&original, &modified)?;
+
+    assert!(
+        map.rows()
+            .take(rows as usize)
+            .flatten()
+            .any(|p| p.0 == [255])
+    );
+    assert!(map.rows().skip(rows as usize).flatten().all(|p| p.0 == [0]));
+
+    let amplified = amplified_change_map(&original, &modified)?;
+    assert_eq!(amplified.iter().copied().max(), Some(255));
+    for (pixel, changed) in amplified.pixels().zip(map.pixels()) {
+        assert_eq!(pixel.0 != [0; 3], changed.0 == [255]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_only_lsbs_changed() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 32, Format::Png);
+    let input = b"Only the low bits".repeat(8);
+
+    for lsbs in 1..=3 {
+        let embedded = embed(
+            &input,
+            "txt",
+            &container,
+            lsbs,
+            Hash::Blake3,
+            42,
+            Format::Png,
+        )?;
+
+        let stats = assert_only_lsbs_changed(&container, &embedded, lsbs)?;
+        assert_eq!(stats.samples, 32 * 32 * 3);
+        assert!(stats.changed_samples > 0);
+        assert!(stats.changed_bits >= stats.changed_samples);
+
+        if lsbs > 1 {
+            assert!(matches!(
+                assert_only_lsbs_changed(&container, &embedded, lsbs - 1),
+                Err(StegError::UnexpectedChange { .. })
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_only_lsbs_changed_names_pixel() {
+    let mut image = ::image::RgbImage::from_pixel(8, 8, ::image::Rgb([100, 100, 100]));
+    let original = test_util::png(&image);
+    image.put_pixel(5, 2, ::image::Rgb([100, 100, 102]));
+    let modified = test_util::png(&image);
+
+    let result = assert_only_lsbs_changed(&original, &modified, 1);
+    assert!(
+        matches!(
+            result,
+            Err(StegError::UnexpectedChange {
+                x: 5,
+                y: 2,
+                channel: 2,
+                original: 100,
+                modified: 102,
+                lsbs: 1
+            })
+        ),
+        "{:?}",
+        result
+    );
+    assert!(assert_only_lsbs_changed(&original, &modified, 2).is_ok());
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, image::DynamicImage, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Any color type will do.";
+
+fn containers() -> Vec<DynamicImage> {
+    let rgb = test_util::container(48, 32);
+
+    vec![
+        DynamicImage::ImageRgb8(rgb.clone()),
+        DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(rgb.clone()).into_luma8()),
+        DynamicImage::ImageRgba8(DynamicImage::ImageRgb8(rgb).into_rgba8()),
+    ]
+}
+
+#[test]
+fn test_dynamic_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    for container in containers() {
+        for lsbs in [1, 4, 8] {
+            let options = EmbedOptions::new().lsbs(lsbs).seed(3);
+
+            let embedded = embed_dynamic(INPUT, "txt", &container, &options)?;
+            assert_eq!(embedded.color(), ::image::ColorType::Rgb8);
+
+            let extracted = extract_dynamic(&embedded, &ExtractOptions::from(&options))?;
+            assert_eq!(extracted.data, INPUT);
+            assert_eq!(extracted.extension, "txt");
+            assert_eq!(extracted.lsbs, lsbs);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_dynamic_matches_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    for container in containers() {
+        let options = EmbedOptions::new().lsbs(LsbSelection::Auto);
+
+        let embedded = embed_dynamic(INPUT, "txt", &container, &options)?;
+        let bytes = embed_with(
+            INPUT,
+            "txt",
+            &test_util::encode(container.clone(), Format::Png),
+            &options,
+        )?
+        .image;
+        assert_eq!(embedded, ::image::load_from_memory(&bytes)?);
+
+        assert_eq!(
+            extract_dynamic(&embedded, &ExtractOptions::new())?,
+            extract_with(&bytes, &ExtractOptions::new())?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_dynamic_validates_like_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    let container = containers().remove(1);
+
+    let result = embed_dynamic(INPUT, "txt", &container, &EmbedOptions::new().lsbs(0));
+    assert!(matches!(result, Err(StegError::InvalidLsbValue { .. })));
+
+    let embedded = embed_dynamic(INPUT, "txt", &container, &EmbedOptions::new())?;
+    let options = EmbedOptions::new().overwrite_policy(OverwritePolicy::Error);
+    let result = embed_dynamic(INPUT, "txt", &embedded, &options);
+    assert!(matches!(result, Err(StegError::ExistingPayload(_))));
+
+    assert!(extract_dynamic(&container, &ExtractOptions::new()).is_err());
+
+    Ok(())
+}
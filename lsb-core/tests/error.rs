@@ -0,0 +1,312 @@
+#![cfg(feature = "std")]
+
+use std::{error::Error, path::Path, string::FromUtf8Error};
+
+use lsb_core::{
+    error::{ErrorDetail, ErrorKind, StegError},
+    hash::Hash,
+    image::output_format_from_extension,
+    order::Sequential,
+    *,
+};
+
+mod test_util;
+
+#[test]
+fn test_invalid_lsbs_is_an_invalid_argument() {
+    let container = test_util::encoded_container(16, 16, Format::Png);
+    let err = extract(&container, 9, 0).unwrap_err();
+
+    assert!(matches!(
+        err,
+        StegError::InvalidLsbValue { given: 9, max: 8 }
+    ));
+    assert_eq!(err.kind(), ErrorKind::InvalidArgument);
+    assert_eq!(err.kind().as_str(), "invalid_argument");
+    assert_eq!(err.detail(), None);
+    assert_eq!(err.capacity_shortfall(), None);
+}
+
+#[test]
+fn test_insufficient_capacity_reports_bits_and_pixels() {
+    let container = test_util::encoded_container(16, 16, Format::Png);
+    let capacity = capacity(&container, 1).unwrap();
+    let input = vec![0u8; capacity + 1];
+    let missing = required_pixels(input.len(), 1, Hash::Blake3, "").unwrap() - 16 * 16;
+
+    let err = embed(&input, "", &container, 1, Hash::Blake3, 0, None).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InsufficientCapacity);
+    let (required, available) = err.capacity_shortfall().unwrap();
+    assert!(required > available);
+    assert!(
+        err.to_string()
+            .contains(&format!("{} bits required", required))
+    );
+    assert!(
+        err.to_string()
+            .ends_with(&format!("you need at least {} more pixels", missing)),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_unsupported_output_format_reports_the_alternatives() {
+    let err = output_format_from_extension("xyz").unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::UnsupportedFormat);
+    assert_eq!(err.code(), "unsupported_output_format");
+    assert_eq!(err.detail(), None);
+
+    let details = err.report().details;
+    assert_eq!(details["format"], ErrorDetail::Text("xyz".to_string()));
+    let ErrorDetail::Text(supported) = &details["supported"] else {
+        panic!("{:?}", details);
+    };
+    assert!(supported.starts_with("Png, WebP"), "{}", supported);
+}
+
+#[test]
+fn test_invalid_extension_keeps_its_source() -> Result<(), Box<dyn Error>> {
+    let embedded = embed_with_order(
+        b"data",
+        "txt",
+        &test_util::encoded_container(16, 16, Format::Png),
+        &EmbedOptions::default(),
+        &Sequential,
+    )?;
+
+    // The first byte of the extension follows the length and the extension length
+    let mut image = ::image::load_from_memory(&embedded)?.into_rgb8();
+    for sample in &mut image.as_mut()[5 * 8..6 * 8] {
+        *sample |= 1;
+    }
+    let mut corrupted = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut corrupted, ::image::ImageFormat::Png)?;
+
+    let err = extract_with_order(corrupted.get_ref(), 1, &Sequential).unwrap_err();
+
+    // The invalid extension is why no payload was found
+    let StegError::NoPayloadFound { cause } = &err else {
+        panic!("{:?}", err);
+    };
+    assert!(matches!(**cause, StegError::InvalidExtensionUtf8 { .. }));
+    assert_eq!(cause.kind(), ErrorKind::NotFound);
+    assert!(cause.to_string().contains("UTF-8"));
+    let source = err
+        .source()
+        .and_then(Error::source)
+        .unwrap()
+        .downcast_ref::<FromUtf8Error>()
+        .unwrap();
+    assert_eq!(source.as_bytes(), [0xff, b'x', b't']);
+
+    Ok(())
+}
+
+#[test]
+fn test_io_error_keeps_its_source() {
+    let err = embed_path(
+        Path::new("missing.txt"),
+        Path::new("missing.png"),
+        Path::new("output.png"),
+        &EmbedOptions::default(),
+    )
+    .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::Io);
+    let source = err
+        .source()
+        .unwrap()
+        .downcast_ref::<std::io::Error>()
+        .unwrap();
+    assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+    assert!(err.to_string().starts_with("I/O error: "));
+}
+
+#[test]
+fn test_image_error_keeps_its_source() {
+    let container = test_util::encoded_container(16, 16, Format::Png);
+    let err = extract(&container[..container.len() / 2], 1, 0).unwrap_err();
+
+    assert!(matches!(err, StegError::ImageProcessing(_)));
+    assert_eq!(err.kind(), ErrorKind::Image);
+    assert!(err.source().unwrap().is::<::image::ImageError>());
+    assert!(err.to_string().starts_with("Image processing error: "));
+}
+
+/// One error of every variant, with the code it must keep.
+fn every_variant() -> Vec<(StegError, &'static str)> {
+    let utf8 = String::from_utf8(vec![0xff]).unwrap_err();
+    let io = || std::io::Error::from(std::io::ErrorKind::NotFound);
+    let text = || "text".to_string();
+
+    vec![
+        (
+            StegError::InvalidLsbValue { given: 9, max: 8 },
+            "invalid_lsb_value",
+        ),
+        (
+            StegError::ImageProcessing(::image::ImageError::IoError(io())),
+            "image_processing",
+        ),
+        (StegError::FormatDetection(text()), "format_detection"),
+        (
+            StegError::ExtensionTooLong { len: 300, max: 255 },
+            "extension_too_long",
+        ),
+        (
+            StegError::InsufficientCapacity {
+                required_bits: 2,
+                available_bits: 1,
+                missing_pixels: 1,
+            },
+            "insufficient_capacity",
+        ),
+        (StegError::PayloadParse(text()), "payload_parse"),
+        (
+            StegError::InvalidExtensionUtf8 { source: utf8 },
+            "invalid_extension_utf8",
+        ),
+        (StegError::ChecksumMismatch, "checksum_mismatch"),
+        (StegError::ContainerModified, "container_modified"),
+        (
+            StegError::PayloadCorrupted {
+                expected: vec![0; 32],
+                actual: vec![1; 32],
+            },
+            "payload_corrupted",
+        ),
+        (
+            StegError::NoPayloadFound {
+                cause: Box::new(StegError::HashFlagParse { flag: 0x7f }),
+            },
+            "no_payload_found",
+        ),
+        (
+            StegError::WrongSeedSuspected {
+                cause: Box::new(StegError::HashFlagParse { flag: 0x7f }),
+            },
+            "wrong_seed_suspected",
+        ),
+        (
+            StegError::PayloadTruncated {
+                required_bits: 2,
+                available_bits: 1,
+            },
+            "payload_truncated",
+        ),
+        (
+            StegError::CalculationOverflow(text()),
+            "calculation_overflow",
+        ),
+        (
+            StegError::CapacityExceedsUsizeMax(text()),
+            "capacity_exceeds_usize_max",
+        ),
+        (StegError::HashFlagParse { flag: 0x7f }, "hash_flag_parse"),
+        (StegError::UnsupportedFormat(text()), "unsupported_format"),
+        (
+            StegError::WatermarkTooLong { len: 300, max: 64 },
+            "watermark_too_long",
+        ),
+        (StegError::WatermarkNotFound, "watermark_not_found"),
+        (StegError::ExistingPayload(text()), "existing_payload"),
+        (
+            StegError::DimensionMismatch {
+                expected: (4, 3),
+                actual: (3, 4),
+            },
+            "dimension_mismatch",
+        ),
+        (
+            StegError::BufferSizeMismatch {
+                width: 4,
+                height: 3,
+                len: 35,
+            },
+            "buffer_size_mismatch",
+        ),
+        (StegError::ColorTypeMismatch(text()), "color_type_mismatch"),
+        (
+            StegError::InvalidBitPlane { channel: 3, bit: 0 },
+            "invalid_bit_plane",
+        ),
+        (
+            StegError::InvalidBlockSize { block_rows: 0 },
+            "invalid_block_size",
+        ),
+        (
+            StegError::SelfVerificationFailed(text()),
+            "self_verification_failed",
+        ),
+        (
+            StegError::UnexpectedChange {
+                x: 1,
+                y: 2,
+                channel: 0,
+                original: 0,
+                modified: 2,
+                lsbs: 1,
+            },
+            "unexpected_change",
+        ),
+        (StegError::UnsupportedOption(text()), "unsupported_option"),
+        (StegError::ThreadPool(text()), "thread_pool"),
+        (
+            StegError::PayloadTooLarge { len: 2, max: 1 },
+            "payload_too_large",
+        ),
+        (StegError::Cancelled, "cancelled"),
+        (
+            StegError::OutOfRange {
+                offset: 1,
+                len: 2,
+                data_len: 2,
+            },
+            "out_of_range",
+        ),
+        (StegError::Io(io()), "io"),
+    ]
+}
+
+#[test]
+fn test_codes_are_stable() {
+    for (err, code) in every_variant() {
+        assert_eq!(err.code(), code, "{:?}", err);
+        assert_eq!(err.report().code, code);
+        assert!(!err.to_string().is_empty());
+    }
+}
+
+#[test]
+fn test_report_details() {
+    let report = StegError::InsufficientCapacity {
+        required_bits: 368,
+        available_bits: 96,
+        missing_pixels: 12,
+    }
+    .report();
+
+    assert_eq!(report.kind, ErrorKind::InsufficientCapacity);
+    assert_eq!(
+        report.message,
+        "Insufficient container capacity: 368 bits required, 96 bits available, you need at \
+         least 12 more pixels"
+    );
+    assert_eq!(
+        report.details.into_iter().collect::<Vec<_>>(),
+        [
+            ("available_bits", ErrorDetail::Number(96)),
+            ("missing_pixels", ErrorDetail::Number(12)),
+            ("required_bits", ErrorDetail::Number(368)),
+        ]
+    );
+
+    let details = StegError::UnsupportedFormat("xyz".to_string())
+        .report()
+        .details;
+    assert_eq!(details["detail"], ErrorDetail::Text("xyz".to_string()));
+    assert!(StegError::ChecksumMismatch.report().details.is_empty());
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, order::KeyedPermutation, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Options on the way out, too.";
+
+#[test]
+fn test_extract_with_matches_extract() -> Result<(), Box<dyn std::error::Error>> {
+    let options = EmbedOptions::new().lsbs(3).hash(Hash::Sha256).seed(7);
+    let embedded = embed_with(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &options,
+    )?
+    .image;
+
+    let extracted = extract_with(&embedded, &ExtractOptions::from(&options))?;
+    assert_eq!(
+        (extracted.data.clone(), extracted.extension.clone()),
+        extract(&embedded, 3, 7)?
+    );
+    assert_eq!(extracted.lsbs, 3);
+    assert_eq!(extracted.hash, Hash::Sha256);
+
+    let auto = ExtractOptions::new().lsbs(LsbSelection::Auto).seed(7);
+    assert_eq!(extract_with(&embedded, &auto)?, extracted);
+
+    assert!(extract_with(&embedded, &ExtractOptions::new().lsbs(3)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_without_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &EmbedOptions::new(),
+    )?
+    .image;
+
+    // Flip the last bit of the data, which comes after all of the metadata
+    let mut image = ::image::load_from_memory(&embedded)?.into_rgb8();
+    let payload_bits = (4 + 1 + 3 + 1 + 8 + 32 + INPUT.len()) as u64 * 8;
+    let position = KeyedPermutation::new(42).position(64 * 64 * 3, payload_bits - 1);
+    *image.get_mut(position as usize).unwrap() ^= 1;
+
+    let mut corrupted = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut corrupted, ::image::ImageFormat::Png)?;
+    let corrupted = corrupted.into_inner();
+
+    assert!(extract_with(&corrupted, &ExtractOptions::new()).is_err());
+
+    let extracted = extract_with(&corrupted, &ExtractOptions::new().verify(false))?;
+    let mut expected = INPUT.to_vec();
+    *expected.last_mut().unwrap() ^= 1;
+    assert_eq!(extracted.data, expected);
+    assert_eq!(extracted.extension, "txt");
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_max_payload() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &EmbedOptions::new(),
+    )?
+    .image;
+
+    let err = extract_with(&embedded, &ExtractOptions::new().max_payload(16)).unwrap_err();
+    assert!(matches!(err, StegError::PayloadTooLarge { .. }), "{err}");
+
+    let length = 1 + 3 + 1 + 8 + 32 + INPUT.len();
+    assert!(extract_with(&embedded, &ExtractOptions::new().max_payload(length - 1)).is_err());
+    assert_eq!(
+        extract_with(&embedded, &ExtractOptions::new().max_payload(length))?.data,
+        INPUT
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_rejects_invalid_lsbs() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &EmbedOptions::default(),
+    )?
+    .image;
+    let image = ::image::load_from_memory(&embedded)?.into_rgb8();
+
+    for lsbs in [0, 9] {
+        let is_invalid = |result: Result<_, StegError>| matches!(result, Err(StegError::InvalidLsbValue { given, max: 8 }) if given == lsbs);
+
+        assert!(is_invalid(extract(&embedded, lsbs, 42).map(drop)));
+        // Rejected before decoding, so even bytes that are no image fail the same way
+        assert!(is_invalid(extract(b"not an image", lsbs, 42).map(drop)));
+        let options = ExtractOptions::new().lsbs(lsbs);
+        assert!(is_invalid(
+            extract_with(b"not an image", &options).map(drop)
+        ));
+        assert!(is_invalid(extract_image(&image, &options).map(drop)));
+        assert!(is_invalid(extract_from_image(&image, lsbs, 42).map(drop)));
+        assert!(is_invalid(
+            extract_to_writer(&embedded, Vec::new(), lsbs, 42).map(drop)
+        ));
+        assert!(is_invalid(
+            extract_range(b"not an image", 0, 1, lsbs, 42).map(drop)
+        ));
+        assert!(is_invalid(
+            extract_with_order(b"not an image", lsbs, &KeyedPermutation::new(42)).map(drop)
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, order::Sequential, *};
+
+const INPUT: &[u8] = b"Tell tampering apart from corruption.";
+/// The length field, extension length, "txt", hash flag, fingerprint and BLAKE3 digest.
+const HEADER_BITS: usize = (4 + 1 + 3 + 1 + 8 + 32) * 8;
+
+mod test_util;
+
+fn embedded() -> Result<::image::RgbImage, Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(48, 48, Format::Png);
+
+    let embedded = embed_with_order(
+        INPUT,
+        "txt",
+        &container,
+        &EmbedOptions::default(),
+        &Sequential,
+    )?;
+    Ok(::image::load_from_memory(&embedded)?.into_rgb8())
+}
+
+#[test]
+fn test_brightness_shift_is_container_modified() -> Result<(), Box<dyn std::error::Error>> {
+    let mut image = embedded()?;
+
+    // Brighten everything after the header, as an editor would, but keep the header readable
+    for sample in image.iter_mut().skip(HEADER_BITS) {
+        *sample = sample.saturating_add(1);
+    }
+
+    let result = extract_with_order(&test_util::png(&image), 1, &Sequential);
+    assert!(
+        matches!(result, Err(StegError::ContainerModified)),
+        "{:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_flipped_payload_bit_is_payload_corrupted() -> Result<(), Box<dyn std::error::Error>> {
+    let mut image = embedded()?;
+
+    // Flip the lowest bit of the first data sample only
+    let sample = &mut image.as_mut()[HEADER_BITS];
+    *sample ^= 1;
+
+    let result = extract_with_order(&test_util::png(&image), 1, &Sequential);
+    assert!(
+        matches!(result, Err(StegError::PayloadCorrupted { .. })),
+        "{:?}",
+        result
+    );
+
+    Ok(())
+}
@@ -0,0 +1,459 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{
+    error::{ErrorKind, StegError},
+    hash::Hash,
+    image::*,
+    *,
+};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Lossless formats keep every bit.";
+
+#[test]
+fn test_supported_formats_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(48, 48, Format::Png);
+
+    for &format in supported_output_formats() {
+        assert!(is_lossless(format));
+
+        let embedded = embed(INPUT, "txt", &container, 1, Hash::Blake3, 42, format)?;
+
+        let options = EmbedOptions {
+            format: Some(format),
+            ..EmbedOptions::default()
+        };
+        assert_only_lsbs_changed_with(&container, &embedded, &options)?;
+
+        // TGA has no signature, so it cannot be detected when extracting
+        if format == Format::Tga {
+            assert!(::image::guess_format(&embedded).is_err());
+            continue;
+        }
+
+        let (data, _) = extract(&embedded, 1, 42)?;
+        assert_eq!(data, INPUT, "Roundtrip failed for {:?}", format);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_lossy_formats_rejected() {
+    let container = test_util::encoded_container(48, 48, Format::Png);
+
+    for format in [Format::Jpeg, Format::Avif] {
+        assert!(!is_lossless(format));
+
+        let result = embed(INPUT, "txt", &container, 1, Hash::Blake3, 42, format);
+        assert!(
+            matches!(&result, Err(err @ StegError::UnsupportedOutputFormat { .. }) if err.to_string().contains("Png, WebP")),
+            "{:?} should be rejected with the supported formats",
+            format
+        );
+    }
+}
+
+#[test]
+fn test_output_format_from_extension() {
+    assert!(matches!(
+        output_format_from_extension("PNG"),
+        Ok(Format::Png)
+    ));
+    assert!(matches!(
+        output_format_from_extension("exr"),
+        Ok(Format::OpenExr)
+    ));
+    assert!(matches!(
+        output_format_from_extension("jpg"),
+        Err(StegError::UnsupportedOutputFormat { format, .. }) if format == "jpg"
+    ));
+    assert!(matches!(
+        output_format_from_extension("nope"),
+        Err(StegError::UnsupportedOutputFormat { .. })
+    ));
+    // Only extensions, unlike `parse_output_format`
+    assert!(output_format_from_extension("OpenExr").is_err());
+}
+
+#[test]
+fn test_parse_output_format() {
+    assert!(matches!(parse_output_format("png"), Ok(Format::Png)));
+    assert!(matches!(parse_output_format(".EXR"), Ok(Format::OpenExr)));
+    assert!(matches!(
+        parse_output_format("OpenExr"),
+        Ok(Format::OpenExr)
+    ));
+    assert!(matches!(
+        parse_output_format("farbfeld"),
+        Ok(Format::Farbfeld)
+    ));
+
+    let err = parse_output_format("JPG").unwrap_err();
+    let StegError::UnsupportedOutputFormat {
+        format,
+        reason,
+        supported,
+    } = &err
+    else {
+        panic!("{:?}", err);
+    };
+    assert_eq!(format, "JPG");
+    assert!(reason.contains("lossy"), "{}", reason);
+    assert_eq!(supported, supported_output_formats());
+    assert_eq!(err.kind(), ErrorKind::UnsupportedFormat);
+    assert!(
+        err.to_string().starts_with(
+            "Unsupported output format \"JPG\": it is lossy, which would destroy the payload, \
+             supported formats are: Png, WebP, Pnm"
+        ),
+        "{}",
+        err
+    );
+
+    let err = parse_output_format("nope").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("\"nope\": it is not a known image format"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_png_decodes_to_embedded_pixels() -> Result<(), Box<dyn std::error::Error>> {
+    let mut container = Vec::new();
+    generate_container(300, 200, NoiseStyle::GradientNoise, 42).write_to(
+        &mut std::io::Cursor::new(&mut container),
+        ::image::ImageFormat::Png,
+    )?;
+
+    // PNM stores the samples as they are, whichever PNG encoder is enabled
+    let png = embed(INPUT, "txt", &container, 2, Hash::Blake3, 42, Format::Png)?;
+    let pnm = embed(INPUT, "txt", &container, 2, Hash::Blake3, 42, Format::Pnm)?;
+
+    assert_eq!(
+        ::image::load_from_memory(&png)?.into_rgb8(),
+        ::image::load_from_memory(&pnm)?.into_rgb8()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_format_conversions() {
+    for image_format in ::image::ImageFormat::all() {
+        let format = Format::try_from(image_format).unwrap();
+        assert_eq!(
+            ::image::ImageFormat::try_from(format).unwrap(),
+            image_format
+        );
+        assert_eq!(format.is_lossless(), LOSSLESS_FORMATS.contains(&format));
+
+        for extension in format.extensions() {
+            assert_eq!(Format::from_extension(extension), Some(format));
+            assert_eq!(
+                Format::from_extension(&extension.to_uppercase()),
+                Some(format)
+            );
+        }
+
+        // Formats without a MIME type of their own are reported as plain bytes
+        let mime_type = format.mime_type();
+        if mime_type != "application/octet-stream" {
+            assert_eq!(
+                Format::from_mime_type(mime_type),
+                Some(format),
+                "{}",
+                mime_type
+            );
+        }
+    }
+
+    assert_eq!(Format::from_mime_type("image/png"), Some(Format::Png));
+    assert_eq!(Format::from_mime_type("image/jpeg"), Some(Format::Jpeg));
+    assert_eq!(Format::from_mime_type("text/plain"), None);
+    assert_eq!(Format::from_extension("jpeg"), Some(Format::Jpeg));
+    assert_eq!(Format::from_extension("nope"), None);
+}
+
+#[test]
+fn test_png_output_options() -> Result<(), Box<dyn std::error::Error>> {
+    let mut container = Vec::new();
+    generate_container(512, 384, NoiseStyle::GradientNoise, 7).write_to(
+        &mut std::io::Cursor::new(&mut container),
+        ::image::ImageFormat::Png,
+    )?;
+    let options = EmbedOptions::new().lsbs(2);
+    let pixels = ::image::load_from_memory(
+        &embed_with(
+            INPUT,
+            "txt",
+            &container,
+            &options.clone().format(Format::Pnm),
+        )?
+        .image,
+    )?
+    .into_rgb8();
+
+    let mut sizes = Vec::new();
+    for png_compression in [
+        PngCompression::Fast,
+        PngCompression::Default,
+        PngCompression::Best,
+    ] {
+        for png_filter in [
+            PngFilter::NoFilter,
+            PngFilter::Sub,
+            PngFilter::Up,
+            PngFilter::Avg,
+            PngFilter::Paeth,
+            PngFilter::Adaptive,
+        ] {
+            let output = OutputOptions::new()
+                .png_compression(png_compression)
+                .png_filter(png_filter);
+            let embedded =
+                embed_with(INPUT, "txt", &container, &options.clone().output(output))?.image;
+
+            // Only the size of the file depends on how it is encoded
+            assert_eq!(::image::load_from_memory(&embedded)?.into_rgb8(), pixels);
+            assert_eq!(extract(&embedded, 2, 42)?.0, INPUT);
+
+            if png_filter == PngFilter::Adaptive {
+                sizes.push(embedded.len());
+            }
+        }
+    }
+
+    assert!(sizes[2] <= sizes[0], "{:?}", sizes);
+
+    Ok(())
+}
+
+#[test]
+fn test_webp_output_is_lossless() -> Result<(), Box<dyn std::error::Error>> {
+    let mut container = Vec::new();
+    generate_container(160, 120, NoiseStyle::GradientNoise, 3).write_to(
+        &mut std::io::Cursor::new(&mut container),
+        ::image::ImageFormat::Png,
+    )?;
+
+    for lsbs in [1, 4, 8] {
+        let options = EmbedOptions::new().lsbs(lsbs).format(Format::WebP);
+        let webp = embed_with(INPUT, "txt", &container, &options)?.image;
+
+        // A RIFF container holding a lossless VP8L bitstream, not a lossy VP8 one
+        assert_eq!(&webp[..4], b"RIFF");
+        assert_eq!(&webp[8..16], b"WEBPVP8L");
+
+        let pnm = embed_with(INPUT, "txt", &container, &options.format(Format::Pnm))?.image;
+        assert_eq!(
+            ::image::load_from_memory(&webp)?.into_rgb8(),
+            ::image::load_from_memory(&pnm)?.into_rgb8()
+        );
+        assert_eq!(extract(&webp, lsbs, 42)?.0, INPUT);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_tiff_compression() -> Result<(), Box<dyn std::error::Error>> {
+    // Smooth like a scan or a screenshot, which LZW compresses too, unlike noise
+    let mut container = Vec::new();
+    ::image::RgbImage::from_fn(512, 384, |x, y| {
+        ::image::Rgb([(x / 2) as u8, (y / 2) as u8, ((x + y) / 4) as u8])
+    })
+    .write_to(
+        &mut std::io::Cursor::new(&mut container),
+        ::image::ImageFormat::Png,
+    )?;
+    let options = EmbedOptions::new().lsbs(2).format(Format::Tiff);
+    let pixels = ::image::load_from_memory(
+        &embed_with(
+            INPUT,
+            "txt",
+            &container,
+            &options.clone().format(Format::Pnm),
+        )?
+        .image,
+    )?
+    .into_rgb8();
+
+    let mut sizes = Vec::new();
+    for tiff_compression in [
+        TiffCompression::None,
+        TiffCompression::Lzw,
+        TiffCompression::Deflate,
+    ] {
+        let output = OutputOptions::new().tiff_compression(tiff_compression);
+        let embedded = embed_with(INPUT, "txt", &container, &options.clone().output(output))?.image;
+
+        assert_eq!(::image::load_from_memory(&embedded)?.into_rgb8(), pixels);
+        assert_eq!(extract(&embedded, 2, 42)?.0, INPUT);
+        sizes.push(embedded.len());
+    }
+
+    // Uncompressed output stays as it was, and compression is not silently ignored
+    assert_eq!(
+        sizes[0],
+        embed_with(INPUT, "txt", &container, &options)?.image.len()
+    );
+    assert!(sizes[1] < sizes[0], "{:?}", sizes);
+    assert!(sizes[2] < sizes[0], "{:?}", sizes);
+
+    Ok(())
+}
+
+#[test]
+fn test_default_format_follows_container() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(48, 48, Format::WebP);
+
+    let embedded = embed_with(INPUT, "txt", &container, &EmbedOptions::new())?.image;
+    assert_eq!(
+        ::image::guess_format(&embedded)?,
+        ::image::ImageFormat::WebP
+    );
+    assert_eq!(extract(&embedded, 1, 42)?.0, INPUT);
+
+    // An explicit format still wins
+    let embedded = embed(INPUT, "txt", &container, 1, Hash::Blake3, 42, Format::Png)?;
+    assert_eq!(::image::guess_format(&embedded)?, ::image::ImageFormat::Png);
+
+    Ok(())
+}
+
+#[test]
+fn test_default_format_rejects_lossy_container() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(48, 48, Format::Jpeg);
+
+    let result = embed_with(INPUT, "txt", &container, &EmbedOptions::new());
+    assert!(
+        matches!(&result, Err(StegError::UnsupportedFormat(msg)) if msg.contains("Jpeg") && msg.contains("Png")),
+        "A JPEG container should need an output format, got {:?}",
+        result
+    );
+    assert!(matches!(
+        plan_embed(INPUT.len(), "txt", &container, &EmbedOptions::new()),
+        Err(StegError::UnsupportedFormat(_))
+    ));
+
+    let options = EmbedOptions::new().format(Format::Png);
+    let embedded = embed_with(INPUT, "txt", &container, &options)?.image;
+    assert_eq!(extract(&embedded, 1, 42)?.0, INPUT);
+
+    Ok(())
+}
+
+#[test]
+fn test_output_dimension_limits() -> Result<(), Box<dyn std::error::Error>> {
+    let container_of = |width, height| {
+        let image = ::image::RgbImage::from_fn(width, height, |x, y| {
+            ::image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+        });
+        let mut output = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut output, ::image::ImageFormat::Png)
+            .unwrap();
+        output.into_inner()
+    };
+
+    for (format, largest, limit) in [
+        (Format::Ico, 256, "256x256"),
+        (Format::WebP, 16383, "16383x16383"),
+        (Format::Tga, 65535, "65535x65535"),
+    ] {
+        let options = EmbedOptions::new().format(format);
+
+        // Rejected before any bits are written, whichever side is too long
+        for container in [container_of(largest + 1, 4), container_of(4, largest + 1)] {
+            let result = embed_with(INPUT, "txt", &container, &options);
+            assert!(
+                matches!(&result, Err(StegError::UnsupportedFormat(msg)) if msg.contains(limit)),
+                "{:?} should reject an oversized container, got {:?}",
+                format,
+                result.map(|_| ())
+            );
+            assert!(matches!(
+                plan_embed(INPUT.len(), "txt", &container, &options),
+                Err(StegError::UnsupportedFormat(_))
+            ));
+            assert!(matches!(
+                strip(&container, 1, format),
+                Err(StegError::UnsupportedFormat(_))
+            ));
+        }
+
+        let embedded = embed_with(INPUT, "txt", &container_of(largest, 4), &options)?.image;
+        let embedded = StegoContainer::with_format(&embedded, format)?;
+        assert_eq!(Stego::from(options).extract(&embedded)?.0, INPUT);
+    }
+
+    // Upscaling may not grow the output past the limit either
+    let options =
+        EmbedOptions::new()
+            .format(Format::Ico)
+            .resize_policy(ResizePolicy::UpscaleToFit {
+                max_scale: 100.0,
+                filter: FilterType::Nearest,
+            });
+    assert!(matches!(
+        embed_with(&[0; 30_000], "bin", &container_of(200, 200), &options),
+        Err(StegError::UnsupportedFormat(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_output_size_limits() {
+    // Only the header of the container is read for a plan, so it needs no pixels
+    let header = |width: u32, height: u32| format!("P5 {} {} 255\n", width, height).into_bytes();
+
+    for (format, container) in [
+        (Format::Bmp, header(40_000, 40_000)),
+        (Format::Qoi, header(20_001, 20_000)),
+        (Format::Png, header(1 << 31, 1)),
+        (Format::Hdr, header(4_000_000_000, 4_000_000_000)),
+        (Format::Farbfeld, header(4_000_000_000, 4_000_000_000)),
+    ] {
+        let result = plan_embed(
+            INPUT.len(),
+            "txt",
+            &container,
+            &EmbedOptions::new().format(format),
+        );
+        assert!(
+            matches!(&result, Err(StegError::UnsupportedFormat(msg)) if msg.contains(&format!("{:?}", format))),
+            "{:?} should reject an oversized container, got {:?}",
+            format,
+            result
+        );
+    }
+
+    // Within the limits, the capacity is what fails
+    assert!(matches!(
+        plan_embed(
+            INPUT.len(),
+            "txt",
+            &header(4, 4),
+            &EmbedOptions::new().format(Format::Ico)
+        ),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+}
+
+#[cfg(not(feature = "jxl"))]
+#[test]
+fn test_jxl_needs_feature() {
+    let container = include_bytes!("../../data/container.jxl");
+
+    let result = embed_with(INPUT, "txt", container, &EmbedOptions::new());
+    assert!(matches!(result, Err(StegError::UnsupportedFormat(_))));
+    assert!(matches!(
+        output_format_from_extension("jxl"),
+        Err(StegError::UnsupportedOutputFormat { reason, .. }) if reason.contains("`jxl` feature")
+    ));
+}
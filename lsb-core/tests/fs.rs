@@ -0,0 +1,195 @@
+#![cfg(feature = "std")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+const INPUT: &[u8] = b"Straight from the disk and back.";
+
+/// A directory of its own for every test, removed when it is dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("lsb-fs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn write_container(path: &Path) {
+    ::image::RgbImage::from_fn(64, 48, |x, y| {
+        ::image::Rgb([(x * 3) as u8, (y * 5) as u8, (x ^ y) as u8])
+    })
+    .save(path)
+    .unwrap();
+}
+
+fn files(dir: &TempDir) -> Vec<String> {
+    let mut files = fs::read_dir(&dir.0)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_path_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new("roundtrip");
+    write_container(&dir.join("container.png"));
+    fs::write(dir.join("secret.txt"), INPUT)?;
+    fs::create_dir(dir.join("out"))?;
+
+    let options = EmbedOptions::new().lsbs(2).hash(Hash::Sha256).seed(11);
+    let report = embed_path(
+        &dir.join("secret.txt"),
+        &dir.join("container.png"),
+        &dir.join("holder.v1.bmp"),
+        &options,
+    )?;
+    assert_eq!(report.data_bytes, INPUT.len());
+    assert_eq!((report.width, report.height, report.lsbs), (64, 48, 2));
+
+    // The output format follows the output path, not the options
+    let output = fs::read(dir.join("holder.v1.bmp"))?;
+    assert_eq!(::image::guess_format(&output)?, ::image::ImageFormat::Bmp);
+    assert_eq!(
+        output,
+        embed_with(
+            INPUT,
+            "txt",
+            &fs::read(dir.join("container.png"))?,
+            &options.clone().format(Format::Bmp)
+        )?
+        .image
+    );
+
+    let (extracted, info) = extract_path(
+        &dir.join("holder.v1.bmp"),
+        &dir.join("out"),
+        &ExtractOptions::from(&options),
+    )?;
+    assert_eq!(extracted, dir.join("out").join("holder.v1.txt"));
+    assert_eq!(fs::read(&extracted)?, INPUT);
+    assert_eq!((&*info.extension, info.hash), ("txt", Hash::Sha256));
+
+    Ok(())
+}
+
+#[test]
+fn test_path_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new("defaults");
+    write_container(&dir.join("container.png"));
+    fs::write(dir.join("secret"), INPUT)?;
+
+    embed_path(
+        &dir.join("secret"),
+        &dir.join("container.png"),
+        &dir.join("embedded.png"),
+        &EmbedOptions::new(),
+    )?;
+
+    let options = ExtractOptions::new().lsbs(LsbSelection::Auto);
+    let (extracted, _) = extract_path(&dir.join("embedded.png"), &dir.0, &options)?;
+    assert_eq!(extracted, dir.join("embedded.bin"));
+    assert_eq!(fs::read(&extracted)?, INPUT);
+
+    Ok(())
+}
+
+#[test]
+fn test_path_empty_file() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new("empty");
+    write_container(&dir.join("container.png"));
+    fs::write(dir.join("marker.txt"), [])?;
+    fs::create_dir(dir.join("out"))?;
+
+    let report = embed_path(
+        &dir.join("marker.txt"),
+        &dir.join("container.png"),
+        &dir.join("embedded.png"),
+        &EmbedOptions::new(),
+    )?;
+    assert_eq!(report.data_bytes, 0);
+
+    let (extracted, info) = extract_path(
+        &dir.join("embedded.png"),
+        &dir.join("out"),
+        &ExtractOptions::new(),
+    )?;
+    assert_eq!(extracted, dir.join("out").join("embedded.txt"));
+    assert_eq!(info.data_bytes, 0);
+    assert_eq!(fs::read(&extracted)?, []);
+
+    Ok(())
+}
+
+#[test]
+fn test_path_errors_name_the_path() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = TempDir::new("errors");
+    write_container(&dir.join("container.png"));
+    fs::write(dir.join("secret.txt"), INPUT)?;
+
+    let missing = dir.join("missing.txt");
+    let result = embed_path(
+        &missing,
+        &dir.join("container.png"),
+        &dir.join("embedded.png"),
+        &EmbedOptions::new(),
+    );
+    assert!(
+        matches!(&result, Err(StegError::Io(e)) if e.to_string().contains(&*missing.to_string_lossy())),
+        "{:?}",
+        result
+    );
+
+    let result = embed_path(
+        &dir.join("secret.txt"),
+        &dir.join("container.png"),
+        &dir.join("embedded.jpg"),
+        &EmbedOptions::new(),
+    );
+    assert!(matches!(
+        result,
+        Err(StegError::UnsupportedOutputFormat { .. })
+    ));
+
+    // A failed embedding or extraction leaves no file behind
+    let result = embed_path(
+        &dir.join("secret.txt"),
+        &dir.join("container.png"),
+        &dir.join("embedded.png"),
+        &EmbedOptions::new().lsbs(9),
+    );
+    assert!(matches!(result, Err(StegError::InvalidLsbValue { .. })));
+
+    let result = extract_path(&dir.join("container.png"), &dir.0, &ExtractOptions::new());
+    assert!(result.is_err());
+
+    let missing = dir.join("missing");
+    let result = extract_path(&dir.join("container.png"), &missing, &ExtractOptions::new());
+    assert!(
+        matches!(&result, Err(StegError::Io(e)) if e.to_string().contains(&*missing.to_string_lossy())),
+        "{:?}",
+        result
+    );
+
+    assert_eq!(files(&dir), ["container.png", "secret.txt"]);
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{
+    hash::Hash,
+    image::{NoiseStyle, generate_container, generate_container_for},
+    *,
+};
+
+mod test_util;
+
+#[test]
+fn test_generated_container_fits_payload() -> Result<(), Box<dyn std::error::Error>> {
+    for (len, lsbs, style) in [
+        (1000, 1, NoiseStyle::Uniform),
+        (5000, 3, NoiseStyle::GradientNoise),
+    ] {
+        let input = vec![0x42; len];
+        let container = generate_container_for(len, lsbs, Hash::Sha256, "bin", style, 5)?;
+
+        let embedded = embed(
+            &input,
+            "bin",
+            &test_util::png(&container),
+            lsbs,
+            Hash::Sha256,
+            42,
+            Format::Png,
+        )?;
+        assert_eq!(extract(&embedded, lsbs, 42)?.0, input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_generated_container_is_deterministic() {
+    for style in [NoiseStyle::Uniform, NoiseStyle::GradientNoise] {
+        let container = generate_container(40, 30, style, 8);
+
+        assert_eq!(container.dimensions(), (40, 30));
+        assert_eq!(container, generate_container(40, 30, style, 8));
+        assert_ne!(container, generate_container(40, 30, style, 9));
+    }
+}
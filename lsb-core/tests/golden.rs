@@ -0,0 +1,107 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{image::*, *};
+
+const INPUT: &[u8] = b"Golden output";
+const GOLDEN_PNG: &[u8] = include_bytes!("../../data/golden-v1.png");
+const GOLDEN_WEBP: &[u8] = include_bytes!("../../data/golden-v1.webp");
+const GOLDEN_TIFF: &[u8] = include_bytes!("../../data/golden-v1.tiff");
+
+fn container() -> Vec<u8> {
+    let mut container = Vec::new();
+    generate_container(64, 48, NoiseStyle::GradientNoise, 11)
+        .write_to(
+            &mut std::io::Cursor::new(&mut container),
+            ::image::ImageFormat::Png,
+        )
+        .unwrap();
+    container
+}
+
+/// The outputs recorded in `data/`, with the options they were embedded with. The PNG
+/// filter is given, as its default depends on the `fast-png` feature.
+fn goldens() -> [(EmbedOptions, &'static [u8]); 3] {
+    let options = EmbedOptions::new().lsbs(2);
+    [
+        (
+            options
+                .clone()
+                .output(OutputOptions::new().png_filter(PngFilter::Adaptive)),
+            GOLDEN_PNG,
+        ),
+        (options.clone().format(Format::WebP), GOLDEN_WEBP),
+        (
+            options
+                .format(Format::Tiff)
+                .output(OutputOptions::new().tiff_compression(TiffCompression::Deflate)),
+            GOLDEN_TIFF,
+        ),
+    ]
+}
+
+/// Fresh embeddings must match the recorded ones byte for byte. An intended change of
+/// the output bumps `FORMAT_OUTPUT_VERSION` and records new `data/golden-v*` files.
+#[test]
+fn test_output_matches_golden_files() -> Result<(), Box<dyn std::error::Error>> {
+    assert_eq!(
+        FORMAT_OUTPUT_VERSION, 1,
+        "Record golden files for the new version"
+    );
+    let container = container();
+
+    for (options, golden) in goldens() {
+        let embedded = embed_with(INPUT, "txt", &container, &options)?.image;
+        assert!(
+            embedded == golden,
+            "{:?} output differs from its golden file",
+            options.format
+        );
+
+        // Streaming the output writes the same bytes
+        let mut streamed = std::io::Cursor::new(Vec::new());
+        embed_to_writer(INPUT, "txt", &container, &options, &mut streamed)?;
+        assert!(streamed.get_ref() == golden);
+    }
+
+    Ok(())
+}
+
+/// Pins what stays the same even if upstream compressors change their bytes: the
+/// pixels of every output format and the layout of PNG chunks.
+#[test]
+fn test_output_structure_matches_golden_files() -> Result<(), Box<dyn std::error::Error>> {
+    let container = container();
+    let pixels = ::image::load_from_memory(GOLDEN_PNG)?.into_rgb8();
+
+    for (options, golden) in goldens() {
+        assert_eq!(::image::load_from_memory(golden)?.into_rgb8(), pixels);
+        assert_eq!(extract(golden, 2, 42)?.0, INPUT);
+
+        let embedded = embed_with(INPUT, "txt", &container, &options)?.image;
+        assert_eq!(::image::load_from_memory(&embedded)?.into_rgb8(), pixels);
+    }
+
+    // Signature, then IHDR, a single IDAT and IEND
+    let mut kinds = Vec::new();
+    let mut rest = &GOLDEN_PNG[8..];
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest[..4].try_into()?) as usize;
+        kinds.push(&rest[4..8]);
+        rest = &rest[12 + length..];
+    }
+    assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+    assert_eq!(&GOLDEN_WEBP[8..16], b"WEBPVP8L");
+
+    // The formats the `image` crate writes keep the same pixels too
+    for &format in supported_output_formats() {
+        if matches!(format, Format::Hdr | Format::OpenExr | Format::Jxl) {
+            continue;
+        }
+        let options = EmbedOptions::new().lsbs(2).format(format);
+        let embedded = embed_with(INPUT, "txt", &container, &options)?.image;
+        let decoded = ::image::load_from_memory_with_format(&embedded, format.try_into()?)?;
+        assert_eq!(decoded.into_rgb8(), pixels, "{:?}", format);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use lsb_core::hash::{HASH_NAMES, Hash, HashParseError};
+
+#[test]
+fn test_accepted_hash_names() {
+    let accepted = [
+        ("BLAKE3", Hash::Blake3),
+        ("blake3", Hash::Blake3),
+        ("Blake3", Hash::Blake3),
+        ("blake-3", Hash::Blake3),
+        ("BLAKE_3", Hash::Blake3),
+        ("SHA256", Hash::Sha256),
+        ("sha256", Hash::Sha256),
+        ("sha-256", Hash::Sha256),
+        ("SHA_256", Hash::Sha256),
+        ("sha2-256", Hash::Sha256),
+        ("Sha2_256", Hash::Sha256),
+        ("SHA512", Hash::Sha512),
+        ("sha-512", Hash::Sha512),
+        ("sha2-512", Hash::Sha512),
+        ("SHA1", Hash::Sha1),
+        ("sha-1", Hash::Sha1),
+    ];
+
+    for (name, hash) in accepted {
+        assert_eq!(Hash::parse(name), Ok(hash), "{}", name);
+        assert_eq!(name.parse::<Hash>(), Ok(hash), "{}", name);
+    }
+}
+
+#[test]
+fn test_every_listed_name_parses() {
+    for name in HASH_NAMES {
+        assert!(Hash::parse(name).is_ok(), "{}", name);
+        assert!(Hash::parse(&name.to_ascii_uppercase()).is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn test_rejected_hash_names() {
+    let rejected = [
+        "", "md5", "sha", "sha-2", "sha3-256", "sha384", "blake2", "blake", " sha256", "sha256 ",
+        "sha--256", "sha 256", "sha2561",
+    ];
+
+    for name in rejected {
+        let err: HashParseError = Hash::parse(name).unwrap_err();
+        assert_eq!(err.name(), name);
+        assert_eq!(err.accepted(), HASH_NAMES);
+    }
+}
+
+#[test]
+fn test_parse_error_lists_accepted_names() {
+    let message = Hash::parse("md5").unwrap_err().to_string();
+
+    assert!(message.contains("\"md5\""), "{}", message);
+    for name in HASH_NAMES {
+        assert!(message.contains(name), "{}", message);
+    }
+}
+
+#[test]
+fn test_digest_sizes_match_the_hashers() {
+    use sha2::Digest;
+
+    for &hash in Hash::all() {
+        let size = match hash {
+            Hash::Blake3 => blake3::hash(b"").as_bytes().len(),
+            Hash::Sha256 => sha2::Sha256::output_size(),
+            Hash::Sha512 => sha2::Sha512::output_size(),
+            Hash::Sha1 => sha1::Sha1::output_size(),
+        };
+        assert_eq!(hash.digest_size(), size, "{}", hash);
+    }
+}
+
+#[test]
+fn test_display_round_trips() {
+    assert_eq!(Hash::all()[0], Hash::Blake3);
+
+    for &hash in Hash::all() {
+        assert_eq!(hash.to_string(), hash.name());
+        assert!(HASH_NAMES.contains(&hash.name()), "{}", hash);
+        assert_eq!(Hash::parse(&hash.to_string()), Ok(hash));
+    }
+    for name in HASH_NAMES {
+        assert!(
+            Hash::all().contains(&Hash::parse(name).unwrap()),
+            "{}",
+            name
+        );
+    }
+}
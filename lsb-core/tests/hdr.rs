@@ -0,0 +1,68 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+const CONTAINER: &[u8] = include_bytes!("../../data/container.hdr");
+const INPUT: &[u8] = b"High dynamic range containers keep their float samples.";
+
+fn max_sample(image: &[u8]) -> f32 {
+    let image = ::image::load_from_memory(image).unwrap().into_rgb32f();
+    image.pixels().flat_map(|p| p.0).fold(0.0, f32::max)
+}
+
+#[test]
+fn test_hdr_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    for lsbs in [1, 4, 7] {
+        let embedded = embed(INPUT, "txt", CONTAINER, lsbs, Hash::Sha256, 42, Format::Hdr)?;
+
+        let (data, ext) = extract(&embedded, lsbs, 42)?;
+
+        assert_eq!(data, INPUT, "Extracted data does not match input");
+        assert_eq!(ext, "txt");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_hdr_keeps_dynamic_range() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed(INPUT, "txt", CONTAINER, 1, Hash::Blake3, 42, Format::Hdr)?;
+
+    let original = max_sample(CONTAINER);
+    assert!(original > 1.0, "Fixture should exceed the 8-bit range");
+    assert!(
+        (max_sample(&embedded) - original).abs() / original < 0.01,
+        "Embedding must not clip the dynamic range"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_hdr_rejects_top_mantissa_bit() {
+    let result = embed(INPUT, "txt", CONTAINER, 8, Hash::Blake3, 42, Format::Hdr);
+
+    assert!(matches!(result, Err(StegError::InvalidLsbValue { .. })));
+}
+
+#[test]
+fn test_exr_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    for lsbs in [1, 8] {
+        let embedded = embed(
+            INPUT,
+            "txt",
+            CONTAINER,
+            lsbs,
+            Hash::Sha1,
+            7,
+            Format::OpenExr,
+        )?;
+
+        assert!(max_sample(&embedded) > 1.0);
+
+        let (data, _) = extract(&embedded, lsbs, 7)?;
+        assert_eq!(data, INPUT, "Extracted data does not match input");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+mod test_util;
+
+#[test]
+fn test_in_place_matches_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    let input = b"No decoding, no encoding".repeat(10);
+
+    for lsbs in [1, 3, 8] {
+        let mut in_place = test_util::container(64, 48);
+        embed_into_image(&mut in_place, &input, "txt", lsbs, Hash::Sha256, 9)?;
+
+        let embedded = embed(
+            &input,
+            "txt",
+            &test_util::encoded_container(64, 48, Format::Png),
+            lsbs,
+            Hash::Sha256,
+            9,
+            Format::Png,
+        )?;
+        assert_eq!(in_place, ::image::load_from_memory(&embedded)?.into_rgb8());
+
+        let (data, ext) = extract_from_image(&in_place, lsbs, 9)?;
+        assert_eq!(data, input);
+        assert_eq!(ext, "txt");
+        assert_eq!(extract(&test_util::png(&in_place), lsbs, 9)?, (data, ext));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_in_place_error_leaves_image() {
+    let mut container = test_util::container(8, 8);
+
+    assert!(matches!(
+        embed_into_image(&mut container, &[0; 1024], "bin", 1, Hash::Blake3, 42),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+    assert_eq!(container, test_util::container(8, 8));
+}
+
+#[test]
+fn test_degenerate_images() -> Result<(), Box<dyn std::error::Error>> {
+    for (width, height) in [(0, 0), (0, 16), (1, 1)] {
+        let mut container = test_util::container(width, height);
+
+        let result = embed_into_image(&mut container, &[], "", 8, Hash::Blake3, 42);
+        // An empty payload still needs 4 + 1 + 1 + 8 + 32 bytes of header, in 16 pixels
+        assert!(
+            matches!(
+                result,
+                Err(StegError::InsufficientCapacity { required_bits: 368, available_bits, .. })
+                    if available_bits == u64::from(width * height) * 3 * 8
+            ),
+            "{:?}",
+            result
+        );
+
+        let result = extract_from_image(&container, 1, 42);
+        assert!(
+            matches!(result, Err(StegError::InsufficientCapacity { .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    // The smallest container of an empty payload
+    let mut container = test_util::container(4, 4);
+    embed_into_image(&mut container, &[], "", 8, Hash::Blake3, 42)?;
+    assert_eq!(
+        extract_from_image(&container, 8, 42)?,
+        (Vec::new(), String::new())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_image_matches_embed_with() -> Result<(), Box<dyn std::error::Error>> {
+    // Too large for 1 lsb, so the last options upscale the container
+    let input = b"Straight from the camera".repeat(60);
+    let container = test_util::container(64, 48);
+
+    for options in [
+        EmbedOptions::new().lsbs(2).hash(Hash::Sha256).seed(9),
+        EmbedOptions::new()
+            .lsbs(LsbSelection::Auto)
+            .verify_after_embed(true),
+        EmbedOptions::new().resize_policy(ResizePolicy::UpscaleToFit {
+            max_scale: 4.0,
+            filter: ::image::imageops::FilterType::Nearest,
+        }),
+    ] {
+        let embedded = embed_image(&container, &input, "txt", &options)?;
+        assert_eq!(
+            embedded,
+            ::image::load_from_memory(
+                &embed_with(&input, "txt", &test_util::png(&container), &options)?.image
+            )?
+            .into_rgb8()
+        );
+
+        let extracted = extract_image(&embedded, &ExtractOptions::from(&options))?;
+        assert_eq!(extracted.data, input);
+        assert_eq!(extracted.extension, "txt");
+        assert_eq!(
+            extracted,
+            extract_image(
+                &embedded,
+                &ExtractOptions::new()
+                    .lsbs(LsbSelection::Auto)
+                    .seed(options.seed)
+            )?
+        );
+    }
+
+    // The container itself is left untouched
+    assert_eq!(container, test_util::container(64, 48));
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_image_validates_like_embed_with() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::container(64, 48);
+    let embedded = embed_image(&container, b"first", "txt", &EmbedOptions::new())?;
+
+    let options = EmbedOptions::new().overwrite_policy(OverwritePolicy::Error);
+    assert!(matches!(
+        embed_image(&embedded, b"second", "txt", &options),
+        Err(StegError::ExistingPayload(_))
+    ));
+
+    for format in [Format::Jpeg, Format::Hdr] {
+        let result = embed_image(
+            &container,
+            b"data",
+            "txt",
+            &EmbedOptions::new().format(format),
+        );
+        assert!(result.is_err(), "{:?}", format);
+    }
+
+    assert!(matches!(
+        embed_image(&container, b"data", "txt", &EmbedOptions::new().lsbs(9)),
+        Err(StegError::InvalidLsbValue { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_rgb_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let input = b"Frame after frame".repeat(5);
+
+    for lsbs in [1, 4, 8] {
+        let mut pixels = test_util::container(40, 30).into_raw();
+        embed_raw_rgb(&input, "bin", &mut pixels, 40, 30, lsbs, Hash::Blake3, 5)?;
+
+        let mut in_place = test_util::container(40, 30);
+        embed_into_image(&mut in_place, &input, "bin", lsbs, Hash::Blake3, 5)?;
+        assert_eq!(pixels, in_place.into_raw());
+
+        let (data, ext) = extract_raw_rgb(&pixels, 40, 30, lsbs, 5)?;
+        assert_eq!(data, input);
+        assert_eq!(ext, "bin");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_rgb_length_mismatch() {
+    let original = test_util::container(40, 30).into_raw();
+
+    for (width, height) in [(40, 31), (30, 40 + 1), (u32::MAX, u32::MAX)] {
+        let mut pixels = original.clone();
+        let result = embed_raw_rgb(
+            b"data",
+            "txt",
+            &mut pixels,
+            width,
+            height,
+            1,
+            Hash::Blake3,
+            5,
+        );
+        assert!(
+            matches!(
+                result,
+                Err(StegError::BufferSizeMismatch { width: w, height: h, len: 3600 })
+                    if (w, h) == (width, height)
+            ),
+            "{:?}",
+            result
+        );
+        assert_eq!(pixels, original);
+
+        assert!(matches!(
+            extract_raw_rgb(&pixels, width, height, 1, 5),
+            Err(StegError::BufferSizeMismatch { .. })
+        ));
+    }
+
+    // A buffer that is too long is rejected as well
+    let mut pixels = [original.as_slice(), &[0; 3]].concat();
+    assert!(matches!(
+        embed_raw_rgb(b"data", "txt", &mut pixels, 40, 30, 1, Hash::Blake3, 5),
+        Err(StegError::BufferSizeMismatch { .. })
+    ));
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{
+    error::{ErrorKind, StegError},
+    hash::Hash,
+    layout::LENGTH_SIZE,
+    order::KeyedPermutation,
+    *,
+};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Inspected, never extracted.";
+
+/// Embeds `INPUT` with `lsbs` and seed 3, then applies `change` to the samples.
+fn embedded(lsbs: usize, change: impl FnOnce(&mut [u8])) -> Vec<u8> {
+    let mut image = test_util::container(40, 30);
+    embed_into_image(&mut image, INPUT, "txt", lsbs, Hash::Sha256, 3).unwrap();
+
+    change(image.as_mut());
+    test_util::png(&image)
+}
+
+/// Flips the last data byte, leaving the rest of the payload and the container intact.
+fn flip_last_byte(samples: &mut [u8], lsbs: usize) {
+    let mut plane = Plane::new(samples, 40, 30).unwrap();
+    let order = KeyedPermutation::new(3);
+    let length = plane.read_bytes(lsbs, &order, 0, LENGTH_SIZE).unwrap();
+    let last = LENGTH_SIZE + u32::from_le_bytes(length.try_into().unwrap()) as usize - 1;
+    let byte = plane.read_bytes(lsbs, &order, last, 1).unwrap()[0];
+    plane.write_bytes(&[!byte], lsbs, &order, last).unwrap();
+}
+
+#[test]
+fn test_inspect_matches_extraction() -> Result<(), Box<dyn std::error::Error>> {
+    for lsbs in [1, 2, 5] {
+        let input = embedded(lsbs, |_| {});
+        let options = ExtractOptions::new().lsbs(lsbs).seed(3);
+
+        let inspection = inspect(&input, &options)?;
+        let extracted = extract_with(&input, &options)?;
+        assert_eq!(inspection.extension, extracted.extension);
+        assert_eq!(inspection.data_bytes, extracted.data.len() as u64);
+        assert_eq!(inspection.lsbs, lsbs);
+        assert_eq!(inspection.hash, Hash::Sha256);
+        assert_eq!(inspection.checksum, ChecksumStatus::Valid);
+
+        let inspection = inspect(&input, &options.clone().lsbs(LsbSelection::Auto))?;
+        assert_eq!(inspection.lsbs, lsbs);
+        assert_eq!(inspection.checksum, ChecksumStatus::Valid);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_inspect_reports_checksums() -> Result<(), Box<dyn std::error::Error>> {
+    let options = ExtractOptions::new().lsbs(2).seed(3);
+
+    let corrupted = embedded(2, |samples| flip_last_byte(samples, 2));
+    let inspection = inspect(&corrupted, &options)?;
+    assert_eq!(inspection.checksum, ChecksumStatus::Corrupted);
+    assert_eq!(inspection.data_bytes, INPUT.len() as u64);
+    assert!(matches!(
+        extract_with(&corrupted, &options),
+        Err(StegError::PayloadCorrupted { .. })
+    ));
+
+    let modified = embedded(2, |samples| {
+        flip_last_byte(samples, 2);
+        // Above the bits of the payload
+        samples[0] ^= 0x80;
+    });
+    assert_eq!(
+        inspect(&modified, &options)?.checksum,
+        ChecksumStatus::ContainerModified
+    );
+
+    // Without verification, only the header is read
+    assert_eq!(
+        inspect(&corrupted, &options.clone().verify(false))?.checksum,
+        ChecksumStatus::Unverified
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_inspect_without_payload() {
+    let clean = test_util::png(&test_util::container(40, 30));
+
+    for lsbs in [LsbSelection::Fixed(1), LsbSelection::Auto] {
+        let result = inspect(&clean, &ExtractOptions::new().lsbs(lsbs).seed(3));
+        assert_eq!(result.map_err(|err| err.kind()), Err(ErrorKind::NotFound));
+    }
+
+    let result = inspect(&embedded(1, |_| {}), &ExtractOptions::new().seed(4));
+    assert_eq!(result.map_err(|err| err.kind()), Err(ErrorKind::NotFound));
+}
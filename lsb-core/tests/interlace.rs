@@ -0,0 +1,118 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{image::*, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Seven passes, one order.";
+/// A 37x29 Adam7-interlaced PNG of `pixels`, written by a separate encoder.
+const INTERLACED: &[u8] = include_bytes!("../../data/interlaced.png");
+
+fn pixels() -> RgbImage {
+    RgbImage::from_fn(37, 29, |x, y| {
+        ::image::Rgb([
+            (x * 7 + y * 3) as u8,
+            (x * y * 5) as u8,
+            ((x * 31) ^ (y * 17)) as u8,
+        ])
+    })
+}
+
+/// Whether the header of a PNG marks it as interlaced.
+fn is_interlaced(png: &[u8]) -> bool {
+    assert_eq!(&png[12..16], b"IHDR");
+    png[28] == 1
+}
+
+#[test]
+fn test_interlaced_container_decodes_in_order() -> Result<(), Box<dyn std::error::Error>> {
+    assert!(is_interlaced(INTERLACED));
+
+    let container = StegoContainer::new(INTERLACED)?;
+    assert_eq!(container.image().to_rgb8(), pixels());
+
+    Ok(())
+}
+
+#[test]
+fn test_interlacing_keeps_bit_positions() -> Result<(), Box<dyn std::error::Error>> {
+    let plain = test_util::png(&pixels());
+    assert!(!is_interlaced(&plain));
+
+    // The payload bits land on the same samples whether the container was interlaced
+    for lsbs in [1, 3] {
+        let options = EmbedOptions::new().lsbs(lsbs).seed(7);
+        let from_interlaced = embed_with(INPUT, "txt", INTERLACED, &options)?.image;
+        let from_plain = embed_with(INPUT, "txt", &plain, &options)?.image;
+        assert_eq!(from_interlaced, from_plain);
+
+        assert_eq!(extract(&from_interlaced, lsbs, 7)?.0, INPUT);
+        assert_only_lsbs_changed_with(INTERLACED, &from_interlaced, &options)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_interlaced_output() -> Result<(), Box<dyn std::error::Error>> {
+    let plain = embed_with(INPUT, "txt", INTERLACED, &EmbedOptions::new())?.image;
+    let pixels = ::image::load_from_memory(&plain)?.into_rgb8();
+
+    for png_filter in [
+        PngFilter::NoFilter,
+        PngFilter::Sub,
+        PngFilter::Up,
+        PngFilter::Avg,
+        PngFilter::Paeth,
+        PngFilter::Adaptive,
+    ] {
+        let output = OutputOptions::new()
+            .png_filter(png_filter)
+            .png_interlaced(true);
+        let interlaced = embed_with(
+            INPUT,
+            "txt",
+            INTERLACED,
+            &EmbedOptions::new().output(output),
+        )?
+        .image;
+        assert!(is_interlaced(&interlaced));
+        assert_eq!(
+            ::image::load_from_memory(&interlaced)?.into_rgb8(),
+            pixels,
+            "{:?}",
+            png_filter
+        );
+        assert_eq!(extract(&interlaced, 1, 42)?.0, INPUT);
+
+        // Deinterlacing the output again leaves the payload where it was
+        let deinterlaced = test_util::png(&::image::load_from_memory(&interlaced)?.into_rgb8());
+        assert_eq!(extract(&deinterlaced, 1, 42)?.0, INPUT);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_interlaced_output_with_empty_passes() -> Result<(), Box<dyn std::error::Error>> {
+    // Images under 8 pixels wide or high leave some of the seven passes empty
+    for (width, height) in [(3, 200), (200, 3), (1, 600), (600, 1)] {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            ::image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+        });
+        let options = EmbedOptions::new()
+            .lsbs(8)
+            .format(Format::Png)
+            .output(OutputOptions::new().png_interlaced(true));
+
+        let interlaced = embed_with(INPUT, "txt", &test_util::png(&image), &options)?.image;
+        assert!(is_interlaced(&interlaced));
+        assert_eq!(
+            ::image::load_from_memory(&interlaced)?.into_rgb8(),
+            embed_dynamic(INPUT, "txt", &image.into(), &options)?.into_rgb8()
+        );
+        assert_eq!(extract(&interlaced, 8, 42)?.0, INPUT);
+    }
+
+    Ok(())
+}
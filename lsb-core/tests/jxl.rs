@@ -0,0 +1,90 @@
+// JPEG XL is only decoded and encoded with the `jxl` feature
+#![cfg(feature = "jxl")]
+
+use lsb_core::{image::*, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Archived without losing a bit.";
+const CONTAINER: &[u8] = include_bytes!("../../data/container.jxl");
+
+#[test]
+fn test_jxl_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    // A JPEG XL container is written back as JPEG XL by default
+    let options = EmbedOptions::new().lsbs(2);
+    let embedded = embed_with(INPUT, "txt", CONTAINER, &options)?.image;
+    assert!(embedded.starts_with(&[0xff, 0x0a]));
+
+    let container = StegoContainer::new(&embedded)?;
+    assert_eq!(container.format(), Some(Format::Jxl));
+    assert_eq!(container.dimensions(), (40, 30));
+
+    assert_eq!(
+        extract(&embedded, 2, 42)?,
+        (INPUT.to_vec(), "txt".to_string())
+    );
+    assert_only_lsbs_changed_with(CONTAINER, &embedded, &options)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_jxl_between_formats() -> Result<(), Box<dyn std::error::Error>> {
+    let png = embed_with(
+        INPUT,
+        "txt",
+        CONTAINER,
+        &EmbedOptions::new().format(Format::Png),
+    )?
+    .image;
+    assert_eq!(::image::guess_format(&png)?, ::image::ImageFormat::Png);
+    assert_eq!(extract(&png, 1, 42)?.0, INPUT);
+
+    let jxl = embed_with(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(40, 30, Format::Png),
+        &EmbedOptions::new().format(Format::Jxl),
+    )?
+    .image;
+    assert!(jxl.starts_with(&[0xff, 0x0a]));
+    assert_eq!(extract(&jxl, 1, 42)?.0, INPUT);
+
+    assert_eq!(
+        capacity(CONTAINER, 1)?,
+        capacity(&test_util::encoded_container(40, 30, Format::Png), 1)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_jxl_output_format() -> Result<(), Box<dyn std::error::Error>> {
+    assert!(supported_output_formats().contains(&Format::Jxl));
+    assert_eq!(output_format_from_extension("jxl")?, Format::Jxl);
+    assert_eq!(output_format_from_extension("JXL")?, Format::Jxl);
+    assert_eq!(Format::from_mime_type("image/jxl"), Some(Format::Jxl));
+    assert_eq!(Format::Jxl.extensions(), ["jxl"]);
+    assert!(::image::ImageFormat::try_from(Format::Jxl).is_err());
+
+    let dir = std::env::temp_dir().join(format!("lsb-jxl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let (input, output) = (dir.join("input.txt"), dir.join("embedded.jxl"));
+    std::fs::write(&input, INPUT)?;
+    let container = dir.join("container.png");
+    std::fs::write(
+        &container,
+        test_util::encoded_container(40, 30, Format::Png),
+    )?;
+
+    let result = embed_path(&input, &container, &output, &EmbedOptions::new());
+    let embedded = std::fs::read(&output);
+    let _ = std::fs::remove_dir_all(&dir);
+    result?;
+    assert_eq!(
+        extract(&embedded?, 1, 42)?,
+        (INPUT.to_vec(), "txt".to_string())
+    );
+
+    Ok(())
+}
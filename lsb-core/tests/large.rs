@@ -0,0 +1,154 @@
+#![cfg(feature = "std")]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{self, Read, Write},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use lsb_core::*;
+
+/// Tracks the peak of the heap memory in use, so tests can bound it.
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+/// Held by every test, so that no other test allocates while one measures.
+static MEASURING: Mutex<()> = Mutex::new(());
+
+/// Runs `f`, returning its result and the most heap memory it used at once.
+fn peak_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+
+    let result = f();
+    (result, PEAK.load(Ordering::Relaxed) - before)
+}
+
+const PAYLOAD_BYTES: u64 = 1 << 30;
+
+/// An endless stream of predictable bytes.
+struct Pattern(u64);
+
+impl Read for Pattern {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        for byte in buf.iter_mut() {
+            *byte = (self.0 * 31 % 251) as u8;
+            self.0 += 1;
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Checks that everything written is the `Pattern`, without keeping it.
+struct PatternCheck(u64);
+
+impl Write for PatternCheck {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut expected = Pattern(self.0);
+        let mut chunk = vec![0; buf.len()];
+        expected.read_exact(&mut chunk)?;
+        assert_eq!(buf, chunk, "Data differs after byte {}", self.0);
+
+        self.0 = expected.0;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+#[ignore = "embeds a 1 GiB payload, which is slow and needs several GiB of memory"]
+fn test_large_payload_streams_in_bounded_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let _measuring = MEASURING.lock();
+    let stego = Stego::builder().lsbs(8).format(Format::Pnm).build();
+
+    // Just enough samples for the payload and a generous header at 8 lsbs
+    let side = ((PAYLOAD_BYTES + 1024) as f64 / 3.0).sqrt().ceil() as u32;
+    let container = StegoContainer::from_image(::image::RgbImage::new(side, side).into());
+    let image_bytes = container.image().as_bytes().len();
+
+    let mut embedded = io::Cursor::new(Vec::new());
+    stego.embed_from_reader(Pattern(0), PAYLOAD_BYTES, "bin", &container, &mut embedded)?;
+    drop(container);
+    let embedded = embedded.into_inner();
+
+    let mut writer = PatternCheck(0);
+    let (info, peak) = peak_during(|| extract_to_writer(&embedded, &mut writer, 8, 42));
+    assert_eq!(info?.data_bytes, PAYLOAD_BYTES);
+    assert_eq!(writer.0, PAYLOAD_BYTES);
+
+    // The decoded container and its 8-bit plane, but nothing the size of the payload
+    assert!(
+        peak < 2 * image_bytes + (64 << 20),
+        "Extraction used {} bytes for a {} byte image",
+        peak,
+        image_bytes
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_banded_embedding_in_bounded_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let _measuring = MEASURING.lock();
+    let (width, height) = (4000, 4000);
+
+    // Encode the container row by row, so it is never decoded as a whole either
+    let mut container = Vec::new();
+    let mut encoder = png::Encoder::new(&mut container, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    let mut row = vec![0; width as usize * 3];
+    for y in 0..height as usize {
+        Pattern(y as u64).read_exact(&mut row)?;
+        stream.write_all(&row)?;
+    }
+    stream.finish()?;
+    drop(writer);
+
+    let input = b"Hidden in a huge scan".repeat(1000);
+    let (result, peak) = peak_during(|| {
+        embed_banded(
+            &input,
+            "txt",
+            io::Cursor::new(&container),
+            &EmbedOptions::default(),
+            io::sink(),
+        )
+    });
+    result?;
+
+    let decoded_bytes = width as usize * height as usize * 3;
+    assert!(
+        peak < decoded_bytes / 4,
+        "Banded embedding used {} bytes for a {} byte image",
+        peak,
+        decoded_bytes
+    );
+
+    Ok(())
+}
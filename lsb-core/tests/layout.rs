@@ -0,0 +1,254 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{hash::Hash, image::*, layout::*, order::KeyedPermutation, *};
+
+/// A payload recorded in `data/layout-v1`, with the parameters it was embedded with.
+struct Fixture {
+    name: &'static str,
+    width: u32,
+    height: u32,
+    lsbs: usize,
+    seed: u64,
+    hash: Hash,
+    extension: &'static str,
+    len: usize,
+    image: &'static [u8],
+}
+
+const FIXTURES: [Fixture; 6] = [
+    Fixture {
+        name: "blake3-l1-16x16",
+        width: 16,
+        height: 16,
+        lsbs: 1,
+        seed: 0,
+        hash: Hash::Blake3,
+        extension: "txt",
+        len: 24,
+        image: include_bytes!("../../data/layout-v1/blake3-l1-16x16.png"),
+    },
+    Fixture {
+        name: "sha256-l2-33x7",
+        width: 33,
+        height: 7,
+        lsbs: 2,
+        seed: 42,
+        hash: Hash::Sha256,
+        extension: "bin",
+        len: 100,
+        image: include_bytes!("../../data/layout-v1/sha256-l2-33x7.png"),
+    },
+    Fixture {
+        name: "sha512-l3-20x20",
+        width: 20,
+        height: 20,
+        lsbs: 3,
+        seed: u64::MAX,
+        hash: Hash::Sha512,
+        extension: "",
+        len: 300,
+        image: include_bytes!("../../data/layout-v1/sha512-l3-20x20.png"),
+    },
+    Fixture {
+        name: "sha1-l8-9x13",
+        width: 9,
+        height: 13,
+        lsbs: 8,
+        seed: 7,
+        hash: Hash::Sha1,
+        extension: "md",
+        len: 315,
+        image: include_bytes!("../../data/layout-v1/sha1-l8-9x13.png"),
+    },
+    Fixture {
+        name: "blake3-l4-64x48-empty",
+        width: 64,
+        height: 48,
+        lsbs: 4,
+        seed: 12345,
+        hash: Hash::Blake3,
+        extension: "json",
+        len: 0,
+        image: include_bytes!("../../data/layout-v1/blake3-l4-64x48-empty.png"),
+    },
+    Fixture {
+        name: "sha256-l1-1x200",
+        width: 1,
+        height: 200,
+        lsbs: 1,
+        seed: 1,
+        hash: Hash::Sha256,
+        extension: "txt",
+        len: 10,
+        image: include_bytes!("../../data/layout-v1/sha256-l1-1x200.png"),
+    },
+];
+
+impl Fixture {
+    fn data(&self) -> Vec<u8> {
+        (0..self.len)
+            .map(|i| (i as u8).wrapping_mul(37) ^ self.seed as u8)
+            .collect()
+    }
+
+    fn container(&self) -> Vec<u8> {
+        let mut container = Vec::new();
+        generate_container(
+            self.width,
+            self.height,
+            NoiseStyle::GradientNoise,
+            self.seed,
+        )
+        .write_to(
+            &mut std::io::Cursor::new(&mut container),
+            ::image::ImageFormat::Png,
+        )
+        .unwrap();
+        container
+    }
+
+    /// The filter is given, as the default depends on the `fast-png` feature.
+    fn options(&self) -> EmbedOptions {
+        EmbedOptions::new()
+            .lsbs(self.lsbs)
+            .seed(self.seed)
+            .hash(self.hash)
+            .output(OutputOptions::new().png_filter(PngFilter::Adaptive))
+    }
+}
+
+/// Reads `len` payload bytes from `start` following nothing but the documented layout.
+fn read_bytes(samples: &[u8], fixture: &Fixture, start: usize, len: usize) -> Vec<u8> {
+    assert_eq!(BIT_ORDER, "msb-first");
+    let permutation = KeyedPermutation::new(fixture.seed);
+    let capacity_bits = (samples.len() * fixture.lsbs) as u64;
+
+    (start..start + len)
+        .map(|byte| {
+            (0..8).fold(0, |value, bit| {
+                let position = permutation.position(capacity_bits, (byte * 8 + bit) as u64);
+                let sample = samples[(position / fixture.lsbs as u64) as usize];
+                (value << 1) | ((sample >> (position % fixture.lsbs as u64)) & 1)
+            })
+        })
+        .collect()
+}
+
+/// A reader that does not share any code with extraction, so that a refactor of
+/// extraction cannot change both.
+fn read_payload(fixture: &Fixture) -> Result<(Vec<u8>, String, Hash), Box<dyn std::error::Error>> {
+    let samples = ::image::load_from_memory(fixture.image)?
+        .into_rgb8()
+        .into_raw();
+    assert_eq!(LENGTH_BYTE_ORDER, "little-endian");
+    let length = u32::from_le_bytes(read_bytes(&samples, fixture, 0, LENGTH_SIZE)[..].try_into()?);
+    let payload = read_bytes(&samples, fixture, LENGTH_SIZE, length as usize);
+
+    let mut rest = &payload[..];
+    let (mut extension, mut hash, mut flag, mut checksum) = (String::new(), None, 0, &[][..]);
+    for field in HEADER_FIELDS {
+        rest = match field {
+            HeaderField::Length => rest,
+            HeaderField::ExtensionLength => &rest[1..],
+            HeaderField::Extension => {
+                let len = payload[0] as usize;
+                extension = String::from_utf8(rest[..len].to_vec())?;
+                &rest[len..]
+            }
+            HeaderField::HashFlag => {
+                flag = rest[0];
+                let id = flag & !(FINGERPRINT_FLAG | KEYED_ORDER_FLAG);
+                hash = Hash::all().iter().copied().find(|&hash| hash as u8 == id);
+                &rest[1..]
+            }
+            HeaderField::Fingerprint => &rest[FINGERPRINT_SIZE..],
+            HeaderField::Checksum => {
+                let size = hash.ok_or("unknown hash")?.digest_size();
+                checksum = &rest[..size];
+                &rest[size..]
+            }
+        };
+    }
+    assert_eq!(flag & FINGERPRINT_FLAG, FINGERPRINT_FLAG);
+    assert_eq!(flag & KEYED_ORDER_FLAG, KEYED_ORDER_FLAG);
+
+    let hash = hash.ok_or("unknown hash")?;
+    let expected: Vec<u8> = match hash {
+        Hash::Blake3 => blake3::hash(rest).as_bytes().to_vec(),
+        Hash::Sha256 => <sha2::Sha256 as sha2::Digest>::digest(rest).to_vec(),
+        Hash::Sha512 => <sha2::Sha512 as sha2::Digest>::digest(rest).to_vec(),
+        Hash::Sha1 => <sha1::Sha1 as sha1::Digest>::digest(rest).to_vec(),
+    };
+    assert_eq!(checksum, expected);
+
+    Ok((rest.to_vec(), extension, hash))
+}
+
+/// A change of the layout bumps `FORMAT_VERSION`, records new `data/layout-v*` files and
+/// keeps these tests for the old ones.
+#[test]
+fn test_layout_is_version_1() {
+    assert_eq!(FORMAT_VERSION, 1, "Record fixtures for the new version");
+    assert_eq!(RNG_ALGORITHM, "pcg64mcg");
+    assert_eq!(LENGTH_SIZE, 4);
+    assert_eq!(
+        (FINGERPRINT_SIZE, FINGERPRINT_FLAG, KEYED_ORDER_FLAG),
+        (8, 0x80, 0x40)
+    );
+    assert_eq!(
+        Hash::all()
+            .iter()
+            .map(|&hash| hash as u8)
+            .collect::<Vec<_>>(),
+        [0, 1, 2, 3]
+    );
+}
+
+#[test]
+fn test_fixtures_extract() -> Result<(), Box<dyn std::error::Error>> {
+    for fixture in &FIXTURES {
+        let extracted = extract_with(
+            fixture.image,
+            &ExtractOptions::new().lsbs(fixture.lsbs).seed(fixture.seed),
+        )?;
+        assert_eq!(extracted.data, fixture.data(), "{}", fixture.name);
+        assert_eq!(extracted.extension, fixture.extension, "{}", fixture.name);
+        assert_eq!(extracted.hash, fixture.hash, "{}", fixture.name);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_fixtures_follow_the_documented_layout() -> Result<(), Box<dyn std::error::Error>> {
+    for fixture in &FIXTURES {
+        let (data, extension, hash) = read_payload(fixture)?;
+        assert_eq!(data, fixture.data(), "{}", fixture.name);
+        assert_eq!(extension, fixture.extension, "{}", fixture.name);
+        assert_eq!(hash, fixture.hash, "{}", fixture.name);
+    }
+
+    Ok(())
+}
+
+/// Fresh embeddings must match the recorded ones byte for byte, which also depends on
+/// `FORMAT_OUTPUT_VERSION` for the encoding of the PNG files.
+#[test]
+fn test_embedding_matches_fixtures() -> Result<(), Box<dyn std::error::Error>> {
+    for fixture in &FIXTURES {
+        let embedded = embed_with(
+            &fixture.data(),
+            fixture.extension,
+            &fixture.container(),
+            &fixture.options(),
+        )?
+        .image;
+        assert!(
+            embedded == fixture.image,
+            "{} differs from its fixture",
+            fixture.name
+        );
+    }
+
+    Ok(())
+}
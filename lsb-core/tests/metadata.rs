@@ -0,0 +1,355 @@
+#![cfg(feature = "std")]
+
+use std::collections::HashMap;
+
+use lsb_core::{image::*, *};
+
+const INPUT: &[u8] = b"Metadata stays where it was.";
+const ICC_PROFILE: &[u8] = b"An ICC profile, as far as the PNG format is concerned";
+
+/// A PNG container with an ICC profile, a text chunk, a pixel size and a transparent
+/// color, which an RGB output cannot keep.
+fn container() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let image = generate_container(64, 48, NoiseStyle::GradientNoise, 5);
+
+    let mut info = png::Info::with_size(64, 48);
+    info.color_type = png::ColorType::Rgb;
+    info.bit_depth = png::BitDepth::Eight;
+    info.icc_profile = Some(ICC_PROFILE.into());
+
+    let mut container = Vec::new();
+    let mut encoder = png::Encoder::with_info(&mut container, info)?;
+    encoder.add_text_chunk("Comment".to_string(), "Taken at dawn".to_string())?;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: 2835,
+        yppu: 2835,
+        unit: png::Unit::Meter,
+    }));
+    let mut writer = encoder.write_header()?;
+    writer.write_chunk(png::chunk::tRNS, &[0, 1, 0, 2, 0, 3])?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+
+    Ok(container)
+}
+
+/// The type and data of every chunk of a PNG file.
+fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    let mut rest = &png[8..];
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        chunks.push((rest[4..8].try_into().unwrap(), rest[8..8 + length].to_vec()));
+        rest = &rest[12 + length..];
+    }
+    chunks
+}
+
+fn chunk<'a>(chunks: &'a [([u8; 4], Vec<u8>)], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    chunks
+        .iter()
+        .find(|(chunk_kind, _)| chunk_kind == kind)
+        .map(|(_, data)| &data[..])
+}
+
+#[test]
+fn test_png_chunks_survive_embedding() -> Result<(), Box<dyn std::error::Error>> {
+    let container = container()?;
+    let before = chunks(&container);
+    let embedded = embed_with(INPUT, "txt", &container, &EmbedOptions::new().lsbs(2))?.image;
+    let after = chunks(&embedded);
+
+    for kind in [b"iCCP", b"tEXt", b"pHYs"] {
+        assert!(chunk(&before, kind).is_some());
+        assert_eq!(chunk(&after, kind), chunk(&before, kind), "{:?}", kind);
+    }
+
+    // The transparent color describes samples the output does not have
+    assert!(chunk(&after, b"tRNS").is_none());
+    // Every kept chunk comes before the image data, where all of them are allowed
+    let kinds: Vec<_> = after.iter().map(|(kind, _)| kind).collect();
+    assert_eq!(kinds.first(), Some(&b"IHDR"));
+    assert_eq!(kinds.last(), Some(&b"IEND"));
+    let first_idat = kinds.iter().position(|kind| *kind == b"IDAT").unwrap();
+    assert!(
+        kinds[first_idat..]
+            .iter()
+            .all(|kind| *kind == b"IDAT" || *kind == b"IEND")
+    );
+
+    assert_eq!(extract(&embedded, 2, 42)?.0, INPUT);
+
+    Ok(())
+}
+
+#[test]
+fn test_png_chunks_opt_out() -> Result<(), Box<dyn std::error::Error>> {
+    let container = container()?;
+    let options = EmbedOptions::new().lsbs(2);
+    let kept = embed_with(INPUT, "txt", &container, &options)?.image;
+    let dropped = embed_with(
+        INPUT,
+        "txt",
+        &container,
+        &options.output(OutputOptions::new().keep_metadata(false)),
+    )?
+    .image;
+
+    let kinds: Vec<_> = chunks(&dropped).into_iter().map(|(kind, _)| kind).collect();
+    assert!(
+        kinds
+            .iter()
+            .all(|kind| [b"IHDR", b"IDAT", b"IEND"].contains(&kind)),
+        "{:?}",
+        kinds
+    );
+
+    // Only the chunks differ, not the pixels
+    assert_eq!(
+        ::image::load_from_memory(&kept)?.into_rgb8(),
+        ::image::load_from_memory(&dropped)?.into_rgb8()
+    );
+    assert_eq!(extract(&dropped, 2, 42)?.0, INPUT);
+
+    Ok(())
+}
+
+const XMP: &[u8] = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF/></x:xmpmeta>"#;
+const MAKE: &[u8] = b"Camera\0";
+const DATE_TIME_ORIGINAL: &[u8] = b"2024:05:01 06:30:00\0";
+const ORIENTATION_ROTATE_90: u16 = 6;
+
+/// A big-endian directory at `offset`, followed by the values that do not fit in it.
+fn big_endian_ifd(fields: &[(u16, u16, u32, Vec<u8>)], offset: usize) -> Vec<u8> {
+    let mut ifd = (fields.len() as u16).to_be_bytes().to_vec();
+    let mut values = Vec::new();
+    let values_offset = offset + 2 + fields.len() * 12 + 4;
+    for (tag, kind, count, value) in fields {
+        ifd.extend(tag.to_be_bytes());
+        ifd.extend(kind.to_be_bytes());
+        ifd.extend(count.to_be_bytes());
+        if value.len() <= 4 {
+            ifd.extend(value);
+            ifd.extend(&[0; 4][value.len()..]);
+        } else {
+            ifd.extend(((values_offset + values.len()) as u32).to_be_bytes());
+            values.extend(value);
+        }
+    }
+    ifd.extend(0u32.to_be_bytes());
+    [ifd, values].concat()
+}
+
+/// EXIF as cameras write it, in big-endian byte order with EXIF and GPS directories.
+fn exif() -> Vec<u8> {
+    let mut exif = vec![0; 400];
+    exif[..8].copy_from_slice(b"MM\0*\0\0\0\x08");
+
+    let ifd0 = big_endian_ifd(
+        &[
+            // The size of the full-resolution image, not of the container
+            (256, 4, 1, 4000u32.to_be_bytes().to_vec()),
+            (271, 2, MAKE.len() as u32, MAKE.to_vec()),
+            (274, 3, 1, ORIENTATION_ROTATE_90.to_be_bytes().to_vec()),
+            (34665, 4, 1, 200u32.to_be_bytes().to_vec()),
+            (34853, 4, 1, 300u32.to_be_bytes().to_vec()),
+        ],
+        8,
+    );
+    let exif_ifd = big_endian_ifd(
+        &[
+            (36867, 2, 20, DATE_TIME_ORIGINAL.to_vec()),
+            (37500, 7, 8, b"\0\0\0\x10Nkn".to_vec()),
+        ],
+        200,
+    );
+    let latitude: Vec<u8> = [48u32, 1, 51, 1, 2950, 100]
+        .iter()
+        .flat_map(|number| number.to_be_bytes())
+        .collect();
+    let gps_ifd = big_endian_ifd(&[(1, 2, 2, b"N\0".to_vec()), (2, 5, 3, latitude)], 300);
+
+    exif[8..8 + ifd0.len()].copy_from_slice(&ifd0);
+    exif[200..200 + exif_ifd.len()].copy_from_slice(&exif_ifd);
+    exif[300..300 + gps_ifd.len()].copy_from_slice(&gps_ifd);
+    exif
+}
+
+/// A JPEG container holding `exif()` and `XMP` in `APP1` segments.
+fn jpeg_container() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut jpeg = Vec::new();
+    generate_container(64, 48, NoiseStyle::GradientNoise, 9).write_to(
+        &mut std::io::Cursor::new(&mut jpeg),
+        ::image::ImageFormat::Jpeg,
+    )?;
+
+    let app1 = |prefix: &[u8], data: &[u8]| {
+        let length = (2 + prefix.len() + data.len()) as u16;
+        [&[0xff, 0xe1], &length.to_be_bytes()[..], prefix, data].concat()
+    };
+    Ok([
+        &jpeg[..2],
+        &app1(b"Exif\0\0", &exif()),
+        &app1(b"http://ns.adobe.com/xap/1.0/\0", XMP),
+        &jpeg[2..],
+    ]
+    .concat())
+}
+
+/// The type and data of every chunk of a WebP file.
+fn webp_chunks(webp: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    let mut rest = &webp[12..];
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        let length = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        chunks.push((rest[..4].try_into().unwrap(), rest[8..8 + length].to_vec()));
+        rest = &rest[(8 + length + length % 2).min(rest.len())..];
+    }
+    chunks
+}
+
+/// The type and value of every field of the directory at `offset` of a little-endian
+/// TIFF file, by tag.
+fn tiff_fields(tiff: &[u8], offset: usize) -> HashMap<u16, (u16, Vec<u8>)> {
+    let u16_at = |at: usize| u16::from_le_bytes(tiff[at..at + 2].try_into().unwrap());
+    let u32_at = |at: usize| u32::from_le_bytes(tiff[at..at + 4].try_into().unwrap());
+
+    (0..u16_at(offset) as usize)
+        .map(|index| {
+            let entry = offset + 2 + index * 12;
+            let kind = u16_at(entry + 2);
+            let size = match kind {
+                3 | 8 => 2,
+                4 | 9 | 11 | 13 => 4,
+                5 | 10 | 12 => 8,
+                _ => 1,
+            };
+            let len = u32_at(entry + 4) as usize * size;
+            let start = if len <= 4 {
+                entry + 8
+            } else {
+                u32_at(entry + 8) as usize
+            };
+            (u16_at(entry), (kind, tiff[start..start + len].to_vec()))
+        })
+        .collect()
+}
+
+/// The directory a field of a little-endian TIFF file points to.
+fn tiff_sub_ifd(
+    tiff: &[u8],
+    fields: &HashMap<u16, (u16, Vec<u8>)>,
+    tag: u16,
+) -> HashMap<u16, (u16, Vec<u8>)> {
+    let offset = u32::from_le_bytes(fields[&tag].1[..].try_into().unwrap());
+    tiff_fields(tiff, offset as usize)
+}
+
+#[test]
+fn test_exif_survives_into_webp() -> Result<(), Box<dyn std::error::Error>> {
+    let container = jpeg_container()?;
+    let options = EmbedOptions::new().lsbs(2).format(Format::WebP);
+    let webp = embed_with(INPUT, "txt", &container, &options)?.image;
+
+    // The extended format, with the blobs copied as they were
+    let chunks = webp_chunks(&webp);
+    assert_eq!(
+        chunk(&chunks, b"VP8X").map(|flags| flags[0] & 0b1100),
+        Some(0b1100)
+    );
+    assert_eq!(chunk(&chunks, b"EXIF"), Some(&exif()[..]));
+    assert_eq!(chunk(&chunks, b"XMP "), Some(XMP));
+    assert!(chunk(&chunks, b"VP8L").is_some());
+
+    // And only the metadata differs from an output without it
+    let plain = embed_with(
+        INPUT,
+        "txt",
+        &container,
+        &options.output(OutputOptions::new().keep_metadata(false)),
+    )?
+    .image;
+    assert_eq!(&plain[8..16], b"WEBPVP8L");
+    assert_eq!(
+        ::image::load_from_memory(&webp)?.into_rgb8(),
+        ::image::load_from_memory(&plain)?.into_rgb8()
+    );
+    assert_eq!(extract(&webp, 2, 42)?.0, INPUT);
+
+    Ok(())
+}
+
+#[test]
+fn test_exif_survives_into_tiff() -> Result<(), Box<dyn std::error::Error>> {
+    let container = jpeg_container()?;
+    let options = EmbedOptions::new().lsbs(2).format(Format::Tiff);
+    let tiff = embed_with(INPUT, "txt", &container, &options)?.image;
+
+    assert_eq!(&tiff[..4], b"II*\0");
+    let ifd0 = tiff_fields(&tiff, u32::from_le_bytes(tiff[4..8].try_into()?) as usize);
+    assert_eq!(
+        ifd0[&274],
+        (3, ORIENTATION_ROTATE_90.to_le_bytes().to_vec())
+    );
+    assert_eq!(ifd0[&271], (2, MAKE.to_vec()));
+    assert_eq!(ifd0[&700], (1, XMP.to_vec()));
+    // The tags describing the image data are those of the output
+    assert_eq!(ifd0[&256], (4, 64u32.to_le_bytes().to_vec()));
+
+    let exif_ifd = tiff_sub_ifd(&tiff, &ifd0, 34665);
+    assert_eq!(exif_ifd[&36867], (2, DATE_TIME_ORIGINAL.to_vec()));
+    assert!(!exif_ifd.contains_key(&37500));
+    let gps_ifd = tiff_sub_ifd(&tiff, &ifd0, 34853);
+    assert_eq!(gps_ifd[&1], (2, b"N\0".to_vec()));
+    let latitude: Vec<u8> = [48u32, 1, 51, 1, 2950, 100]
+        .iter()
+        .flat_map(|number| number.to_le_bytes())
+        .collect();
+    assert_eq!(gps_ifd[&2], (5, latitude));
+
+    assert_eq!(extract(&tiff, 2, 42)?.0, INPUT);
+
+    // A TIFF container passes its tags on in turn, whichever the compression
+    let output = OutputOptions::new().tiff_compression(TiffCompression::Deflate);
+    let again = embed_with(INPUT, "txt", &tiff, &options.clone().output(output))?.image;
+    let ifd0 = tiff_fields(&again, u32::from_le_bytes(again[4..8].try_into()?) as usize);
+    assert_eq!(
+        ifd0[&274],
+        (3, ORIENTATION_ROTATE_90.to_le_bytes().to_vec())
+    );
+    assert_eq!(ifd0[&700], (1, XMP.to_vec()));
+    assert_eq!(
+        tiff_sub_ifd(&again, &ifd0, 34665)[&36867],
+        (2, DATE_TIME_ORIGINAL.to_vec())
+    );
+
+    let plain = embed_with(
+        INPUT,
+        "txt",
+        &container,
+        &options.output(OutputOptions::new().keep_metadata(false)),
+    )?
+    .image;
+    let ifd0 = tiff_fields(&plain, u32::from_le_bytes(plain[4..8].try_into()?) as usize);
+    assert!(!ifd0.contains_key(&274) && !ifd0.contains_key(&34665));
+
+    Ok(())
+}
+
+#[test]
+fn test_exif_survives_into_png() -> Result<(), Box<dyn std::error::Error>> {
+    let container = jpeg_container()?;
+    let options = EmbedOptions::new().lsbs(2).format(Format::Png);
+    let png = embed_with(INPUT, "txt", &container, &options)?.image;
+
+    let chunks = chunks(&png);
+    assert_eq!(chunk(&chunks, b"eXIf"), Some(&exif()[..]));
+    let xmp = chunk(&chunks, b"iTXt").unwrap();
+    assert_eq!(
+        xmp.strip_prefix(&b"XML:com.adobe.xmp\0\0\0\0\0"[..]),
+        Some(XMP)
+    );
+    assert_eq!(extract(&png, 2, 42)?.0, INPUT);
+
+    Ok(())
+}
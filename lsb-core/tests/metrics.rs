@@ -0,0 +1,123 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, metrics::*, *};
+
+mod test_util;
+
+#[test]
+fn test_quality_metrics_reference() -> Result<(), Box<dyn std::error::Error>> {
+    let original = image::RgbImage::from_raw(2, 1, vec![0, 0, 0, 10, 10, 10]).unwrap();
+    let modified = image::RgbImage::from_raw(2, 1, vec![1, 0, 0, 10, 10, 12]).unwrap();
+
+    let report = quality_metrics(&original, &modified)?;
+
+    // (1^2 + 2^2) / 6 samples, and 10 * log10(255^2 / MSE)
+    assert!((report.mse - 0.833_333_333).abs() < 1e-6);
+    assert!((report.psnr_db - 48.922_616_069).abs() < 1e-6);
+    assert_eq!(report.max_abs_diff, 2);
+
+    let identical = quality_metrics(&original, &original)?;
+    assert_eq!(identical.mse, 0.0);
+    assert_eq!(identical.psnr_db, f64::INFINITY);
+
+    Ok(())
+}
+
+#[test]
+fn test_psnr_decreases_with_lsbs() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = vec![0x5A; 1000];
+
+    let mut last_psnr = f64::INFINITY;
+    for lsbs in [1, 2, 4] {
+        let embedded = embed(
+            &input,
+            "bin",
+            &container,
+            lsbs,
+            Hash::Blake3,
+            42,
+            Format::Png,
+        )?;
+
+        let report = quality_metrics_from_bytes(&container, &embedded)?;
+        assert!(report.max_abs_diff < 1 << lsbs);
+        assert!(
+            report.psnr_db < last_psnr,
+            "lsbs {} should degrade more",
+            lsbs
+        );
+        last_psnr = report.psnr_db;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_quality_metrics_dimension_mismatch() {
+    let result = quality_metrics_from_bytes(
+        &test_util::encoded_container(8, 8, Format::Png),
+        &test_util::encoded_container(8, 9, Format::Png),
+    );
+
+    assert!(matches!(result, Err(StegError::DimensionMismatch { .. })));
+}
+
+/// Builds the fixture images shared with the reference implementation.
+fn ssim_fixtures() -> (image::RgbImage, image::RgbImage, image::RgbImage) {
+    let original = image::RgbImage::from_fn(20, 16, |x, y| {
+        ::image::Rgb([
+            (x * 13 % 256) as u8,
+            (y * 17 % 256) as u8,
+            (x * y * 3 % 256) as u8,
+        ])
+    });
+    let noisy = image::RgbImage::from_fn(20, 16, |x, y| {
+        let pixel = original.get_pixel(x, y).0;
+        ::image::Rgb(std::array::from_fn(|i| {
+            pixel[i] ^ ((x * 7 + y * 3 + i as u32) % 4) as u8
+        }))
+    });
+    let faded = image::RgbImage::from_fn(20, 16, |x, y| {
+        ::image::Rgb(original.get_pixel(x, y).0.map(|s| s / 2 + 64))
+    });
+
+    (original, noisy, faded)
+}
+
+#[test]
+fn test_ssim_reference() -> Result<(), Box<dyn std::error::Error>> {
+    let (original, noisy, faded) = ssim_fixtures();
+
+    // Reference values from a direct floating-point transcription of Wang et al. (2004)
+    // with 8x8 windows, population statistics and Rec. 601 luma.
+    assert!((ssim(&original, &noisy)? - 0.999_275_103_493).abs() < 1e-9);
+    assert!((ssim(&original, &faded)? - 0.802_724_946_991).abs() < 1e-9);
+    assert!((ssim(&original, &original)? - 1.0).abs() < 1e-12);
+
+    Ok(())
+}
+
+#[test]
+fn test_ssim_decreases_with_lsbs() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = vec![0x5A; 1000];
+
+    let ssim_at = |lsbs| -> Result<f64, Box<dyn std::error::Error>> {
+        let embedded = embed(
+            &input,
+            "bin",
+            &container,
+            lsbs,
+            Hash::Blake3,
+            42,
+            Format::Png,
+        )?;
+        Ok(ssim_from_bytes(&container, &embedded)?)
+    };
+
+    let (low, high) = (ssim_at(1)?, ssim_at(4)?);
+    assert!(low < 1.0 && high < low);
+
+    Ok(())
+}
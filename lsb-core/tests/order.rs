@@ -0,0 +1,253 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{hash::Hash, order::*, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Orders decide where every payload bit goes.";
+
+/// Places the payload from the last embeddable bit backwards.
+struct Reversed;
+
+impl PixelOrder for Reversed {
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        (0..capacity_bits).rev().take(needed as usize)
+    }
+}
+
+#[test]
+fn test_keyed_permutation_matches_embed() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let options = EmbedOptions::default();
+
+    let embedded = embed_with_order(
+        INPUT,
+        "txt",
+        &container,
+        &options,
+        &KeyedPermutation::new(7),
+    )?;
+
+    assert_eq!(
+        embedded,
+        embed(INPUT, "txt", &container, 1, Hash::Blake3, 7, Format::Png)?
+    );
+    assert_eq!(extract(&embedded, 1, 7)?.0, INPUT);
+
+    Ok(())
+}
+
+#[test]
+fn test_seeded_shuffle_still_extracts() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(2),
+        ..EmbedOptions::default()
+    };
+
+    let embedded = embed_with_order(INPUT, "txt", &container, &options, &SeededShuffle::new(7))?;
+
+    assert_eq!(extract(&embedded, 2, 7)?.0, INPUT);
+    assert!(extract(&embedded, 2, 8).is_err());
+
+    // Auto only tries the legacy order when asked to
+    let auto = ExtractOptions::new().seed(7).lsbs(LsbSelection::Auto);
+    assert!(extract_with(&embedded, &auto).is_err());
+    assert_eq!(extract_with(&embedded, &auto.legacy_order(true))?.lsbs, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_keyed_permutation_is_bijective() {
+    let order = KeyedPermutation::new(42);
+
+    for capacity_bits in [1, 2, 3, 7, 64, 1000, 4097] {
+        let mut positions = order
+            .positions(capacity_bits, capacity_bits)
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        assert!(
+            positions.into_iter().eq(0..capacity_bits),
+            "Not a permutation of {} bits",
+            capacity_bits
+        );
+    }
+
+    assert!(
+        order
+            .positions(1000, 100)
+            .eq(order.positions(1000, 1000).take(100))
+    );
+    assert!(
+        !KeyedPermutation::new(43)
+            .positions(1000, 100)
+            .eq(order.positions(1000, 100))
+    );
+}
+
+#[test]
+fn test_keyed_permutation_is_lazy() {
+    // Far more bits than could ever be materialized, e.g. a 48 MP image at 8 lsbs, times 1000
+    let capacity_bits = 48_000_000 * 3 * 8 * 1000;
+    let order = KeyedPermutation::new(42);
+
+    let positions = order.positions(capacity_bits, 1000).collect::<Vec<_>>();
+    assert!(positions.iter().all(|&position| position < capacity_bits));
+    assert_eq!(order.position(capacity_bits, 999), positions[999]);
+
+    // The positions are spread over the whole range, not clustered at its start
+    assert!(
+        positions
+            .iter()
+            .any(|&position| position > capacity_bits / 2)
+    );
+}
+
+#[test]
+fn test_positions_from_skips_the_start() {
+    fn assert_skips(order: &impl PixelOrder, capacity_bits: u64) {
+        for (start, needed) in [(0, 100), (37, 100), (100, 100), (150, 100), (10, 2000)] {
+            assert!(
+                order
+                    .positions_from(capacity_bits, start, needed)
+                    .eq(order.positions(capacity_bits, needed).skip(start as usize)),
+                "{} {}",
+                start,
+                needed
+            );
+        }
+    }
+
+    assert_skips(&KeyedPermutation::new(42), 1000);
+    assert_skips(&Order::new(42, 1000), 1000);
+    assert_skips(&Order::new(42, 1000), 999);
+    assert_skips(&SeededShuffle::new(42), 1000);
+    assert_skips(&Sequential, 1000);
+    assert_skips(&Reversed, 1000);
+
+    // Starting far into a huge order does not generate the positions before it
+    let capacity_bits = 48_000_000 * 3 * 8 * 1000;
+    let order = KeyedPermutation::new(42);
+    assert_eq!(
+        order
+            .positions_from(capacity_bits, capacity_bits - 1, capacity_bits)
+            .collect::<Vec<_>>(),
+        [order.position(capacity_bits, capacity_bits - 1)]
+    );
+}
+
+#[test]
+fn test_keyed_permutation_matches_recorded() {
+    // A 20000x20000 container at 8 lsbs has more bits than a 32-bit `usize` can count,
+    // and lsb-js checks the same positions on wasm32
+    let capacity_bits = 20_000 * 20_000 * 3 * 8;
+    let expected = [
+        7501616010, 1640006662, 1627891994, 3594274094, 5952102025, 6011423532, 1264158924,
+        6459191604,
+    ];
+
+    assert!(
+        KeyedPermutation::new(42)
+            .positions(capacity_bits, 8)
+            .eq(expected)
+    );
+}
+
+#[test]
+fn test_sequential_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let options = EmbedOptions::default();
+
+    let embedded = embed_with_order(INPUT, "txt", &container, &options, &Sequential)?;
+
+    let (data, ext) = extract_with_order(&embedded, 1, &Sequential)?;
+    assert_eq!(data, INPUT);
+    assert_eq!(ext, "txt");
+
+    // Only the first rows can have changed.
+    let before = ::image::load_from_memory(&container)?.into_rgb8();
+    let after = ::image::load_from_memory(&embedded)?.into_rgb8();
+    let payload_bits = (4 + 1 + 3 + 1 + 8 + 32 + INPUT.len()) * 8;
+    assert!(
+        before
+            .iter()
+            .zip(after.iter())
+            .skip(payload_bits)
+            .all(|(a, b)| a == b)
+    );
+    assert_ne!(before, after);
+
+    Ok(())
+}
+
+#[test]
+fn test_custom_order_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(2),
+        ..EmbedOptions::default()
+    };
+
+    let embedded = embed_with_order(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(32, 32, Format::Png),
+        &options,
+        &Reversed,
+    )?;
+
+    assert_eq!(extract_with_order(&embedded, 2, &Reversed)?.0, INPUT);
+    assert!(extract_with_order(&embedded, 2, &Sequential).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_order_matches_embed() -> Result<(), Box<dyn std::error::Error>> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Order>();
+
+    let other = test_util::encoded_container(48, 40, Format::Png);
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(2),
+        seed: 7,
+        ..EmbedOptions::default()
+    };
+    let order = Order::new(7, 64 * 64 * 3 * 2);
+    assert_eq!(order.capacity_bits(), 64 * 64 * 3 * 2);
+
+    // Many embeddings from several threads share the same order
+    let inputs = (0..16u8)
+        .map(|i| vec![i; 100 + i as usize])
+        .collect::<Vec<_>>();
+    std::thread::scope(
+        |scope| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            let handles = inputs
+                .iter()
+                .map(|input| {
+                    scope.spawn(|| embed_with_order(input, "bin", &container, &options, &order))
+                })
+                .collect::<Vec<_>>();
+
+            for (input, handle) in inputs.iter().zip(handles) {
+                let embedded = handle.join().unwrap()?;
+                assert_eq!(
+                    embedded,
+                    embed_with(input, "bin", &container, &options)?.image
+                );
+                assert_eq!(extract_with_order(&embedded, 2, &order)?.0, *input);
+            }
+
+            Ok(())
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Containers of another size still work
+    let embedded = embed_with_order(INPUT, "txt", &other, &options, &order)?;
+    assert_eq!(embedded, embed_with(INPUT, "txt", &other, &options)?.image);
+    assert_eq!(extract(&embedded, 2, 7)?.0, INPUT);
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{hash::Hash, image::FilterType, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Hello, world!";
+
+#[test]
+fn test_outcome_values() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 48, Format::Png);
+    let options = EmbedOptions::new().lsbs(2).seed(3);
+
+    let outcome = embed_with(INPUT, "txt", &container, &options)?;
+    let (embedded, report) = embed_with_report(INPUT, "txt", &container, &options)?;
+
+    assert_eq!(outcome.image, embedded);
+    assert_eq!(
+        outcome.image,
+        embed(INPUT, "txt", &container, 2, Hash::Blake3, 3, None)?
+    );
+    assert_eq!(outcome.lsbs_used, 2);
+    assert_eq!((outcome.width, outcome.height), (64, 48));
+    assert_eq!(outcome.payload_bytes, report.payload_bytes);
+    assert_eq!(outcome.bits_flipped, report.bits_flipped);
+    assert_eq!(outcome.capacity_used, (report.utilization / 100.0) as f32);
+    assert!(outcome.capacity_used > 0.0 && outcome.capacity_used < 0.1);
+    assert!(outcome.warnings.is_empty());
+    assert!(report.warnings.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_outcome_reports_selected_lsbs() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 32, Format::Png);
+    // More than fits with 1 bit per sample
+    let input = vec![7; capacity(&container, 1)? + 1];
+
+    let outcome = embed_with(
+        &input,
+        "",
+        &container,
+        &EmbedOptions::new().lsbs(LsbSelection::Auto),
+    )?;
+
+    assert_eq!(outcome.lsbs_used, 2);
+    assert!(outcome.capacity_used > 0.5 && outcome.capacity_used < 0.51);
+
+    Ok(())
+}
+
+#[test]
+fn test_full_capacity_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 32, Format::Png);
+    // Exactly as much as fits
+    let input = vec![7; capacity(&container, 1)?];
+
+    let outcome = embed_with(&input, "", &container, &EmbedOptions::new())?;
+
+    let [Warning::HighUtilization { capacity_used }] = outcome.warnings[..] else {
+        panic!("{:?}", outcome.warnings);
+    };
+    assert_eq!(capacity_used, outcome.capacity_used);
+    assert_eq!(capacity_used, 1.0);
+    assert_eq!(outcome.warnings[0].code(), "high_utilization");
+    assert!(outcome.warnings[0].to_string().contains("100.0%"));
+
+    Ok(())
+}
+
+#[test]
+fn test_overwritten_payload_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let options = EmbedOptions::new().overwrite_policy(OverwritePolicy::Warn);
+    let embedded = embed_with(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(64, 48, Format::Png),
+        &options,
+    )?
+    .image;
+
+    let outcome = embed_with(b"Another payload", "bin", &embedded, &options)?;
+
+    let [Warning::PayloadOverwritten(message)] = &outcome.warnings[..] else {
+        panic!("{:?}", outcome.warnings);
+    };
+    assert!(message.contains("extension \"txt\""), "{}", message);
+    assert_eq!(outcome.warnings[0].code(), "payload_overwritten");
+
+    Ok(())
+}
+
+#[test]
+fn test_upscaled_container_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(16, 16, Format::Png);
+    let input = vec![7; capacity(&container, 1)? * 2];
+    let options = EmbedOptions::new().resize_policy(ResizePolicy::UpscaleToFit {
+        max_scale: 4.0,
+        filter: FilterType::Triangle,
+    });
+
+    let outcome = embed_with(&input, "", &container, &options)?;
+
+    let Some(Warning::Upscaled { from, to }) = outcome.warnings.first() else {
+        panic!("{:?}", outcome.warnings);
+    };
+    assert_eq!(*from, (16, 16));
+    assert_eq!(*to, (outcome.width, outcome.height));
+    assert!(outcome.width > 16 && outcome.height > 16);
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+mod test_util;
+
+const FIRST: &[u8] = b"The first payload.";
+const SECOND: &[u8] = b"The second payload, which would destroy the first.";
+
+fn options(overwrite_policy: OverwritePolicy) -> EmbedOptions {
+    EmbedOptions {
+        lsbs: LsbSelection::Fixed(1),
+        hash: Hash::Blake3,
+        seed: 42,
+        format: Some(Format::Png),
+        overwrite_policy,
+        ..EmbedOptions::default()
+    }
+}
+
+#[test]
+fn test_overwrite_error() -> Result<(), Box<dyn std::error::Error>> {
+    let options = options(OverwritePolicy::Error);
+    let embedded = embed_with(
+        FIRST,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &options,
+    )?
+    .image;
+
+    let result = embed_with(SECOND, "txt", &embedded, &options);
+
+    assert!(matches!(result, Err(StegError::ExistingPayload(_))));
+    assert_eq!(extract(&embedded, 1, 42)?.0, FIRST);
+
+    Ok(())
+}
+
+#[test]
+fn test_overwrite_error_clean_container() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with(
+        FIRST,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &options(OverwritePolicy::Error),
+    )?
+    .image;
+
+    assert_eq!(extract(&embedded, 1, 42)?.0, FIRST);
+
+    Ok(())
+}
+
+#[test]
+fn test_overwrite_allow() -> Result<(), Box<dyn std::error::Error>> {
+    let options = options(OverwritePolicy::Allow);
+    let embedded = embed_with(
+        FIRST,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &options,
+    )?
+    .image;
+
+    let overwritten = embed_with(SECOND, "txt", &embedded, &options)?.image;
+
+    assert_eq!(
+        overwritten,
+        embed(SECOND, "txt", &embedded, 1, Hash::Blake3, 42, Format::Png)?
+    );
+    assert_eq!(extract(&overwritten, 1, 42)?.0, SECOND);
+
+    Ok(())
+}
+
+#[test]
+fn test_overwrite_warn() -> Result<(), Box<dyn std::error::Error>> {
+    let options = options(OverwritePolicy::Warn);
+    let embedded = embed_with(
+        FIRST,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &options,
+    )?
+    .image;
+
+    let overwritten = embed_with(SECOND, "txt", &embedded, &options)?.image;
+
+    assert_eq!(extract(&overwritten, 1, 42)?.0, SECOND);
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Plans must agree with what embedding does.";
+
+#[test]
+fn test_plan_matches_report() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 32, Format::Png);
+
+    for lsbs in [LsbSelection::Fixed(3), LsbSelection::Auto] {
+        let options = EmbedOptions {
+            lsbs,
+            hash: Hash::Sha512,
+            ..EmbedOptions::default()
+        };
+
+        let plan = plan_embed(INPUT.len(), "txt", &container, &options)?;
+        let (_, report) = embed_with_report(INPUT, "txt", &container, &options)?;
+
+        assert_eq!(plan.payload_bytes, report.payload_bytes);
+        assert_eq!(plan.lsbs, report.lsbs);
+        assert_eq!(plan.capacity_bits, report.capacity_bits);
+        assert_eq!(plan.utilization, report.utilization);
+        assert_eq!(plan.hash, Hash::Sha512);
+        assert_eq!(plan.existing_payload, None);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_plan_failures_match_embed() -> Result<(), Box<dyn std::error::Error>> {
+    let small = test_util::encoded_container(4, 4, Format::Png);
+    let large = test_util::encoded_container(32, 32, Format::Png);
+    let embedded = embed(INPUT, "txt", &large, 1, Hash::Blake3, 42, Format::Png)?;
+    let long_extension = "x".repeat(300);
+
+    let cases = [
+        (
+            &large,
+            "txt",
+            EmbedOptions {
+                format: Some(Format::Jpeg),
+                ..EmbedOptions::default()
+            },
+        ),
+        (
+            &large,
+            "txt",
+            EmbedOptions {
+                lsbs: LsbSelection::Fixed(9),
+                ..EmbedOptions::default()
+            },
+        ),
+        (&large, long_extension.as_str(), EmbedOptions::default()),
+        (&small, "txt", EmbedOptions::default()),
+        (
+            &small,
+            "txt",
+            EmbedOptions {
+                lsbs: LsbSelection::Auto,
+                ..EmbedOptions::default()
+            },
+        ),
+        (
+            &embedded,
+            "txt",
+            EmbedOptions {
+                overwrite_policy: OverwritePolicy::Error,
+                ..EmbedOptions::default()
+            },
+        ),
+    ];
+
+    for (container, extension, options) in cases {
+        let planned = plan_embed(INPUT.len(), extension, container, &options).unwrap_err();
+        let actual = embed_with(INPUT, extension, container, &options).unwrap_err();
+
+        assert_eq!(
+            std::mem::discriminant(&planned),
+            std::mem::discriminant(&actual)
+        );
+        assert_eq!(planned.to_string(), actual.to_string());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_plan_reports_existing_payload() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(32, 32, Format::Png);
+    let embedded = embed(INPUT, "txt", &container, 1, Hash::Blake3, 42, Format::Png)?;
+    let options = EmbedOptions {
+        overwrite_policy: OverwritePolicy::Warn,
+        ..EmbedOptions::default()
+    };
+
+    let plan = plan_embed(INPUT.len(), "txt", &embedded, &options)?;
+    assert!(plan.existing_payload.is_some_and(|m| m.contains("\"txt\"")));
+
+    assert!(matches!(
+        plan_embed(INPUT.len(), "txt", b"not an image", &options),
+        Err(StegError::ImageProcessing(_))
+    ));
+
+    Ok(())
+}
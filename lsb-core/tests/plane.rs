@@ -0,0 +1,164 @@
+use lsb_core::{error::StegError, hash::Hash, layout::*, order::*, *};
+
+const INPUT: &[u8] = b"Planes hold pixels without an image around them.";
+
+fn samples(width: u32, height: u32) -> Vec<u8> {
+    (0..width * height * 3)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect()
+}
+
+#[test]
+fn test_plane_roundtrip() -> Result<(), StegError> {
+    for &hash in Hash::all() {
+        for lsbs in 1..=8 {
+            let mut plane = Plane::new(samples(40, 30), 40, 30)?;
+            plane.embed(INPUT, "txt", lsbs, hash, 7)?;
+
+            let (data, extension) = plane.extract(lsbs, 7)?;
+            assert_eq!(data, INPUT, "{:?} with {} lsbs", hash, lsbs);
+            assert_eq!(extension, "txt");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_plane_borrows_samples() -> Result<(), StegError> {
+    let mut buffer = samples(20, 20);
+    let original = buffer.clone();
+
+    Plane::new(buffer.as_mut_slice(), 20, 20)?.embed(INPUT, "", 2, Hash::Sha256, 3)?;
+    assert_ne!(buffer, original);
+    assert_eq!(Plane::new(&buffer, 20, 20)?.extract(2, 3)?.0, INPUT);
+
+    Ok(())
+}
+
+#[test]
+fn test_read_and_write_bytes() -> Result<(), StegError> {
+    let mut plane = Plane::new(samples(10, 10), 10, 10)?;
+    let order = KeyedPermutation::new(9);
+
+    let flipped = plane.write_bytes(b"abc", 3, &order, 5)?;
+    assert!(flipped <= 24);
+    assert_eq!(plane.read_bytes(3, &order, 5, 3)?, b"abc");
+    assert_eq!(plane.write_bytes(b"abc", 3, &order, 5)?, 0);
+
+    // The last byte fits, the one after it does not
+    let last = plane.capacity_bits(3)? as usize / 8 - 1;
+    plane.write_bytes(b"z", 3, &Sequential, last)?;
+    assert_eq!(plane.read_bytes(3, &Sequential, last, 1)?, b"z");
+    assert!(matches!(
+        plane.read_bytes(3, &Sequential, last, 2),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_plane_errors() -> Result<(), StegError> {
+    assert!(matches!(
+        Plane::new(samples(10, 10), 10, 11),
+        Err(StegError::BufferSizeMismatch { .. })
+    ));
+
+    let mut plane = Plane::new(samples(10, 10), 10, 10)?;
+    let original = plane.samples().to_vec();
+    for lsbs in [0, 9] {
+        assert!(matches!(
+            plane.embed(INPUT, "txt", lsbs, Hash::Blake3, 1),
+            Err(StegError::InvalidLsbValue { .. })
+        ));
+    }
+    assert!(matches!(
+        plane.embed(&[0; 200], "txt", 1, Hash::Blake3, 1),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+    assert_eq!(plane.samples(), original);
+
+    assert!(matches!(
+        plane.extract(1, 1),
+        Err(StegError::NoPayloadFound { .. })
+    ));
+    assert!(matches!(
+        Plane::new(samples(2, 2), 2, 2)?.extract(1, 1),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_plane_detects_changes() -> Result<(), StegError> {
+    let (lsbs, seed) = (1, 5);
+    let order = KeyedPermutation::new(seed);
+    let mut plane = Plane::new(samples(40, 30), 40, 30)?;
+    plane.embed(INPUT, "txt", lsbs, Hash::Blake3, seed)?;
+
+    // Flip the last byte of the data
+    let length = plane.read_bytes(lsbs, &order, 0, LENGTH_SIZE)?;
+    let last =
+        LENGTH_SIZE + u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize - 1;
+    let byte = plane.read_bytes(lsbs, &order, last, 1)?[0];
+    plane.write_bytes(&[!byte], lsbs, &order, last)?;
+    assert!(matches!(
+        plane.extract(lsbs, seed),
+        Err(StegError::PayloadCorrupted { .. })
+    ));
+
+    // Outside of the payload bits, the fingerprint changes
+    let mut samples = plane.into_samples();
+    samples[0] ^= 0x80;
+    assert!(matches!(
+        Plane::new(samples, 40, 30)?.extract(lsbs, seed),
+        Err(StegError::ContainerModified)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_plane_reads_seeded_shuffle_payloads() -> Result<(), StegError> {
+    let checksum = blake3::hash(INPUT);
+    let payload = [
+        &[3][..],
+        b"bin",
+        &[Hash::Blake3 as u8],
+        checksum.as_bytes(),
+        INPUT,
+    ]
+    .concat();
+    let length = (payload.len() as u32).to_le_bytes();
+
+    let mut plane = Plane::new(samples(30, 30), 30, 30)?;
+    let order = SeededShuffle::new(11);
+    plane.write_bytes(&[&length[..], &payload].concat(), 2, &order, 0)?;
+
+    let (data, extension) = plane.extract(2, 11)?;
+    assert_eq!(data, INPUT);
+    assert_eq!(extension, "bin");
+    assert_eq!(plane.extract_with_order(2, &order)?, (data, extension));
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_plane_matches_raw_rgb() -> Result<(), StegError> {
+    for lsbs in [1, 3, 8] {
+        let mut plane = Plane::new(samples(40, 30), 40, 30)?;
+        plane.embed(INPUT, "bin", lsbs, Hash::Sha512, 13)?;
+
+        let mut pixels = samples(40, 30);
+        embed_raw_rgb(INPUT, "bin", &mut pixels, 40, 30, lsbs, Hash::Sha512, 13)?;
+        assert_eq!(plane.samples(), pixels);
+
+        let (data, extension) = extract_raw_rgb(plane.samples(), 40, 30, lsbs, 13)?;
+        assert_eq!((data.as_slice(), extension.as_str()), (INPUT, "bin"));
+    }
+
+    Ok(())
+}
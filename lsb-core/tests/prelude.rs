@@ -0,0 +1,29 @@
+#![cfg(feature = "std")]
+
+use lsb_core::prelude::*;
+
+#[test]
+fn test_prelude_roundtrip() -> StegResult<()> {
+    let image = ::image::RgbImage::from_fn(32, 32, |x, y| ::image::Rgb([x as u8, y as u8, 0]));
+    let mut container = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut container, ::image::ImageFormat::Png)?;
+
+    let options = EmbedOptions::new()
+        .lsbs(2)
+        .hash(Hash::Sha256)
+        .format(Format::Bmp);
+    let embedded = lsb_core::embed_with(b"prelude", "txt", container.get_ref(), &options)?.image;
+
+    let container = StegoContainer::new(&embedded)?;
+    assert_eq!(container.format(), Some(Format::Bmp));
+    let (data, extension) = Stego::from(options.clone()).extract(&container)?;
+    assert_eq!((&data[..], &extension[..]), (&b"prelude"[..], "txt"));
+
+    let extracted: Extracted = lsb_core::extract_with(&embedded, &ExtractOptions::from(&options))?;
+    assert_eq!(extracted.data, data);
+
+    let result = lsb_core::embed_with(b"prelude", "txt", &embedded, &options.lsbs(9));
+    assert!(matches!(result, Err(StegError::InvalidLsbValue { .. })));
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "std")]
+
+use std::{
+    io::Cursor,
+    sync::{Arc, Mutex},
+};
+
+use lsb_core::*;
+
+mod test_util;
+
+/// Collects every progress report of an operation.
+#[derive(Clone, Default)]
+struct Reports(Arc<Mutex<Vec<Progress>>>);
+
+impl Reports {
+    fn callback(&self) -> impl Fn(Progress) + Send + Sync + 'static {
+        let reports = self.0.clone();
+        move |progress| reports.lock().unwrap().push(progress)
+    }
+
+    fn take(&self) -> Vec<Progress> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+fn phases(reports: &[Progress]) -> Vec<Phase> {
+    let mut phases = reports.iter().map(|p| p.phase).collect::<Vec<_>>();
+    phases.dedup();
+    phases
+}
+
+/// Checks that the reports of `phase` grow and end with all of its work done.
+fn assert_completes(reports: &[Progress], phase: Phase) {
+    let reports = reports
+        .iter()
+        .filter(|p| p.phase == phase)
+        .collect::<Vec<_>>();
+    assert!(!reports.is_empty(), "{:?} was not reported", phase);
+
+    for pair in reports.windows(2) {
+        assert!(pair[0].completed < pair[1].completed, "{:?}", pair);
+        assert_eq!(pair[0].total, pair[1].total);
+    }
+    let last = reports.last().unwrap();
+    assert_eq!(last.completed, last.total);
+}
+
+#[test]
+fn test_embed_progress() -> Result<(), Box<dyn std::error::Error>> {
+    let input = (0..200_000u32).map(|i| (i * 31) as u8).collect::<Vec<_>>();
+    let container = test_util::encoded_container(512, 512, Format::Png);
+    let reports = Reports::default();
+
+    let options = EmbedOptions::new().lsbs(4).threads(4);
+    let tracked = options.clone().progress(reports.callback());
+    let output = embed_with(&input, "bin", &container, &tracked)?.image;
+    // Reporting progress does not change the output
+    assert_eq!(
+        output,
+        embed_with(&input, "bin", &container, &options)?.image
+    );
+
+    let reports = reports.take();
+    assert_eq!(
+        phases(&reports),
+        [Phase::Decode, Phase::Order, Phase::Embed, Phase::Encode]
+    );
+    assert_completes(&reports, Phase::Embed);
+    // The payload is written in more than one chunk
+    assert!(reports.iter().filter(|p| p.phase == Phase::Embed).count() > 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_progress_streamed_and_verified() -> Result<(), Box<dyn std::error::Error>> {
+    let input = vec![0x5a; 300_000];
+    let container = test_util::encoded_container(512, 512, Format::Png);
+    let reports = Reports::default();
+
+    let options = EmbedOptions::new()
+        .lsbs(4)
+        .verify_after_embed(true)
+        .progress(reports.callback());
+    embed_from_reader(
+        &input[..],
+        input.len() as u64,
+        "bin",
+        &container,
+        &options,
+        Cursor::new(Vec::new()),
+    )?;
+
+    let reports = reports.take();
+    assert_eq!(
+        phases(&reports),
+        [Phase::Decode, Phase::Embed, Phase::Encode, Phase::Verify]
+    );
+    assert_completes(&reports, Phase::Embed);
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_progress() -> Result<(), Box<dyn std::error::Error>> {
+    let input = (0..1_200_000u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let container = test_util::encoded_container(1024, 1024, Format::Png);
+
+    // Long payloads are read in sorted buckets, short ones in payload order
+    for input in [&input[..], &input[..1000]] {
+        let embedded = embed_with(input, "dat", &container, &EmbedOptions::new().lsbs(4))?.image;
+        let reports = Reports::default();
+        let options = ExtractOptions::new()
+            .lsbs(4)
+            .threads(4)
+            .progress(reports.callback());
+        assert_eq!(extract_with(&embedded, &options)?.data, input);
+
+        let reports = reports.take();
+        assert_eq!(phases(&reports), [Phase::Decode, Phase::Extract]);
+        assert_completes(&reports, Phase::Extract);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_progress_streamed() -> Result<(), Box<dyn std::error::Error>> {
+    let input = vec![0xa5; 300_000];
+    let embedded = embed_with(
+        &input,
+        "bin",
+        &test_util::encoded_container(512, 512, Format::Png),
+        &EmbedOptions::new().lsbs(4),
+    )?
+    .image;
+    let reports = Reports::default();
+
+    // The options of a session reach its extractions too
+    let options = EmbedOptions::new().lsbs(4).progress(reports.callback());
+    let mut output = Vec::new();
+    Stego::from(options).extract_to_writer(&StegoContainer::new(&embedded)?, &mut output)?;
+    assert_eq!(output, input);
+
+    let reports = reports.take();
+    assert_eq!(phases(&reports), [Phase::Extract]);
+    assert_completes(&reports, Phase::Extract);
+    assert_eq!(reports.last().unwrap().total, input.len() as u64 * 8);
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{
+    error::{ErrorKind, StegError},
+    order::SeededShuffle,
+    *,
+};
+
+mod test_util;
+
+#[test]
+fn test_range_matches_full_extraction() -> Result<(), Box<dyn std::error::Error>> {
+    let input = (0..20_000u32)
+        .map(|i| (i * 7 % 256) as u8)
+        .collect::<Vec<_>>();
+    let container = test_util::encoded_container(256, 256, Format::Png);
+
+    for lsbs in [1, 3] {
+        let options = EmbedOptions::new().lsbs(lsbs).seed(99);
+        let embedded = embed_with(&input, "tar", &container, &options)?.image;
+        let (data, _) = extract(&embedded, lsbs, 99)?;
+
+        for (offset, len) in [
+            (0, 0),
+            (0, 16),
+            (1, 1),
+            (4096, 513),
+            (19_990, 10),
+            (20_000, 0),
+        ] {
+            assert_eq!(
+                extract_range(&embedded, offset, len, lsbs, 99)?,
+                data[offset..offset + len],
+                "{} bytes at {} with {} lsbs",
+                len,
+                offset,
+                lsbs
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_range_skips_seeded_shuffle() -> Result<(), Box<dyn std::error::Error>> {
+    let input = (0..5_000u32).map(|i| (i % 253) as u8).collect::<Vec<_>>();
+    let options = EmbedOptions::new().lsbs(2);
+    let embedded = embed_with_order(
+        &input,
+        "bin",
+        &test_util::encoded_container(128, 128, Format::Png),
+        &options,
+        &SeededShuffle::new(5),
+    )?;
+
+    // The positions of a shuffle cannot be skipped, so only the keyed order is read
+    assert_eq!(
+        extract_range(&embedded, 1234, 100, 2, 5)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::NotFound
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_range_out_of_bounds() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_with(
+        b"0123456789",
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        &EmbedOptions::new(),
+    )?
+    .image;
+
+    assert_eq!(extract_range(&embedded, 8, 2, 1, 42)?, b"89");
+    for (offset, len) in [(8, 3), (11, 0), (0, 11), (usize::MAX, 2)] {
+        let result = extract_range(&embedded, offset, len, 1, 42);
+        assert!(
+            matches!(result, Err(StegError::OutOfRange { .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    // Without a payload there is no data to take a range of
+    assert!(
+        extract_range(
+            &test_util::encoded_container(64, 64, Format::Png),
+            0,
+            1,
+            1,
+            42
+        )
+        .is_err()
+    );
+
+    Ok(())
+}
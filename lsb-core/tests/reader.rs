@@ -0,0 +1,112 @@
+#![cfg(feature = "std")]
+
+use std::io::Cursor;
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+mod test_util;
+
+#[test]
+fn test_reader_matches_slice() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(512, 512, Format::Png);
+    // Larger than a single read chunk
+    let input = (0..100_000)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+
+    for (lsbs, hash, format, verify_after_embed) in [
+        (2, Hash::Blake3, Format::Png, false),
+        (3, Hash::Sha256, Format::Png, true),
+        (4, Hash::Sha1, Format::OpenExr, false),
+    ] {
+        let options = EmbedOptions {
+            lsbs: LsbSelection::Fixed(lsbs),
+            hash,
+            format: Some(format),
+            verify_after_embed,
+            ..EmbedOptions::default()
+        };
+
+        let mut writer = Cursor::new(Vec::new());
+        embed_from_reader(
+            Cursor::new(&input),
+            input.len() as u64,
+            "bin",
+            &container,
+            &options,
+            &mut writer,
+        )?;
+
+        assert_eq!(
+            writer.into_inner(),
+            embed_with(&input, "bin", &container, &options)?.image
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_ends_early() {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+
+    let result = embed_from_reader(
+        Cursor::new(b"short"),
+        100,
+        "txt",
+        &container,
+        &EmbedOptions::default(),
+        Cursor::new(Vec::new()),
+    );
+    assert!(matches!(result, Err(StegError::Io(_))), "{:?}", result);
+}
+
+#[test]
+fn test_chunks_match_slice() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(512, 512, Format::Png);
+    let input = (0..100_000)
+        .map(|i| (i * 17 % 253) as u8)
+        .collect::<Vec<_>>();
+    let options = EmbedOptions::new().lsbs(3).hash(Hash::Sha256).seed(5);
+    let expected = embed_with(&input, "bin", &container, &options)?.image;
+
+    // Many small chunks of uneven sizes, some of them empty
+    let mut chunks = Vec::new();
+    let mut rest = &input[..];
+    for size in (0..).map(|i| i % 97) {
+        if rest.is_empty() {
+            break;
+        }
+        let (chunk, tail) = rest.split_at(size.min(rest.len()));
+        chunks.push(chunk.to_vec());
+        rest = tail;
+    }
+    assert!(chunks.iter().any(Vec::is_empty));
+
+    let len = input.len() as u64;
+    assert_eq!(
+        embed_chunks(&chunks, len, "bin", &container, &options)?,
+        expected
+    );
+    assert_eq!(
+        embed_chunks(input.chunks(1), len, "bin", &container, &options)?,
+        expected
+    );
+    assert_eq!(
+        embed_chunks([&input[..]], len, "bin", &container, &options)?,
+        expected
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_chunks_wrong_length() {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let chunks = [b"some".to_vec(), b"thing".to_vec()];
+
+    for len in [5, 12] {
+        let result = embed_chunks(&chunks, len, "txt", &container, &EmbedOptions::default());
+        assert!(matches!(result, Err(StegError::Io(_))), "{:?}", result);
+    }
+}
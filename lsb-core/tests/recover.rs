@@ -0,0 +1,55 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{hash::Hash, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"Lost parameters, found data.";
+
+#[test]
+fn test_recover_among_decoys() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed(
+        INPUT,
+        "md",
+        &test_util::encoded_container(48, 48, Format::Png),
+        2,
+        Hash::Sha256,
+        1337,
+        Format::Png,
+    )?;
+
+    let seeds = (1300..1351).filter(|&seed| seed != 1337).chain([1337]);
+    let hits = recover(&embedded, 1..=2, seeds, None);
+
+    assert_eq!(hits.len(), 1, "{:?}", hits);
+    let hit = &hits[0];
+    assert_eq!((hit.lsbs, hit.seed), (2, 1337));
+    assert_eq!(hit.extension, "md");
+    assert_eq!(hit.hash, Hash::Sha256);
+    assert!(hit.verified);
+    assert_eq!(hit.data.as_deref(), Some(INPUT));
+
+    Ok(())
+}
+
+#[test]
+fn test_recover_unverified_over_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed(
+        INPUT,
+        "md",
+        &test_util::encoded_container(48, 48, Format::Png),
+        1,
+        Hash::Blake3,
+        9,
+        Format::Png,
+    )?;
+
+    let hits = recover(&embedded, 1..=8, 0..20, Some(16));
+
+    assert_eq!(hits.len(), 1, "{:?}", hits);
+    assert!(!hits[0].verified && hits[0].data.is_none());
+    assert_eq!(hits[0].confidence, 0.5);
+    assert!(recover(b"not an image", 1..=1, 0..1, None).is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "std")]
+
+use lsb_core::*;
+
+mod test_util;
+
+const INPUT: &[u8] = b"Reports tell how much of the container was used.";
+
+#[test]
+fn test_report_values() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(2),
+        ..EmbedOptions::default()
+    };
+
+    let (embedded, report) = embed_with_report(INPUT, "txt", &container, &options)?;
+
+    assert_eq!(
+        embedded,
+        embed_with(INPUT, "txt", &container, &options)?.image
+    );
+    assert_eq!(report.data_bytes, INPUT.len());
+    assert_eq!(report.payload_bytes, 4 + 1 + 3 + 1 + 8 + 32 + INPUT.len());
+    assert_eq!(report.bits_written, report.payload_bytes * 8);
+    assert_eq!(report.capacity_bits, 64 * 64 * 3 * 2);
+    assert!(report.bits_flipped > 0 && report.bits_flipped <= report.bits_written);
+    assert!(
+        (report.utilization - report.bits_written as f64 / report.capacity_bits as f64 * 100.0)
+            .abs()
+            < 1e-9
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_report_counts_flipped_bits() -> Result<(), Box<dyn std::error::Error>> {
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Auto,
+        ..EmbedOptions::default()
+    };
+
+    let (embedded, first) = embed_with_report(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(32, 32, Format::Png),
+        &options,
+    )?;
+    assert_eq!(first.lsbs, 1);
+
+    let before =
+        ::image::load_from_memory(&test_util::encoded_container(32, 32, Format::Png))?.into_rgb8();
+    let after = ::image::load_from_memory(&embedded)?.into_rgb8();
+    let changed = before
+        .iter()
+        .zip(after.iter())
+        .map(|(a, b)| (a ^ b).count_ones() as usize)
+        .sum::<usize>();
+    assert_eq!(first.bits_flipped, changed);
+
+    // Embedding the same payload again changes nothing
+    let (_, second) = embed_with_report(INPUT, "txt", &embedded, &options)?;
+    assert_eq!(second.bits_flipped, 0);
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{
+    hash::Hash,
+    image::{NoiseStyle, generate_container},
+    metrics::{quality_metrics_from_bytes, ssim_from_bytes},
+    *,
+};
+
+const INPUT: &[u8] = include_bytes!("../../data/input.webp");
+const CONTAINER: &[u8] = include_bytes!("../../data/container.webp");
+
+fn digest(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+/// Embeds and analyzes fixed inputs, checking the results against digests recorded from
+/// the parallel build, so that the sequential build must produce the same bits.
+#[test]
+fn test_output_matches_recorded() -> Result<(), Box<dyn std::error::Error>> {
+    let mut digests = Vec::new();
+
+    for lsbs in [1, 3] {
+        let embedded = embed(
+            INPUT,
+            "webp",
+            CONTAINER,
+            lsbs,
+            Hash::Sha256,
+            42,
+            Format::Pnm,
+        )?;
+        assert_eq!(extract(&embedded, lsbs, 42)?.0, INPUT);
+
+        let analysis = format!(
+            "{:?} {:?} {:?} {:?}",
+            diff(CONTAINER, &embedded)?,
+            quality_metrics_from_bytes(CONTAINER, &embedded)?,
+            ssim_from_bytes(CONTAINER, &embedded)?,
+            chi_square_analysis(&embedded, 64)?,
+        );
+
+        digests.push(digest(&embedded));
+        digests.push(digest(analysis.as_bytes()));
+    }
+
+    assert_eq!(
+        digests,
+        [
+            "408c3a0bd5ed96cfb89b51b2921ae2e26041e3cede4b16cfcbbb604a740e6367",
+            "29eb09e356daa43e732d779ee8caaf48af259e031e4e9c28f04ce29a0384c9da",
+            "cd27b64f32267257c801aa02aefe48667167ee5d2c353a1f682cfbdfdc20450d",
+            "cf76f3f6475e553578efda50b5dc2fa4336f0e2ade226dd9198c81df7c430784",
+        ]
+    );
+
+    Ok(())
+}
+
+/// Embeds payloads with every hash and extensions of every length, directly and from a
+/// reader, checking the results against a digest recorded before the payload builder
+/// was rewritten.
+#[test]
+fn test_payload_matches_recorded() -> Result<(), Box<dyn std::error::Error>> {
+    let container = generate_container(64, 64, NoiseStyle::Uniform, 7);
+    let mut encoded = Vec::new();
+    container.write_to(
+        &mut std::io::Cursor::new(&mut encoded),
+        ::image::ImageFormat::Pnm,
+    )?;
+
+    let long_extension = "x".repeat(u8::MAX as usize);
+    let mut hasher = blake3::Hasher::new();
+
+    for hash in [Hash::Blake3, Hash::Sha256, Hash::Sha512, Hash::Sha1] {
+        for extension in ["", "txt", &long_extension] {
+            for input in [&b""[..], b"Payload bytes"] {
+                let mut image = container.clone();
+                embed_into_image(&mut image, input, extension, 2, hash, 42)?;
+                hasher.update(&image);
+
+                let options = EmbedOptions {
+                    lsbs: LsbSelection::Fixed(2),
+                    hash,
+                    format: Some(Format::Pnm),
+                    ..EmbedOptions::default()
+                };
+                let mut output = std::io::Cursor::new(Vec::new());
+                embed_from_reader(
+                    input,
+                    input.len() as u64,
+                    extension,
+                    &encoded,
+                    &options,
+                    &mut output,
+                )?;
+                hasher.update(output.get_ref());
+            }
+        }
+    }
+
+    assert_eq!(
+        hasher.finalize().to_hex().as_str(),
+        "897d17b8f960d5bad028a64ee305cbc6e8e9337ca6164ff247fd68a6d02b3253"
+    );
+
+    Ok(())
+}
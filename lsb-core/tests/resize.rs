@@ -0,0 +1,75 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, image::FilterType, *};
+
+mod test_util;
+
+fn options(max_scale: f32) -> EmbedOptions {
+    EmbedOptions {
+        resize_policy: ResizePolicy::UpscaleToFit {
+            max_scale,
+            filter: FilterType::Triangle,
+        },
+        ..EmbedOptions::default()
+    }
+}
+
+/// A payload that needs about 10% more pixels than a 100x100 container has at 1 lsb.
+fn oversized_input() -> Vec<u8> {
+    let payload_bits = 100 * 100 * 3 * 11 / 10;
+    (0..payload_bits / 8 - 46).map(|i| i as u8).collect()
+}
+
+#[test]
+fn test_upscale_to_fit() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(100, 100, Format::Png);
+    let input = oversized_input();
+
+    let plan = plan_embed(input.len(), "bin", &container, &options(1.2))?;
+    assert!(plan.width > 100 && plan.width <= 110);
+    assert!(plan.height > 100 && plan.height <= 110);
+
+    let (embedded, report) = embed_with_report(&input, "bin", &container, &options(1.2))?;
+    assert_eq!((report.width, report.height), (plan.width, plan.height));
+
+    let output = ::image::load_from_memory(&embedded)?;
+    assert_eq!((output.width(), output.height()), (plan.width, plan.height));
+
+    let (data, ext) = extract(&embedded, 1, 42)?;
+    assert_eq!(data, input);
+    assert_eq!(ext, "bin");
+
+    Ok(())
+}
+
+#[test]
+fn test_upscale_not_needed() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(100, 100, Format::Png);
+
+    let (_, report) = embed_with_report(b"small", "txt", &container, &options(1.2))?;
+    assert_eq!((report.width, report.height), (100, 100));
+
+    Ok(())
+}
+
+#[test]
+fn test_resize_never_is_default() {
+    let container = test_util::encoded_container(100, 100, Format::Png);
+    let input = oversized_input();
+
+    assert!(matches!(
+        embed_with(&input, "bin", &container, &EmbedOptions::default()),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+}
+
+#[test]
+fn test_upscale_exceeds_max_scale() {
+    let container = test_util::encoded_container(100, 100, Format::Png);
+    let input = oversized_input();
+
+    assert!(matches!(
+        embed_with(&input, "bin", &container, &options(1.02)),
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+}
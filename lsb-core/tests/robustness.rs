@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{hash::Hash, layout::*, order::KeyedPermutation, *};
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+
+const GOLDEN_PNG: &[u8] = include_bytes!("../../data/golden-v1.png");
+
+/// Generates a payload whose header fields are each valid, corrupt or at an extreme,
+/// independently of each other.
+fn corrupt_payload(rng: &mut Pcg64Mcg) -> Vec<u8> {
+    let data: Vec<u8> = (0..rng.random_range(0..64)).map(|_| rng.random()).collect();
+    let extension: Vec<u8> = match rng.random_range(0..3) {
+        0 => b"txt".to_vec(),
+        1 => vec![0xff, 0xfe],
+        _ => (0..rng.random_range(0..300))
+            .map(|_| rng.random())
+            .collect(),
+    };
+    let hash = *Hash::all().choose(rng).unwrap();
+    let hash_flag = match rng.random_range(0..3) {
+        0 => hash as u8 | FINGERPRINT_FLAG | KEYED_ORDER_FLAG,
+        1 => hash as u8,
+        _ => rng.random(),
+    };
+    let checksum_len = match rng.random_range(0..3) {
+        0 => hash.digest_size(),
+        1 => 0,
+        _ => rng.random_range(0..80),
+    };
+
+    let mut payload = vec![match rng.random_range(0..3) {
+        0 => extension.len() as u8,
+        1 => u8::MAX,
+        _ => rng.random(),
+    }];
+    payload.extend_from_slice(&extension);
+    payload.push(hash_flag);
+    payload.extend((0..FINGERPRINT_SIZE).map(|_| rng.random::<u8>()));
+    payload.extend((0..checksum_len).map(|_| rng.random::<u8>()));
+    payload.extend_from_slice(&data);
+
+    let length: u32 = match rng.random_range(0..4) {
+        0 => payload.len() as u32,
+        1 => u32::MAX,
+        2 => 0,
+        _ => rng.random(),
+    };
+    [&length.to_le_bytes()[..], &payload].concat()
+}
+
+/// Writes as many bits of `payload` as fit into `samples` where extraction with `lsbs`
+/// and `seed` reads them.
+fn write_payload(samples: &mut [u8], payload: &[u8], lsbs: usize, seed: u64) {
+    let capacity_bits = (samples.len() * lsbs) as u64;
+    let permutation = KeyedPermutation::new(seed);
+    let bits = payload
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+
+    for (index, bit) in (0..capacity_bits).zip(bits) {
+        let position = permutation.position(capacity_bits, index);
+        let sample = &mut samples[(position / lsbs as u64) as usize];
+        let bit_in_sample = position % lsbs as u64;
+        *sample = (*sample & !(1 << bit_in_sample)) | (bit << bit_in_sample);
+    }
+}
+
+/// Runs every extraction of `input` with `lsbs` and `seed`, which may fail but must not
+/// panic.
+fn extract_all(input: &[u8], lsbs: usize, seed: u64) {
+    let _ = extract(input, lsbs, seed);
+    let _ = extract_with(
+        input,
+        &ExtractOptions::new().lsbs(lsbs).seed(seed).verify(false),
+    );
+    let _ = extract_range(input, 0, 16, lsbs, seed);
+    let _ = extract_to_writer(input, std::io::sink(), lsbs, seed);
+    let _ = recover(input, lsbs..=lsbs, [seed].into_iter(), Some(1024));
+}
+
+#[test]
+fn test_corrupt_headers_fail_without_panicking() {
+    let mut rng = Pcg64Mcg::seed_from_u64(7);
+
+    for _ in 0..500 {
+        let (width, height) = (rng.random_range(0..40), rng.random_range(0..40));
+        let lsbs = rng.random_range(1..=8);
+        let seed = rng.random();
+
+        let mut pixels = vec![rng.random(); (width * height * 3) as usize];
+        write_payload(&mut pixels, &corrupt_payload(&mut rng), lsbs, seed);
+
+        let _ = extract_raw_rgb(&pixels, width, height, lsbs, seed);
+        let _ = extract_raw_rgb(&pixels, width, height, rng.random_range(0..=9), seed);
+    }
+}
+
+#[test]
+fn test_random_bytes_fail_without_panicking() {
+    let mut rng = Pcg64Mcg::seed_from_u64(11);
+
+    for len in [0, 1, 8, 33, 100, 1000] {
+        let input: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+        extract_all(&input, 2, 42);
+    }
+}
+
+#[test]
+fn test_mutated_images_fail_without_panicking() {
+    let mut rng = Pcg64Mcg::seed_from_u64(13);
+
+    for _ in 0..100 {
+        let mut input = GOLDEN_PNG.to_vec();
+        for _ in 0..rng.random_range(1..8) {
+            let index = rng.random_range(0..input.len());
+            input[index] ^= 1 << rng.random_range(0..8);
+        }
+        input.truncate(rng.random_range(0..=input.len()));
+        extract_all(&input, 2, 42);
+    }
+
+    // Truncated files and every lsbs value on the intact one
+    for len in (0..GOLDEN_PNG.len()).step_by(97) {
+        extract_all(&GOLDEN_PNG[..len], 2, 42);
+    }
+    for lsbs in 0..=9 {
+        extract_all(GOLDEN_PNG, lsbs, 42);
+    }
+}
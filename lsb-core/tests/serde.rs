@@ -0,0 +1,37 @@
+// Error reports are only serializable with the `serde` feature
+#![cfg(feature = "serde")]
+
+use lsb_core::{error::StegError, *};
+use serde_json::json;
+
+#[test]
+fn test_error_report_json() {
+    let err = extract(b"not an image", 1, 0).unwrap_err();
+    let json = serde_json::to_value(err.report()).unwrap();
+
+    assert_eq!(json["code"], err.code());
+    assert_eq!(json["kind"], err.kind().as_str());
+    assert_eq!(json["message"], err.to_string());
+
+    let err = StegError::InsufficientCapacity {
+        required_bits: 368,
+        available_bits: 96,
+        missing_pixels: 12,
+    };
+    assert_eq!(
+        serde_json::to_value(err.report()).unwrap(),
+        json!({
+            "code": "insufficient_capacity",
+            "kind": "insufficient_capacity",
+            "message": "Insufficient container capacity: 368 bits required, 96 bits available, \
+                        you need at least 12 more pixels",
+            "details": { "required_bits": 368, "available_bits": 96, "missing_pixels": 12 },
+        })
+    );
+
+    let err = StegError::UnsupportedFormat("xyz".to_string());
+    assert_eq!(
+        serde_json::to_value(err.report()).unwrap()["details"],
+        json!({ "detail": "xyz" })
+    );
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "std")]
+
+use std::sync::Mutex;
+
+use lsb_core::{error::StegResult, hash::Hash, *};
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+
+/// Held by every test, as the SIMD path is switched for the whole process.
+static SWITCHING: Mutex<()> = Mutex::new(());
+
+/// Extracts with the SIMD path disabled or enabled.
+fn extract_with_simd(
+    enabled: bool,
+    image: &::image::RgbImage,
+    lsbs: usize,
+    seed: u64,
+) -> StegResult<(Vec<u8>, String)> {
+    set_simd_enabled(enabled);
+    extract_from_image(image, lsbs, seed)
+}
+
+#[test]
+fn test_simd_matches_scalar() {
+    let _switching = SWITCHING.lock();
+    let mut rng = Pcg64Mcg::seed_from_u64(355);
+
+    for _ in 0..40 {
+        let (width, height) = (rng.random_range(8..300), rng.random_range(8..300));
+        let lsbs = if rng.random_bool(0.5) {
+            1
+        } else {
+            rng.random_range(2..=8)
+        };
+        let seed = rng.random();
+
+        let mut image =
+            ::image::RgbImage::from_fn(width, height, |_, _| ::image::Rgb(rng.random()));
+        // Up to the whole capacity, so that the last samples and payload bytes are hit
+        let capacity = (width * height * 3) as usize * lsbs / 8 - 64;
+        let input = (0..rng.random_range(0..capacity))
+            .map(|_| rng.random())
+            .collect::<Vec<u8>>();
+        embed_into_image(&mut image, &input, "bin", lsbs, Hash::Blake3, seed).unwrap();
+
+        let scalar = extract_with_simd(false, &image, lsbs, seed).unwrap();
+        let simd = extract_with_simd(true, &image, lsbs, seed).unwrap();
+
+        assert_eq!(scalar.0, input);
+        assert_eq!(
+            scalar, simd,
+            "{}x{} at {} lsbs with seed {}",
+            width, height, lsbs, seed
+        );
+    }
+
+    set_simd_enabled(true);
+}
+
+#[test]
+fn test_simd_matches_scalar_without_payload() {
+    let _switching = SWITCHING.lock();
+    let image = ::image::RgbImage::from_fn(97, 61, |x, y| {
+        ::image::Rgb([(x * 7) as u8, (y * 3) as u8, (x ^ y) as u8])
+    });
+
+    // Whatever the header decodes to, both paths must read the same bits
+    let scalar = format!("{:?}", extract_with_simd(false, &image, 1, 7));
+    let simd = format!("{:?}", extract_with_simd(true, &image, 1, 7));
+    assert_eq!(scalar, simd);
+
+    set_simd_enabled(true);
+}
@@ -0,0 +1,226 @@
+#![cfg(feature = "std")]
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use lsb_core::{error::StegError, *};
+use tracing::{
+    Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, layer::Context, prelude::*, registry::LookupSpan};
+
+mod test_util;
+
+/// A span as it was created, with the values its fields were given.
+#[derive(Debug, Clone)]
+struct Recorded {
+    name: &'static str,
+    parent: Option<&'static str>,
+    fields: BTreeMap<&'static str, String>,
+}
+
+impl Visit for Recorded {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+/// Collects every span created while it is the subscriber, in order of creation.
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<(Id, Recorded)>>>);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Spans {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut recorded = Recorded {
+            name: span.name(),
+            parent: span.parent().map(|parent| parent.name()),
+            fields: BTreeMap::new(),
+        };
+        attrs.record(&mut recorded);
+
+        self.0.lock().unwrap().push((id.clone(), recorded));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        let mut spans = self.0.lock().unwrap();
+        // Ids are reused once spans close, so the latest span with the id is the open one
+        if let Some((_, recorded)) = spans.iter_mut().rev().find(|(span, _)| span == id) {
+            values.record(recorded);
+        }
+    }
+}
+
+impl Spans {
+    /// Runs `operation` with this collector as the subscriber of the calling thread.
+    fn collect<T>(&self, operation: impl FnOnce() -> T) -> T {
+        let subscriber = tracing_subscriber::registry().with(self.clone());
+        tracing::subscriber::with_default(subscriber, operation)
+    }
+
+    fn take(&self) -> Vec<Recorded> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+            .into_iter()
+            .map(|(_, recorded)| recorded)
+            .collect()
+    }
+}
+
+/// The distinct `(name, parent)` pairs of `spans`, in order of first creation.
+fn hierarchy(spans: &[Recorded]) -> Vec<(&'static str, Option<&'static str>)> {
+    let mut pairs = Vec::new();
+    for span in spans {
+        if !pairs.contains(&(span.name, span.parent)) {
+            pairs.push((span.name, span.parent));
+        }
+    }
+    pairs
+}
+
+#[test]
+fn test_embed_spans() {
+    let spans = Spans::default();
+    let options = EmbedOptions::new()
+        .lsbs(LsbSelection::Auto)
+        .overwrite_policy(OverwritePolicy::Allow);
+
+    spans
+        .collect(|| {
+            embed_with(
+                b"Hello, world!",
+                "txt",
+                &test_util::encoded_container(64, 48, Format::Png),
+                &options,
+            )
+        })
+        .unwrap();
+    let spans = spans.take();
+
+    assert_eq!(
+        hierarchy(&spans),
+        [
+            ("decode", None),
+            ("embed", None),
+            ("embed_bytes", Some("embed")),
+            ("order", Some("embed_bytes")),
+            ("encode", Some("embed")),
+        ]
+    );
+
+    let fields = |name| &spans.iter().find(|span| span.name == name).unwrap().fields;
+    assert_eq!(fields("decode")["format"], "Some(Png)");
+    assert_eq!(fields("decode")["width"], "64");
+    assert_eq!(fields("embed")["data_bytes"], "13");
+    assert_eq!(fields("embed")["extension"], "\"txt\"");
+    assert_eq!(fields("embed")["height"], "48");
+    // Selected once the embedding is planned
+    assert_eq!(fields("embed")["lsbs"], "1");
+    assert_eq!(fields("encode")["format"], "Png");
+}
+
+#[test]
+fn test_extract_spans() -> Result<(), StegError> {
+    let embedded = embed_with(
+        b"Hello, world!",
+        "txt",
+        &test_util::encoded_container(64, 48, Format::Png),
+        &EmbedOptions::default(),
+    )?
+    .image;
+
+    let spans = Spans::default();
+    spans.collect(|| extract_with(&embedded, &ExtractOptions::default()))?;
+    let spans = spans.take();
+
+    assert_eq!(
+        hierarchy(&spans),
+        [
+            ("decode", None),
+            ("extract", None),
+            ("read_bytes", Some("extract")),
+        ]
+    );
+    let extract = spans.iter().find(|span| span.name == "extract").unwrap();
+    assert_eq!(extract.fields["lsbs"], "1");
+    assert_eq!(extract.fields["width"], "64");
+
+    Ok(())
+}
+
+#[test]
+fn test_verification_extracts_inside_the_embedding() {
+    let spans = Spans::default();
+    let options = EmbedOptions::new()
+        .overwrite_policy(OverwritePolicy::Allow)
+        .verify_after_embed(true);
+
+    spans
+        .collect(|| {
+            embed_with(
+                b"Hello, world!",
+                "txt",
+                &test_util::encoded_container(64, 48, Format::Png),
+                &options,
+            )
+        })
+        .unwrap();
+    let hierarchy = hierarchy(&spans.take());
+
+    assert!(
+        hierarchy.contains(&("decode", Some("embed"))),
+        "{:?}",
+        hierarchy
+    );
+    assert!(
+        hierarchy.contains(&("read_bytes", Some("extract"))),
+        "{:?}",
+        hierarchy
+    );
+}
+
+#[test]
+fn test_wrong_seed_with_auto_never_builds_seeded_shuffle() -> Result<(), StegError> {
+    let embedded = embed_with(
+        b"Hello, world!",
+        "txt",
+        &test_util::encoded_container(64, 48, Format::Png),
+        &EmbedOptions::default(),
+    )?
+    .image;
+    let wrong_seed = ExtractOptions::new().seed(7);
+
+    let spans = Spans::default();
+    let auto = wrong_seed.clone().lsbs(LsbSelection::Auto);
+    spans.collect(|| {
+        assert!(extract_with(&embedded, &auto).is_err());
+        assert!(inspect(&embedded, &auto).is_err());
+        assert!(extract_range(&embedded, 0, 1, 1, 7).is_err());
+    });
+    let names = spans
+        .take()
+        .iter()
+        .map(|span| span.name)
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"extract"), "{:?}", names);
+    assert!(!names.contains(&"seeded_shuffle"), "{:?}", names);
+
+    // A fixed lsbs, or auto with the legacy order, falls back to it
+    for options in [wrong_seed.lsbs(1), auto.legacy_order(true)] {
+        spans
+            .collect(|| extract_with(&embedded, &options))
+            .unwrap_err();
+        assert!(
+            spans
+                .take()
+                .iter()
+                .any(|span| span.name == "seeded_shuffle")
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{error::StegError, hash::Hash, *};
+
+mod test_util;
+
+#[test]
+fn test_builder_matches_embed_with() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = b"Built, not passed around";
+
+    let stego = Stego::builder().lsbs(2).hash(Hash::Sha256).seed(7).build();
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(2),
+        hash: Hash::Sha256,
+        seed: 7,
+        ..EmbedOptions::default()
+    };
+
+    let embedded = stego.embed(input, "txt", &StegoContainer::new(&container)?)?;
+    assert_eq!(
+        embedded,
+        embed_with(input, "txt", &container, &options)?.image
+    );
+
+    let (data, ext) = stego.extract(&StegoContainer::new(&embedded)?)?;
+    assert_eq!(data, input);
+    assert_eq!(ext, "txt");
+
+    Ok(())
+}
+
+#[test]
+fn test_options_builder_matches_fields() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = b"Chained, not listed";
+
+    let built = EmbedOptions::new()
+        .lsbs(3)
+        .hash(Hash::Sha512)
+        .seed(11)
+        .format(Format::Tiff)
+        .verify_after_embed(true);
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(3),
+        hash: Hash::Sha512,
+        seed: 11,
+        format: Some(Format::Tiff),
+        verify_after_embed: true,
+        ..EmbedOptions::default()
+    };
+
+    assert_eq!(
+        embed_with(input, "txt", &container, &built)?.image,
+        embed_with(input, "txt", &container, &options)?.image
+    );
+    // The defaults are those of `embed` and the bindings
+    assert_eq!(
+        embed_with(input, "txt", &container, &EmbedOptions::new())?.image,
+        embed(input, "txt", &container, 1, Hash::Blake3, 42, Format::Png)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_container_reused_across_operations() -> Result<(), Box<dyn std::error::Error>> {
+    let decoded = StegoContainer::new(&test_util::encoded_container(64, 64, Format::Png))?;
+    let stego = Stego::builder()
+        .overwrite_policy(OverwritePolicy::Error)
+        .build();
+
+    assert_eq!(decoded.dimensions(), (64, 64));
+    assert_eq!(decoded.format(), Some(Format::Png));
+    assert_eq!(
+        stego.capacity(&decoded)?,
+        capacity_with(
+            &test_util::encoded_container(64, 64, Format::Png),
+            1,
+            Hash::Blake3,
+            0
+        )?
+    );
+
+    let plan = stego.plan(100, "bin", &decoded)?;
+    assert_eq!(plan.existing_payload, None);
+
+    for input in [&b"first"[..], &b"second"[..]] {
+        let embedded = stego.embed(input, "bin", &decoded)?;
+        assert_eq!(extract(&embedded, 1, 42)?.0, input);
+
+        // The embedded output now holds a payload the session refuses to overwrite
+        assert!(matches!(
+            stego.embed(input, "bin", &StegoContainer::new(&embedded)?),
+            Err(StegError::ExistingPayload(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_auto_lsbs() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let embedded = Stego::builder().lsbs(3).build().embed(
+        b"auto",
+        "txt",
+        &StegoContainer::new(&container)?,
+    )?;
+
+    let decoded = StegoContainer::new(&embedded)?;
+    let (data, ext) = Stego::builder()
+        .lsbs(LsbSelection::Auto)
+        .build()
+        .extract(&decoded)?;
+    assert_eq!(data, b"auto");
+    assert_eq!(ext, "txt");
+
+    Ok(())
+}
+
+#[test]
+fn test_with_format() -> Result<(), Box<dyn std::error::Error>> {
+    let stego = Stego::builder().format(Format::Tga).build();
+    let embedded = stego.embed(
+        b"targa",
+        "txt",
+        &StegoContainer::new(&test_util::encoded_container(32, 32, Format::Png))?,
+    )?;
+
+    // TGA cannot be detected from its contents
+    let decoded = StegoContainer::with_format(&embedded, Format::Tga)?;
+    assert_eq!(stego.extract(&decoded)?.0, b"targa");
+
+    Ok(())
+}
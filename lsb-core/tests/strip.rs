@@ -0,0 +1,67 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{hash::Hash, *};
+
+mod test_util;
+
+const INPUT: &[u8] = b"This payload should not survive stripping.";
+
+#[test]
+fn test_strip_destroys_payload() -> Result<(), Box<dyn std::error::Error>> {
+    let format = Format::Png;
+    let embedded = embed(
+        INPUT,
+        "txt",
+        &test_util::encoded_container(64, 64, Format::Png),
+        2,
+        Hash::Blake3,
+        42,
+        format,
+    )?;
+    assert!(extract(&embedded, 2, 42).is_ok());
+
+    let stripped = strip(&embedded, 2, format)?;
+
+    assert!(extract(&stripped, 2, 42).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_strip_only_changes_low_bits() -> Result<(), Box<dyn std::error::Error>> {
+    let original = test_util::encoded_container(64, 64, Format::Png);
+
+    for lsbs in [1, 3, 8] {
+        let stripped = strip(&original, lsbs, Format::Png)?;
+
+        let before = ::image::load_from_memory(&original)?.into_rgb8();
+        let after = ::image::load_from_memory(&stripped)?.into_rgb8();
+        let mask = (u16::MAX << lsbs) as u8;
+
+        assert!(
+            before
+                .iter()
+                .zip(after.iter())
+                .all(|(a, b)| a & mask == b & mask),
+            "Bits above the low {} planes changed",
+            lsbs
+        );
+        assert_ne!(before, after, "No bits were randomized");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_strip_rejects_lossy_format() {
+    let result = strip(
+        &test_util::encoded_container(8, 8, Format::Png),
+        1,
+        Format::Jpeg,
+    );
+
+    assert!(matches!(
+        result,
+        Err(error::StegError::UnsupportedOutputFormat { .. })
+    ));
+}
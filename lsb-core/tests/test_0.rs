@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 use lsb_core::{hash::Hash, *};
 
 const INPUT: &[u8] = include_bytes!("../../data/input.webp");
@@ -9,7 +11,7 @@ fn test_embed() {
     let hash = Hash::Sha256;
     let seed = 42;
     let lsbs = 1;
-    let format = image::ImageFormat::WebP;
+    let format = Format::WebP;
 
     let result = embed(INPUT, "webp", CONTAINER, lsbs, hash, seed, format);
 
@@ -35,7 +37,7 @@ fn test_embed_extract() -> Result<(), Box<dyn std::error::Error>> {
     let hash = Hash::Sha256;
     let seed = 42;
     let lsbs = 1;
-    let format = image::ImageFormat::WebP;
+    let format = Format::WebP;
 
     let embedded_result = embed(INPUT, "webp", CONTAINER, lsbs, hash, seed, format);
 
@@ -0,0 +1,60 @@
+//! Deterministic fixtures shared by the integration tests and the benchmarks.
+//!
+//! Benchmarks include this module with `#[path = "../tests/test_util/mod.rs"]`, so
+//! nothing here may depend on the test harness.
+
+#![allow(dead_code)]
+
+use std::io::Cursor;
+
+use lsb_core::{
+    Format,
+    image::{NoiseStyle, generate_container},
+};
+
+/// The container sizes of the benchmark suite: about 0.5, 8 and 48 megapixels.
+pub const CONTAINER_SIZES: [(&str, u32, u32); 3] = [
+    ("0.5mp", 1000, 500),
+    ("8mp", 4000, 2000),
+    ("48mp", 8000, 6000),
+];
+
+/// The payload sizes of the benchmark suite, in bytes.
+pub const PAYLOAD_SIZES: [(&str, usize); 3] = [
+    ("1kb", 1024),
+    ("1mb", 1024 * 1024),
+    ("32mb", 32 * 1024 * 1024),
+];
+
+/// Generates the container of a given size, the same on every call.
+pub fn container(width: u32, height: u32) -> ::image::RgbImage {
+    generate_container(width, height, NoiseStyle::GradientNoise, 42)
+}
+
+/// Generates a container and encodes it in `format`.
+pub fn encoded_container(width: u32, height: u32, format: Format) -> Vec<u8> {
+    encode(container(width, height), format)
+}
+
+/// Encodes an image of any color type in `format`.
+pub fn encode(image: impl Into<::image::DynamicImage>, format: Format) -> Vec<u8> {
+    let mut output = Cursor::new(Vec::new());
+    image
+        .into()
+        .write_to(&mut output, format.try_into().unwrap())
+        .unwrap();
+    output.into_inner()
+}
+
+/// Encodes an image as PNG.
+pub fn png(image: &::image::RgbImage) -> Vec<u8> {
+    encode(image.clone(), Format::Png)
+}
+
+/// Generates a payload of `len` bytes, the same on every call.
+///
+/// The bytes repeat with a period of 251, a prime, so they are neither constant nor
+/// aligned with any power of two.
+pub fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "std")]
+
+use std::{
+    cell::RefCell,
+    io::{self, Cursor, Write},
+    rc::Rc,
+};
+
+use lsb_core::*;
+
+mod test_util;
+
+fn container(width: u32, height: u32) -> StegoContainer {
+    StegoContainer::from_image(test_util::container(width, height).into())
+}
+
+#[test]
+fn test_output_independent_of_threads() -> Result<(), Box<dyn std::error::Error>> {
+    let container = container(400, 300);
+    let input = b"The same with any number of threads".repeat(400);
+
+    let mut outputs = Vec::new();
+    for threads in [None, Some(1), Some(2), Some(5)] {
+        let stego = Stego::builder()
+            .lsbs(2)
+            .format(Format::Pnm)
+            .threads(threads)
+            .build();
+
+        let embedded = stego.embed(&input, "txt", &container)?;
+        let mut streamed = Cursor::new(Vec::new());
+        stego.embed_from_reader(
+            &input[..],
+            input.len() as u64,
+            "txt",
+            &container,
+            &mut streamed,
+        )?;
+        assert_eq!(streamed.into_inner(), embedded);
+
+        let embedded_container = StegoContainer::new(&embedded)?;
+        let (data, extension) = stego.extract(&embedded_container)?;
+        assert_eq!(data, input);
+        assert_eq!(extension, "txt");
+
+        outputs.push(embedded);
+    }
+
+    assert!(outputs.windows(2).all(|pair| pair[0] == pair[1]));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn test_runs_in_installed_pool() -> Result<(), Box<dyn std::error::Error>> {
+    use rayon::ThreadPoolBuilder;
+
+    let container = container(200, 200);
+    let input = b"Installed by the caller".repeat(100);
+    let stego = Stego::builder().lsbs(1).build();
+
+    let expected = stego.embed(&input, "txt", &container)?;
+    let pool = ThreadPoolBuilder::new().num_threads(3).build()?;
+    let embedded = pool.install(|| stego.embed(&input, "txt", &container))?;
+    assert_eq!(embedded, expected);
+
+    Ok(())
+}
+
+/// A writer that cannot be sent to another thread.
+struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_threads_with_local_writer() -> Result<(), Box<dyn std::error::Error>> {
+    let container = container(200, 200);
+    let input = b"Written from the calling thread".repeat(100);
+    let stego = Stego::builder().lsbs(1).threads(2).build();
+
+    let embedded = StegoContainer::new(&stego.embed(&input, "bin", &container)?)?;
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let info = stego.extract_to_writer(&embedded, SharedWriter(Rc::clone(&output)))?;
+
+    assert_eq!(info.data_bytes, input.len() as u64);
+    assert_eq!(*output.borrow(), input);
+
+    Ok(())
+}
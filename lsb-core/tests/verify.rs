@@ -0,0 +1,59 @@
+#![cfg(feature = "std")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use lsb_core::{error::StegError, order::PixelOrder, *};
+
+mod test_util;
+
+/// A broken order that shifts its positions by one every time it is asked.
+#[derive(Default)]
+struct Drifting(AtomicUsize);
+
+impl PixelOrder for Drifting {
+    fn positions(&self, capacity_bits: u64, needed: u64) -> impl Iterator<Item = u64> {
+        let shift = self.0.fetch_add(1, Ordering::Relaxed);
+        (0..needed).map(move |i| (i + shift as u64) % capacity_bits)
+    }
+}
+
+fn options(format: Format) -> EmbedOptions {
+    EmbedOptions {
+        format: Some(format),
+        verify_after_embed: true,
+        ..EmbedOptions::default()
+    }
+}
+
+#[test]
+fn test_verify_passes() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = b"Verify me".repeat(20);
+
+    for format in [Format::Png, Format::Tga, Format::Hdr, Format::OpenExr] {
+        embed_with(&input, "txt", &container, &options(format))?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_catches_faults() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = b"Verify me".repeat(20);
+    let options = options(Format::Png);
+
+    assert!(matches!(
+        embed_with_order(&input, "txt", &container, &options, &Drifting::default()),
+        Err(StegError::SelfVerificationFailed(_))
+    ));
+
+    // Without verification, the fault goes unnoticed until extraction
+    let unverified = EmbedOptions {
+        verify_after_embed: false,
+        ..options
+    };
+    embed_with_order(&input, "txt", &container, &unverified, &Drifting::default())?;
+
+    Ok(())
+}
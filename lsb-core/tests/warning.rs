@@ -0,0 +1,123 @@
+#![cfg(feature = "std")]
+
+use std::fs;
+
+use lsb_core::*;
+
+mod test_util;
+
+use test_util::{container, encode};
+
+const INPUT: &[u8] = b"Hello, world!";
+
+#[test]
+fn test_alpha_dropped_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let container = ::image::DynamicImage::from(container(64, 48)).into_rgba8();
+    let container = encode(container, Format::Png);
+
+    let outcome = embed_with(INPUT, "txt", &container, &EmbedOptions::default())?;
+    assert_eq!(outcome.warnings, [Warning::AlphaDropped]);
+    assert_eq!(outcome.warnings[0].code(), "alpha_dropped");
+
+    // The output has no alpha channel of its own to drop
+    let extracted = extract_with(&outcome.image, &ExtractOptions::default())?;
+    assert!(extracted.warnings.is_empty());
+
+    let with_alpha = ::image::load_from_memory(&outcome.image)?.to_rgba8();
+    let with_alpha = encode(with_alpha, Format::Png);
+    let extracted = extract_with(&with_alpha, &ExtractOptions::default())?;
+    assert_eq!(extracted.data, INPUT);
+    assert_eq!(extracted.warnings, [Warning::AlphaDropped]);
+
+    let mut writer = Vec::new();
+    let info = extract_to_writer(&with_alpha, &mut writer, 1, 42)?;
+    assert_eq!(info.warnings, [Warning::AlphaDropped]);
+
+    Ok(())
+}
+
+#[test]
+fn test_high_utilization_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let container = encode(container(32, 32), Format::Png);
+    let capacity = capacity(&container, 1)?;
+
+    let quarter = embed_with(
+        &vec![7; capacity / 4],
+        "",
+        &container,
+        &EmbedOptions::default(),
+    )?;
+    assert!(quarter.capacity_used <= 0.5, "{}", quarter.capacity_used);
+    assert!(quarter.warnings.is_empty());
+
+    let outcome = embed_with(
+        &vec![7; capacity * 3 / 4],
+        "",
+        &container,
+        &EmbedOptions::default(),
+    )?;
+    let [Warning::HighUtilization { capacity_used }] = outcome.warnings[..] else {
+        panic!("{:?}", outcome.warnings);
+    };
+    assert_eq!(capacity_used, outcome.capacity_used);
+    assert!(capacity_used > 0.5);
+
+    Ok(())
+}
+
+#[test]
+fn test_extension_defaulted_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("lsb-warning-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    container(64, 48).save(dir.join("container.png"))?;
+    fs::write(dir.join("secret"), INPUT)?;
+    fs::write(dir.join("secret.txt"), INPUT)?;
+
+    let report = embed_path(
+        &dir.join("secret"),
+        &dir.join("container.png"),
+        &dir.join("embedded.png"),
+        &EmbedOptions::default(),
+    );
+    let with_extension = embed_path(
+        &dir.join("secret.txt"),
+        &dir.join("container.png"),
+        &dir.join("embedded.png"),
+        &EmbedOptions::default(),
+    );
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(
+        report?.warnings,
+        [Warning::ExtensionDefaulted("bin".to_string())]
+    );
+    assert!(with_extension?.warnings.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_format_changed_warns() -> Result<(), Box<dyn std::error::Error>> {
+    let container = encode(container(64, 48), Format::Bmp);
+
+    let outcome = embed_with(
+        INPUT,
+        "txt",
+        &container,
+        &EmbedOptions::new().format(Format::Png),
+    )?;
+    assert_eq!(
+        outcome.warnings,
+        [Warning::FormatChanged {
+            from: Format::Bmp,
+            to: Format::Png,
+        }]
+    );
+    assert!(outcome.warnings[0].to_string().contains("Bmp"));
+
+    // Without a format, the output keeps the format of the container
+    let kept = embed_with(INPUT, "txt", &container, &EmbedOptions::default())?;
+    assert!(kept.warnings.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "std")]
+
+use lsb_core::{Format, error::StegError, watermark::*};
+
+mod test_util;
+
+const MESSAGE: &[u8] = b"(c) 2025 Fay Ash #0042";
+
+fn crop(image: &[u8], x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+    let image = ::image::load_from_memory(image).unwrap();
+
+    let mut output = std::io::Cursor::new(Vec::new());
+    image
+        .crop_imm(x, y, width, height)
+        .write_to(&mut output, ::image::ImageFormat::Png)
+        .unwrap();
+    output.into_inner()
+}
+
+#[test]
+fn test_watermark_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_watermark(
+        MESSAGE,
+        &test_util::encoded_container(320, 256, Format::Png),
+        42,
+        Format::Png,
+    )?;
+
+    let watermark = extract_watermark(&embedded, 42)?;
+
+    assert_eq!(watermark.message, MESSAGE);
+    assert_eq!(watermark.confidence, 1.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_watermark_survives_crop() -> Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = (320, 256);
+    let embedded = embed_watermark(
+        MESSAGE,
+        &test_util::encoded_container(width, height, Format::Png),
+        42,
+        Format::Png,
+    )?;
+
+    // Remove 30% of each dimension, off the block grid.
+    let cropped = crop(
+        &embedded,
+        width * 15 / 100 + 3,
+        height * 15 / 100 + 5,
+        width * 70 / 100,
+        height * 70 / 100,
+    );
+
+    let watermark = extract_watermark(&cropped, 42)?;
+
+    assert_eq!(watermark.message, MESSAGE);
+    assert!(
+        watermark.confidence > 0.5,
+        "Low confidence: {}",
+        watermark.confidence
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_watermark_wrong_seed() -> Result<(), Box<dyn std::error::Error>> {
+    let embedded = embed_watermark(
+        MESSAGE,
+        &test_util::encoded_container(128, 128, Format::Png),
+        42,
+        Format::Png,
+    )?;
+
+    let result = extract_watermark(&embedded, 43);
+
+    assert!(matches!(result, Err(StegError::WatermarkNotFound)));
+
+    Ok(())
+}
+
+#[test]
+fn test_watermark_too_long() {
+    let message = [0; MAX_WATERMARK_LEN + 1];
+
+    let result = embed_watermark(
+        &message,
+        &test_util::encoded_container(128, 128, Format::Png),
+        42,
+        Format::Png,
+    );
+
+    assert!(matches!(result, Err(StegError::WatermarkTooLong { .. })));
+}
+
+#[test]
+fn test_watermark_container_too_small() {
+    let small = WATERMARK_BLOCK_SIZE - 1;
+
+    let result = embed_watermark(
+        MESSAGE,
+        &test_util::encoded_container(small, small, Format::Png),
+        42,
+        Format::Png,
+    );
+
+    assert!(matches!(
+        result,
+        Err(StegError::InsufficientCapacity { .. })
+    ));
+}
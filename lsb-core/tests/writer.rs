@@ -0,0 +1,115 @@
+#![cfg(feature = "std")]
+
+use std::io::Cursor;
+
+use lsb_core::{error::StegError, hash::Hash, order::KeyedPermutation, *};
+
+mod test_util;
+
+use test_util::payload;
+
+#[test]
+fn test_writer_matches_vec() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = b"Straight into the sink".repeat(8);
+
+    for format in [Format::Png, Format::Tiff, Format::OpenExr] {
+        for verify_after_embed in [false, true] {
+            let options = EmbedOptions {
+                format: Some(format),
+                verify_after_embed,
+                ..EmbedOptions::default()
+            };
+
+            let mut writer = Cursor::new(Vec::new());
+            embed_to_writer(&input, "txt", &container, &options, &mut writer)?;
+
+            assert_eq!(
+                writer.into_inner(),
+                embed_with(&input, "txt", &container, &options)?.image
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_to_writer_matches_extract() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(256, 256, Format::Png);
+    // Several read chunks, the last one partial
+    let input = payload(150_000);
+    let options = EmbedOptions {
+        lsbs: LsbSelection::Fixed(8),
+        hash: Hash::Sha512,
+        ..EmbedOptions::default()
+    };
+    let embedded = embed_with(&input, "bin", &container, &options)?.image;
+
+    let mut writer = Vec::new();
+    let info = extract_to_writer(&embedded, &mut writer, 8, 42)?;
+
+    assert_eq!(writer, extract(&embedded, 8, 42)?.0);
+    assert_eq!(
+        info,
+        ExtractInfo {
+            extension: "bin".to_string(),
+            data_bytes: input.len() as u64,
+            hash: Hash::Sha512,
+            warnings: Vec::new(),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sorted_extract_matches_streamed() -> Result<(), Box<dyn std::error::Error>> {
+    let original = ::image::RgbImage::from_fn(1800, 1800, |x, y| {
+        ::image::Rgb([(x * 7) as u8, (y * 3) as u8, (x ^ y) as u8])
+    });
+    // Above the size from which extraction sorts the positions, while streaming reads
+    // them a chunk at a time in payload order
+    let input = payload(1_200_000);
+
+    for lsbs in [1, 3] {
+        let mut image = original.clone();
+        embed_into_image(&mut image, &input, "bin", lsbs, Hash::Blake3, 42)?;
+        let mut embedded = Cursor::new(Vec::new());
+        image.write_to(&mut embedded, ::image::ImageFormat::Pnm)?;
+
+        let mut writer = Vec::new();
+        extract_to_writer(embedded.get_ref(), &mut writer, lsbs, 42)?;
+
+        assert_eq!(writer, input);
+        assert_eq!(extract_from_image(&image, lsbs, 42)?.0, input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_to_writer_detects_corruption() -> Result<(), Box<dyn std::error::Error>> {
+    let container = test_util::encoded_container(64, 64, Format::Png);
+    let input = b"Checked at the end".repeat(10);
+    let embedded = embed_with(&input, "txt", &container, &EmbedOptions::default())?.image;
+
+    let mut image = ::image::load_from_memory(&embedded)?.into_rgb8();
+    // Flip the bit of the last data byte, which at 1 lsb is the lowest bit of a sample
+    let payload_bits = (4 + 1 + 3 + 1 + 8 + 32 + input.len() as u64) * 8;
+    let position = KeyedPermutation::new(42).position(image.len() as u64, payload_bits - 1);
+    image.as_mut()[position as usize] ^= 1;
+    let container = StegoContainer::from_image(image.into());
+
+    let mut writer = Vec::new();
+    let result = Stego::default().extract_to_writer(&container, &mut writer);
+    assert!(
+        matches!(result, Err(StegError::PayloadCorrupted { .. })),
+        "{:?}",
+        result
+    );
+    // The data was written before the mismatch was found
+    assert_eq!(writer.len(), input.len());
+
+    Ok(())
+}
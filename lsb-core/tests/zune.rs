@@ -0,0 +1,67 @@
+// JPEG containers are only decoded with zune-jpeg directly with the `zune` feature
+#![cfg(feature = "zune")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lsb_core::*;
+
+/// The files of `dir` and of its subdirectories.
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(self::files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Every container in `data/` must decode to the same pixels as through `image`, so
+/// that the `zune` feature never changes which bits a payload is read from. Only JPEG
+/// goes through zune-jpeg, every other format must be left to `image` as it is.
+#[test]
+fn test_zune_decodes_like_image() -> Result<(), Box<dyn std::error::Error>> {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("../data");
+
+    let mut formats = Vec::new();
+    for path in files(&data) {
+        let bytes = fs::read(&path)?;
+        // Formats `image` cannot decode by itself, such as JPEG XL, are not compared
+        let Ok(expected) = ::image::load_from_memory(&bytes) else {
+            continue;
+        };
+
+        let container = StegoContainer::new(&bytes)?;
+        assert_eq!(container.image(), &expected, "{}", path.display());
+        formats.push(container.format());
+    }
+
+    let count = |format| formats.iter().filter(|&&f| f == Some(format)).count();
+    assert_eq!(
+        count(Format::Jpeg),
+        2,
+        "data/ holds a color and a grayscale JPEG"
+    );
+    assert_eq!(count(Format::Png), 10);
+    assert_eq!(formats.len(), 17, "{:?}", formats);
+
+    Ok(())
+}
+
+#[test]
+fn test_zune_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let container = include_bytes!("../../data/container.jpg");
+    let options = EmbedOptions::new().lsbs(2).format(Format::Png);
+
+    let embedded = embed_with(b"Decoded by zune", "txt", container, &options)?.image;
+    assert_eq!(extract(&embedded, 2, 42)?.0, b"Decoded by zune");
+
+    Ok(())
+}
@@ -1,12 +1,13 @@
+use js_sys::{Error, Reflect};
 use log::{ParseLevelError, SetLoggerError};
-use lsb_core::{error::StegError, hash::ParseError};
+use lsb_core::{error::StegError, hash::HashParseError};
 use wasm_bindgen::JsValue;
 
 #[derive(Debug)]
 pub enum LsbError {
     Steg(StegError),
     ParseLevel(ParseLevelError),
-    ParseHash(ParseError),
+    ParseHash(HashParseError),
     SetLogger(SetLoggerError),
 }
 
@@ -50,14 +51,38 @@ impl From<StegError> for LsbError {
     }
 }
 
-impl From<ParseError> for LsbError {
-    fn from(err: ParseError) -> Self {
+impl From<HashParseError> for LsbError {
+    fn from(err: HashParseError) -> Self {
         LsbError::ParseHash(err)
     }
 }
 
+/// Converts errors into JavaScript `Error`s. Those of the library are named `LsbError`
+/// and carry the kind of the error in `kind` and its stable identifier in `code`, along with `requiredBits` and
+/// `availableBits` when the container is too small.
 impl From<LsbError> for JsValue {
     fn from(val: LsbError) -> Self {
-        JsValue::from_str(&val.to_string())
+        let error = Error::new(&val.to_string());
+
+        if let LsbError::Steg(err) = &val {
+            error.set_name("LsbError");
+            // Setting properties of a fresh `Error` cannot fail
+            let _ = Reflect::set(&error, &"kind".into(), &err.kind().as_str().into());
+            let _ = Reflect::set(&error, &"code".into(), &err.code().into());
+            if let Some((required_bits, available_bits)) = err.capacity_shortfall() {
+                let _ = Reflect::set(
+                    &error,
+                    &"requiredBits".into(),
+                    &(required_bits as f64).into(),
+                );
+                let _ = Reflect::set(
+                    &error,
+                    &"availableBits".into(),
+                    &(available_bits as f64).into(),
+                );
+            }
+        }
+
+        error.into()
     }
 }
@@ -5,7 +5,7 @@ use std::str::FromStr;
 
 use error::LsbError;
 use log::Level;
-use lsb_core::image::ImageFormat;
+use lsb_core::{image::parse_output_format, prelude::*};
 use wasm_bindgen::prelude::*;
 
 /// Exposes the JavaScript `alert` function.
@@ -22,9 +22,11 @@ extern "C" {
 /// * `extension` - The file extension of the payload.
 /// * `container` - The container image data.
 /// * `lsbs` - The number of least significant bits to use for encoding. Defaults to 1.
-/// * `hash` - The hashing algorithm to use. Defaults to "BLAKE3".
+/// * `hash` - The hashing algorithm to use, such as "blake3" or "sha-256", in any case.
+///   Defaults to "blake3". See `supportedHashes`.
 /// * `seed` - The seed for the random number generator. Defaults to 42.
-/// * `format` - The image format of the container. Defaults to "PNG".
+/// * `format` - The image format of the output, as an extension or a name such as "png" or
+///   "OpenExr". Defaults to the format of the container, which must then be lossless.
 ///
 /// # Returns
 ///
@@ -39,23 +41,161 @@ pub fn embed(
     seed: Option<u64>,
     format: Option<String>,
 ) -> Result<Vec<u8>, LsbError> {
-    let lsbs = lsbs.unwrap_or(1);
-    let hash = hash.unwrap_or("BLAKE3".to_string());
+    let options = embed_options(lsbs.unwrap_or(1).into(), hash, seed, format)?;
+
+    Ok(lsb_core::embed_with(input, extension, container, &options)?.image)
+}
+
+/// Embeds a payload into a container image, using the fewest least significant bits
+/// that fit it.
+///
+/// # Arguments
+///
+/// * `input` - The payload data to embed.
+/// * `extension` - The file extension of the payload.
+/// * `container` - The container image data.
+/// * `hash` - The hashing algorithm to use, such as "blake3" or "sha-256", in any case.
+///   Defaults to "blake3". See `supportedHashes`.
+/// * `seed` - The seed for the random number generator. Defaults to 42.
+/// * `format` - The image format of the output, as an extension or a name such as "png" or
+///   "OpenExr". Defaults to the format of the container, which must then be lossless.
+///
+/// # Returns
+///
+/// A `Result` containing the new image data with the embedded payload, or an `LsbError` if an error occurs.
+#[wasm_bindgen]
+pub fn embed_auto(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    hash: Option<String>,
+    seed: Option<u64>,
+    format: Option<String>,
+) -> Result<Vec<u8>, LsbError> {
+    let options = embed_options(LsbSelection::Auto, hash, seed, format)?;
+
+    Ok(lsb_core::embed_with(input, extension, container, &options)?.image)
+}
+
+/// The result of `embedWithOutcome`: the new image along with how the payload was
+/// embedded.
+#[wasm_bindgen]
+pub struct EmbedOutcome {
+    /// The new image data with the embedded payload.
+    #[wasm_bindgen(getter_with_clone)]
+    pub image: Vec<u8>,
+    /// The number of least significant bits per channel that were used.
+    #[wasm_bindgen(js_name = lsbsUsed)]
+    pub lsbs_used: usize,
+    /// The share of the capacity that was used, from 0 to 1.
+    #[wasm_bindgen(js_name = capacityUsed)]
+    pub capacity_used: f32,
+    /// The width of the new image.
+    pub width: u32,
+    /// The height of the new image.
+    pub height: u32,
+    /// The messages of what the caller may want to know about the embedding, such as a
+    /// payload filling most of the capacity.
+    #[wasm_bindgen(getter_with_clone)]
+    pub warnings: Vec<String>,
+}
+
+/// Embeds a payload into a container image like `embed`, reporting how it was embedded.
+///
+/// # Arguments
+///
+/// * `input` - The payload data to embed.
+/// * `extension` - The file extension of the payload.
+/// * `container` - The container image data.
+/// * `lsbs` - The number of least significant bits to use for encoding. Defaults to the
+///   fewest that fit the payload, like `embed_auto`.
+/// * `hash` - The hashing algorithm to use, such as "blake3" or "sha-256", in any case.
+///   Defaults to "blake3". See `supportedHashes`.
+/// * `seed` - The seed for the random number generator. Defaults to 42.
+/// * `format` - The image format of the output, as an extension or a name such as "png" or
+///   "OpenExr". Defaults to the format of the container, which must then be lossless.
+///
+/// # Returns
+///
+/// A `Result` containing an `EmbedOutcome` with the new image data, or an `LsbError` if an error occurs.
+#[wasm_bindgen(js_name = embedWithOutcome)]
+pub fn embed_with_outcome(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    lsbs: Option<usize>,
+    hash: Option<String>,
+    seed: Option<u64>,
+    format: Option<String>,
+) -> Result<EmbedOutcome, LsbError> {
+    let lsbs = lsbs.map_or(LsbSelection::Auto, LsbSelection::Fixed);
+    let options = embed_options(lsbs, hash, seed, format)?;
+
+    let outcome = lsb_core::embed_with(input, extension, container, &options)?;
+
+    Ok(EmbedOutcome {
+        image: outcome.image,
+        lsbs_used: outcome.lsbs_used,
+        capacity_used: outcome.capacity_used,
+        width: outcome.width,
+        height: outcome.height,
+        warnings: outcome.warnings.iter().map(ToString::to_string).collect(),
+    })
+}
+
+/// Builds the options of the embedding functions from their optional arguments.
+fn embed_options(
+    lsbs: LsbSelection,
+    hash: Option<String>,
+    seed: Option<u64>,
+    format: Option<String>,
+) -> Result<EmbedOptions, LsbError> {
     let seed = seed.unwrap_or(42);
-    let format = format.unwrap_or("PNG".to_string());
 
-    let hash = lsb_core::hash::Hash::from_str(&hash)?;
+    let hash = hash
+        .as_deref()
+        .map(Hash::parse)
+        .transpose()?
+        .unwrap_or(Hash::Blake3);
 
-    let format = ImageFormat::from_extension(&format).ok_or(LsbError::Steg(
-        lsb_core::error::StegError::UnsupportedFormat(format!(
-            "Unsupported image format: {}",
-            format
-        )),
-    ))?;
+    let format = format
+        .as_deref()
+        .map(parse_output_format)
+        .transpose()?;
+
+    Ok(EmbedOptions::new()
+        .lsbs(lsbs)
+        .hash(hash)
+        .seed(seed)
+        .format(format)
+        .overwrite_policy(OverwritePolicy::Allow))
+}
 
-    Ok(lsb_core::embed(
-        input, extension, container, lsbs, hash, seed, format,
-    )?)
+/// A hashing algorithm that payloads can be embedded with.
+#[wasm_bindgen]
+pub struct HashInfo {
+    /// The canonical name of the algorithm, as accepted by `embed`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub name: String,
+    /// The size of its checksum, which every payload spends, in bytes.
+    #[wasm_bindgen(js_name = digestSize)]
+    pub digest_size: usize,
+}
+
+/// Lists the hashing algorithms payloads can be embedded with, the default first.
+///
+/// # Returns
+///
+/// The name and checksum size of every algorithm.
+#[wasm_bindgen(js_name = supportedHashes)]
+pub fn supported_hashes() -> Vec<HashInfo> {
+    Hash::all()
+        .iter()
+        .map(|hash| HashInfo {
+            name: hash.to_string(),
+            digest_size: hash.digest_size(),
+        })
+        .collect()
 }
 
 /// Represents the result of an extraction operation.
@@ -67,6 +207,10 @@ pub struct ExtractResult(
     /// The file extension of the extracted payload.
     #[wasm_bindgen(getter_with_clone)]
     pub String,
+    /// The messages of what the caller may want to know about the extraction, such as an
+    /// alpha channel that was dropped.
+    #[wasm_bindgen(getter_with_clone)]
+    pub Vec<String>,
 );
 
 /// Extracts a payload from a container image.
@@ -79,8 +223,8 @@ pub struct ExtractResult(
 ///
 /// # Returns
 ///
-/// A `Result` containing an `ExtractResult` with the extracted payload and its extension,
-/// or an `LsbError` if an error occurs.
+/// A `Result` containing an `ExtractResult` with the extracted payload, its extension and
+/// warnings, or an `LsbError` if an error occurs.
 #[wasm_bindgen]
 pub fn extract(
     container: &[u8],
@@ -90,13 +234,49 @@ pub fn extract(
     let lsbs = lsbs.unwrap_or(1);
     let seed = seed.unwrap_or(42);
 
-    let (data, extension) = lsb_core::extract(container, lsbs, seed)?;
+    let options = ExtractOptions::new().lsbs(lsbs).seed(seed);
+
+    let extracted = lsb_core::extract_with(container, &options)?;
+
+    Ok(ExtractResult(
+        extracted.data,
+        extracted.extension,
+        extracted.warnings.iter().map(ToString::to_string).collect(),
+    ))
+}
+
+/// Extracts a payload from a container image embedded with an unknown number of
+/// least significant bits, such as by `embed_auto`.
+///
+/// # Arguments
+///
+/// * `container` - The container image data.
+/// * `seed` - The seed for the random number generator. Defaults to 42.
+///
+/// # Returns
+///
+/// A `Result` containing an `ExtractResult` with the extracted payload, its extension and
+/// warnings, or an `LsbError` if an error occurs.
+#[wasm_bindgen]
+pub fn extract_auto(container: &[u8], seed: Option<u64>) -> Result<ExtractResult, LsbError> {
+    let seed = seed.unwrap_or(42);
+
+    let options = ExtractOptions::new().lsbs(LsbSelection::Auto).seed(seed);
 
-    Ok(ExtractResult(data, extension))
+    let extracted = lsb_core::extract_with(container, &options)?;
+
+    Ok(ExtractResult(
+        extracted.data,
+        extracted.extension,
+        extracted.warnings.iter().map(ToString::to_string).collect(),
+    ))
 }
 
 /// Initializes the logger with a specified log level.
 ///
+/// The events of lsb-core are logged to the console. At "trace", so is every span of its
+/// phases, such as `decode`, `embed_bytes` and `encode`, when it is entered and exited.
+///
 /// # Arguments
 ///
 /// * `level` - The log level to set. Defaults to "info".
@@ -49,8 +49,151 @@ fn test_embed_extract() -> Result<(), Box<dyn std::error::Error>> {
 
     let result = lsb_js::extract(&result, Some(1), Some(42));
     assert!(result.is_ok());
-    let ExtractResult(result, _) = result?;
+    let ExtractResult(result, _, _) = result?;
     assert_eq!(result, INPUT);
 
     Ok(())
 }
+
+#[wasm_bindgen_test]
+fn test_embed_extract_auto() -> Result<(), Box<dyn std::error::Error>> {
+    let result = lsb_js::embed_auto(
+        INPUT,
+        EXTENSION,
+        CONTAINER,
+        Some("BLAKE3".to_string()),
+        Some(42),
+        Some("PNG".to_string()),
+    )?;
+
+    let ExtractResult(result, extension, _) = lsb_js::extract_auto(&result, Some(42))?;
+    assert_eq!(result, INPUT);
+    assert_eq!(extension, EXTENSION);
+
+    Ok(())
+}
+
+/// The first positions of a 20000x20000 container at 8 lsbs, whose bits cannot be
+/// counted in a 32-bit `usize`, as recorded from the native build in lsb-core's
+/// `tests/order.rs`.
+#[wasm_bindgen_test]
+fn test_keyed_permutation_matches_native() {
+    use lsb_core::order::{KeyedPermutation, PixelOrder};
+
+    let capacity_bits = 20_000 * 20_000 * 3 * 8;
+    let expected = [
+        7501616010, 1640006662, 1627891994, 3594274094, 5952102025, 6011423532, 1264158924,
+        6459191604,
+    ];
+
+    assert!(KeyedPermutation::new(42)
+        .positions(capacity_bits, 8)
+        .eq(expected));
+}
+
+#[wasm_bindgen_test]
+fn test_error_kind() {
+    let error =
+        wasm_bindgen::JsValue::from(lsb_js::extract(EMBEDDED, Some(9), Some(42)).unwrap_err());
+
+    let kind = js_sys::Reflect::get(&error, &"kind".into()).unwrap();
+    assert_eq!(kind.as_string().as_deref(), Some("invalid_argument"));
+    let code = js_sys::Reflect::get(&error, &"code".into()).unwrap();
+    assert_eq!(code.as_string().as_deref(), Some("invalid_lsb_value"));
+}
+
+#[wasm_bindgen_test]
+fn test_unsupported_format_names_the_alternatives() {
+    let error = lsb_js::embed(
+        INPUT,
+        EXTENSION,
+        CONTAINER,
+        Some(1),
+        None,
+        Some(42),
+        Some("jpg".to_string()),
+    )
+    .unwrap_err();
+
+    assert!(error.to_string().starts_with(
+        "Unsupported output format \"jpg\": it is lossy, which would destroy the payload, \
+         supported formats are: Png, WebP"
+    ));
+    let error = wasm_bindgen::JsValue::from(error);
+    let code = js_sys::Reflect::get(&error, &"code".into()).unwrap();
+    assert_eq!(
+        code.as_string().as_deref(),
+        Some("unsupported_output_format")
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_hash_names() -> Result<(), Box<dyn std::error::Error>> {
+    for hash in ["Blake3", "sha-512", "SHA1"] {
+        let embedded = lsb_js::embed(
+            INPUT,
+            EXTENSION,
+            CONTAINER,
+            Some(1),
+            Some(hash.to_string()),
+            Some(42),
+            Some("png".to_string()),
+        )?;
+        let ExtractResult(result, _, _) = lsb_js::extract(&embedded, Some(1), Some(42))?;
+        assert_eq!(result, INPUT);
+    }
+
+    assert!(lsb_js::embed(
+        INPUT,
+        EXTENSION,
+        CONTAINER,
+        Some(1),
+        Some("md5".to_string()),
+        Some(42),
+        None,
+    )
+    .is_err());
+
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_supported_hashes() {
+    let hashes = lsb_js::supported_hashes();
+
+    assert_eq!(hashes[0].name, "blake3");
+    assert_eq!(hashes[0].digest_size, 32);
+    assert!(hashes
+        .iter()
+        .any(|hash| hash.name == "sha512" && hash.digest_size == 64));
+}
+
+#[wasm_bindgen_test]
+fn test_embed_with_outcome() -> Result<(), Box<dyn std::error::Error>> {
+    let outcome = lsb_js::embed_with_outcome(
+        INPUT,
+        EXTENSION,
+        CONTAINER,
+        None,
+        None,
+        Some(42),
+        Some("png".to_string()),
+    )?;
+
+    let ExtractResult(result, _, warnings) =
+        lsb_js::extract(&outcome.image, Some(outcome.lsbs_used), Some(42))?;
+    assert_eq!(result, INPUT);
+    assert!(warnings.is_empty());
+    assert!(outcome.capacity_used > 0.0 && outcome.capacity_used <= 1.0);
+    // The WebP container is written as PNG
+    assert!(
+        outcome
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("Png")),
+        "{:?}",
+        outcome.warnings
+    );
+
+    Ok(())
+}
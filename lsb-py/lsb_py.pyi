@@ -1,11 +1,54 @@
+from typing import Literal, Optional, Union
+
+
+class LsbError(RuntimeError):
+    """Raised when embedding or extracting fails."""
+
+    kind: str
+    """The category of the error, such as "insufficient_capacity"."""
+
+    code: str
+    """The stable identifier of the error, such as "checksum_mismatch"."""
+
+
+class InvalidArgumentError(LsbError):
+    """Raised when an argument is out of range or not supported."""
+
+
+class UnsupportedFormatError(LsbError):
+    """Raised when an image format cannot be read, detected or written."""
+
+
+class InsufficientCapacityError(LsbError):
+    """Raised when the container is too small."""
+
+    required_bits: int
+    """The bits the operation needs."""
+    available_bits: int
+    """The bits the container offers."""
+
+
+class PayloadNotFoundError(LsbError):
+    """Raised when no payload is found in the image."""
+
+
+class CorruptedPayloadError(LsbError):
+    """Raised when a payload is found, but its checksum does not match."""
+
+
+class LsbWarning(UserWarning):
+    """Warned when embedding or extracting succeeds, but under a condition the caller may
+    want to know about."""
+
+
 def embed(
     input: bytes,
     extension: str,
     container: bytes,
-    lsbs: int = 1,
-    hash: str = "blake3",
+    lsbs: Union[int, Literal["auto"]] = 1,
+    hash: Optional[str] = None,
     seed: int = 42,
-    format: str = "png",
+    format: Optional[str] = None,
 ) -> bytes:
     """Embeds a payload into a container image.
 
@@ -13,30 +56,120 @@ def embed(
         input (bytes): The payload to embed.
         extension (str): The extension of the payload.
         container (bytes): The container image.
-        lsbs (int): The number of least significant bits to use.
-        hash (str): The hash algorithm to use.
+        lsbs (int | str): The number of least significant bits to use, or "auto" to use
+            the fewest that fit the payload.
+        hash (str | None): The hash algorithm to use, such as "blake3" or "sha-256", in any
+            case, or None for "blake3". See `list_hashes`.
         seed (int): The seed for the random number generator.
-        format (str): The format of the container image.
+        format (str | None): The format of the output image, as an extension or a name such
+            as "png" or "OpenExr", or None for the format of the container, which must then
+            be lossless.
 
     Returns:
         bytes: The container image with the embedded payload.
+
+    Warns:
+        LsbWarning: For each condition the caller may want to know about, such as a
+            payload filling more than half of the capacity.
+
+    Raises:
+        InsufficientCapacityError: If the container is too small to hold the payload.
+        UnsupportedFormatError: If the container or the output format is not supported.
+        InvalidArgumentError: If `lsbs` or `extension` is out of range.
+        ValueError: If `hash` is not the name of a hash algorithm.
+        LsbError: If another error occurs during embedding.
+    """
+    ...
+
+
+class EmbedOutcome:
+    """The result of `embed_with_outcome`: the container image with the embedded payload,
+    along with how the payload was embedded."""
+
+    image: bytes
+    """The container image with the embedded payload."""
+
+    lsbs_used: int
+    """The number of least significant bits per channel that were used."""
+
+    capacity_used: float
+    """The share of the capacity that was used, from 0 to 1."""
+
+    width: int
+    """The width of the output image."""
+
+    height: int
+    """The height of the output image."""
+
+    warnings: list[tuple[str, str]]
+    """The code and message of everything the caller may want to know about the
+    embedding, such as ("high_utilization", ...)."""
+
+
+def embed_with_outcome(
+    input: bytes,
+    extension: str,
+    container: bytes,
+    lsbs: Union[int, Literal["auto"]] = 1,
+    hash: Optional[str] = None,
+    seed: int = 42,
+    format: Optional[str] = None,
+) -> EmbedOutcome:
+    """Embeds a payload into a container image like `embed`, reporting how it was embedded.
+
+    The warnings are returned in the outcome rather than warned.
+
+    Args:
+        See `embed`.
+
+    Returns:
+        EmbedOutcome: The container image with the embedded payload, the number of
+            least significant bits used, the share of the capacity used, and warnings.
+
+    Raises:
+        See `embed`.
     """
     ...
 
 
 def extract(
     input: bytes,
-    lsbs: int = 1,
+    lsbs: Union[int, Literal["auto"]] = 1,
     seed: int = 42,
+    legacy_order: bool = False,
 ) -> tuple[bytes, str]:
     """Extracts a payload from a container image.
 
     Args:
         input (bytes): The container image with the embedded payload.
-        lsbs (int): The number of least significant bits used for embedding.
+        lsbs (int | str): The number of least significant bits used for embedding, or "auto"
+            to detect it.
         seed (int): The seed for the random number generator used for embedding.
+        legacy_order (bool): Whether to also read images embedded by older versions with
+            "auto", which costs memory for every bit of the image for every value tried.
+            A fixed `lsbs` always reads them.
 
     Returns:
         tuple[bytes, str]: A tuple containing the extracted payload and its extension.
+
+    Warns:
+        LsbWarning: For each condition the caller may want to know about, such as an
+            alpha channel that was dropped.
+
+    Raises:
+        PayloadNotFoundError: If the image holds no payload with these parameters.
+        CorruptedPayloadError: If the checksum of the payload does not match.
+        InvalidArgumentError: If `lsbs` is out of range.
+        LsbError: If another error occurs during extraction.
     """
-    ...
\ No newline at end of file
+    ...
+
+
+def list_hashes() -> list[tuple[str, int]]:
+    """Lists the hash algorithms payloads can be embedded with, the default first.
+
+    Returns:
+        list[tuple[str, int]]: The name of every algorithm, as accepted by `embed`, and
+            the size of its checksum in bytes, which every payload spends.
+    """
+    ...
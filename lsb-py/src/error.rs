@@ -1,12 +1,69 @@
 use core::fmt;
+use std::ffi::CString;
 
-use lsb_core::{error::StegError, hash::ParseError};
+use lsb_core::{
+    error::{ErrorKind, StegError},
+    hash::HashParseError,
+    Warning,
+};
 use pyo3::prelude::*;
 
+/// The exceptions raised for a `StegError`, chosen by its kind.
+pub mod exceptions {
+    use pyo3::{
+        create_exception,
+        exceptions::{PyRuntimeError, PyUserWarning},
+    };
+
+    create_exception!(
+        lsb_py,
+        LsbError,
+        PyRuntimeError,
+        "Raised when embedding or extracting fails. `kind` names the category of the error, and `code` the error itself."
+    );
+    create_exception!(
+        lsb_py,
+        InvalidArgumentError,
+        LsbError,
+        "Raised when an argument is out of range or not supported."
+    );
+    create_exception!(
+        lsb_py,
+        UnsupportedFormatError,
+        LsbError,
+        "Raised when an image format cannot be read, detected or written."
+    );
+    create_exception!(
+        lsb_py,
+        InsufficientCapacityError,
+        LsbError,
+        "Raised when the container is too small. `required_bits` and `available_bits` tell by how much."
+    );
+    create_exception!(
+        lsb_py,
+        PayloadNotFoundError,
+        LsbError,
+        "Raised when no payload is found in the image."
+    );
+    create_exception!(
+        lsb_py,
+        CorruptedPayloadError,
+        LsbError,
+        "Raised when a payload is found, but its checksum does not match."
+    );
+    create_exception!(
+        lsb_py,
+        LsbWarning,
+        PyUserWarning,
+        "Warned when embedding or extracting succeeds, but under a condition the caller may want to know about."
+    );
+}
+
 #[derive(Debug)]
 pub enum LsbError {
     Steg(StegError),
-    Parse(ParseError),
+    Parse(HashParseError),
+    Python(PyErr),
 }
 
 impl fmt::Display for LsbError {
@@ -14,6 +71,7 @@ impl fmt::Display for LsbError {
         match self {
             LsbError::Steg(err) => write!(f, "{}", err),
             LsbError::Parse(err) => write!(f, "ParseError: {}", err),
+            LsbError::Python(err) => write!(f, "{}", err),
         }
     }
 }
@@ -23,12 +81,13 @@ impl std::error::Error for LsbError {
         match self {
             LsbError::Steg(err) => Some(err),
             LsbError::Parse(err) => Some(err),
+            LsbError::Python(err) => Some(err),
         }
     }
 }
 
-impl From<ParseError> for LsbError {
-    fn from(err: ParseError) -> Self {
+impl From<HashParseError> for LsbError {
+    fn from(err: HashParseError) -> Self {
         LsbError::Parse(err)
     }
 }
@@ -39,15 +98,69 @@ impl From<StegError> for LsbError {
     }
 }
 
+impl From<PyErr> for LsbError {
+    fn from(err: PyErr) -> Self {
+        LsbError::Python(err)
+    }
+}
+
 impl std::convert::From<LsbError> for PyErr {
     fn from(err: LsbError) -> Self {
         match err {
-            LsbError::Steg(steg_err) => {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(steg_err.to_string())
-            }
+            LsbError::Steg(steg_err) => steg_exception(steg_err),
             LsbError::Parse(parse_err) => {
                 PyErr::new::<pyo3::exceptions::PyValueError, _>(parse_err.to_string())
             }
+            LsbError::Python(err) => err,
         }
     }
 }
+
+/// Builds the exception of the kind of `err`, with the values it carries as attributes.
+fn steg_exception(err: StegError) -> PyErr {
+    let message = err.to_string();
+    let exception = match err.kind() {
+        ErrorKind::InvalidArgument => exceptions::InvalidArgumentError::new_err(message),
+        ErrorKind::UnsupportedFormat => exceptions::UnsupportedFormatError::new_err(message),
+        ErrorKind::InsufficientCapacity => exceptions::InsufficientCapacityError::new_err(message),
+        ErrorKind::NotFound => exceptions::PayloadNotFoundError::new_err(message),
+        ErrorKind::Corrupted => exceptions::CorruptedPayloadError::new_err(message),
+        _ => exceptions::LsbError::new_err(message),
+    };
+
+    Python::with_gil(|py| {
+        let value = exception.value(py);
+        let attributes = value
+            .setattr("kind", err.kind().as_str())
+            .and_then(|()| value.setattr("code", err.code()))
+            .and_then(|()| match err.capacity_shortfall() {
+                Some((required_bits, available_bits)) => {
+                    value.setattr("required_bits", required_bits)?;
+                    value.setattr("available_bits", available_bits)
+                }
+                None => Ok(()),
+            });
+
+        match attributes {
+            Ok(()) => exception,
+            Err(err) => err,
+        }
+    })
+}
+
+/// Warns of each of `warnings` through the `warnings` module, as an `LsbWarning`.
+///
+/// # Errors
+///
+/// Returns the exception of a warning that the warning filters turn into one.
+pub fn warn(warnings: &[Warning]) -> PyResult<()> {
+    Python::with_gil(|py| {
+        let category = py.get_type::<exceptions::LsbWarning>();
+        for warning in warnings {
+            let message = CString::new(warning.to_string()).unwrap_or_default();
+            PyErr::warn(py, &category, &message, 1)?;
+        }
+
+        Ok(())
+    })
+}
@@ -1,76 +1,254 @@
 mod error;
 
-use std::{borrow::Cow, str::FromStr};
+use std::borrow::Cow;
 
-use error::LsbError;
-use lsb_core::{error::StegError, hash, image::ImageFormat};
+use error::{exceptions, warn, LsbError};
+use lsb_core::{image::parse_output_format, prelude::*};
 use pyo3::prelude::*;
 
+/// The `lsbs` argument: either a number of bits or the string "auto".
+#[derive(FromPyObject)]
+enum Lsbs {
+    Fixed(usize),
+    Named(String),
+}
+
+impl TryFrom<Lsbs> for LsbSelection {
+    type Error = LsbError;
+
+    fn try_from(lsbs: Lsbs) -> Result<Self, Self::Error> {
+        match lsbs {
+            Lsbs::Fixed(lsbs) => Ok(LsbSelection::Fixed(lsbs)),
+            Lsbs::Named(name) => Ok(name.parse()?),
+        }
+    }
+}
+
 /// Embeds a payload into a container image.
 ///
 /// Args:
 ///     input (bytes): The payload to embed.
 ///     extension (str): The extension of the payload.
 ///     container (bytes): The container image.
-///     lsbs (int): The number of least significant bits to use.
-///     hash (str): The hash algorithm to use.
+///     lsbs (int | str): The number of least significant bits to use, or "auto" to use
+///         the fewest that fit the payload.
+///     hash (str | None): The hash algorithm to use, such as "blake3" or "sha-256", in any
+///         case, or None for "blake3". See `list_hashes`.
 ///     seed (int): The seed for the random number generator.
-///     format (str): The format of the container image.
+///     format (str | None): The format of the output image, as an extension or a name such
+///         as "png" or "OpenExr", or None for the format of the container, which must then
+///         be lossless.
 ///
 /// Returns:
 ///     bytes: The container image with the embedded payload.
 ///
+/// Warns:
+///     LsbWarning: For each condition the caller may want to know about, such as a
+///         payload filling more than half of the capacity.
+///
 /// Raises:
-///     LsbError: If an error occurs during embedding.
+///     InsufficientCapacityError: If the container is too small to hold the payload.
+///     UnsupportedFormatError: If the container or the output format is not supported.
+///     InvalidArgumentError: If `lsbs` or `extension` is out of range.
+///     ValueError: If `hash` is not the name of a hash algorithm.
+///     LsbError: If another error occurs during embedding.
 #[pyfunction]
 #[pyo3(
-    signature = (input, extension, container, lsbs=1, hash="BLAKE3", seed=42, format="PNG")
+    signature = (input, extension, container, lsbs=Lsbs::Fixed(1), hash=None, seed=42, format=None)
 )]
 fn embed<'a>(
     input: &[u8],
     extension: &str,
     container: &[u8],
-    lsbs: usize,
-    hash: &str,
+    lsbs: Lsbs,
+    hash: Option<&str>,
     seed: u64,
-    format: &str,
+    format: Option<&str>,
 ) -> Result<Cow<'a, [u8]>, LsbError> {
-    let hash = hash::Hash::from_str(hash)?;
+    let options = embed_options(lsbs, hash, seed, format)?;
 
-    let format = ImageFormat::from_extension(format).ok_or(LsbError::Steg(
-        StegError::UnsupportedFormat(format!("Unsupported image format: {}", format)),
-    ))?;
+    let outcome = lsb_core::embed_with(input, extension, container, &options)?;
+    warn(&outcome.warnings)?;
 
-    Ok(lsb_core::embed(input, extension, container, lsbs, hash, seed, format)?.into())
+    Ok(outcome.image.into())
+}
+
+/// The result of `embed_with_outcome`: the container image with the embedded payload,
+/// along with how the payload was embedded.
+#[pyclass(frozen, get_all, module = "lsb_py")]
+struct EmbedOutcome {
+    /// bytes: The container image with the embedded payload.
+    image: Vec<u8>,
+    /// int: The number of least significant bits per channel that were used.
+    lsbs_used: usize,
+    /// float: The share of the capacity that was used, from 0 to 1.
+    capacity_used: f32,
+    /// int: The width of the output image.
+    width: u32,
+    /// int: The height of the output image.
+    height: u32,
+    /// list[tuple[str, str]]: The code and message of everything the caller may want to
+    /// know about the embedding, such as ("high_utilization", ...).
+    warnings: Vec<(String, String)>,
+}
+
+/// Embeds a payload into a container image like `embed`, reporting how it was embedded.
+///
+/// The warnings are returned in the outcome rather than warned.
+///
+/// Args:
+///     See `embed`.
+///
+/// Returns:
+///     EmbedOutcome: The container image with the embedded payload, the number of
+///         least significant bits used, the share of the capacity used, and warnings.
+///
+/// Raises:
+///     See `embed`.
+#[pyfunction]
+#[pyo3(
+    signature = (input, extension, container, lsbs=Lsbs::Fixed(1), hash=None, seed=42, format=None)
+)]
+fn embed_with_outcome(
+    input: &[u8],
+    extension: &str,
+    container: &[u8],
+    lsbs: Lsbs,
+    hash: Option<&str>,
+    seed: u64,
+    format: Option<&str>,
+) -> Result<EmbedOutcome, LsbError> {
+    let options = embed_options(lsbs, hash, seed, format)?;
+
+    let outcome = lsb_core::embed_with(input, extension, container, &options)?;
+
+    Ok(EmbedOutcome {
+        image: outcome.image,
+        lsbs_used: outcome.lsbs_used,
+        capacity_used: outcome.capacity_used,
+        width: outcome.width,
+        height: outcome.height,
+        warnings: outcome
+            .warnings
+            .iter()
+            .map(|warning| (warning.code().to_string(), warning.to_string()))
+            .collect(),
+    })
+}
+
+/// Builds the options of the embedding functions from their arguments.
+fn embed_options(
+    lsbs: Lsbs,
+    hash: Option<&str>,
+    seed: u64,
+    format: Option<&str>,
+) -> Result<EmbedOptions, LsbError> {
+    let hash = hash.map(Hash::parse).transpose()?.unwrap_or(Hash::Blake3);
+
+    let format = format.map(parse_output_format).transpose()?;
+
+    Ok(EmbedOptions::new()
+        .lsbs(LsbSelection::try_from(lsbs)?)
+        .hash(hash)
+        .seed(seed)
+        .format(format)
+        .overwrite_policy(OverwritePolicy::Allow))
 }
 
 /// Extracts a payload from a container image.
 ///
 /// Args:
 ///     input (bytes): The container image with the embedded payload.
-///     lsbs (int): The number of least significant bits used for embedding.
+///     lsbs (int | str): The number of least significant bits used for embedding, or "auto"
+///         to detect it.
 ///     seed (int): The seed for the random number generator used for embedding.
+///     legacy_order (bool): Whether to also read images embedded by older versions with
+///         "auto", which costs memory for every bit of the image for every value tried.
+///         A fixed `lsbs` always reads them.
 ///
 /// Returns:
 ///     tuple[bytes, str]: A tuple containing the extracted payload and its extension.
 ///
+/// Warns:
+///     LsbWarning: For each condition the caller may want to know about, such as an
+///         alpha channel that was dropped.
+///
 /// Raises:
-///     LsbError: If an error occurs during extraction.
+///     PayloadNotFoundError: If the image holds no payload with these parameters.
+///     CorruptedPayloadError: If the checksum of the payload does not match.
+///     InvalidArgumentError: If `lsbs` is out of range.
+///     LsbError: If another error occurs during extraction.
 #[pyfunction]
-#[pyo3(signature = (input, lsbs=1, seed=42))]
-fn extract<'a>(input: &[u8], lsbs: usize, seed: u64) -> Result<(Cow<'a, [u8]>, String), LsbError> {
-    let (data, ext) = lsb_core::extract(input, lsbs, seed)?;
+#[pyo3(signature = (input, lsbs=Lsbs::Fixed(1), seed=42, legacy_order=false))]
+fn extract<'a>(
+    input: &[u8],
+    lsbs: Lsbs,
+    seed: u64,
+    legacy_order: bool,
+) -> Result<(Cow<'a, [u8]>, String), LsbError> {
+    let options = ExtractOptions::new()
+        .lsbs(LsbSelection::try_from(lsbs)?)
+        .seed(seed)
+        .legacy_order(legacy_order);
+
+    let extracted = lsb_core::extract_with(input, &options)?;
+    warn(&extracted.warnings)?;
 
-    Ok((data.into(), ext))
+    Ok((extracted.data.into(), extracted.extension))
+}
+
+/// Lists the hash algorithms payloads can be embedded with, the default first.
+///
+/// Returns:
+///     list[tuple[str, int]]: The name of every algorithm, as accepted by `embed`, and
+///         the size of its checksum in bytes, which every payload spends.
+#[pyfunction]
+fn list_hashes() -> Vec<(String, usize)> {
+    Hash::all()
+        .iter()
+        .map(|hash| (hash.to_string(), hash.digest_size()))
+        .collect()
 }
 
 /// A Python module implementing LSB steganography.
+///
+/// The events of lsb-core are forwarded to the `logging` module, under loggers named
+/// after their Rust modules, such as "lsb_core.embed". Entering and exiting the spans of
+/// its phases, such as `decode` and `encode`, is logged under "tracing.span" at level 5.
 #[pymodule]
 fn lsb_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
     pyo3_log::init();
 
     m.add_function(wrap_pyfunction!(embed, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_with_outcome, m)?)?;
     m.add_function(wrap_pyfunction!(extract, m)?)?;
+    m.add_function(wrap_pyfunction!(list_hashes, m)?)?;
+
+    m.add_class::<EmbedOutcome>()?;
+
+    let py = m.py();
+    m.add("LsbError", py.get_type::<exceptions::LsbError>())?;
+    m.add(
+        "InvalidArgumentError",
+        py.get_type::<exceptions::InvalidArgumentError>(),
+    )?;
+    m.add(
+        "UnsupportedFormatError",
+        py.get_type::<exceptions::UnsupportedFormatError>(),
+    )?;
+    m.add(
+        "InsufficientCapacityError",
+        py.get_type::<exceptions::InsufficientCapacityError>(),
+    )?;
+    m.add(
+        "PayloadNotFoundError",
+        py.get_type::<exceptions::PayloadNotFoundError>(),
+    )?;
+    m.add(
+        "CorruptedPayloadError",
+        py.get_type::<exceptions::CorruptedPayloadError>(),
+    )?;
+    m.add("LsbWarning", py.get_type::<exceptions::LsbWarning>())?;
 
     Ok(())
 }
@@ -1,4 +1,5 @@
 import unittest
+import warnings
 import lsb_py
 from pathlib import Path
 
@@ -43,6 +44,118 @@ class TestLsbPy(unittest.TestCase):
         self.assertEqual(result, input_data)
         self.assertEqual(format, "webp")
 
+    def test_embed_extract_auto(self):
+        with self.input.open("rb") as f:
+            input_data = f.read()
+        with self.container.open("rb") as f:
+            container_data = f.read()
+
+        extension = self.input.suffix[1:]
+        embedded_data = lsb_py.embed(input_data, extension, container_data, lsbs="auto")
+        result, format = lsb_py.extract(embedded_data, lsbs="auto")
+
+        self.assertEqual(result, input_data)
+        self.assertEqual(format, "webp")
+
+    def test_hash_names(self):
+        with self.input.open("rb") as f:
+            input_data = f.read()
+        with self.container.open("rb") as f:
+            container_data = f.read()
+
+        for hash in ["sha256", "Sha-256", "SHA2_256"]:
+            embedded_data = lsb_py.embed(input_data, "webp", container_data, hash=hash)
+            self.assertEqual(lsb_py.extract(embedded_data)[0], input_data)
+
+        with self.assertRaises(ValueError) as context:
+            lsb_py.embed(input_data, "webp", container_data, hash="md5")
+        self.assertIn("sha-256", str(context.exception))
+
+    def test_embed_with_outcome(self):
+        with self.input.open("rb") as f:
+            input_data = f.read()
+        with self.container.open("rb") as f:
+            container_data = f.read()
+
+        extension = self.input.suffix[1:]
+        outcome = lsb_py.embed_with_outcome(input_data, extension, container_data, lsbs="auto")
+
+        self.assertEqual(outcome.image, lsb_py.embed(input_data, extension, container_data, lsbs="auto"))
+        extracted, _ = lsb_py.extract(outcome.image, lsbs=outcome.lsbs_used)
+        self.assertEqual(extracted, input_data)
+        self.assertGreater(outcome.capacity_used, 0.0)
+        self.assertLessEqual(outcome.capacity_used, 1.0)
+        self.assertIsInstance(outcome.warnings, list)
+
+    def test_warnings(self):
+        with self.input.open("rb") as f:
+            input_data = f.read()
+        with self.container.open("rb") as f:
+            container_data = f.read()
+
+        extension = self.input.suffix[1:]
+        outcome = lsb_py.embed_with_outcome(input_data, extension, container_data, lsbs="auto", format="png")
+        self.assertIn("format_changed", [code for code, _ in outcome.warnings])
+
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter("always")
+            lsb_py.embed(input_data, extension, container_data, lsbs="auto", format="png")
+
+        self.assertTrue(all(issubclass(w.category, lsb_py.LsbWarning) for w in caught))
+        self.assertEqual([str(w.message) for w in caught], [message for _, message in outcome.warnings])
+
+        with warnings.catch_warnings():
+            warnings.simplefilter("error", lsb_py.LsbWarning)
+            with self.assertRaises(lsb_py.LsbWarning):
+                lsb_py.embed(input_data, extension, container_data, lsbs="auto", format="png")
+
+    def test_list_hashes(self):
+        hashes = lsb_py.list_hashes()
+
+        self.assertEqual(hashes[0], ("blake3", 32))
+        self.assertIn(("sha512", 64), hashes)
+
+    def test_insufficient_capacity(self):
+        with self.container.open("rb") as f:
+            container_data = f.read()
+
+        with self.assertRaises(lsb_py.InsufficientCapacityError) as context:
+            lsb_py.embed(bytes(4_000_000), "bin", container_data)
+
+        self.assertIsInstance(context.exception, lsb_py.LsbError)
+        self.assertEqual(context.exception.kind, "insufficient_capacity")
+        self.assertGreater(
+            context.exception.required_bits, context.exception.available_bits
+        )
+
+    def test_unsupported_format(self):
+        with self.input.open("rb") as f:
+            input_data = f.read()
+        with self.container.open("rb") as f:
+            container_data = f.read()
+
+        with self.assertRaises(lsb_py.UnsupportedFormatError) as context:
+            lsb_py.embed(input_data, "webp", container_data, format="jpg")
+
+        self.assertEqual(context.exception.code, "unsupported_output_format")
+        self.assertTrue(
+            str(context.exception).startswith(
+                'Unsupported output format "jpg": it is lossy, which would destroy the payload, '
+                "supported formats are: Png, WebP"
+            ),
+            str(context.exception),
+        )
+
+    def test_invalid_lsbs(self):
+        with self.embedded.open("rb") as f:
+            embedded_data = f.read()
+
+        with self.assertRaises(lsb_py.InvalidArgumentError) as context:
+            lsb_py.extract(embedded_data, lsbs=9)
+
+        self.assertEqual(context.exception.kind, "invalid_argument")
+        self.assertEqual(context.exception.code, "invalid_lsb_value")
+
 
 if __name__ == "__main__":
     unittest.main()